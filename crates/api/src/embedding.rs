@@ -0,0 +1,180 @@
+use async_std::task;
+use crate::{ApiProvider, Error};
+use crate::audit::{AuditRecordAt, dump_api_usage};
+use crate::model::Model;
+use ragit_fs::write_log;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// A request to an OpenAI-compatible `/embeddings` endpoint. The `api_url` of
+/// the model has to point at the endpoint, e.g. `http://127.0.0.1:11434/v1/embeddings`.
+#[derive(Clone, Debug)]
+pub struct EmbeddingRequest {
+    pub model: Model,
+    pub inputs: Vec<String>,
+
+    /// milliseconds
+    pub timeout: Option<u64>,
+
+    /// It tries 1 + max_retry times.
+    pub max_retry: usize,
+
+    /// milliseconds
+    pub sleep_between_retries: u64,
+    pub dump_api_usage_at: Option<AuditRecordAt>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+    usage: Option<EmbeddingUsage>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingUsage {
+    prompt_tokens: u64,
+}
+
+impl EmbeddingRequest {
+    pub fn build_json_body(&self) -> Value {
+        let mut result = Map::new();
+        result.insert(String::from("model"), self.model.api_name.clone().into());
+        result.insert(String::from("input"), self.inputs.clone().into());
+        result.into()
+    }
+
+    /// It returns a vector for each input, in the same order as `self.inputs`.
+    pub async fn send(&self) -> Result<Vec<Vec<f32>>, Error> {
+        if self.inputs.is_empty() {
+            return Ok(vec![]);
+        }
+
+        match &self.model.api_provider {
+            ApiProvider::Test(test_model) => {
+                let mut result = Vec::with_capacity(self.inputs.len());
+
+                for input in self.inputs.iter() {
+                    result.push(test_model.get_dummy_embedding(input)?);
+                }
+
+                self.record_api_usage(0);
+                return Ok(result);
+            },
+            ApiProvider::OpenAi { .. } => {},
+            api_provider => {
+                return Err(Error::UnsupportedEmbeddingApi(api_provider.to_string()));
+            },
+        }
+
+        let client = reqwest::Client::new();
+        let mut curr_error = Error::NoTry;
+        let post_url = self.model.get_api_url()?;
+        let body = serde_json::to_string(&self.build_json_body())?;
+        let api_key = self.model.get_api_key()?;
+        write_log(
+            "embedding_request::send",
+            &format!("entered embedding_request::send() with {} inputs, model: {}", self.inputs.len(), self.model.name),
+        );
+
+        for _ in 0..(self.max_retry + 1) {
+            let mut request = client.post(&post_url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+
+            if !api_key.is_empty() {
+                request = request.bearer_auth(api_key.clone());
+            }
+
+            if let Some(t) = self.timeout {
+                request = request.timeout(Duration::from_millis(t));
+            }
+
+            match request.send().await {
+                Ok(response) => match response.status().as_u16() {
+                    200 => match response.text().await {
+                        Ok(text) => match serde_json::from_str::<EmbeddingResponse>(&text) {
+                            Ok(mut response) if response.data.len() == self.inputs.len() => {
+                                response.data.sort_by_key(|data| data.index);
+
+                                // If an index is duplicated or out of range, some inputs don't have an embedding.
+                                match response.data.iter().enumerate().find(|(i, data)| data.index != *i) {
+                                    Some((i, _)) => {
+                                        curr_error = Error::WrongSchema(format!("the embedding of input {i} is missing"));
+                                    },
+                                    None => {
+                                        self.record_api_usage(response.usage.map(|usage| usage.prompt_tokens).unwrap_or(0));
+                                        return Ok(response.data.into_iter().map(|data| data.embedding).collect());
+                                    },
+                                }
+                            },
+                            Ok(response) => {
+                                curr_error = Error::WrongSchema(format!(
+                                    "expected {} embeddings, got {}",
+                                    self.inputs.len(),
+                                    response.data.len(),
+                                ));
+                            },
+                            Err(e) => {
+                                write_log(
+                                    "embedding_request::send",
+                                    &format!("failed to parse the response: {e:?}"),
+                                );
+                                curr_error = Error::JsonSerdeError(e);
+                            },
+                        },
+                        Err(e) => {
+                            curr_error = Error::ReqwestError(e);
+                        },
+                    },
+                    status_code => {
+                        curr_error = Error::ServerError {
+                            status_code,
+                            body: response.text().await,
+                        };
+
+                        // Assumption: if the input is invalid, there's no point in retrying over and over.
+                        if status_code == 400 {
+                            return Err(curr_error);
+                        }
+                    },
+                },
+                Err(e) => {
+                    write_log(
+                        "request.send().await",
+                        &format!("request.send().await failed with {e:?}"),
+                    );
+                    curr_error = Error::ReqwestError(e);
+                },
+            }
+
+            task::sleep(Duration::from_millis(self.sleep_between_retries)).await
+        }
+
+        Err(curr_error)
+    }
+
+    fn record_api_usage(&self, input_tokens: u64) {
+        if let Some(key) = &self.dump_api_usage_at {
+            if let Err(e) = dump_api_usage(
+                key,
+                input_tokens,
+                0,
+                self.model.dollars_per_1b_input_tokens,
+                self.model.dollars_per_1b_output_tokens,
+                false,
+            ) {
+                write_log(
+                    "dump_api_usage",
+                    &format!("dump_api_usage({key:?}, ..) failed with {e:?}"),
+                );
+            }
+        }
+    }
+}
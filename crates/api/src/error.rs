@@ -14,6 +14,9 @@ pub enum Error {
         candidates: Vec<String>,
     },
     InvalidApiProvider(String),
+
+    /// Only OpenAI-compatible apis can create embeddings.
+    UnsupportedEmbeddingApi(String /* api provider */),
     PdlError(ragit_pdl::Error),
     FileError(FileError),
     ApiKeyNotFound { env_var: Option<String> },
@@ -2,6 +2,7 @@ use ragit_fs::{WriteMode, read_string, write_string};
 
 mod api_provider;
 pub mod audit;
+mod embedding;
 mod error;
 mod message;
 mod model;
@@ -13,6 +14,7 @@ mod tests;
 
 pub use crate::api_provider::ApiProvider;
 pub use crate::audit::AuditRecord;
+pub use crate::embedding::EmbeddingRequest;
 pub use crate::error::Error;
 pub use crate::message::message_contents_to_json_array;
 pub use crate::model::{Model, ModelRaw, get_model_by_name};
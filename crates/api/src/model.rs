@@ -5,6 +5,8 @@ use ragit_fs::join4;
 use ragit_pdl::Message;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io::{Read, Write, stdin, stdout};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -391,6 +393,8 @@ impl From<&Model> for ModelRaw {
     }
 }
 
+const DUMMY_EMBEDDING_DIMENSION: usize = 64;
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum TestModel {
     Dummy,  // it always returns `"dummy"`
@@ -421,6 +425,33 @@ impl TestModel {
             TestModel::Error => Err(Error::TestModel),
         }
     }
+
+    /// A hashed bag-of-words. Texts that share words get similar vectors,
+    /// which is good enough for testing hybrid retrievals without network.
+    pub fn get_dummy_embedding(&self, input: &str) -> Result<Vec<f32>, Error> {
+        match self {
+            TestModel::Dummy | TestModel::Stdin => {
+                let mut result = vec![0.0; DUMMY_EMBEDDING_DIMENSION];
+
+                for word in input.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+                    let mut hasher = DefaultHasher::new();
+                    word.to_lowercase().hash(&mut hasher);
+                    result[(hasher.finish() % DUMMY_EMBEDDING_DIMENSION as u64) as usize] += 1.0;
+                }
+
+                let norm = result.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+                if norm > 0.0 {
+                    for v in result.iter_mut() {
+                        *v /= norm;
+                    }
+                }
+
+                Ok(result)
+            },
+            TestModel::Error => Err(Error::TestModel),
+        }
+    }
 }
 
 fn partial_match(haystack: &str, needle: &str) -> bool {
@@ -60,6 +60,17 @@ impl JsonType {
                 Ok(v) => Ok(v),
                 Err(_) => Ok(Value::from(s)),
             },
+            JsonType::Object | JsonType::Array => match serde_json::from_str::<Value>(s) {
+                Ok(v) => match (self, &v) {
+                    (JsonType::Object, Value::Object(_))
+                    | (JsonType::Array, Value::Array(_)) => Ok(v),
+                    _ => Err(Error::JsonTypeError {
+                        expected: *self,
+                        got: (&v).into(),
+                    }),
+                },
+                Err(e) => Err(e.into()),
+            },
             _ => todo!(),
         }
     }
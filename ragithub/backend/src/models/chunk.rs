@@ -1,4 +1,4 @@
-use ragit::{Chunk, ChunkSource, MultiModalContent, into_multi_modal_contents};
+use ragit::{Chunk, ChunkSource, MultiModalContent, SourceRange, TimeRange, into_multi_modal_contents};
 use serde::{Deserialize, Serialize};
 
 // `ragit::Chunk` is becoming more and more complicated and I don't want to
@@ -15,6 +15,14 @@ pub struct ChunkDetail {
     pub file: Option<String>,
     pub file_index: Option<usize>,
     pub page_no: Option<usize>,
+
+    // line range of the chunk in the file (see `ragit::SourceRange`)
+    #[serde(default)]
+    pub range: Option<SourceRange>,
+
+    // time range of the chunk in a recording (see `ragit::TimeRange`)
+    #[serde(default)]
+    pub time: Option<TimeRange>,
     pub timestamp: i64,
     pub model: String,
     pub ragit_version: String,
@@ -35,9 +43,9 @@ impl ChunkDetail {
 
 impl From<Chunk> for ChunkDetail {
     fn from(c: Chunk) -> ChunkDetail {
-        let (file, file_index, page_no) = match &c.source {
-            ChunkSource::File { path, index, page } => (Some(path.to_string()), Some(*index), page.clone()),
-            // _ => (None, None, None),
+        let (file, file_index, page_no, range, time) = match &c.source {
+            ChunkSource::File { path, index, page, range, time, .. } => (Some(path.to_string()), Some(*index), page.clone(), *range, *time),
+            // _ => (None, None, None, None, None),
         };
 
         ChunkDetail {
@@ -49,6 +57,8 @@ impl From<Chunk> for ChunkDetail {
             file,
             file_index,
             page_no,
+            range,
+            time,
             timestamp: c.timestamp,
             model: c.build_info.model.clone(),
             ragit_version: c.build_info.ragit_version.clone(),
@@ -201,7 +201,7 @@ impl AgentResponse {
                     }
                 },
                 ActionResult::ReadChunk(chunk) => {
-                    chunks.insert(chunk.uid, chunk.clone());
+                    chunks.insert(chunk.uid, (**chunk).clone());
                 },
 
                 // `ReadFileLong` and `Search` have chunks, but many of them are
@@ -2,7 +2,8 @@ use super::FileTree;
 use crate::Keywords;
 use crate::chunk::{Chunk, RenderedChunk};
 use crate::error::Error;
-use crate::index::Index;
+use crate::index::{Index, PositionalQuery};
+use crate::index::tfidf::tokenize;
 use crate::query::QueryResponse;
 use crate::uid::{Uid, UidQueryConfig};
 use ragit_cli::substr_edit_distance;
@@ -204,7 +205,7 @@ impl Action {
 
                     match chunk_uids.len() {
                         0 => ActionResult::NoSuchChunk(argument.to_string()),
-                        1 => ActionResult::ReadChunk(index.get_chunk_by_uid(chunk_uids[0])?),
+                        1 => ActionResult::ReadChunk(Box::new(index.get_chunk_by_uid(chunk_uids[0])?)),
                         2..=10 => {
                             let mut chunks = Vec::with_capacity(chunk_uids.len());
 
@@ -232,13 +233,25 @@ impl Action {
                     10
                 };
 
+                // An exact match of whole words is also a phrase match, so exact search first
+                // looks at the chunks that have the phrase. If the inverted-index has positions,
+                // it doesn't have to read the other chunks. But tokenizing is lossy (partial
+                // words, punctuation, `img_<uid>` in the data), so it's just a shortcut. It
+                // isn't used if the argument is changed by tokenizing, and it falls back to
+                // the full tfidf search if the phrase doesn't give enough exact matches.
+                let mut phrase = match *self {
+                    Action::SearchExact if tokenize(&argument).join(" ") == argument.to_ascii_lowercase() => PositionalQuery::phrase(&argument).into_iter().collect::<Vec<_>>(),
+                    _ => vec![],
+                };
+                let mut chunks_exact_match: Vec<Chunk> = vec![];
+
                 let chunks = 'chunks_loop: loop {
-                    let candidates = index.run_tfidf(
+                    let candidates = index.run_tfidf_with_positions(
                         Keywords::from_raw(vec![argument.to_string()]),
+                        &phrase,
                         limit,
                     )?;
                     let mut chunks = Vec::with_capacity(candidates.len());
-                    let mut chunks_exact_match = vec![];
 
                     for c in candidates.iter() {
                         chunks.push(index.get_chunk_by_uid(c.id)?);
@@ -249,6 +262,10 @@ impl Action {
                     }
 
                     for chunk in chunks.iter() {
+                        if chunks_exact_match.iter().any(|c| c.uid == chunk.uid) {
+                            continue;
+                        }
+
                         if chunk.title.contains(&argument)
                         || chunk.summary.contains(&argument)
                         || chunk.data.contains(&argument)
@@ -264,6 +281,12 @@ impl Action {
                     // We have a complete set of the tfidf result, so there's
                     // no point in increasing the limit.
                     if candidates.len() < limit || limit == index.chunk_count {
+                        if !phrase.is_empty() {
+                            phrase = vec![];
+                            limit = 100;
+                            continue;
+                        }
+
                         break chunks_exact_match;
                     }
 
@@ -363,7 +386,7 @@ pub enum ActionResult {
         dir: String,
         similar_dirs: Vec<String>,
     },
-    ReadChunk(Chunk),
+    ReadChunk(Box<Chunk>),
     NoSuchChunk(String),
     ReadChunkAmbiguous {
         query: String,
@@ -443,7 +466,8 @@ impl ActionResult {
                     String::new()
                 },
             ),
-            ActionResult::ReadChunk(chunk) => chunk.data.clone(),
+            // The source tells the AI where the chunk is (e.g. "src/foo.rs:120-188"), so that it can cite it.
+            ActionResult::ReadChunk(chunk) => format!("source: {}\n\n{}", chunk.render_source(), chunk.data),
             ActionResult::NoSuchChunk(query) => {
                 if !Uid::is_valid_prefix(&query) {
                     format!("{query:?} is not a valid uid. A uid is a 9 ~ 64 characters long hexadecimal string that uniquely identifies a chunk.")
@@ -23,6 +23,7 @@ pub struct PartialApiConfig {
     pub sleep_after_llm_call: Option<u64>,
     pub dump_log: Option<bool>,
     pub dump_api_usage: Option<bool>,
+    pub embedding_model: Option<String>,
 }
 
 impl PartialApiConfig {
@@ -52,6 +53,9 @@ impl PartialApiConfig {
         if let Some(dump_api_usage) = self.dump_api_usage {
             config.dump_api_usage = dump_api_usage.clone();
         }
+        if let Some(embedding_model) = &self.embedding_model {
+            config.embedding_model = Some(embedding_model.clone());
+        }
     }
 }
 
@@ -78,6 +82,12 @@ pub struct ApiConfig {
 
     /// It records how many tokens are used.
     pub dump_api_usage: bool,
+
+    /// A model in `models.json` that creates embeddings. Its `api_url` has to be
+    /// an OpenAI-compatible `/embeddings` endpoint. If it's set, `rag build` embeds
+    /// chunks and `rag query` fuses vector search results with tfidf results.
+    #[serde(default)]
+    pub embedding_model: Option<String>,
 }
 
 impl Default for ApiConfig {
@@ -91,6 +101,7 @@ impl Default for ApiConfig {
             timeout: Some(120_000),
             sleep_after_llm_call: None,
             model: String::from("llama3.3-70b-groq"),
+            embedding_model: None,
         }
     }
 }
@@ -1,7 +1,7 @@
 use chrono::Local;
 use crate::error::Error;
-use crate::index::{Index, tfidf};
-use crate::index::file::{AtomicToken, Image};
+use crate::index::{BuildConfig, Index, NearDuplicates, tfidf};
+use crate::index::file::{AtomicToken, Image, estimate_token_count};
 use crate::uid::Uid;
 use flate2::Compression;
 use flate2::read::{GzDecoder, GzEncoder};
@@ -37,7 +37,8 @@ mod tests;
 pub use build_info::ChunkBuildInfo;
 pub use multi_modal::{MultiModalContent, into_multi_modal_contents};
 pub use render::RenderedChunk;
-pub use source::ChunkSource;
+pub use source::{ChunkSource, MessageInfo, SourceRange, TimeRange};
+pub(crate) use source::render_timestamp;
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Chunk {
@@ -154,14 +155,15 @@ impl Chunk {
         result
     }
 
+    /// `build_config` is the config of the file (see `BuildConfig::resolve`), not `index.build_config`.
     pub(crate) async fn create_chunk_from(
         index: &Index,
+        build_config: &BuildConfig,
         tokens: &[AtomicToken],
-        file: String,
-        file_index: usize,
+        source: ChunkSource,
         build_info: ChunkBuildInfo,
         previous_turn: Option<(Chunk, ChunkSchema)>,
-        extra_info: Option<ChunkExtraInfo>,
+        near_duplicates: Option<&NearDuplicates>,
     ) -> Result<Self, Error> {
         let mut context = tera::Context::new();
         let mut chunk = vec![];  // what LLM actually sees when building a chunk
@@ -187,19 +189,26 @@ impl Chunk {
 
                 // These tokens are supposed to be filtered out before passed to this function
                 AtomicToken::PageBreak
-               | AtomicToken::ChunkExtraInfo(_) => {
+               | AtomicToken::ChunkExtraInfo(_)
+               | AtomicToken::SourcePosition { .. } => {
                     // invisible
                 },
             }
         }
 
+        // It tells the LLM where the chunk is from, if the file reader knows something more than
+        // the path of the file (e.g. name of the function or the section).
+        if let Some(location) = source.render_location() {
+            context.insert("chunk_source", &location);
+        }
+
         context.insert("chunk", &chunk.concat());
-        context.insert("max_summary_len", &index.build_config.max_summary_len);
+        context.insert("max_summary_len", &build_config.max_summary_len);
 
         // It's ridiculous to ask for a 300 characters summary from a 10 characters chunk.
         context.insert(
             "min_summary_len",
-            &index.build_config.min_summary_len.min(approx_data_len / 2),
+            &build_config.min_summary_len.min(approx_data_len / 2),
         );
 
         if let Some((previous_chunk, previous_schema)) = &previous_turn {
@@ -240,7 +249,8 @@ impl Chunk {
                 // invisible
                 // it must be filtered out before passed to this function
                 AtomicToken::PageBreak
-               | AtomicToken::ChunkExtraInfo(_) => {},
+               | AtomicToken::ChunkExtraInfo(_)
+               | AtomicToken::SourcePosition { .. } => {},
             }
         }
 
@@ -259,11 +269,29 @@ impl Chunk {
             ..Request::default()
         };
 
+        let twin = match near_duplicates {
+            Some(near_duplicates) => near_duplicates.find_twin(index, &data, &build_info),
+            None => None,
+        };
+        let mut build_info = build_info;
+
         // some apis reject empty requests
         let response = if data.is_empty() {
             ChunkSchema::empty()
-        } else {
-            request.send_and_validate::<ChunkSchema>(ChunkSchema::dummy(&data, index.build_config.max_summary_len)).await?
+        }
+
+        // A near-duplicate reuses the title and the summary of its twin,
+        // so there's no need to call the LLM.
+        else if let Some(twin) = twin {
+            build_info.duplicate_of = Some(twin.build_info.duplicate_of.unwrap_or(twin.uid));
+            ChunkSchema {
+                title: twin.title,
+                summary: twin.summary,
+            }
+        }
+
+        else {
+            request.send_and_validate::<ChunkSchema>(ChunkSchema::dummy(&data, build_config.max_summary_len)).await?
         };
 
         let mut result = Chunk {
@@ -273,11 +301,7 @@ impl Chunk {
             image_count,
             title: response.title,
             summary: response.summary,
-            source: ChunkSource::File {
-                path: normalize(&file)?,
-                index: file_index,
-                page: extra_info.map(|i| i.page_no).unwrap_or(None),
-            },
+            source,
             searchable: true,
             uid: Uid::dummy(),
             build_info,
@@ -288,6 +312,18 @@ impl Chunk {
         Ok(result)
     }
 
+    /// The estimated number of tokens of the text (see `estimate_token_count`).
+    /// Images are not counted.
+    pub fn token_len(&self) -> usize {
+        let mut data = self.data.clone();
+
+        for image in self.images.iter() {
+            data = data.replace(&format!("img_{image}"), "");
+        }
+
+        estimate_token_count(&data)
+    }
+
     pub fn render_source(&self) -> String {
         self.source.render()
     }
@@ -312,11 +348,11 @@ pub fn merge_and_convert_chunks(index: &Index, chunks: Vec<Chunk>) -> Result<Vec
 
     for chunk in chunks.into_iter() {
         match &chunk.source {
-            ChunkSource::File { path, index, page: _ } if *index > 0 => {
+            ChunkSource::File { path, index, .. } if *index > 0 => {
                 merge_candidates.insert((path.clone(), *index - 1));
                 curr_chunks.insert((path.clone(), *index), chunk);
             },
-            ChunkSource::File { path, index, page: _ } => {
+            ChunkSource::File { path, index, .. } => {
                 curr_chunks.insert((path.clone(), *index), chunk);
             },
             // NOTE: there used to be another `ChunkSource`
@@ -375,11 +411,21 @@ fn merge_chunks(pre: Chunk, post: Chunk) -> Chunk {
         path: pre_path,
         index: pre_index,
         page: pre_page,
+        item: pre_item,
+        heading: pre_heading,
+        range: pre_range,
+        time: pre_time,
+        message: pre_message,
     } = pre.source.clone();
     let ChunkSource::File {
         path: post_path,
         index: post_index,
         page: post_page,
+        item: post_item,
+        heading: _,
+        range: post_range,
+        time: post_time,
+        message: _,
     } = post.source.clone();
     assert_eq!(pre_path, post_path);
     assert_eq!(pre_index + 1, post_index);
@@ -388,6 +434,19 @@ fn merge_chunks(pre: Chunk, post: Chunk) -> Chunk {
     } else {
         None
     };
+    let item = if pre_item == post_item {
+        pre_item
+    } else {
+        None
+    };
+    let range = match (pre_range, post_range) {
+        (Some(pre), Some(post)) => Some(pre.extend(&post)),
+        _ => None,
+    };
+    let time = match (pre_time, post_time) {
+        (Some(pre), Some(post)) => Some(pre.extend(&post)),
+        _ => None,
+    };
 
     let Chunk {
         data: data_pre,
@@ -413,7 +472,8 @@ fn merge_chunks(pre: Chunk, post: Chunk) -> Chunk {
         timestamp: Local::now().timestamp(),
 
         // When 1st and 2nd chunks are merged, the result is 1st, not 2nd.
-        source: ChunkSource::File { path: pre_path, index: pre_index, page: page_no },
+        // The merged chunk starts where the 1st chunk starts, so it belongs to the 1st chunk's section (and message).
+        source: ChunkSource::File { path: pre_path, index: pre_index, page: page_no, item, heading: pre_heading, range, time, message: pre_message },
 
         // If source is `File`, it must be searchable
         searchable: true,
@@ -487,11 +547,26 @@ impl From<&Chunk> for ChunkSchema {
 /// I know it's becoming overly complicated... but I cannot help it.
 /// Sometimes `FileReader`s want to add extra information to a chunk
 /// (e.g. page number) that is only available to them. In those cases,
-/// they can use `AtomicToken::PageBreak { extra_info: ChunkExtraInfo }`
-/// to pass the information.
-#[derive(Clone, Copy, Debug)]
+/// they can use `AtomicToken::ChunkExtraInfo` to pass the information.
+#[derive(Clone, Debug, Default)]
 pub struct ChunkExtraInfo {
     pub page_no: Option<usize>,
+
+    /// The name of the item (e.g. "fn `build_worker`") in a source code file.
+    pub item: Option<String>,
+
+    /// The path of the headings (e.g. "Guide > Install > Linux") in a markdown file.
+    pub heading: Option<String>,
+
+    /// File readers don't have to set this field. `FileReader` fills it
+    /// if the file reader emits `AtomicToken::SourcePosition`.
+    pub range: Option<SourceRange>,
+
+    /// Start and end of the chunk in a recording (e.g. subtitles).
+    pub time: Option<TimeRange>,
+
+    /// Headers of the message in an email file.
+    pub message: Option<MessageInfo>,
 }
 
 impl ChunkExtraInfo {
@@ -504,6 +579,54 @@ impl ChunkExtraInfo {
             (None, None) => None,
         };
 
-        ChunkExtraInfo { page_no }
+        let item = match (&self.item, &other.item) {
+            (Some(a), Some(b)) if a == b || a.ends_with(", ...") => Some(a.to_string()),
+
+            // I don't want it to be too long
+            (Some(a), Some(_)) if a.len() > 120 => Some(format!("{a}, ...")),
+            (Some(a), Some(b)) => Some(format!("{a}, {b}")),
+            (Some(a), None) | (None, Some(a)) => Some(a.to_string()),
+            (None, None) => None,
+        };
+
+        // A chunk belongs to the section where it starts.
+        let heading = match (&self.heading, &other.heading) {
+            (Some(h), _) | (None, Some(h)) => Some(h.to_string()),
+            (None, None) => None,
+        };
+
+        let range = match (&self.range, &other.range) {
+            (Some(a), Some(b)) => Some(a.extend(b)),
+            (Some(r), None) | (None, Some(r)) => Some(*r),
+            (None, None) => None,
+        };
+
+        let time = match (&self.time, &other.time) {
+            (Some(a), Some(b)) => Some(a.extend(b)),
+            (Some(t), None) | (None, Some(t)) => Some(*t),
+            (None, None) => None,
+        };
+
+        // A chunk belongs to the message where it starts.
+        let message = match (&self.message, &other.message) {
+            (Some(m), _) | (None, Some(m)) => Some(m.clone()),
+            (None, None) => None,
+        };
+
+        ChunkExtraInfo { page_no, item, heading, range, time, message }
+    }
+
+    /// The source of the `index`th chunk of `path`.
+    pub fn into_file_source(self, path: &str, index: usize) -> Result<ChunkSource, Error> {
+        Ok(ChunkSource::File {
+            path: normalize(path)?,
+            index,
+            page: self.page_no,
+            item: self.item,
+            heading: self.heading,
+            range: self.range,
+            time: self.time,
+            message: self.message,
+        })
     }
 }
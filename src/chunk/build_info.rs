@@ -1,3 +1,5 @@
+use crate::index::{BuildOverrides, FileEncoding, Path};
+use crate::uid::Uid;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -6,6 +8,24 @@ pub struct ChunkBuildInfo {
     pub prompt_hash: String,
     pub model: String,
     pub ragit_version: String,
+
+    // It's `None` if the file is UTF-8 or the file reader doesn't care about encodings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<FileEncoding>,
+
+    // `build_rules` that were applied to the file. If the rules change, the file has to be rebuilt.
+    #[serde(default, skip_serializing_if = "BuildOverrides::is_empty")]
+    pub build_overrides: BuildOverrides,
+
+    // Files that the file reader read other than the file itself (e.g. `\input` of LaTeX),
+    // and their uids. If any of them changes, the file has to be rebuilt.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<(Path, Uid)>,
+
+    // If it's set, the chunk is a near-duplicate of this chunk, and its title and summary
+    // are copied from the chunk instead of calling the LLM. See `detect_near_duplicates`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<Uid>,
 }
 
 impl ChunkBuildInfo {
@@ -15,6 +35,10 @@ impl ChunkBuildInfo {
             prompt_hash: String::new(),
             model: String::new(),
             ragit_version: String::new(),
+            encoding: None,
+            build_overrides: BuildOverrides::default(),
+            dependencies: vec![],
+            duplicate_of: None,
         }
     }
 
@@ -22,12 +46,19 @@ impl ChunkBuildInfo {
         file_reader_key: String,
         prompt_hash: String,
         model: String,
+        encoding: Option<FileEncoding>,
+        build_overrides: BuildOverrides,
+        dependencies: Vec<(Path, Uid)>,
     ) -> Self {
         ChunkBuildInfo {
             file_reader_key,
             prompt_hash,
             model,
             ragit_version: crate::VERSION.to_string(),
+            encoding,
+            build_overrides,
+            dependencies,
+            duplicate_of: None,
         }
     }
 }
@@ -12,19 +12,174 @@ pub enum ChunkSource {
 
         // If the chunk is from a pdf file, it tells which page the chunk is from.
         page: Option<usize>,
+
+        // If the chunk is from a source code file, it tells which item (function, impl, class, ...)
+        // the chunk is from. e.g. "fn `build_worker`"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        item: Option<String>,
+
+        // If the chunk is from a markdown file, it tells which section the chunk is from.
+        // e.g. "Guide > Install > Linux"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        heading: Option<String>,
+
+        // If the file reader reads the file line by line (e.g. markdown, plain text, source code),
+        // it tells where the chunk is in the file. Chunks that are built by older versions of ragit
+        // don't have this field.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        range: Option<SourceRange>,
+
+        // If the chunk is from a subtitle or a transcript, it tells which part of the recording
+        // the chunk is about.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        time: Option<TimeRange>,
+
+        // If the chunk is from an email, it tells the headers of the message where the chunk starts.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<MessageInfo>,
     },
 }
 
+/// Where a chunk is in the original file.
+///
+/// Lines are 1-base and inclusive, like the line numbers of your editor.
+/// Bytes are 0-base and exclusive (`start_byte..end_byte`), so that you can slice the file with them.
+/// If a chunk ends with a newline character, `end_line` is the line of the newline character.
+/// If the file is not UTF-8 (see `FileEncoding`), bytes are offsets in the original file, not in the converted text.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SourceRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl SourceRange {
+    /// A range that covers both `self` and `other`. `other` has to come after `self`.
+    pub fn extend(&self, other: &SourceRange) -> SourceRange {
+        SourceRange {
+            start_line: self.start_line,
+            end_line: other.end_line.max(self.end_line),
+            start_byte: self.start_byte,
+            end_byte: other.end_byte.max(self.end_byte),
+        }
+    }
+
+    /// e.g. "120-188", or "120" if it's a single line
+    pub fn render_lines(&self) -> String {
+        if self.start_line == self.end_line {
+            self.start_line.to_string()
+        } else {
+            format!("{}-{}", self.start_line, self.end_line)
+        }
+    }
+}
+
+/// Start and end of a chunk in a recording, in milliseconds.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TimeRange {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl TimeRange {
+    /// A range that covers both `self` and `other`. `other` has to come after `self`.
+    pub fn extend(&self, other: &TimeRange) -> TimeRange {
+        TimeRange {
+            start_ms: self.start_ms,
+            end_ms: other.end_ms.max(self.end_ms),
+        }
+    }
+
+    /// e.g. "00:12:03–00:15:40"
+    pub fn render(&self) -> String {
+        format!("{}–{}", render_timestamp(self.start_ms), render_timestamp(self.end_ms))
+    }
+}
+
+/// Headers of an email message. Addresses are kept as they are in the headers
+/// (e.g. "Alice <alice@example.com>").
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct MessageInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// `To` and `Cc` of the message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub to: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+impl MessageInfo {
+    /// e.g. "`Release schedule` from Alice <alice@example.com> to list@example.com on Mon, 3 Mar 2025 10:00:00 +0900"
+    pub fn render(&self) -> String {
+        let mut result = vec![];
+
+        if let Some(subject) = &self.subject {
+            result.push(format!("`{subject}`"));
+        }
+
+        if let Some(from) = &self.from {
+            result.push(format!("from {from}"));
+        }
+
+        if !self.to.is_empty() {
+            result.push(format!("to {}", self.to.join(", ")));
+        }
+
+        if let Some(date) = &self.date {
+            result.push(format!("on {date}"));
+        }
+
+        result.join(" ")
+    }
+
+    fn hash_str(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.subject.as_deref().unwrap_or(""),
+            self.from.as_deref().unwrap_or(""),
+            self.to.join("\n"),
+            self.date.as_deref().unwrap_or(""),
+        )
+    }
+}
+
 impl ChunkSource {
     // this value is directly used to hash this instance
     pub fn hash_str(&self) -> String {
         match self {
-            ChunkSource::File { path, index, page } => format!(
-                "{path}{index}{}",
+            ChunkSource::File { path, index, page, item, heading, range, time, message } => format!(
+                "{path}{index}{}{}{}{}{}{}",
                 match page {
                     Some(page) => format!("p{page}"),
                     None => String::new(),
                 },
+                match item {
+                    Some(item) => format!("i{item}"),
+                    None => String::new(),
+                },
+                match heading {
+                    Some(heading) => format!("h{heading}"),
+                    None => String::new(),
+                },
+                match range {
+                    Some(range) => format!("l{}-{}b{}-{}", range.start_line, range.end_line, range.start_byte, range.end_byte),
+                    None => String::new(),
+                },
+                match time {
+                    Some(time) => format!("t{}-{}", time.start_ms, time.end_ms),
+                    None => String::new(),
+                },
+                match message {
+                    Some(message) => format!("m{}", message.hash_str()),
+                    None => String::new(),
+                },
             ),
         }
     }
@@ -47,27 +202,94 @@ impl ChunkSource {
             // 1. `index` is mandatory but `page` is optional.
             // 2. `index` is guaranteed to be unique and sequential,
             //    while `page` can have arbitrary values (it's up to file readers).
-            ChunkSource::File { path, index, page: _ } => format!("file: {path}-{index:09}"),
+            ChunkSource::File { path, index, .. } => format!("file: {path}-{index:09}"),
         }
     }
 
     pub fn render(&self) -> String {
         match self {
-            ChunkSource::File { path, index, page } => format!(
-                "{} chunk of {path}{}",
+            ChunkSource::File { path, index, page, item, heading, range, time, message } => {
+                // e.g. "src/foo.rs:120-188"
+                let path = render_path(path, range);
+
                 // it's 0-base
-                match index {
+                let nth = match index {
                     0 => String::from("1st"),
                     1 => String::from("2nd"),
                     2 => String::from("3rd"),
                     n => format!("{}th", n + 1),
-                },
-                // it's 1-base
-                match page {
-                    Some(page) => format!(" (page {page})"),
-                    None => String::new(),
-                },
-            ),
+                };
+
+                match (item, heading) {
+                    // e.g. "message 3 (`Re: Release schedule` from Carol <carol@example.com> ...) in list.mbox (3rd chunk)"
+                    (Some(item), None) if message.is_some() => format!("{item} ({}) in {path} ({nth} chunk)", message.as_ref().unwrap().render()),
+
+                    // e.g. "fn `build_worker` in src/index/commands/build.rs (2nd chunk)"
+                    (Some(item), None) => format!("{item} in {path} ({nth} chunk)"),
+
+                    // e.g. "section `Guide > Install` in docs/guide.md (2nd chunk)"
+                    (None, Some(heading)) => format!("section `{heading}` in {path} ({nth} chunk)"),
+
+                    // e.g. "cell 3 (section `Analysis`) in analysis.ipynb (2nd chunk)"
+                    (Some(item), Some(heading)) => format!("{item} (section `{heading}`) in {path} ({nth} chunk)"),
+
+                    // e.g. "00:12:03–00:15:40 of talks/keynote.vtt"
+                    (None, None) if time.is_some() => format!("{} of {path}", time.unwrap().render()),
+                    (None, None) => format!(
+                        "{nth} chunk of {path}{}",
+                        // it's 1-base
+                        match page {
+                            Some(page) => format!(" (page {page})"),
+                            None => String::new(),
+                        },
+                    ),
+                }
+            },
         }
     }
+
+    /// It tells where the chunk is from, if the file reader knows something more than
+    /// the path of the file (e.g. name of the function or the section).
+    pub fn render_location(&self) -> Option<String> {
+        match self {
+            ChunkSource::File { path, item, heading, range, time, message, .. } => match (item, heading) {
+                (Some(item), None) if message.is_some() => Some(format!("{item} ({}) in {}", message.as_ref().unwrap().render(), render_path(path, range))),
+                (Some(item), None) => Some(format!("{item} in {}", render_path(path, range))),
+                (None, Some(heading)) => Some(format!("from section `{heading}` of {}", render_path(path, range))),
+                (Some(item), Some(heading)) => Some(format!("{item} (section `{heading}`) in {}", render_path(path, range))),
+                (None, None) => time.map(|time| format!("{} of {}", time.render(), render_path(path, range))),
+            },
+        }
+    }
+
+    pub fn range(&self) -> Option<SourceRange> {
+        match self {
+            ChunkSource::File { range, .. } => *range,
+        }
+    }
+
+    pub fn time(&self) -> Option<TimeRange> {
+        match self {
+            ChunkSource::File { time, .. } => *time,
+        }
+    }
+
+    pub fn message(&self) -> Option<&MessageInfo> {
+        match self {
+            ChunkSource::File { message, .. } => message.as_ref(),
+        }
+    }
+}
+
+fn render_path(path: &str, range: &Option<SourceRange>) -> String {
+    match range {
+        Some(range) => format!("{path}:{}", range.render_lines()),
+        None => path.to_string(),
+    }
+}
+
+// e.g. "01:02:03" (milliseconds are dropped)
+pub(crate) fn render_timestamp(ms: u64) -> String {
+    let seconds = ms / 1000;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
 }
@@ -1,4 +1,4 @@
-use crate::chunk::{Chunk, ChunkSource};
+use crate::chunk::{Chunk, ChunkSource, MessageInfo, TimeRange};
 use crate::index::Index;
 use super::merge_and_convert_chunks;
 
@@ -41,6 +41,11 @@ fn test_merge_and_convert_chunks() {
                         path: file.to_string(),
                         index,
                         page: None,
+                        item: None,
+                        heading: None,
+                        range: None,
+                        time: None,
+                        message: None,
                     },
                 )
             ).collect::<Vec<_>>(),
@@ -59,10 +64,52 @@ fn test_merge_and_convert_chunks() {
         let answer = answer.into_iter().map(
             |(data, file, index)| (
                 data.to_string(),
-                ChunkSource::File { path: file.to_string(), index, page: None }.render(),
+                ChunkSource::File { path: file.to_string(), index, page: None, item: None, heading: None, range: None, time: None, message: None }.render(),
             )
         ).collect::<Vec<_>>();
 
         assert_eq!(result, answer);
     }
 }
+
+#[test]
+fn test_time_range() {
+    let source = ChunkSource::File {
+        path: String::from("talks/keynote.vtt"),
+        index: 3,
+        page: None,
+        item: None,
+        heading: None,
+        range: None,
+        time: Some(TimeRange { start_ms: 723_000, end_ms: 940_500 }),
+        message: None,
+    };
+
+    assert_eq!(source.render(), "00:12:03–00:15:40 of talks/keynote.vtt");
+    assert_eq!(source.render_location().unwrap(), "00:12:03–00:15:40 of talks/keynote.vtt");
+
+    let time = TimeRange { start_ms: 0, end_ms: 5_000 }.extend(&TimeRange { start_ms: 4_000, end_ms: 3_725_000 });
+    assert_eq!(time.render(), "00:00:00–01:02:05");
+}
+
+#[test]
+fn test_message_info() {
+    let source = ChunkSource::File {
+        path: String::from("list.mbox"),
+        index: 2,
+        page: None,
+        item: Some(String::from("message 3")),
+        heading: None,
+        range: None,
+        time: None,
+        message: Some(MessageInfo {
+            subject: Some(String::from("Re: Release schedule")),
+            from: Some(String::from("Carol <carol@example.com>")),
+            to: vec![String::from("list@example.com"), String::from("Bob <bob@example.com>")],
+            date: None,
+        }),
+    };
+
+    assert_eq!(source.render(), "message 3 (`Re: Release schedule` from Carol <carol@example.com> to list@example.com, Bob <bob@example.com>) in list.mbox (3rd chunk)");
+    assert_eq!(source.render_location().unwrap(), "message 3 (`Re: Release schedule` from Carol <carol@example.com> to list@example.com, Bob <bob@example.com>) in list.mbox");
+}
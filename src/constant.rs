@@ -3,6 +3,7 @@ pub const ARCHIVE_DIR_NAME: &str = "archives";
 pub const BUILD_CONFIG_FILE_NAME: &str = "build.json";
 pub const CHUNK_DIR_NAME: &str = "chunks";
 pub const CONFIG_DIR_NAME: &str = "configs";
+pub const EMBEDDING_DIR_NAME: &str = "embeddings";
 pub const FILE_INDEX_DIR_NAME: &str = "files";
 pub const II_DIR_NAME: &str = "ii";
 pub const IMAGE_DIR_NAME: &str = "images";
@@ -10,7 +11,12 @@ pub const INDEX_DIR_NAME: &str = ".ragit";
 pub const INDEX_FILE_NAME: &str = "index.json";
 pub const LOG_DIR_NAME: &str = "logs";
 pub const METADATA_FILE_NAME: &str = "meta.json";
+pub const MINHASH_FILE_NAME: &str = "minhash";
 pub const MODEL_FILE_NAME: &str = "models.json";
 pub const PROMPT_DIR_NAME: &str = "prompts";
 pub const QUERY_CONFIG_FILE_NAME: &str = "query.json";
 pub const QUERY_HISTORY_DIR_NAME: &str = "queries";
+pub const VIRTUAL_FILE_DIR_NAME: &str = "virtual_files";
+
+/// `bundle.zip!/docs/a.md` is `docs/a.md` in `bundle.zip`.
+pub const VIRTUAL_FILE_SEPARATOR: &str = "!/";
@@ -58,6 +58,10 @@ pub enum Error {
         key: String,
         message: String,
     },
+    InvalidBuildRule {
+        pattern: String,
+        message: String,
+    },
     InvalidMergePrefix(String),
     MergeConflict(Uid),
     MPSCError(String),
@@ -107,6 +111,14 @@ pub enum Error {
     /// see <https://docs.rs/mupdf/latest/mupdf/error/enum.Error.html>
     MuPdfError(mupdf::Error),
 
+    #[cfg(any(feature = "office", feature = "epub", feature = "archive"))]
+    /// see <https://docs.rs/zip/latest/zip/result/enum.ZipError.html>
+    ZipError(zip::result::ZipError),
+
+    #[cfg(any(feature = "office", feature = "epub"))]
+    /// see <https://docs.rs/roxmltree/latest/roxmltree/enum.Error.html>
+    XmlError(roxmltree::Error),
+
     #[cfg(feature = "svg")]
     /// see <https://docs.rs/usvg/0.45.1/usvg/enum.Error.html>
     UsvgError(resvg::usvg::Error),
@@ -191,6 +203,20 @@ impl From<mupdf::Error> for Error {
     }
 }
 
+#[cfg(any(feature = "office", feature = "epub", feature = "archive"))]
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::ZipError(e)
+    }
+}
+
+#[cfg(any(feature = "office", feature = "epub"))]
+impl From<roxmltree::Error> for Error {
+    fn from(e: roxmltree::Error) -> Self {
+        Error::XmlError(e)
+    }
+}
+
 #[cfg(feature = "svg")]
 impl From<resvg::usvg::Error> for Error {
     fn from(e: resvg::usvg::Error) -> Self {
@@ -63,9 +63,13 @@ use std::collections::{HashMap, HashSet};
 
 mod commands;
 mod config;
+mod embedding;
 pub mod file;
 mod ii;
+mod near_duplicate;
+mod phrase;
 pub mod tfidf;
+mod virtual_file;
 
 pub use commands::{
     AddMode,
@@ -84,10 +88,13 @@ pub use commands::{
     VersionInfo,
     get_compatibility_warning,
 };
-pub use config::BuildConfig;
-pub use file::{FileReader, ImageDescription};
+pub use config::{BuildConfig, BuildOverrides, BuildRule, ChunkUnit, FileReaderKind, FileReaderRule, PdfMode};
+pub use embedding::{Embeddings, reciprocal_rank_fusion};
+pub use file::{EncodingDetection, FileEncoding, FileReader, ImageDescription, estimate_token_count};
 pub use ii::IIStatus;
-pub use tfidf::{ProcessedDoc, TfidfResult, TfidfState, consume_processed_doc};
+pub use near_duplicate::{MinHash, NearDuplicates, collapse_near_duplicates};
+pub use phrase::{PositionalQuery, parse_positional_query};
+pub use tfidf::{ProcessedDoc, Ranking, TfidfResult, TfidfState, consume_processed_doc};
 
 pub type Path = String;
 
@@ -368,6 +375,11 @@ impl Index {
         let mut result = serde_json::from_str::<Index>(&index_json)?;
         result.root_dir = root_dir;
 
+        // An ii built by an older version of ragit cannot be read anymore.
+        if result.ii_status == IIStatus::Complete && result.has_old_ii() {
+            result.ii_status = IIStatus::Outdated;
+        }
+
         if let Some(warn) = get_compatibility_warning(&result.ragit_version, crate::VERSION) {
             eprintln!("Warning: {warn}");
         }
@@ -412,15 +424,36 @@ impl Index {
         limit: usize,
     ) -> Result<Vec<Chunk>, Error> {
         if self.chunk_count > limit {
-            let keywords = self.extract_keywords(query).await?;
-            let tfidf_results = self.run_tfidf(
+            let mut keywords = self.extract_keywords(query).await?;
+
+            // An LLM extracts keywords from the query, so quoted phrases and `NEAR/k`
+            // have to be parsed from the raw query. The raw words are added to the
+            // keywords, so that the chunks that match the phrases always have a score.
+            // A user might quote a few words of a natural-language question, so the
+            // phrases only boost the chunks that match them, instead of filtering the others.
+            let (positional_queries, raw_keywords) = parse_positional_query(query);
+
+            if !positional_queries.is_empty() {
+                keywords.extra.extend(raw_keywords);
+            }
+
+            let tfidf_results = self.run_tfidf_with_phrase_boost(
                 keywords,
+                &positional_queries,
                 limit,
             )?;
-            let mut chunks = Vec::with_capacity(tfidf_results.len());
+            let mut uids = tfidf_results.into_iter().map(|tfidf_result| tfidf_result.id).collect::<Vec<_>>();
+
+            // Keyword search misses paraphrases and vector search misses exact terms.
+            // Reciprocal rank fusion takes the best of both without comparing their scores.
+            if self.api_config.embedding_model.is_some() {
+                let vector_results = self.run_vector_search(query, limit).await?;
+                uids = reciprocal_rank_fusion(&[uids, vector_results], limit);
+            }
 
-            for tfidf_result in tfidf_results.into_iter() {
-                let uid = tfidf_result.id;
+            let mut chunks = Vec::with_capacity(uids.len());
+
+            for uid in uids.into_iter() {
                 chunks.push(self.get_chunk_by_uid(uid)?);
             }
 
@@ -608,7 +641,18 @@ impl Index {
         keywords: Keywords,
         limit: usize,
     ) -> Result<Vec<TfidfResult<Uid>>, Error> {
-        let mut tfidf_state = TfidfState::new(&keywords);
+        self.run_tfidf_with_boost(keywords, limit, &HashMap::new())
+    }
+
+    /// Like `run_tfidf`, but the score of each chunk in `boost` is multiplied by its value.
+    /// The boosted chunks are always scored, even if the inverted-index doesn't choose them.
+    pub(crate) fn run_tfidf_with_boost(
+        &self,
+        keywords: Keywords,
+        limit: usize,
+        boost: &HashMap<Uid, f32>,
+    ) -> Result<Vec<TfidfResult<Uid>>, Error> {
+        let mut tfidf_state = TfidfState::new(&keywords).with_ranking(self.query_config.ranking);
 
         // TODO: I'm still trying to figure out the best value for `ii_coeff`.
         //       I found that 20 is too small. 50 works on most cases, but `tests/ii.py` is still failing.
@@ -616,10 +660,23 @@ impl Index {
         let ii_coeff = 50;
 
         if self.query_config.enable_ii && self.is_ii_built() {
-            for chunk_uid in self.get_search_candidates(
+            // The candidates are a small subset of the knowledge-base, so BM25 would
+            // get a wrong idf from them.
+            if self.query_config.ranking != Ranking::Tfidf {
+                let document_frequency = self.get_document_frequencies(tfidf_state.terms.keys())?;
+                tfidf_state.set_corpus_stat(self.chunk_count, document_frequency);
+            }
+
+            let mut candidates = self.get_search_candidates(
                 &tfidf_state.terms,
                 limit * ii_coeff,
-            )? {
+            )?;
+            let candidate_set = candidates.iter().copied().collect::<HashSet<_>>();
+            let mut boosted = boost.keys().filter(|uid| !candidate_set.contains(uid)).copied().collect::<Vec<_>>();
+            boosted.sort();
+            candidates.extend(boosted);
+
+            for chunk_uid in candidates {
                 let processed_doc = self.get_tfidf_by_chunk_uid(chunk_uid)?;
                 consume_processed_doc(
                     processed_doc,
@@ -638,7 +695,22 @@ impl Index {
             }
         }
 
-        Ok(tfidf_state.get_top(limit))
+        if boost.is_empty() {
+            return Ok(tfidf_state.get_top(limit));
+        }
+
+        // A chunk below `limit` might get to the top after it's boosted.
+        let mut result = tfidf_state.get_top(usize::MAX);
+
+        for r in result.iter_mut() {
+            if let Some(boost) = boost.get(&r.id) {
+                r.score *= boost;
+            }
+        }
+
+        result.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        result.truncate(limit);
+        Ok(result)
     }
 
     pub fn run_tfidf_on(
@@ -647,7 +719,7 @@ impl Index {
         keywords: Keywords,
         limit: usize,
     ) -> Result<Vec<TfidfResult<Uid>>, Error> {
-        let mut tfidf_state = TfidfState::new(&keywords);
+        let mut tfidf_state = TfidfState::new(&keywords).with_ranking(self.query_config.ranking);
 
         for chunk in chunks.iter() {
             let processed_doc = self.get_tfidf_by_chunk_uid(*chunk)?;
@@ -785,23 +857,6 @@ impl Index {
         Ok(result)
     }
 
-    // root_dir/.ragit/ii/term_hash_prefix/term_hash_suffix
-    fn get_ii_path(root_dir: &str, term_hash: String) -> Path {
-        let ii_at = join3(
-            root_dir,
-            &INDEX_DIR_NAME,
-            &II_DIR_NAME,
-        ).unwrap();
-        let term_hash_prefix = term_hash.get(0..2).unwrap().to_string();
-        let term_hash_suffix = term_hash.get(2..).unwrap().to_string();
-
-        join3(
-            &ii_at,
-            &term_hash_prefix,
-            &term_hash_suffix,
-        ).unwrap()
-    }
-
     fn get_api_config_path(&self) -> Result<Path, Error> {
         Ok(Index::get_rag_path(
             &self.root_dir,
@@ -1,9 +1,11 @@
 use super::Index;
 use crate::constant::INDEX_DIR_NAME;
 use crate::error::Error;
+use crate::index::virtual_file::is_archive;
 use crate::uid::Uid;
 use ragit_fs::{exists, get_relative_path, is_dir, is_symlink, join, read_string};
 use ragit_ignore::Ignore;
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
@@ -52,6 +54,8 @@ impl Index {
     /// |-----------|--------------------|------------------------|--------------|-----------|-----------|
     /// | ignore    | stage              | ignore                 | ignore       | stage     | error     |
     /// | n ignore  | stage              | ignore                 | ignore       | stage     | error     |
+    ///
+    /// A processed file is also "modified" if `build_rules` that apply to the file have changed.
     pub fn add_files(
         &mut self,
         files: &Vec<String>,
@@ -144,6 +148,39 @@ impl Index {
             }
         }
 
+        // `rag add bundle.zip` adds the files in the archive (`bundle.zip!/docs/a.md`).
+        // It also remembers the uids of the files in the archives, so that it doesn't
+        // have to read the archives again.
+        let mut virtual_file_uids = HashMap::new();
+        let unfolded_files = {
+            let mut result = Vec::with_capacity(unfolded_files.len());
+
+            for file in unfolded_files.into_iter() {
+                if is_archive(&file) {
+                    match self.unfold_archive(&file) {
+                        Ok(files) => {
+                            for (file, uid) in files.into_iter() {
+                                result.push(file.clone());
+                                virtual_file_uids.insert(file, uid);
+                            }
+                        },
+                        Err(e) => {
+                            return Err(Error::CannotAddFile {
+                                message: format!("failed to read `{file}`: {e:?}"),
+                                file,
+                            });
+                        },
+                    }
+                }
+
+                else {
+                    result.push(file);
+                }
+            }
+
+            result
+        };
+
         let mut newly_staged_files = vec![];
         let mut ignored_file: Option<String> = None;  // for an error message
 
@@ -154,9 +191,13 @@ impl Index {
             }
 
             else if let Some(prev_hash) = self.processed_files.get(file) {
-                let curr_hash = Uid::new_file(&self.root_dir, &join(&self.root_dir, &file)?)?;
+                let curr_hash = match virtual_file_uids.get(file) {
+                    Some(uid) => *uid,
+                    None => self.get_file_uid(file)?,
+                };
+                let is_modified = *prev_hash != curr_hash || self.has_stale_build_info(file, *prev_hash)?;
 
-                match (mode, *prev_hash != curr_hash) {
+                match (mode, is_modified) {
                     (None, true) => {
                         result.staged += 1;
                         newly_staged_files.push(file.to_string());
@@ -207,6 +248,33 @@ impl Index {
         Ok(result)
     }
 
+    // It compares the `build_rules` of the file with the ones that were applied when the file was built.
+    // It also checks whether the dependencies of the file (e.g. `\input` of LaTeX) have changed.
+    fn has_stale_build_info(&self, file: &str, file_uid: Uid) -> Result<bool, Error> {
+        match self.get_chunks_of_file(file_uid)?.first() {
+            Some(chunk_uid) => {
+                let chunk = self.get_chunk_by_uid(*chunk_uid)?;
+
+                if chunk.build_info.build_overrides != self.build_config.get_build_overrides(file) {
+                    return Ok(true);
+                }
+
+                for (dependency, uid) in chunk.build_info.dependencies.iter() {
+                    let real_path = Index::get_data_path(&self.root_dir, dependency)?;
+
+                    if !exists(&real_path) || Uid::new_file(&self.root_dir, &real_path)? != *uid {
+                        return Ok(true);
+                    }
+                }
+
+                Ok(false)
+            },
+
+            // an empty file
+            None => Ok(false),
+        }
+    }
+
     pub fn read_ignore_file(&self) -> Result<Ignore, Error> {
         let mut ignore_file_at = String::new();
 
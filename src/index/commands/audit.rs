@@ -49,6 +49,8 @@ impl Index {
             "rephrase_multi_turn",
             "raw_request",
             "extract_keywords",
+            "embed_chunks",
+            "embed_query",
             "agent",
             "pdl",
         ] {
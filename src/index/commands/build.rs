@@ -1,18 +1,23 @@
 use super::{Index, erase_lines};
 use crate::chunk;
-use crate::constant::{CHUNK_DIR_NAME, IMAGE_DIR_NAME};
+use crate::constant::{CHUNK_DIR_NAME, IMAGE_DIR_NAME, INDEX_DIR_NAME, MINHASH_FILE_NAME};
 use crate::error::Error;
 use crate::index::{
     ChunkBuildInfo,
     FileReader,
     IIStatus,
     LoadMode,
+    NearDuplicates,
+    Path,
+    near_duplicate,
 };
 use crate::uid::Uid;
 use ragit_api::audit::AuditRecord;
 use ragit_fs::{
     WriteMode,
     exists,
+    get_relative_path,
+    join3,
     parent,
     remove_file,
     set_extension,
@@ -20,7 +25,9 @@ use ragit_fs::{
     write_bytes,
 };
 use sha3::{Digest, Sha3_256};
+use std::collections::HashSet;
 use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
@@ -34,7 +41,14 @@ pub struct BuildResult {
 
 impl Index {
     pub async fn build(&mut self, workers: usize, quiet: bool) -> Result<BuildResult, Error> {
-        let mut workers = init_workers(workers, self.root_dir.clone());
+        let near_duplicates = if self.build_config.detect_near_duplicates && !self.staged_files.is_empty() {
+            Some(Arc::new(self.load_near_duplicates()?))
+        } else {
+            None
+        };
+
+        let virtual_file_uids = self.extract_virtual_files()?;
+        let mut workers = init_workers(workers, self.root_dir.clone(), near_duplicates.clone());
         let started_at = Instant::now();
 
         // TODO: API is messy. I want `rag build` to be generous. When it fails to process
@@ -42,7 +56,7 @@ impl Index {
         // return on error but pushes the errors to `result.errors`.
         // Still, there're unrecoverable errors. They just kill all the workers and return immediately.
         // That's why the code is messy with `?` and `match _ { Err(_) => {} }`
-        match self.build_worker(&mut workers, started_at, quiet).await {
+        let result = match self.build_worker(&mut workers, &virtual_file_uids, started_at, quiet).await {
             Ok(result) => {
                 if !quiet {
                     let elapsed_time = Instant::now().duration_since(started_at).as_secs();
@@ -81,12 +95,21 @@ impl Index {
 
                 Err(e)
             },
+        };
+
+        self.clean_up_virtual_files()?;
+
+        if let Some(near_duplicates) = &near_duplicates {
+            self.save_near_duplicates(near_duplicates)?;
         }
+
+        result
     }
 
     async fn build_worker(
         &mut self,
         workers: &mut Vec<Channel>,
+        virtual_file_uids: &HashMap<Path, Uid>,
         started_at: Instant,
         quiet: bool,
     ) -> Result<BuildResult, Error> {
@@ -269,7 +292,7 @@ impl Index {
                 ).map(
                     |staged_file| staged_file.to_string()
                 ).collect();
-                let mut ii_buffer = HashMap::new();
+                let mut added_chunk_uids = vec![];
 
                 for file in curr_completed_files.iter() {
                     let real_path = Index::get_data_path(
@@ -277,7 +300,20 @@ impl Index {
                         file,
                     )?;
 
-                    if self.processed_files.contains_key(file) {
+                    if let Some(prev_file_uid) = self.processed_files.get(file) {
+                        // If a chunk of the new version is the same as a chunk of the previous version
+                        // (e.g. only an input file of a latex file has changed), they have the same uid,
+                        // and removing the previous version removes the new chunk. So it reads such
+                        // chunks before removing the previous version, and saves them again.
+                        let new_chunk_uids = buffer.get(file).unwrap().values().collect::<HashSet<_>>();
+                        let mut kept_chunks = vec![];
+
+                        for uid in self.get_chunks_of_file(*prev_file_uid)? {
+                            if new_chunk_uids.contains(&uid) {
+                                kept_chunks.push(self.get_chunk_by_uid(uid)?);
+                            }
+                        }
+
                         self.remove_file(
                             real_path.clone(),
                             false,  // dry run
@@ -286,9 +322,23 @@ impl Index {
                             false,  // staged
                             true,   // processed
                         )?;
+
+                        for kept_chunk in kept_chunks.iter() {
+                            chunk::save_to_file(
+                                &Index::get_uid_path(&self.root_dir, CHUNK_DIR_NAME, kept_chunk.uid, Some("chunk"))?,
+                                kept_chunk,
+                                self.build_config.compression_threshold,
+                                self.build_config.compression_level,
+                                &self.root_dir,
+                                true,  // create tfidf
+                            )?;
+                        }
                     }
 
-                    let file_uid = Uid::new_file(&self.root_dir, &real_path)?;
+                    let file_uid = match virtual_file_uids.get(file) {
+                        Some(uid) => *uid,
+                        None => self.get_file_uid(file)?,
+                    };
                     let mut chunk_uids = buffer.get(file).unwrap().iter().map(
                         |(index, uid)| (*index, *uid)
                     ).collect::<Vec<_>>();
@@ -299,9 +349,7 @@ impl Index {
 
                     match self.ii_status {
                         IIStatus::Complete => {
-                            for chunk_uid in chunk_uids.iter() {
-                                self.update_ii_buffer(&mut ii_buffer, *chunk_uid)?;
-                            }
+                            added_chunk_uids.extend(chunk_uids);
                         },
                         IIStatus::Ongoing(_)
                         | IIStatus::Outdated => {
@@ -313,14 +361,14 @@ impl Index {
                     buffer.remove(file);
                 }
 
-                if let IIStatus::Complete = self.ii_status {
-                    self.flush_ii_buffer(ii_buffer)?;
-                }
-
                 self.chunk_count += buffered_chunk_count;
                 self.reset_uid(false /* save to file */)?;
                 self.save_to_file()?;
 
+                if let IIStatus::Complete = self.ii_status {
+                    self.update_ii(&[], &added_chunk_uids)?;
+                }
+
                 buffered_chunk_count = 0;
                 curr_completed_files = vec![];
                 flush_count += 1;
@@ -348,6 +396,14 @@ impl Index {
         self.save_to_file()?;
         self.calculate_and_save_uid()?;
 
+        // It also fills the embeddings of the chunks that are built before `embedding_model` is set.
+        // The chunks are already built, so a failure here must not fail `rag build`.
+        // `rag query` uses tfidf only for the chunks without embeddings.
+        // It does nothing if `embedding_model` is not set.
+        if let Err(e) = self.build_embeddings(quiet).await {
+            eprintln!("Warning: failed to embed the chunks: {e:?}");
+        }
+
         // 1. If there's an error, the knowledge-base is incomplete. We should not create a summary.
         // 2. If there's no success and no error and we already have a summary, then
         //    `self.get_summary().is_none()` would be false, and we'll not create a summary.
@@ -368,6 +424,49 @@ impl Index {
         })
     }
 
+    // It remembers all the chunks in the knowledge-base, so that a new chunk can
+    // reuse the summary of its near-duplicate. The old chunks of the staged files
+    // are not included, because they're removed when the files are rebuilt.
+    // It only loads the chunks whose signatures are not cached.
+    fn load_near_duplicates(&self) -> Result<NearDuplicates, Error> {
+        let near_duplicates = NearDuplicates::new();
+        let cached_signatures = near_duplicate::load_signatures(&self.get_minhash_path()?)?;
+        let mut chunks_of_staged_files = HashSet::new();
+
+        for file in self.staged_files.iter() {
+            if let Some(file_uid) = self.processed_files.get(file) {
+                chunks_of_staged_files.extend(self.get_chunks_of_file(*file_uid)?);
+            }
+        }
+
+        for uid in self.get_all_chunk_uids()? {
+            if chunks_of_staged_files.contains(&uid) {
+                continue;
+            }
+
+            match cached_signatures.get(&uid) {
+                Some(signature) => { near_duplicates.insert_signature(uid, signature.clone()); },
+                None => { near_duplicates.insert(uid, &self.get_chunk_by_uid(uid)?.data); },
+            }
+        }
+
+        Ok(near_duplicates)
+    }
+
+    // The signatures of the removed chunks are not saved.
+    fn save_near_duplicates(&self, near_duplicates: &NearDuplicates) -> Result<(), Error> {
+        let chunk_uids = self.get_all_chunk_uids()?.into_iter().collect::<HashSet<_>>();
+        let signatures = near_duplicates.get_signatures().into_iter().filter(
+            |(uid, _)| chunk_uids.contains(uid)
+        ).collect();
+
+        near_duplicate::save_signatures(&self.get_minhash_path()?, &signatures)
+    }
+
+    fn get_minhash_path(&self) -> Result<Path, Error> {
+        Ok(join3(&self.root_dir, INDEX_DIR_NAME, MINHASH_FILE_NAME)?)
+    }
+
     pub async fn build_dry_run(&self, quiet: bool) -> BuildResult {
         let mut built_chunks = 0;
         let mut built_files = 0;
@@ -378,6 +477,7 @@ impl Index {
 
         // I don't want it to render dashboard too often.
         let mut last_dashboard_at = None;
+        let _ = self.extract_virtual_files();
 
         while let Some(staged_file) = staged_files.pop() {
             match self.build_dry_run_worker(&staged_file).await {
@@ -405,6 +505,8 @@ impl Index {
             }
         }
 
+        let _ = self.clean_up_virtual_files();
+
         if !quiet {
             self.render_build_dry_run_dashboard(
                 &staged_files,
@@ -444,7 +546,7 @@ impl Index {
     }
 
     pub async fn build_dry_run_worker(&self, file: &str) -> Result<usize, Error> {
-        let real_path = Index::get_data_path(
+        let real_path = Index::get_readable_path(
             &self.root_dir,
             &file.to_string(),
         )?;
@@ -567,9 +669,10 @@ async fn build_chunks(
     index: &Index,
     file: String,
     prompt_hash: String,
+    near_duplicates: Option<&NearDuplicates>,
     tx_to_main: mpsc::UnboundedSender<Response>,
 ) -> Result<(), Error> {
-    let real_path = Index::get_data_path(
+    let real_path = Index::get_readable_path(
         &index.root_dir,
         &file,
     )?;
@@ -588,6 +691,9 @@ async fn build_chunks(
         // but different `get_model_by_name().name` always refer to
         // different models
         index.get_model_by_name(&index.api_config.model)?.name,
+        fd.encoding(),
+        index.build_config.get_build_overrides(&file),
+        get_dependencies(&index.root_dir, &fd)?,
     );
     let mut index_in_file = 0;
     let mut previous_summary = None;
@@ -598,6 +704,7 @@ async fn build_chunks(
             build_info.clone(),
             previous_summary.clone(),
             index_in_file,
+            near_duplicates,
         ).await?;
         previous_summary = Some((new_chunk.clone(), (&new_chunk).into()));
         let new_chunk_uid = new_chunk.uid;
@@ -637,6 +744,12 @@ async fn build_chunks(
             &index.root_dir,
             true,  // create tfidf
         )?;
+
+        // The other workers can find this chunk only after it's saved.
+        if let Some(near_duplicates) = near_duplicates {
+            near_duplicates.insert(new_chunk_uid, &new_chunk.data);
+        }
+
         tx_to_main.send(Response::ChunkComplete {
             file: file.clone(),
             index: index_in_file,
@@ -680,11 +793,29 @@ impl Channel {
     }
 }
 
-fn init_workers(n: usize, root_dir: String) -> Vec<Channel> {
-    (0..n).map(|_| init_worker(root_dir.clone())).collect()
+// A virtual file is extracted to `.ragit/`, and so are its dependencies. They're removed
+// after `rag build`, so they're not recorded. They're in the same archive anyway.
+fn get_dependencies(root_dir: &str, fd: &FileReader) -> Result<Vec<(Path, Uid)>, Error> {
+    let mut result = vec![];
+
+    for dependency in fd.dependencies() {
+        let rel_path = get_relative_path(root_dir, &dependency)?;
+
+        if rel_path.starts_with(INDEX_DIR_NAME) {
+            continue;
+        }
+
+        result.push((rel_path, Uid::new_file(root_dir, &dependency)?));
+    }
+
+    Ok(result)
+}
+
+fn init_workers(n: usize, root_dir: String, near_duplicates: Option<Arc<NearDuplicates>>) -> Vec<Channel> {
+    (0..n).map(|_| init_worker(root_dir.clone(), near_duplicates.clone())).collect()
 }
 
-fn init_worker(root_dir: String) -> Channel {
+fn init_worker(root_dir: String, near_duplicates: Option<Arc<NearDuplicates>>) -> Channel {
     let (tx_to_main, rx_to_main) = mpsc::unbounded_channel();
     let (tx_from_main, mut rx_from_main) = mpsc::unbounded_channel();
 
@@ -723,6 +854,7 @@ fn init_worker(root_dir: String) -> Channel {
                     &index,
                     file,
                     prompt_hash.clone(),
+                    near_duplicates.as_deref(),
                     tx_to_main.clone(),
                 ).await {
                     Ok(_) => {},
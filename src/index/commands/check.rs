@@ -2,7 +2,7 @@ use super::Index;
 use crate::{ApiConfig, IIStatus, QueryConfig};
 use crate::chunk::{self, ChunkSource};
 use crate::error::Error;
-use crate::index::{BuildConfig, ImageDescription, tfidf};
+use crate::index::{BuildConfig, FileEncoding, ImageDescription, tfidf};
 use crate::uid::{self, Uid};
 use ragit_fs::{
     basename,
@@ -59,7 +59,7 @@ impl Index {
             }
 
             match &chunk.source {
-                ChunkSource::File { path, index, page: _ } => {
+                ChunkSource::File { path, index, .. } => {
                     chunks_to_files.insert(chunk_uid, (path.to_string(), *index));
                     processed_files.insert(path.to_string());
                 },
@@ -221,4 +221,26 @@ impl Index {
 
         Ok(())
     }
+
+    /// It's not a corruption, but `rag check` warns about these files. If a file is not
+    /// UTF-8 and ragit is not sure about its encoding (see `FileEncoding::is_suspicious`),
+    /// the chunks of the file might be full of garbage characters.
+    pub fn get_files_with_suspicious_encoding(&self) -> Result<Vec<(String, FileEncoding)>, Error> {
+        let mut result = HashMap::new();
+
+        for chunk_file in self.get_all_chunk_files()? {
+            let chunk = chunk::load_from_file(&chunk_file)?;
+
+            match (&chunk.source, chunk.build_info.encoding) {
+                (ChunkSource::File { path, .. }, Some(encoding)) if encoding.is_suspicious() => {
+                    result.insert(path.to_string(), encoding);
+                },
+                _ => {},
+            }
+        }
+
+        let mut result = result.into_iter().collect::<Vec<_>>();
+        result.sort_by_key(|(path, _)| path.to_string());
+        Ok(result)
+    }
 }
@@ -23,6 +23,16 @@ lazy_static! {
         ("super_rerank", (Value::Bool(false), ConfigType::Query)),
         ("enable_rag", (Value::Bool(true), ConfigType::Query)),
         ("summary_after_build", (Value::Bool(false), ConfigType::Build)),
+        ("file_readers", (Value::Array(vec![]), ConfigType::Build)),
+        ("build_rules", (Value::Array(vec![]), ConfigType::Build)),
+        ("chunk_unit", (Value::String(String::from("char")), ConfigType::Build)),
+        ("prefer_boundaries", (Value::Bool(false), ConfigType::Build)),
+        ("detect_near_duplicates", (Value::Bool(false), ConfigType::Build)),
+        ("collapse_near_duplicates", (Value::Bool(false), ConfigType::Query)),
+        ("ranking", (Value::String(String::from("tfidf")), ConfigType::Query)),
+        ("embedding_model", (Value::Null, ConfigType::Api)),
+        ("pdf_mode", (Value::String(String::from("image")), ConfigType::Build)),
+        ("ii_flush_threshold", (Value::from(65536), ConfigType::Build)),
     ].into_iter().map(
         |(key, value)| (key.to_string(), value)
     ).collect();
@@ -225,6 +235,7 @@ impl Index {
         self.build_config = serde_json::from_str::<BuildConfig>(
             &read_string(&self.get_build_config_path()?)?,
         )?;
+        self.build_config.check_build_rules()?;
         self.query_config = serde_json::from_str::<QueryConfig>(
             &read_string(&self.get_query_config_path()?)?,
         )?;
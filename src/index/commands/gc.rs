@@ -5,10 +5,13 @@ use crate::index::LOG_DIR_NAME;
 use crate::uid::Uid;
 use ragit_fs::{
     WriteMode,
+    basename,
     exists,
     file_name,
+    is_dir,
     parent,
     read_dir,
+    remove_dir_all,
     remove_file,
     set_extension,
     write_string,
@@ -72,6 +75,53 @@ impl Index {
         Ok(count)
     }
 
+    /// `rag gc --ii`
+    ///
+    /// It merges all the segments of the ii into one, and removes the files
+    /// that are not a part of the ii. If the ii is not complete, it removes
+    /// the entire ii.
+    ///
+    /// It returns how many files it removed.
+    pub fn gc_ii(&mut self) -> Result<usize, Error> {
+        let ii_dir = self.get_ii_dir()?;
+
+        if !exists(&ii_dir) {
+            return Ok(0);
+        }
+
+        if !self.is_ii_built() {
+            let count = read_dir(&ii_dir, false)?.len();
+
+            if count > 0 {
+                self.reset_ii()?;
+            }
+
+            return Ok(count);
+        }
+
+        self.compact_ii()?;
+        let ii_files = self.get_ii_files()?;
+        let mut count = 0;
+
+        for file in read_dir(&ii_dir, false)? {
+            if ii_files.contains(&basename(&file)?) {
+                continue;
+            }
+
+            if is_dir(&file) {
+                remove_dir_all(&file)?;
+            }
+
+            else {
+                remove_file(&file)?;
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     /// `rag gc --audit`
     pub fn gc_audit(&mut self) -> Result<(), Error> {
         let usages_at = Index::get_rag_path(
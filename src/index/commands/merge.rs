@@ -70,6 +70,7 @@ impl Index {
         let mut old_images = HashSet::new();
         let other = Index::load(path, LoadMode::OnlyJson)?;
         let mut has_to_erase_lines = false;
+        let mut added_chunk_uids = vec![];
 
         for (rel_path, uid_other) in other.processed_files.iter() {
             let mut new_file_path = rel_path.clone();
@@ -231,6 +232,7 @@ impl Index {
             }
 
             if !dry_run {
+                added_chunk_uids.extend(modified_new_chunk_uids.iter().copied());
                 self.add_file_index(new_file_uid, &modified_new_chunk_uids)?;
                 self.processed_files.insert(new_file_path, new_file_uid);
             }
@@ -294,8 +296,18 @@ impl Index {
             result.overriden_images += 1;
         }
 
-        if !dry_run && (result.added_chunks > 0 || result.removed_chunks > 0) && self.ii_status != IIStatus::None {
-            self.ii_status = IIStatus::Outdated;
+        if !dry_run && (result.added_chunks > 0 || result.removed_chunks > 0) {
+            match self.ii_status {
+                // `remove_file` has already removed the postings of the removed chunks.
+                IIStatus::Complete => {
+                    self.update_ii(&[], &added_chunk_uids)?;
+                },
+                IIStatus::Ongoing(_)
+                | IIStatus::Outdated => {
+                    self.ii_status = IIStatus::Outdated;
+                },
+                IIStatus::None => {},
+            }
         }
 
         if !dry_run {
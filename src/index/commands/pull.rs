@@ -12,6 +12,7 @@ use crate::constant::{
     PROMPT_DIR_NAME,
 };
 use crate::error::Error;
+use crate::index::LoadMode;
 use ragit_fs::{
     exists,
     join,
@@ -19,6 +20,7 @@ use ragit_fs::{
     rename,
 };
 use reqwest::Url;
+use std::collections::HashSet;
 
 pub enum PullResult {
     PulledArchives,
@@ -90,7 +92,10 @@ impl Index {
         ii: bool,
         quiet: bool,
     ) -> Result<PullResult, Error> {
-        let cloned_blocks = Index::clone(repo_url, Some(tmp_clone_dir.to_string()), ii, quiet).await?;
+        // If the local ii is complete, it updates the local ii instead of building
+        // a new one from scratch. Building an ii takes much longer than updating it.
+        let update_ii = ii && self.is_ii_built();
+        let cloned_blocks = Index::clone(repo_url, Some(tmp_clone_dir.to_string()), ii && !update_ii, quiet).await?;
         let cloned_configs = cloned_blocks.get(&BlockType::Config).map(|n| *n).unwrap_or(0) > 1;
         let cloned_prompts = cloned_blocks.get(&BlockType::Prompt).map(|n| *n).unwrap_or(0) > 1;
 
@@ -103,6 +108,20 @@ impl Index {
             INDEX_DIR_NAME,
         )?;
 
+        let ii_update = if update_ii {
+            let new_index = Index::load(tmp_clone_dir.to_string(), LoadMode::OnlyJson)?;
+            let old_chunk_uids = self.get_all_chunk_uids()?.into_iter().collect::<HashSet<_>>();
+            let new_chunk_uids = new_index.get_all_chunk_uids()?.into_iter().collect::<HashSet<_>>();
+            let mut removed_chunk_uids = old_chunk_uids.difference(&new_chunk_uids).copied().collect::<Vec<_>>();
+            let mut added_chunk_uids = new_chunk_uids.difference(&old_chunk_uids).copied().collect::<Vec<_>>();
+            removed_chunk_uids.sort();
+            added_chunk_uids.sort();
+
+            Some((removed_chunk_uids, added_chunk_uids))
+        } else {
+            None
+        };
+
         // If power goes down while moving `.ragit/files/`, you can run `rag check --recover` to recover
         // If power goes down while moving `chunks/`, `images/` or `index.json`, you can run `rag pull` again to recover
         // If power goes down while moving `meta.json`... you cannot tell whether something's wrong or not. That's a problem
@@ -146,12 +165,22 @@ impl Index {
             )?;
         }
 
-        if exists(&join(&new_index_dir, II_DIR_NAME)?) {
-            remove_dir_all(&join(&curr_index_dir, II_DIR_NAME)?)?;
-            rename(
-                &join(&new_index_dir, II_DIR_NAME)?,
-                &join(&curr_index_dir, II_DIR_NAME)?,
-            )?;
+        match ii_update {
+            Some((removed_chunk_uids, added_chunk_uids)) => {
+                // `index.json` is from the remote, which doesn't know about the local ii.
+                // `update_ii` marks the ii complete.
+                let mut index = Index::load(self.root_dir.clone(), LoadMode::OnlyJson)?;
+                index.update_ii(&removed_chunk_uids, &added_chunk_uids)?;
+            },
+            None => {
+                if exists(&join(&new_index_dir, II_DIR_NAME)?) {
+                    remove_dir_all(&join(&curr_index_dir, II_DIR_NAME)?)?;
+                    rename(
+                        &join(&new_index_dir, II_DIR_NAME)?,
+                        &join(&curr_index_dir, II_DIR_NAME)?,
+                    )?;
+                }
+            },
         }
 
         Ok(PullResult::PulledArchives)
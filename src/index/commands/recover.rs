@@ -110,7 +110,7 @@ impl Index {
                 result.created_tfidfs += 1;
             }
 
-            let ChunkSource::File { path, index, page: _ } = &chunk_.source;
+            let ChunkSource::File { path, index, .. } = &chunk_.source;
 
             match processed_files.entry(path.to_string()) {
                 Entry::Occupied(mut chunks) => {
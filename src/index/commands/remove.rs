@@ -1,4 +1,5 @@
 use super::Index;
+use crate::constant::VIRTUAL_FILE_SEPARATOR;
 use crate::error::Error;
 use crate::index::{CHUNK_DIR_NAME, IIStatus};
 use ragit_fs::{exists, get_relative_path, remove_file, set_extension};
@@ -54,16 +55,18 @@ impl Index {
 
             (staged_candidates, processed_candidates)
         } else {
-            let staged_candidates = if self.staged_files.contains(&rel_path) {
-                vec![rel_path.clone()]
-            } else {
-                vec![]
-            };
-            let processed_candidates = if self.processed_files.contains_key(&rel_path) {
-                vec![rel_path.clone()]
-            } else {
-                vec![]
-            };
+            // `rag rm bundle.zip` removes all the files in the archive (`bundle.zip!/*`).
+            let archive_prefix = format!("{rel_path}{VIRTUAL_FILE_SEPARATOR}");
+            let staged_candidates = self.staged_files.iter().filter(
+                |file| **file == rel_path || file.starts_with(&archive_prefix)
+            ).map(
+                |file| file.to_string()
+            ).collect::<Vec<_>>();
+            let processed_candidates = self.processed_files.keys().filter(
+                |file| **file == rel_path || file.starts_with(&archive_prefix)
+            ).map(
+                |file| file.to_string()
+            ).collect::<Vec<_>>();
 
             (staged_candidates, processed_candidates)
         };
@@ -81,23 +84,8 @@ impl Index {
         }
 
         if auto {
-            let mut staged_candidates_new = vec![];
-            let mut processed_candidates_new = vec![];
-
-            for file in staged_candidates.into_iter() {
-                if !exists(&Index::get_data_path(&self.root_dir, &file)?) {
-                    staged_candidates_new.push(file)
-                }
-            }
-
-            for file in processed_candidates.into_iter() {
-                if !exists(&Index::get_data_path(&self.root_dir, &file)?) {
-                    processed_candidates_new.push(file)
-                }
-            }
-
-            staged_candidates = staged_candidates_new;
-            processed_candidates = processed_candidates_new;
+            staged_candidates = Index::get_missing_files(&self.root_dir, staged_candidates)?;
+            processed_candidates = Index::get_missing_files(&self.root_dir, processed_candidates)?;
         }
 
         if !dry_run {
@@ -108,6 +96,22 @@ impl Index {
                 |file| file.to_string()
             ).collect();
 
+            // If the ii is complete, it removes the removed chunks from the ii
+            // instead of marking the ii outdated.
+            let ii_removal = if self.is_ii_built() {
+                let mut chunk_uids = vec![];
+
+                for file in processed_candidates.iter() {
+                    if let Some(file_uid) = self.processed_files.get(file) {
+                        chunk_uids.extend(self.get_chunks_of_file(*file_uid)?);
+                    }
+                }
+
+                Some(chunk_uids)
+            } else {
+                None
+            };
+
             self.ii_status = IIStatus::Outdated;
 
             for file in processed_candidates.iter() {
@@ -139,6 +143,10 @@ impl Index {
             self.reset_uid(false /* save_to_file */)?;
             self.save_to_file()?;
 
+            if let Some(ii_removal) = ii_removal {
+                self.update_ii(&ii_removal, &[])?;
+            }
+
             // If there's no chunk, an empty ii is a complete ii!
             if self.chunk_count == 0 {
                 self.ii_status = IIStatus::Complete;
@@ -1,3 +1,6 @@
+use crate::error::Error;
+use crate::index::file::{FileReaderOptions, estimate_token_count};
+use ragit_ignore::Pattern;
 use serde::{Deserialize, Serialize};
 
 // This struct is used for loading partial configurations from ~/.config/ragit/build.json
@@ -12,6 +15,13 @@ pub struct PartialBuildConfig {
     pub compression_threshold: Option<u64>,
     pub compression_level: Option<u32>,
     pub summary_after_build: Option<bool>,
+    pub file_readers: Option<Vec<FileReaderRule>>,
+    pub build_rules: Option<Vec<BuildRule>>,
+    pub chunk_unit: Option<ChunkUnit>,
+    pub prefer_boundaries: Option<bool>,
+    pub detect_near_duplicates: Option<bool>,
+    pub pdf_mode: Option<PdfMode>,
+    pub ii_flush_threshold: Option<usize>,
 }
 
 impl PartialBuildConfig {
@@ -44,6 +54,27 @@ impl PartialBuildConfig {
         if let Some(summary_after_build) = self.summary_after_build {
             config.summary_after_build = summary_after_build;
         }
+        if let Some(file_readers) = &self.file_readers {
+            config.file_readers = file_readers.clone();
+        }
+        if let Some(build_rules) = &self.build_rules {
+            config.build_rules = build_rules.clone();
+        }
+        if let Some(chunk_unit) = self.chunk_unit {
+            config.chunk_unit = chunk_unit;
+        }
+        if let Some(prefer_boundaries) = self.prefer_boundaries {
+            config.prefer_boundaries = prefer_boundaries;
+        }
+        if let Some(detect_near_duplicates) = self.detect_near_duplicates {
+            config.detect_near_duplicates = detect_near_duplicates;
+        }
+        if let Some(pdf_mode) = self.pdf_mode {
+            config.pdf_mode = pdf_mode;
+        }
+        if let Some(ii_flush_threshold) = self.ii_flush_threshold {
+            config.ii_flush_threshold = ii_flush_threshold;
+        }
     }
 }
 
@@ -59,9 +90,19 @@ pub struct BuildConfig {
 
     pub slide_len: usize,
 
-    /// An image is treated like an N characters string, and this is N.
+    /// An image is treated like an N characters (or tokens) string, and this is N.
     pub image_size: usize,
 
+    /// The unit of `chunk_size`, `slide_len` and `image_size`.
+    #[serde(default)]
+    pub chunk_unit: ChunkUnit,
+
+    /// If it's set, a chunk prefers to end at a blank line, then at the end of a sentence,
+    /// and then at a line break. A chunk can be a bit (20%) smaller or bigger than `chunk_size`
+    /// for that. Sliding windows also start at such boundaries.
+    #[serde(default = "_false")]
+    pub prefer_boundaries: bool,
+
     /// It forces the LLM to generate a summary that has at least `min_summary_len` characters
     /// and at most `max_summary_len` characters.
     pub min_summary_len: usize,
@@ -82,6 +123,273 @@ pub struct BuildConfig {
     /// If it's set, it runs `rag summary` after `rag build` is complete.
     #[serde(default = "_false")]
     pub summary_after_build: bool,
+
+    /// By default, ragit chooses a file reader by the extension of a file.
+    /// You can override it with these rules. The first rule that matches
+    /// the file is applied, and the default one is used if nothing matches.
+    #[serde(default)]
+    pub file_readers: Vec<FileReaderRule>,
+
+    /// Some files need different chunk sizes or summary lengths than the others.
+    /// Each rule overrides the configs above for the files that match the rule.
+    /// Unlike `file_readers`, all the matching rules are applied in order, so
+    /// a later rule overrides the earlier ones.
+    #[serde(default)]
+    pub build_rules: Vec<BuildRule>,
+
+    /// If it's set, a chunk that's a near-duplicate of another chunk (e.g. the same
+    /// license text in different directories) reuses the title and the summary of
+    /// the other chunk, instead of asking the LLM.
+    #[serde(default = "_false")]
+    pub detect_near_duplicates: bool,
+
+    /// How `PdfReader` reads a page. See `PdfMode`.
+    #[serde(default)]
+    pub pdf_mode: PdfMode,
+
+    /// While building an inverted index, it writes the buffered postings to a new
+    /// segment when the buffer has more terms than this. A bigger number makes
+    /// `rag ii-build` faster, but it uses more memory.
+    #[serde(default = "_ii_flush_threshold")]
+    pub ii_flush_threshold: usize,
+}
+
+impl BuildConfig {
+    /// `rel_path` is a normalized, relative path of a file.
+    pub fn get_file_reader_rule(&self, rel_path: &str) -> Option<&FileReaderRule> {
+        self.file_readers.iter().find(|rule| rule.is_match(rel_path))
+    }
+
+    /// `rel_path` is a normalized, relative path of a file.
+    /// It merges all the `build_rules` that match the file.
+    pub fn get_build_overrides(&self, rel_path: &str) -> BuildOverrides {
+        let mut result = BuildOverrides::default();
+
+        for rule in self.build_rules.iter() {
+            if rule.is_match(rel_path) {
+                result.merge(&rule.overrides);
+            }
+        }
+
+        result
+    }
+
+    /// The config that's used to build `rel_path`.
+    pub fn resolve(&self, rel_path: &str) -> BuildConfig {
+        let mut result = self.clone();
+        self.get_build_overrides(rel_path).apply_to(&mut result);
+        result
+    }
+
+    /// A rule may override `chunk_size` without `slide_len` (or vice versa), so it checks
+    /// each rule against the global config.
+    pub fn check_build_rules(&self) -> Result<(), Error> {
+        for rule in self.build_rules.iter() {
+            let mut config = self.clone();
+            rule.overrides.apply_to(&mut config);
+
+            if config.slide_len >= config.chunk_size {
+                return Err(Error::InvalidBuildRule {
+                    pattern: rule.pattern.to_string(),
+                    message: format!("`slide_len` ({}) has to be smaller than `chunk_size` ({}).", config.slide_len, config.chunk_size),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct FileReaderRule {
+    /// A gitignore-style glob pattern (e.g. `docs/*.txt`) or an extension (e.g. `mdx`).
+    /// If it has no `/`, `.`, `*`, `?` nor `[`, it's treated as an extension.
+    pub pattern: String,
+    pub reader: FileReaderKind,
+
+    #[serde(default, skip_serializing_if = "FileReaderOptions::is_empty")]
+    pub options: FileReaderOptions,
+}
+
+impl FileReaderRule {
+    pub fn is_match(&self, rel_path: &str) -> bool {
+        is_pattern_match(&self.pattern, rel_path)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(from = "BuildRuleFields")]
+pub struct BuildRule {
+    /// The same as `FileReaderRule::pattern`.
+    pub pattern: String,
+
+    #[serde(flatten)]
+    pub overrides: BuildOverrides,
+}
+
+impl BuildRule {
+    pub fn is_match(&self, rel_path: &str) -> bool {
+        is_pattern_match(&self.pattern, rel_path)
+    }
+}
+
+// `#[serde(deny_unknown_fields)]` doesn't work with `#[serde(flatten)]`, so `BuildRule`
+// is deserialized from this struct. Otherwise, a typo (e.g. `chunk_sise`) would be silently ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BuildRuleFields {
+    pattern: String,
+    chunk_size: Option<usize>,
+    slide_len: Option<usize>,
+    image_size: Option<usize>,
+    min_summary_len: Option<usize>,
+    max_summary_len: Option<usize>,
+}
+
+impl From<BuildRuleFields> for BuildRule {
+    fn from(fields: BuildRuleFields) -> BuildRule {
+        BuildRule {
+            pattern: fields.pattern,
+            overrides: BuildOverrides {
+                chunk_size: fields.chunk_size,
+                slide_len: fields.slide_len,
+                image_size: fields.image_size,
+                min_summary_len: fields.min_summary_len,
+                max_summary_len: fields.max_summary_len,
+            },
+        }
+    }
+}
+
+/// The configs that a `BuildRule` can override. It's also recorded in `ChunkBuildInfo`,
+/// so that ragit knows which files have to be rebuilt when a rule changes.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BuildOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slide_len: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_summary_len: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_summary_len: Option<usize>,
+}
+
+impl BuildOverrides {
+    pub fn is_empty(&self) -> bool {
+        self == &BuildOverrides::default()
+    }
+
+    // `other` wins
+    fn merge(&mut self, other: &BuildOverrides) {
+        if other.chunk_size.is_some() {
+            self.chunk_size = other.chunk_size;
+        }
+        if other.slide_len.is_some() {
+            self.slide_len = other.slide_len;
+        }
+        if other.image_size.is_some() {
+            self.image_size = other.image_size;
+        }
+        if other.min_summary_len.is_some() {
+            self.min_summary_len = other.min_summary_len;
+        }
+        if other.max_summary_len.is_some() {
+            self.max_summary_len = other.max_summary_len;
+        }
+    }
+
+    fn apply_to(&self, config: &mut BuildConfig) {
+        if let Some(chunk_size) = self.chunk_size {
+            config.chunk_size = chunk_size;
+        }
+        if let Some(slide_len) = self.slide_len {
+            config.slide_len = slide_len;
+        }
+        if let Some(image_size) = self.image_size {
+            config.image_size = image_size;
+        }
+        if let Some(min_summary_len) = self.min_summary_len {
+            config.min_summary_len = min_summary_len;
+        }
+        if let Some(max_summary_len) = self.max_summary_len {
+            config.max_summary_len = max_summary_len;
+        }
+    }
+}
+
+// If `pattern` has no `/`, `.`, `*`, `?` nor `[`, it's treated as an extension.
+fn is_pattern_match(pattern: &str, rel_path: &str) -> bool {
+    let pattern = if pattern.chars().any(|c| "/.*?[".contains(c)) {
+        pattern.to_string()
+    } else {
+        format!("*.{pattern}")
+    };
+
+    Pattern::parse(&pattern).is_match(rel_path)
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileReaderKind {
+    Markdown,
+    PlainText,
+    Line,
+    Csv,
+    Image,
+    Pdf,
+    Code,
+    Html,
+    Ipynb,
+    Docx,
+    Pptx,
+    Xlsx,
+    Odt,
+    Epub,
+    Email,
+    Subtitle,
+    Structured,
+    Latex,
+}
+
+/// `Char` counts characters, and `Token` estimates the number of tokens (see
+/// `estimate_token_count`). 4000 characters of an english text is about 1000 tokens,
+/// but 4000 characters of a Korean text is about 4000 tokens.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkUnit {
+    #[default]
+    Char,
+    Token,
+}
+
+impl ChunkUnit {
+    /// The length of `s` in this unit.
+    pub fn measure(&self, s: &str) -> usize {
+        match self {
+            ChunkUnit::Char => s.chars().count(),
+            ChunkUnit::Token => estimate_token_count(s),
+        }
+    }
+}
+
+/// `Image` converts each page to an image, and a vision model reads the image.
+/// `Text` extracts the text layer of each page, so it works with a text-only model,
+/// but cannot read scanned pages and figures. `Hybrid` extracts the text layer, but
+/// also converts a page to an image if the page has (almost) no text or has a figure.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfMode {
+    #[default]
+    Image,
+    Text,
+    Hybrid,
 }
 
 fn _true() -> bool {
@@ -92,18 +400,29 @@ fn _false() -> bool {
     false
 }
 
+fn _ii_flush_threshold() -> usize {
+    65536
+}
+
 impl Default for BuildConfig {
     fn default() -> Self {
         BuildConfig {
             chunk_size: 4_000,
             slide_len: 1_000,
             image_size: 2_000,
+            chunk_unit: ChunkUnit::Char,
+            prefer_boundaries: false,
             min_summary_len: 200,
             max_summary_len: 1000,
             strict_file_reader: false,
             compression_threshold: 2048,
             compression_level: 3,
             summary_after_build: false,
+            file_readers: vec![],
+            build_rules: vec![],
+            detect_near_duplicates: false,
+            pdf_mode: PdfMode::Image,
+            ii_flush_threshold: 65536,
         }
     }
 }
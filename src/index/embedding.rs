@@ -0,0 +1,348 @@
+// Embeddings are optional. If `embedding_model` is set, `rag build` embeds
+// the chunks and `rag query` fuses the vector search results with the tfidf
+// results. Otherwise, ragit is keyword-only.
+//
+// The vectors are a cache of the chunks, like tfidf files. They're not a part
+// of the uid of a knowledge-base, and `rag build` fills the missing ones.
+//
+// All the vectors are in a single binary file, `.ragit/embeddings/vectors`.
+// Vector search reads all of them anyway, so it's faster to read one file than
+// a file per chunk. Numbers are in little endian.
+//
+// 1. model name: length (u32) and the utf-8 bytes
+// 2. dimension (u32)
+// 3. records, sorted by uid: a uid (32 bytes) and `dimension` f32s
+
+use super::Index;
+use crate::constant::{EMBEDDING_DIR_NAME, INDEX_DIR_NAME};
+use crate::error::Error;
+use crate::uid::Uid;
+use ragit_api::{EmbeddingRequest, Model};
+use ragit_fs::{
+    WriteMode,
+    create_dir_all,
+    exists,
+    join3,
+    join4,
+    read_bytes,
+    write_bytes,
+};
+use std::collections::{HashMap, HashSet};
+
+// It sends this many chunks in a request.
+const EMBEDDING_BATCH_SIZE: usize = 32;
+
+// It saves the vectors after this many requests, so that an interrupted
+// `rag build` doesn't lose all the vectors it has embedded.
+const SAVE_EMBEDDINGS_EVERY: usize = 8;
+
+// An embedding model reads at most this many characters of a chunk.
+const EMBEDDING_INPUT_LIMIT: usize = 8192;
+
+// `k` of reciprocal rank fusion. 60 is the value from the original paper.
+const RRF_K: usize = 60;
+
+const VECTOR_FILE_NAME: &str = "vectors";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Embeddings {
+    /// Name of the model in `models.json`. Vectors from different models
+    /// are not comparable, so a file has vectors of a single model.
+    pub model: String,
+    pub vectors: HashMap<Uid, Vec<f32>>,
+}
+
+impl Embeddings {
+    pub fn new(model: String) -> Self {
+        Embeddings {
+            model,
+            vectors: HashMap::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let dimension = self.vectors.values().next().map(|vector| vector.len()).unwrap_or(0);
+        let mut uids = self.vectors.keys().copied().collect::<Vec<_>>();
+        uids.sort();
+
+        let mut result = Vec::with_capacity(8 + self.model.len() + uids.len() * (32 + dimension * 4));
+        result.extend((self.model.len() as u32).to_le_bytes());
+        result.extend(self.model.as_bytes());
+        result.extend((dimension as u32).to_le_bytes());
+
+        for uid in uids.iter() {
+            let vector = self.vectors.get(uid).unwrap();
+
+            if vector.len() != dimension {
+                return Err(Error::Internal(format!("The embedding model `{}` returned vectors of different dimensions: {dimension} and {}", self.model, vector.len())));
+            }
+
+            uid.encode(&mut result);
+
+            for n in vector.iter() {
+                result.extend(n.to_le_bytes());
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let broken = || Error::BrokenIndex(String::from("Failed to decode the embeddings."));
+        let read_u32 = |offset: usize| -> Result<usize, Error> {
+            match bytes.get(offset..(offset + 4)) {
+                Some(n) => Ok(u32::from_le_bytes(n.try_into().unwrap()) as usize),
+                None => Err(broken()),
+            }
+        };
+
+        let model_len = read_u32(0)?;
+        let model = match bytes.get(4..(4 + model_len)) {
+            Some(model) => String::from_utf8(model.to_vec()).map_err(|_| broken())?,
+            None => { return Err(broken()); },
+        };
+        let dimension = read_u32(4 + model_len)?;
+        let records = &bytes[(8 + model_len)..];
+        let record_len = 32 + dimension * 4;
+
+        if !records.len().is_multiple_of(record_len) {
+            return Err(broken());
+        }
+
+        let mut vectors = HashMap::with_capacity(records.len() / record_len);
+
+        for record in records.chunks(record_len) {
+            let uid = Uid::decode(&record[..32])?;
+            let vector = record[32..].chunks(4).map(|n| f32::from_le_bytes(n.try_into().unwrap())).collect();
+            vectors.insert(uid, vector);
+        }
+
+        Ok(Embeddings { model, vectors })
+    }
+}
+
+impl Index {
+    /// It embeds the chunks that don't have an embedding, and removes the
+    /// embeddings of removed chunks. If the embedding model has changed, it
+    /// embeds all the chunks again. It does nothing if `embedding_model` is not set.
+    ///
+    /// It saves the vectors every `SAVE_EMBEDDINGS_EVERY` requests. If the api
+    /// fails, it keeps the vectors it has so far and returns. The
+    /// chunks without a vector are embedded by the next `rag build`, and until
+    /// then, `rag query` doesn't find them with vector search.
+    ///
+    /// It returns how many chunks it embedded.
+    pub async fn build_embeddings(&self, quiet: bool) -> Result<usize, Error> {
+        let Some(model) = &self.api_config.embedding_model else {
+            return Ok(0);
+        };
+        let model = self.get_model_by_name(model)?;
+        let (mut embeddings, mut has_changed) = match self.load_embeddings()? {
+            Some(embeddings) if embeddings.model == model.name => (embeddings, false),
+            Some(_) => (Embeddings::new(model.name.clone()), true),
+            None => (Embeddings::new(model.name.clone()), false),
+        };
+        let chunk_uids = self.get_all_chunk_uids()?;
+        let chunk_uid_set = chunk_uids.iter().copied().collect::<HashSet<_>>();
+        let vector_count = embeddings.vectors.len();
+        embeddings.vectors.retain(|uid, _| chunk_uid_set.contains(uid));
+        has_changed |= embeddings.vectors.len() != vector_count;
+        let missing = chunk_uids.into_iter().filter(|uid| !embeddings.vectors.contains_key(uid)).collect::<Vec<_>>();
+        let mut embedded = 0;
+
+        if !missing.is_empty() && !quiet {
+            println!("Embedding {} chunk{}...", missing.len(), if missing.len() > 1 { "s" } else { "" });
+        }
+
+        for (batch_index, uids) in missing.chunks(EMBEDDING_BATCH_SIZE).enumerate() {
+            let mut inputs = Vec::with_capacity(uids.len());
+
+            for uid in uids.iter() {
+                let chunk = self.get_chunk_by_uid(*uid)?;
+                inputs.push(
+                    format!("{}\n{}\n{}", chunk.title, chunk.summary, chunk.data)
+                        .chars()
+                        .take(EMBEDDING_INPUT_LIMIT)
+                        .collect::<String>()
+                );
+            }
+
+            match self.embed(&model, inputs, "embed_chunks").await {
+                Ok(vectors) => {
+                    for (uid, vector) in uids.iter().zip(vectors) {
+                        embeddings.vectors.insert(*uid, vector);
+                        embedded += 1;
+                    }
+
+                    has_changed = true;
+
+                    if (batch_index + 1) % SAVE_EMBEDDINGS_EVERY == 0 {
+                        self.save_embeddings(&embeddings)?;
+                        has_changed = false;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Warning: failed to embed {} chunk{}: {e:?}", missing.len() - embedded, if missing.len() - embedded > 1 { "s" } else { "" });
+                    break;
+                },
+            }
+        }
+
+        if has_changed {
+            self.save_embeddings(&embeddings)?;
+        }
+
+        Ok(embedded)
+    }
+
+    /// It returns the uids of the chunks that are the most similar to `query`,
+    /// the most similar one first. It's a brute-force search over all the vectors.
+    ///
+    /// If the api fails, it returns an empty list with a warning, so that
+    /// `rag query` falls back to tfidf.
+    pub async fn run_vector_search(&self, query: &str, limit: usize) -> Result<Vec<Uid>, Error> {
+        let Some(model) = &self.api_config.embedding_model else {
+            return Ok(vec![]);
+        };
+        let model = self.get_model_by_name(model)?;
+        let embeddings = match self.load_embeddings()? {
+            Some(embeddings) if embeddings.model == model.name => embeddings,
+            _ => { return Ok(vec![]); },
+        };
+        let query_vector = match self.embed(&model, vec![query.to_string()], "embed_query").await {
+            Ok(mut vectors) => match vectors.pop() {
+                Some(vector) => vector,
+                None => { return Ok(vec![]); },
+            },
+            Err(e) => {
+                eprintln!("Warning: vector search failed, so it only uses tfidf: {e:?}");
+                return Ok(vec![]);
+            },
+        };
+        let mut scores = vec![];
+
+        // It only iterates chunks, so that the embeddings of removed chunks are ignored.
+        for uid in self.get_all_chunk_uids()? {
+            if let Some(vector) = embeddings.vectors.get(&uid) {
+                scores.push((uid, cosine_similarity(&query_vector, vector)));
+            }
+        }
+
+        scores.sort_by(|(uid_a, score_a), (uid_b, score_b)| score_b.total_cmp(score_a).then(uid_a.cmp(uid_b)));
+        Ok(scores.into_iter().take(limit).map(|(uid, _)| uid).collect())
+    }
+
+    /// It returns `None` if no chunk is embedded yet.
+    pub fn load_embeddings(&self) -> Result<Option<Embeddings>, Error> {
+        let vector_file = self.get_vector_file_path()?;
+
+        if !exists(&vector_file) {
+            return Ok(None);
+        }
+
+        Ok(Some(Embeddings::decode(&read_bytes(&vector_file)?)?))
+    }
+
+    fn save_embeddings(&self, embeddings: &Embeddings) -> Result<(), Error> {
+        let embedding_dir = join3(&self.root_dir, INDEX_DIR_NAME, EMBEDDING_DIR_NAME)?;
+
+        if !exists(&embedding_dir) {
+            create_dir_all(&embedding_dir)?;
+        }
+
+        write_bytes(
+            &self.get_vector_file_path()?,
+            &embeddings.encode()?,
+            WriteMode::Atomic,
+        )?;
+        Ok(())
+    }
+
+    fn get_vector_file_path(&self) -> Result<String, Error> {
+        Ok(join4(&self.root_dir, INDEX_DIR_NAME, EMBEDDING_DIR_NAME, VECTOR_FILE_NAME)?)
+    }
+
+    async fn embed(&self, model: &Model, inputs: Vec<String>, job: &str) -> Result<Vec<Vec<f32>>, Error> {
+        let request = EmbeddingRequest {
+            model: model.clone(),
+            inputs,
+            timeout: self.api_config.timeout,
+            max_retry: self.api_config.max_retry,
+            sleep_between_retries: self.api_config.sleep_between_retries,
+            dump_api_usage_at: self.api_config.dump_api_usage_at(&self.root_dir, job),
+        };
+
+        Ok(request.send().await?)
+    }
+}
+
+/// It merges rankings into one. A document gets `1 / (RRF_K + rank)` from each
+/// ranking it appears in, so a document that's ranked high in any ranking comes
+/// first, regardless of the scales of the scores.
+pub fn reciprocal_rank_fusion(rankings: &[Vec<Uid>], limit: usize) -> Vec<Uid> {
+    let mut scores: HashMap<Uid, f64> = HashMap::new();
+
+    for ranking in rankings.iter() {
+        for (rank, uid) in ranking.iter().enumerate() {
+            *scores.entry(*uid).or_insert(0.0) += 1.0 / (RRF_K + rank + 1) as f64;
+        }
+    }
+
+    let mut scores = scores.into_iter().collect::<Vec<_>>();
+    scores.sort_by(|(uid_a, score_a), (uid_b, score_b)| score_b.total_cmp(score_a).then(uid_a.cmp(uid_b)));
+    scores.into_iter().take(limit).map(|(uid, _)| uid).collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum::<f32>();
+    let norm_a = a.iter().map(|a| a * a).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|b| b * b).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Embeddings, reciprocal_rank_fusion};
+    use crate::uid::Uid;
+
+    #[test]
+    fn rrf_test() {
+        let uids = (0..4).map(|i| format!("{i:064x}").parse::<Uid>().unwrap()).collect::<Vec<_>>();
+        let keyword = vec![uids[0], uids[1], uids[2]];
+        let vector = vec![uids[1], uids[3], uids[0]];
+
+        // `uids[1]` is ranked high in both rankings
+        assert_eq!(reciprocal_rank_fusion(&[keyword.clone(), vector.clone()], 4), vec![uids[1], uids[0], uids[3], uids[2]]);
+        assert_eq!(reciprocal_rank_fusion(&[keyword.clone(), vector], 2), vec![uids[1], uids[0]]);
+
+        // a single ranking doesn't change
+        assert_eq!(reciprocal_rank_fusion(std::slice::from_ref(&keyword), 3), keyword);
+    }
+
+    #[test]
+    fn embeddings_test() {
+        let mut embeddings = Embeddings::new(String::from("dummy"));
+        assert_eq!(Embeddings::decode(&embeddings.encode().unwrap()).unwrap(), embeddings);
+
+        for i in 0..4 {
+            embeddings.vectors.insert(Uid::new_image(&[i]), vec![i as f32, 0.5, -1.0]);
+        }
+
+        let bytes = embeddings.encode().unwrap();
+        assert_eq!(bytes.len(), 4 + 5 + 4 + 4 * (32 + 3 * 4));
+        assert_eq!(Embeddings::decode(&bytes).unwrap(), embeddings);
+        assert!(Embeddings::decode(&bytes[..(bytes.len() - 1)]).is_err());
+
+        embeddings.vectors.insert(Uid::new_image(&[4]), vec![0.0]);
+        assert!(embeddings.encode().is_err());
+    }
+}
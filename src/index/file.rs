@@ -1,17 +1,30 @@
-use super::BuildConfig;
-use crate::chunk::{Chunk, ChunkBuildInfo, ChunkExtraInfo, ChunkSchema};
-use crate::error::Error;
+use super::{BuildConfig, ChunkUnit, FileReaderKind, NearDuplicates};
+use crate::chunk::{Chunk, ChunkBuildInfo, ChunkExtraInfo, ChunkSchema, SourceRange};
+use crate::error::{Error, JsonType};
 use crate::index::Index;
 use crate::uid::Uid;
 use ragit_fs::extension;
 use ragit_pdl::{MessageContent, ImageType};
-use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use boundary::{BOUNDARY_TOLERANCE, boundary_score, pick_boundary};
 use url::Url;
 
+mod boundary;
+mod code;
+mod email;
+mod encoding;
+mod html;
 mod image;
+mod ipynb;
+mod latex;
 mod line;
 mod markdown;
 mod plain_text;
+mod structured;
+mod subtitle;
+mod token_count;
 
 #[cfg(feature = "csv")]
 mod csv;
@@ -19,16 +32,40 @@ mod csv;
 #[cfg(feature = "csv")]
 pub use csv::CsvReader;
 
+#[cfg(any(feature = "office", feature = "epub"))]
+mod archive;
+
+#[cfg(feature = "epub")]
+mod epub;
+
+#[cfg(feature = "epub")]
+pub use epub::EpubReader;
+
+#[cfg(feature = "office")]
+mod office;
+
+#[cfg(feature = "office")]
+pub use office::{DocxReader, OdtReader, PptxReader, XlsxReader};
+
 #[cfg(feature = "pdf")]
 mod pdf;
 
 #[cfg(feature = "pdf")]
 pub use pdf::PdfReader;
 
+pub use code::CodeReader;
+pub use email::EmailReader;
+pub use encoding::{EncodingDetection, FileEncoding};
+pub use html::HtmlReader;
 pub use image::{Image, ImageDescription, ImageReader};
+pub use ipynb::IpynbReader;
+pub use latex::LatexReader;
 pub use line::LineReader;
 pub use markdown::MarkdownReader;
 pub use plain_text::PlainTextReader;
+pub use structured::StructuredReader;
+pub use subtitle::SubtitleReader;
+pub use token_count::estimate_token_count;
 
 pub type Path = String;
 
@@ -39,7 +76,9 @@ pub type Path = String;
 /// is false. It's designed like this because some files are too big to load to
 /// memory at once.
 pub trait FileReaderImpl {
-    fn new(path: &str, root_dir: &str, config: &BuildConfig) -> Result<Self, Error> where Self: Sized;
+    /// `options` come from `file_readers` in the build config. If there's an option that
+    /// the reader doesn't understand, it has to return an error (see `FileReaderOptions::check_keys`).
+    fn new(path: &str, root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> where Self: Sized;
 
     /// `load_tokens` loads tokens to buffer. This method *empties* and returns the buffer.
     /// You don't have to care about the length of its returned vector. If it contains
@@ -58,46 +97,118 @@ pub trait FileReaderImpl {
 
     /// It's used by `BuildInfo`. It's used to distinguish `FileReader`s.
     fn key(&self) -> String;
+
+    /// If the file reader converts the file to UTF-8 (see `FileEncoding`), it tells
+    /// the original encoding. It's recorded in `ChunkBuildInfo`.
+    fn encoding(&self) -> Option<FileEncoding> {
+        None
+    }
+
+    /// If the file reader converts the file to UTF-8, the byte offsets of
+    /// `AtomicToken::SourcePosition` are offsets in the converted text. It converts
+    /// them to offsets in the original file. It's called with increasing offsets.
+    fn get_original_offset(&self, byte: usize) -> usize {
+        byte
+    }
+
+    /// Files that the file reader reads other than the file itself (e.g. `\input`
+    /// of LaTeX). They're recorded in `ChunkBuildInfo`, and if any of them changes,
+    /// `rag add` stages the file again.
+    fn dependencies(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 pub struct FileReader {  // of a single file
     rel_path: Path,
     inner: Box<dyn FileReaderImpl + Send>,
-    buffer: VecDeque<AtomicToken>,
+
+    // Each token remembers where it is in the file, if the file reader tells us (see `AtomicToken::SourcePosition`).
+    buffer: VecDeque<(AtomicToken, Option<SourceRange>)>,
     curr_buffer_size: usize,
     pub images: HashMap<Uid, Vec<u8>>,
     config: BuildConfig,
 
+    // The last `ChunkExtraInfo` is applied to the following chunks until
+    // there's a `PageBreak`. For example, if a function is split into 3 chunks,
+    // all the 3 chunks have the name of the function.
+    curr_extra_info: Option<ChunkExtraInfo>,
+
+    // (line, byte) of the next token in the file. It's 1-base line and 0-base byte.
+    curr_position: Option<(usize, usize)>,
+    file_reader_key: String,
+
     // this is a cache, purely for optimizing `fetch_images_from_web()`
     fetched_images: HashMap<String, Uid>,  // HashMap<url, image_uid>
 }
 
 impl FileReader {
+    /// `config` is the global build config. The `build_rules` that match `rel_path` are applied here.
     pub fn new(rel_path: Path, real_path: Path, root_dir: &str, config: BuildConfig) -> Result<Self, Error> {
-        // TODO: use a config file, instead of hard-coding the extensions
-        let inner = match extension(&rel_path)?.unwrap_or(String::new()).to_ascii_lowercase().as_str() {
-            "md" => Box::new(MarkdownReader::new(&real_path, root_dir, &config)?) as Box<dyn FileReaderImpl + Send>,
-            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" => Box::new(ImageReader::new(&real_path, root_dir, &config)?),
-            "jsonl" => Box::new(LineReader::new(&real_path, root_dir, &config)?),
-            "csv" => {
+        let config = config.resolve(&rel_path);
+
+        // Otherwise, the sliding window would never move forward.
+        if config.slide_len >= config.chunk_size {
+            return Err(Error::FileReaderError(format!("`slide_len` ({}) of `{rel_path}` has to be smaller than its `chunk_size` ({}). Check `slide_len`, `chunk_size` and `build_rules`.", config.slide_len, config.chunk_size)));
+        }
+        let (kind, options) = match config.get_file_reader_rule(&rel_path) {
+            Some(rule) => (rule.reader, rule.options.clone()),
+            None => (default_file_reader_kind(&rel_path)?, FileReaderOptions::default()),
+        };
+
+        let inner = match kind {
+            FileReaderKind::Markdown => Box::new(MarkdownReader::new(&real_path, root_dir, &config, &options)?) as Box<dyn FileReaderImpl + Send>,
+            FileReaderKind::PlainText => Box::new(PlainTextReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Line => Box::new(LineReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Image => Box::new(ImageReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Code => Box::new(CodeReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Html => Box::new(HtmlReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Ipynb => Box::new(IpynbReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Email => Box::new(EmailReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Subtitle => Box::new(SubtitleReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Structured => Box::new(StructuredReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Latex => Box::new(LatexReader::new(&real_path, root_dir, &config, &options)?),
+            FileReaderKind::Csv => {
                 #[cfg(feature = "csv")]
-                { Box::new(CsvReader::new(&real_path, root_dir, &config)?) }
+                { Box::new(CsvReader::new(&real_path, root_dir, &config, &options)?) }
 
                 #[cfg(not(feature = "csv"))]
-                { Box::new(PlainTextReader::new(&real_path, root_dir, &config)?) }
+                { Box::new(PlainTextReader::new(&real_path, root_dir, &config, &FileReaderOptions::default())?) }
             },
-            "pdf" => {
+            FileReaderKind::Pdf => {
                 #[cfg(feature = "pdf")]
-                { Box::new(PdfReader::new(&real_path, root_dir, &config)?) }
+                { Box::new(PdfReader::new(&real_path, root_dir, &config, &options)?) }
 
                 #[cfg(not(feature = "pdf"))]
                 { return Err(Error::FeatureNotEnabled { feature: String::from("pdf"), action: format!("read `{rel_path}`") }); }
             },
+            FileReaderKind::Docx | FileReaderKind::Pptx | FileReaderKind::Xlsx | FileReaderKind::Odt => {
+                #[cfg(feature = "office")]
+                {
+                    match kind {
+                        FileReaderKind::Docx => Box::new(DocxReader::new(&real_path, root_dir, &config, &options)?) as Box<dyn FileReaderImpl + Send>,
+                        FileReaderKind::Pptx => Box::new(PptxReader::new(&real_path, root_dir, &config, &options)?),
+                        FileReaderKind::Xlsx => Box::new(XlsxReader::new(&real_path, root_dir, &config, &options)?),
+                        _ => Box::new(OdtReader::new(&real_path, root_dir, &config, &options)?),
+                    }
+                }
 
-            // "py" | "rs" => Box::new(CodeReader::new(&real_path, &config)?),
+                #[cfg(not(feature = "office"))]
+                { return Err(Error::FeatureNotEnabled { feature: String::from("office"), action: format!("read `{rel_path}`") }); }
+            },
+            FileReaderKind::Epub => {
+                #[cfg(feature = "epub")]
+                { Box::new(EpubReader::new(&real_path, root_dir, &config, &options)?) }
 
-            // all the unknown extensions are treated as plain texts
-            _ => Box::new(PlainTextReader::new(&real_path, root_dir, &config)?),
+                #[cfg(not(feature = "epub"))]
+                { return Err(Error::FeatureNotEnabled { feature: String::from("epub"), action: format!("read `{rel_path}`") }); }
+            },
+        };
+        let file_reader_key = if options.is_empty() {
+            inner.key()
+        } else {
+            // If the options are changed, the chunks are different.
+            format!("{}{}", inner.key(), serde_json::to_string(&options)?)
         };
 
         Ok(FileReader {
@@ -107,6 +218,9 @@ impl FileReader {
             curr_buffer_size: 0,
             images: HashMap::new(),
             config,
+            curr_extra_info: None,
+            curr_position: None,
+            file_reader_key,
             fetched_images: HashMap::new(),
         })
     }
@@ -121,23 +235,39 @@ impl FileReader {
         self.fill_buffer_until_n_chunks(2)?;
 
         // prevent creating too small chunk
-        let next_chunk_size = if self.config.chunk_size < self.curr_buffer_size && self.curr_buffer_size < self.config.chunk_size * 2 {
+        // If the chunk ends with a `PageBreak`, it's not split, because file readers
+        // use `PageBreak` to group tokens that have to be in the same chunk.
+        let next_chunk_size = if self.config.chunk_size < self.curr_buffer_size && self.curr_buffer_size < self.config.chunk_size * 2 && !self.has_page_break_within(self.config.chunk_size) {
             self.curr_buffer_size / 2
         } else {
             self.config.chunk_size
         };
+        let next_chunk_size = if self.config.prefer_boundaries {
+            self.align_chunk_size(next_chunk_size)
+        } else {
+            next_chunk_size
+        };
 
         let mut chunk_deque = VecDeque::new();
         let mut curr_chunk_size = 0;
         let mut has_page_break = false;
-        let mut chunk_extra_info: Option<ChunkExtraInfo> = None;
+        let mut chunk_extra_info: Option<ChunkExtraInfo> = self.curr_extra_info.clone();
+        let inherited_extra_info = self.curr_extra_info.clone();
+
+        // (n, extra_info): `extra_info` comes right before the `n`th token of `chunk_deque`
+        let mut extra_infos = vec![];
 
         // step 1. collect tokens for a chunk
         while curr_chunk_size < next_chunk_size && !self.buffer.is_empty() {
-            let token = self.buffer.pop_front().unwrap();
+            let (token, range) = self.buffer.pop_front().unwrap();
 
             if let AtomicToken::ChunkExtraInfo(extra_info) = &token {
                 match &mut chunk_extra_info {
+                    // If the chunk starts with a `ChunkExtraInfo`, the inherited one is not
+                    // about this chunk.
+                    Some(_) if chunk_deque.is_empty() => {
+                        chunk_extra_info = Some(extra_info.clone());
+                    },
                     Some(old) => {
                         *old = old.merge(extra_info);
                     },
@@ -146,17 +276,29 @@ impl FileReader {
                     },
                 }
 
+                self.curr_extra_info = Some(extra_info.clone());
+                extra_infos.push((chunk_deque.len(), extra_info.clone()));
                 continue;
             }
 
             if let AtomicToken::PageBreak = &token {
                 has_page_break = true;
+                self.curr_extra_info = None;
                 break;
             }
 
-            self.curr_buffer_size -= token.len(self.config.image_size);
-            curr_chunk_size += token.len(self.config.image_size);
-            chunk_deque.push_back(token);
+            let token_len = self.token_len(&token);
+            self.curr_buffer_size -= token_len;
+            curr_chunk_size += token_len;
+            chunk_deque.push_back((token, range));
+        }
+
+        // If the chunk is full right before a `PageBreak`, the `PageBreak` belongs to
+        // this chunk. Otherwise, the next chunk would be empty.
+        if !has_page_break && matches!(self.buffer.front(), Some((AtomicToken::PageBreak, _))) {
+            self.buffer.pop_front();
+            has_page_break = true;
+            self.curr_extra_info = None;
         }
 
         // step 2. create a sliding window
@@ -165,19 +307,51 @@ impl FileReader {
         if !has_page_break && (!self.buffer.is_empty() || chunk_deque.len() == 1) {
             let mut sliding_window_deque = VecDeque::new();
             let mut curr_sliding_window_size = 0;
+            let slide_len = if self.config.prefer_boundaries {
+                self.align_slide_len(&chunk_deque, curr_chunk_size)
+            } else {
+                self.config.slide_len
+            };
+
+            while curr_sliding_window_size < slide_len && !chunk_deque.is_empty() {
+                let (token, range) = chunk_deque.pop_back().unwrap();
+                let token_len = self.token_len(&token);
+                curr_sliding_window_size += token_len;
+                self.buffer.push_front((token.clone(), range));
+                self.curr_buffer_size += token_len;
+                sliding_window_deque.push_front((token, range));
+
+                // `ChunkExtraInfo`s in the sliding window also go back to the buffer, so that
+                // the next chunk knows the extra info of its first token (e.g. `TimeRange` of
+                // the first cue in the sliding window).
+                while let Some((n, _)) = extra_infos.last() {
+                    if *n < chunk_deque.len() {
+                        break;
+                    }
 
-            while curr_sliding_window_size < self.config.slide_len && !chunk_deque.is_empty() {
-                let token = chunk_deque.pop_back().unwrap();
-                curr_sliding_window_size += token.len(self.config.image_size);
-                self.buffer.push_front(token.clone());
-                self.curr_buffer_size += token.len(self.config.image_size);
-                sliding_window_deque.push_front(token);
+                    let (_, extra_info) = extra_infos.pop().unwrap();
+                    self.buffer.push_front((AtomicToken::ChunkExtraInfo(extra_info), None));
+                }
             }
 
+            self.curr_extra_info = match extra_infos.last() {
+                Some((_, extra_info)) => Some(extra_info.clone()),
+                None => inherited_extra_info,
+            };
+
             // prevent infinite loop
             if curr_sliding_window_size == curr_chunk_size {
-                let token = self.buffer.pop_front().unwrap();
-                self.curr_buffer_size -= token.len(self.config.image_size);
+                loop {
+                    let (token, _) = self.buffer.pop_front().unwrap();
+
+                    if let AtomicToken::ChunkExtraInfo(extra_info) = token {
+                        self.curr_extra_info = Some(extra_info);
+                        continue;
+                    }
+
+                    self.curr_buffer_size -= self.token_len(&token);
+                    break;
+                }
             }
 
             for token in sliding_window_deque.into_iter() {
@@ -185,7 +359,25 @@ impl FileReader {
             }
         }
 
-        let tokens = merge_tokens(chunk_deque);
+        let mut range: Option<SourceRange> = None;
+
+        for (_, token_range) in chunk_deque.iter() {
+            if let Some(token_range) = token_range {
+                range = Some(match range {
+                    Some(range) => range.extend(token_range),
+                    None => *token_range,
+                });
+            }
+        }
+
+        if range.is_some() {
+            chunk_extra_info = Some(ChunkExtraInfo {
+                range,
+                ..chunk_extra_info.unwrap_or_default()
+            });
+        }
+
+        let tokens = merge_tokens(chunk_deque.into_iter().map(|(token, _)| token).collect());
 
         for token in tokens.iter() {
             if let AtomicToken::Image(Image { uid, bytes, .. }) = token {
@@ -202,25 +394,105 @@ impl FileReader {
         build_info: ChunkBuildInfo,
         previous_turn: Option<(Chunk, ChunkSchema)>,
         index_in_file: usize,
+        near_duplicates: Option<&NearDuplicates>,
     ) -> Result<Chunk, Error> {
         let (tokens, chunk_extra_info) = self.next_chunk()?;
         let tokens = self.fetch_images_from_web(tokens).await?;
+        let source = chunk_extra_info.unwrap_or_default().into_file_source(&self.rel_path, index_in_file)?;
 
         let chunk = Chunk::create_chunk_from(
             index,
+            &self.config,
             &tokens,
-            self.rel_path.clone(),
-            index_in_file,
+            source,
             build_info,
             previous_turn,
-            chunk_extra_info,
-        ).await;
+            near_duplicates,
+        ).await?;
 
-        if let Some(ms) = index.api_config.sleep_after_llm_call {
+        // a near-duplicate doesn't call the LLM
+        if let Some(ms) = index.api_config.sleep_after_llm_call.filter(|_| chunk.build_info.duplicate_of.is_none()) {
             tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
         }
 
-        chunk
+        Ok(chunk)
+    }
+
+    // If `prefer_boundaries` is set, the next chunk ends at the best boundary around `size`.
+    // It returns the size of such chunk.
+    fn align_chunk_size(&self, size: usize) -> usize {
+        let tolerance = size * BOUNDARY_TOLERANCE / 100;
+        let mut candidates = vec![];
+        let mut curr_size = 0;
+
+        for (index, (token, _)) in self.buffer.iter().enumerate() {
+            match token {
+                // The chunk ends here anyway. If it's a bit bigger than `size`, it's okay.
+                AtomicToken::PageBreak => {
+                    return size.max(curr_size);
+                },
+                AtomicToken::ChunkExtraInfo(_) | AtomicToken::SourcePosition { .. } => {
+                    continue;
+                },
+                _ => {},
+            }
+
+            curr_size += self.token_len(token);
+
+            if curr_size > size + tolerance {
+                return pick_boundary(&candidates, size);
+            }
+
+            if curr_size + tolerance >= size {
+                candidates.push((curr_size, boundary_score(&self.buffer, index + 1)));
+            }
+        }
+
+        // the rest of the file fits in the chunk
+        size
+    }
+
+    // If `prefer_boundaries` is set, the sliding window starts at the best boundary
+    // around `slide_len`. It returns the size of such window.
+    fn align_slide_len(&self, chunk: &VecDeque<(AtomicToken, Option<SourceRange>)>, chunk_size: usize) -> usize {
+        let slide_len = self.config.slide_len;
+        let tolerance = slide_len * BOUNDARY_TOLERANCE / 100;
+        let mut candidates = vec![];
+        let mut curr_size = 0;
+
+        // The sliding window cannot be the entire chunk.
+        for index in (1..chunk.len()).rev() {
+            curr_size += self.token_len(&chunk[index].0);
+
+            if curr_size > slide_len + tolerance || curr_size >= chunk_size {
+                break;
+            }
+
+            if curr_size + tolerance >= slide_len {
+                candidates.push((curr_size, boundary_score(chunk, index)));
+            }
+        }
+
+        pick_boundary(&candidates, slide_len)
+    }
+
+    // whether there's a `PageBreak` in the first `size` characters of the buffer
+    fn has_page_break_within(&self, size: usize) -> bool {
+        let mut curr_size = 0;
+
+        for (token, _) in self.buffer.iter() {
+            if let AtomicToken::PageBreak = token {
+                return true;
+            }
+
+            curr_size += self.token_len(token);
+
+            if curr_size >= size {
+                return false;
+            }
+        }
+
+        false
     }
 
     fn fill_buffer_until_n_chunks(&mut self, n: usize) -> Result<(), Error> {
@@ -232,8 +504,38 @@ impl FileReader {
             self.inner.load_tokens()?;
 
             for token in self.inner.pop_all_tokens()? {
-                self.curr_buffer_size += token.len(self.config.image_size);
-                self.buffer.push_back(token);
+                let range = match &token {
+                    AtomicToken::SourcePosition { line, byte } => {
+                        self.curr_position = Some((*line, *byte));
+                        continue;
+                    },
+                    AtomicToken::String { data, .. } => match self.curr_position {
+                        Some((line, byte)) if !data.is_empty() => {
+                            let newlines = data.matches('\n').count();
+                            self.curr_position = Some((line + newlines, byte + data.len()));
+
+                            // A token might start or end with newline characters (e.g. "\nfoo" or "foo\n").
+                            // Those newlines don't make the token belong to the previous or the next line.
+                            let end_line = line + newlines - if data.ends_with('\n') { 1 } else { 0 };
+                            let leading_newlines = data.len() - data.trim_start_matches('\n').len();
+
+                            Some(SourceRange {
+                                start_line: (line + leading_newlines).min(end_line),
+                                end_line,
+                                start_byte: self.inner.get_original_offset(byte),
+                                end_byte: self.inner.get_original_offset(byte + data.len()),
+                            })
+                        },
+                        _ => None,
+                    },
+
+                    // Images are not in the text, so they don't move the cursor. If a file reader
+                    // converts an image syntax to an image, it has to emit `SourcePosition` again.
+                    _ => None,
+                };
+
+                self.curr_buffer_size += self.token_len(&token);
+                self.buffer.push_back((token, range));
             }
 
             if !self.inner.has_more_to_read() {
@@ -242,8 +544,24 @@ impl FileReader {
         }
     }
 
+    // the length of a token in `chunk_unit`
+    fn token_len(&self, token: &AtomicToken) -> usize {
+        match (self.config.chunk_unit, token) {
+            (ChunkUnit::Token, AtomicToken::String { data, .. }) => estimate_token_count(data),
+            _ => token.len(self.config.image_size),
+        }
+    }
+
     pub fn file_reader_key(&self) -> String {
-        self.inner.key()
+        self.file_reader_key.clone()
+    }
+
+    pub fn encoding(&self) -> Option<FileEncoding> {
+        self.inner.encoding()
+    }
+
+    pub fn dependencies(&self) -> Vec<String> {
+        self.inner.dependencies()
     }
 
     /// It replaces `AtomicToken::WebImage` in `tokens` with `AtomicToken::Image`.
@@ -287,6 +605,110 @@ impl FileReader {
     }
 }
 
+// If there's no `file_readers` rule for the file, it chooses a file reader by its extension.
+fn default_file_reader_kind(rel_path: &str) -> Result<FileReaderKind, Error> {
+    let kind = match extension(rel_path)?.unwrap_or(String::new()).to_ascii_lowercase().as_str() {
+        "md" | "markdown" | "mdx" => FileReaderKind::Markdown,
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" => FileReaderKind::Image,
+        "jsonl" | "ndjson" => FileReaderKind::Line,
+        "html" | "htm" | "xhtml" => FileReaderKind::Html,
+        "ipynb" => FileReaderKind::Ipynb,
+        "eml" | "mbox" => FileReaderKind::Email,
+        "srt" | "vtt" => FileReaderKind::Subtitle,
+        "json" | "yaml" | "yml" | "toml" => FileReaderKind::Structured,
+        "tex" | "ltx" => FileReaderKind::Latex,
+        "docx" | "docm" => FileReaderKind::Docx,
+        "pptx" | "pptm" => FileReaderKind::Pptx,
+        "xlsx" | "xlsm" => FileReaderKind::Xlsx,
+        "odt" => FileReaderKind::Odt,
+        "epub" => FileReaderKind::Epub,
+        "csv" | "tsv" => FileReaderKind::Csv,
+        "pdf" => FileReaderKind::Pdf,
+        "rs" | "py" | "pyi"
+        | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts"
+        | "go"
+        | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" | "java" | "cs" => FileReaderKind::Code,
+
+        // all the unknown extensions are treated as plain texts
+        _ => FileReaderKind::PlainText,
+    };
+
+    Ok(kind)
+}
+
+/// Options for a file reader. You can set them in `file_readers` of the build config.
+///
+/// ```json
+/// { "pattern": "*.tsv", "reader": "csv", "options": { "delimiter": "\t" } }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct FileReaderOptions(BTreeMap<String, Value>);
+
+impl FileReaderOptions {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// It returns an error if there's an option that is not in `keys`.
+    /// It's to prevent typos from being silently ignored.
+    pub fn check_keys(&self, reader: &str, keys: &[&str]) -> Result<(), Error> {
+        for key in self.0.keys() {
+            if !keys.contains(&key.as_str()) {
+                return Err(Error::FileReaderError(format!(
+                    "`{reader}` does not have an option `{key}`. {}",
+                    if keys.is_empty() {
+                        String::from("It does not have any option.")
+                    } else {
+                        format!("Available options are: {}", keys.iter().map(|key| format!("`{key}`")).collect::<Vec<_>>().join(", "))
+                    },
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<Option<String>, Error> {
+        match self.0.get(key) {
+            Some(Value::String(s)) => Ok(Some(s.to_string())),
+            Some(Value::Null) | None => Ok(None),
+            Some(v) => Err(Error::JsonTypeError {
+                expected: JsonType::String,
+                got: v.into(),
+            }),
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, Error> {
+        match self.0.get(key) {
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(Value::Null) | None => Ok(None),
+            Some(v) => Err(Error::JsonTypeError {
+                expected: JsonType::Boolean,
+                got: v.into(),
+            }),
+        }
+    }
+
+    pub fn get_usize(&self, key: &str) -> Result<Option<usize>, Error> {
+        match self.0.get(key) {
+            Some(Value::Number(n)) => match n.as_u64() {
+                Some(n) => Ok(Some(n as usize)),
+                None => Err(Error::JsonTypeError {
+                    expected: JsonType::Usize,
+                    got: JsonType::Number,
+                }),
+            },
+            Some(Value::Null) | None => Ok(None),
+            Some(v) => Err(Error::JsonTypeError {
+                expected: JsonType::Usize,
+                got: v.into(),
+            }),
+        }
+    }
+}
+
 fn merge_tokens(tokens: VecDeque<AtomicToken>) -> Vec<AtomicToken> {
     let mut buffer = vec![];
     let mut result = vec![];
@@ -309,7 +731,8 @@ fn merge_tokens(tokens: VecDeque<AtomicToken>) -> Vec<AtomicToken> {
 
             // not rendered
             AtomicToken::PageBreak
-           | AtomicToken::ChunkExtraInfo(_) => {},
+           | AtomicToken::ChunkExtraInfo(_)
+           | AtomicToken::SourcePosition { .. } => {},
         }
     }
 
@@ -385,6 +808,20 @@ pub enum AtomicToken {
     /// If there are multiple `ChunkExtraInfo`s in a chunk,
     /// ragit will do *its best* to interpret them.
     ChunkExtraInfo(ChunkExtraInfo),
+
+    /// It's an invisible AtomicToken. It tells ragit that the next
+    /// token starts at `line` (1-base) and `byte` (0-base) of the file.
+    ///
+    /// If your file reader copies the file verbatim, you only have to
+    /// emit this token once, at the beginning of the file. Ragit counts
+    /// the lines and bytes of the following `String` tokens by itself, and
+    /// records the line range of each chunk. If your file reader modifies
+    /// the text (e.g. an image syntax in markdown is converted to an image),
+    /// you have to emit this token again after the modified part.
+    ///
+    /// If your file reader never emits this token, the chunks don't have
+    /// line ranges.
+    SourcePosition { line: usize, byte: usize },
 }
 
 impl AtomicToken {
@@ -394,7 +831,8 @@ impl AtomicToken {
             AtomicToken::Image(_) => image_size,
             AtomicToken::WebImage { .. } => image_size,
             AtomicToken::PageBreak
-           | AtomicToken::ChunkExtraInfo(_) => 0,
+           | AtomicToken::ChunkExtraInfo(_)
+           | AtomicToken::SourcePosition { .. } => 0,
         }
     }
 }
@@ -414,7 +852,8 @@ impl From<AtomicToken> for MessageContent {
 
             // this branch is not supposed to be reached
             AtomicToken::PageBreak
-           | AtomicToken::ChunkExtraInfo(_) => MessageContent::String(String::new()),
+           | AtomicToken::ChunkExtraInfo(_)
+           | AtomicToken::SourcePosition { .. } => MessageContent::String(String::new()),
         }
     }
 }
@@ -0,0 +1,127 @@
+use super::{AtomicToken, Image};
+use crate::error::Error;
+use ragit_fs::{FileError, extension};
+use ragit_pdl::ImageType;
+use roxmltree::Node;
+use std::fs::File;
+use std::io::Read;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+/// Office files (docx, pptx, xlsx and odt) and epub files are zip archives of xml files.
+pub(super) struct Archive {
+    path: String,
+    zip: ZipArchive<File>,
+}
+
+impl Archive {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        match File::open(path) {
+            Ok(f) => Ok(Archive {
+                path: path.to_string(),
+                zip: ZipArchive::new(f)?,
+            }),
+            Err(e) => Err(FileError::from_std(e, path).into()),
+        }
+    }
+
+    /// It returns `None` if there's no such file in the archive.
+    pub fn read_bytes(&mut self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let mut file = match self.zip.by_name(name) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => {
+                return Ok(None);
+            },
+            Err(e) => {
+                return Err(e.into());
+            },
+        };
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    pub fn read_string(&mut self, name: &str) -> Result<Option<String>, Error> {
+        match self.read_bytes(name)? {
+            Some(bytes) => Ok(Some(String::from_utf8(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// It's like `read_string`, but it's an error if the file is missing.
+    pub fn read_string_mandatory(&mut self, name: &str) -> Result<String, Error> {
+        match self.read_string(name)? {
+            Some(s) => Ok(s),
+            None => Err(Error::FileReaderError(format!("`{}` is broken: `{name}` is missing.", self.path))),
+        }
+    }
+
+    /// It returns `None` if ragit cannot read the image (e.g. emf).
+    pub fn load_image(&mut self, name: &str, strict_mode: bool) -> Result<Option<AtomicToken>, Error> {
+        let image_type = match extension(name)?.map(|ext| ImageType::from_extension(&ext)) {
+            Some(Ok(image_type)) => image_type,
+
+            // Office files often have images that are not supported (e.g. emf and wmf).
+            // They're usually icons and decorations, so it just skips them, even in strict mode.
+            _ => {
+                return Ok(None);
+            },
+        };
+
+        let bytes = match self.read_bytes(name)? {
+            Some(bytes) => bytes,
+            None => if strict_mode {
+                return Err(Error::FileReaderError(format!("`{}` is broken: `{name}` is missing.", self.path)));
+            } else {
+                return Ok(None);
+            },
+        };
+
+        match Image::new(bytes, image_type) {
+            Ok(image) => Ok(Some(AtomicToken::Image(image))),
+            Err(e) => if strict_mode {
+                Err(e)
+            } else {
+                Ok(None)
+            },
+        }
+    }
+}
+
+// `target` is relative to `dir`, unless it starts with '/'.
+pub(super) fn resolve_path(dir: &str, target: &str) -> String {
+    if let Some(target) = target.strip_prefix('/') {
+        return target.to_string();
+    }
+
+    let mut components = dir.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+
+    for component in target.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => { components.pop(); },
+            c => { components.push(c); },
+        }
+    }
+
+    components.join("/")
+}
+
+// Office files and epub files use many namespaces, but ragit doesn't care about namespaces
+// of elements. Local names are enough to tell which element it is.
+pub(super) fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.tag_name().name() == name)
+}
+
+pub(super) fn child<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+pub(super) fn descendants<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.descendants().filter(move |child| child.tag_name().name() == name)
+}
+
+// It ignores the namespace.
+pub(super) fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(|attribute| attribute.name() == name).map(|attribute| attribute.value())
+}
@@ -0,0 +1,134 @@
+use super::AtomicToken;
+use crate::chunk::SourceRange;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+// If `prefer_boundaries` is set, a chunk (or a sliding window) can be this much (in percent)
+// smaller or bigger than `chunk_size` (or `slide_len`), so that it ends at a better boundary.
+pub(super) const BOUNDARY_TOLERANCE: usize = 20;
+
+// A higher score is a better place to cut.
+const PARAGRAPH: usize = 3;
+const SENTENCE: usize = 2;
+const LINE: usize = 1;
+const WORD: usize = 0;
+
+// It only looks at this many tokens before and after a cut.
+const LOOKAROUND: usize = 16;
+
+const SENTENCE_ENDS: [char; 6] = ['.', '!', '?', '。', '！', '？'];
+
+// `He said "Hi."` ends with `.`, not `"`.
+const CLOSING_CHARS: [char; 9] = ['"', '\'', ')', ']', '”', '’', '」', '』', '）'];
+
+/// How good it is to cut `tokens` right before `tokens[index]`: blank lines,
+/// then sentence ends, then line breaks, then the others.
+pub(super) fn boundary_score(tokens: &VecDeque<(AtomicToken, Option<SourceRange>)>, index: usize) -> usize {
+    let mut whitespaces = String::new();
+    let mut last_text = None;
+
+    // the end of the text before the cut
+    for (token, _) in tokens.range(..index).rev().take(LOOKAROUND) {
+        match token {
+            AtomicToken::String { data, .. } => {
+                let trimmed = data.trim_end();
+                whitespaces.push_str(&data[trimmed.len()..]);
+
+                if !trimmed.is_empty() {
+                    last_text = Some(trimmed);
+                    break;
+                }
+            },
+
+            // an image is like a line
+            AtomicToken::Image(_) | AtomicToken::WebImage { .. } => {
+                whitespaces.push('\n');
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    // the start of the text after the cut
+    for (token, _) in tokens.range(index..).take(LOOKAROUND) {
+        match token {
+            AtomicToken::String { data, .. } => {
+                let trimmed = data.trim_start();
+                whitespaces.push_str(&data[..(data.len() - trimmed.len())]);
+
+                if !trimmed.is_empty() {
+                    break;
+                }
+            },
+            AtomicToken::Image(_) | AtomicToken::WebImage { .. } => {
+                whitespaces.push('\n');
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    let newlines = whitespaces.matches('\n').count();
+    let is_sentence_end = match last_text.and_then(|text| text.trim_end_matches(CLOSING_CHARS).chars().last()) {
+        // CJK sentences don't need a whitespace after the period.
+        Some(c) => SENTENCE_ENDS.contains(&c) && (!whitespaces.is_empty() || !c.is_ascii()),
+        None => false,
+    };
+
+    if newlines >= 2 {
+        PARAGRAPH
+    }
+
+    else if is_sentence_end {
+        SENTENCE
+    }
+
+    else if newlines == 1 {
+        LINE
+    }
+
+    else {
+        WORD
+    }
+}
+
+/// `candidates` are (size, score) of the cuts. It returns the size of the cut with
+/// the highest score. If there are ties, it chooses the one closest to `size`, and
+/// then the smaller one.
+pub(super) fn pick_boundary(candidates: &[(usize, usize)], size: usize) -> usize {
+    candidates.iter().max_by_key(
+        |(candidate, score)| (*score, Reverse(candidate.abs_diff(size)), Reverse(*candidate))
+    ).map(
+        |(candidate, _)| *candidate
+    ).unwrap_or(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LINE, PARAGRAPH, SENTENCE, WORD, boundary_score, pick_boundary};
+    use crate::index::file::AtomicToken;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn boundary_test() {
+        let tokens = [
+            "Hello", " world.", "\n", "\nIt", " is", " a", " \"test.\"", " Is", " it?\n", "Yes", "。それは", "テスト。", "次の文。",
+        ].iter().map(
+            |s| (AtomicToken::String { data: s.to_string(), char_len: s.chars().count() }, None)
+        ).collect::<VecDeque<_>>();
+
+        assert_eq!(boundary_score(&tokens, 1), WORD);
+        assert_eq!(boundary_score(&tokens, 2), PARAGRAPH);
+        assert_eq!(boundary_score(&tokens, 3), PARAGRAPH);
+        assert_eq!(boundary_score(&tokens, 4), WORD);
+        assert_eq!(boundary_score(&tokens, 7), SENTENCE);
+        assert_eq!(boundary_score(&tokens, 9), SENTENCE);
+        assert_eq!(boundary_score(&tokens, 10), WORD);
+        assert_eq!(boundary_score(&tokens, 11), WORD);
+        assert_eq!(boundary_score(&tokens, 12), SENTENCE);
+
+        assert_eq!(pick_boundary(&[(90, WORD), (95, LINE), (105, LINE), (110, WORD)], 100), 95);
+        assert_eq!(pick_boundary(&[(90, SENTENCE), (99, LINE), (100, WORD)], 100), 90);
+        assert_eq!(pick_boundary(&[], 100), 100);
+    }
+}
@@ -0,0 +1,939 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::{BuildConfig, ChunkUnit};
+use lazy_static::lazy_static;
+use ragit_fs::{FileError, extension};
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+lazy_static! {
+    static ref RUST_FN_RE: Regex = Regex::new(r#"^(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default|extern(?:\s+"[^"]*")?)\s+)*fn\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RUST_ITEM_RE: Regex = Regex::new(r"^(?:pub(?:\([^)]*\))?\s+)?(?:unsafe\s+)?(struct|enum|trait|union|mod|type|const|static|macro_rules!)\s*(?:mut\s+)?([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref RUST_IMPL_RE: Regex = Regex::new(r"^(?:unsafe\s+)?impl\b").unwrap();
+    static ref PYTHON_ITEM_RE: Regex = Regex::new(r"^(?:async\s+)?(def|class)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref JS_PREFIX_RE: Regex = Regex::new(r"^(?:(?:export|default|declare|abstract|async|static|public|private|protected|readonly|get|set)\s+)*").unwrap();
+    static ref JS_ITEM_RE: Regex = Regex::new(r"^(function\*?|class|interface|type|enum|namespace|module|const|let|var)\s*([A-Za-z_$][A-Za-z0-9_$]*)").unwrap();
+    static ref JS_METHOD_RE: Regex = Regex::new(r"^\*?\s*([A-Za-z_$][A-Za-z0-9_$]*)\s*(?:<[^>]*>)?\s*\(").unwrap();
+    static ref GO_FUNC_RE: Regex = Regex::new(r"^func\s+(?:\(([^)]*)\)\s*)?([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref GO_ITEM_RE: Regex = Regex::new(r"^(type|var|const)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref C_TYPE_RE: Regex = Regex::new(r"\b(class|struct|union|enum|interface|namespace|record)\s+(?:class\s+)?([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref C_FUNC_RE: Regex = Regex::new(r"([A-Za-z_~][A-Za-z0-9_:~]*)\s*\(").unwrap();
+}
+
+/// It splits source code at item (function, impl, class, ...) boundaries.
+///
+/// It's not a real parser. It's a bunch of heuristics that count brackets (or
+/// indentations, in Python) outside of strings and comments. Consecutive items
+/// are grouped until the group reaches `chunk_size`, and each group is separated
+/// by `AtomicToken::PageBreak`. An item that's bigger than `chunk_size` is split
+/// by `FileReader`, like any other text.
+///
+/// Container items (`impl`, `trait`, `mod`, `class`, `namespace`) are not
+/// treated as a single item. Ragit looks into the container and splits its members.
+///
+/// Doc comments, attributes and decorators are attached to the item that comes
+/// right after them. The name of each item (e.g. "fn `build_worker`") is passed
+/// to the chunk via `ChunkExtraInfo`.
+pub struct CodeReader {
+    lines: BufReader<File>,
+    language: Language,
+    lexer: Lexer,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+    chunk_size: usize,
+    chunk_unit: ChunkUnit,
+
+    // comments, attributes and empty lines that belong to the next item
+    pending: Vec<String>,
+    curr_item: Option<CodeItem>,
+
+    // the current group ends with a `PageBreak`
+    curr_group_len: usize,
+
+    // brace languages: depth of the current container
+    // python: indentation of the items in the current container
+    containers: Vec<usize>,
+
+    // python: `class Foo:` is seen, and its body is not seen yet
+    expect_container_body: bool,
+
+    // rust and c-family: it's reading a multi-line attribute
+    in_attribute: bool,
+
+    // c-family: it's reading a multi-line preprocessor directive
+    in_preprocessor: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,  // and TypeScript
+    Go,
+    C,  // and C++, Java and C#
+}
+
+impl Language {
+    // It also accepts the names of the languages, so that users can set `language` option.
+    fn from_extension(ext: &str) -> Option<Language> {
+        match ext {
+            "rs" | "rust" => Some(Language::Rust),
+            "py" | "pyi" | "python" => Some(Language::Python),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" | "javascript" | "typescript" => Some(Language::JavaScript),
+            "go" => Some(Language::Go),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" | "java" | "cs" | "c++" | "csharp" => Some(Language::C),
+            _ => None,
+        }
+    }
+}
+
+struct CodeItem {
+    name: Option<String>,
+    lines: Vec<String>,
+
+    // in `chunk_unit`
+    len: usize,
+
+    // If an item is too long, it's flushed before it's complete.
+    is_flushed: bool,
+    has_opened_brace: bool,
+    header_lines: usize,
+}
+
+impl CodeItem {
+    fn new(name: Option<String>) -> Self {
+        CodeItem {
+            name,
+            lines: vec![],
+            len: 0,
+            is_flushed: false,
+            has_opened_brace: false,
+            header_lines: 0,
+        }
+    }
+
+    fn push(&mut self, line: String, unit: ChunkUnit) {
+        self.len += unit.measure(&line);
+        self.lines.push(line);
+    }
+}
+
+impl FileReaderImpl for CodeReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("code", &["language"])?;
+
+        // If there's no `language` option, it guesses the language from the extension.
+        let language = match options.get_string("language")? {
+            Some(language) => language,
+            None => extension(path)?.unwrap_or(String::new()).to_ascii_lowercase(),
+        };
+        let language = match Language::from_extension(&language) {
+            Some(language) => language,
+            None => {
+                return Err(Error::FileReaderError(format!("`CodeReader` cannot read `{path}`: unknown language `{language}`")));
+            },
+        };
+
+        match File::open(path) {
+            Ok(f) => Ok(CodeReader {
+                lines: BufReader::new(f),
+                language,
+                lexer: Lexer::new(language),
+                tokens: vec![AtomicToken::SourcePosition { line: 1, byte: 0 }],
+                is_exhausted: false,
+                strict_mode: config.strict_file_reader,
+                chunk_size: config.chunk_size,
+                chunk_unit: config.chunk_unit,
+                pending: vec![],
+                curr_item: None,
+                curr_group_len: 0,
+                containers: vec![0],
+                expect_container_body: false,
+                in_attribute: false,
+                in_preprocessor: false,
+            }),
+            Err(e) => Err(FileError::from_std(e, path).into()),
+        }
+    }
+
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        // It reads until at least 1 token is generated.
+        while self.tokens.is_empty() {
+            let mut bytes = vec![];
+
+            if self.lines.read_until(b'\n', &mut bytes)? == 0 {
+                self.is_exhausted = true;
+                self.end_item();
+
+                let pending = std::mem::take(&mut self.pending);
+                self.push_lines(pending);
+                break;
+            }
+
+            let line = if self.strict_mode {
+                String::from_utf8(bytes)?
+            } else {
+                String::from_utf8_lossy(&bytes).to_string()
+            };
+
+            match self.language {
+                Language::Python => self.feed_python_line(line),
+                _ => self.feed_brace_line(line),
+            }
+
+            // If an item is too long, it doesn't wait until the item ends.
+            if self.curr_item.as_ref().map(|item| item.lines.len() > 256).unwrap_or(false) {
+                self.flush_item(false);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("code_reader_v1")
+    }
+}
+
+impl CodeReader {
+    fn feed_brace_line(&mut self, line: String) {
+        let container_depth = *self.containers.last().unwrap();
+        let trimmed = line.trim();
+
+        // c-family: preprocessor directives are not lexed at all
+        if self.language == Language::C && (self.in_preprocessor || self.curr_item.is_none() && trimmed.starts_with('#')) {
+            self.in_preprocessor = trimmed.ends_with('\\');
+            self.start_item(None);
+            self.push_to_item(line);
+
+            if !self.in_preprocessor {
+                self.end_item();
+            }
+
+            return;
+        }
+
+        let info = self.lexer.lex_line(&line);
+
+        match &mut self.curr_item {
+            Some(item) => {
+                // It tries to find the name of the item in the header of the item.
+                // e.g. `template <typename T>\nclass Foo {`
+                if item.name.is_none() && !item.has_opened_brace && item.header_lines < 4 {
+                    item.name = parse_item_name(self.language, trimmed, self.containers.len() > 1);
+                    item.header_lines += 1;
+                }
+
+                item.has_opened_brace |= info.has_opened_brace;
+                self.push_to_item(line);
+            },
+            None => {
+                if self.in_attribute || !info.has_code || is_attribute(self.language, trimmed) {
+                    self.in_attribute = self.lexer.depth > container_depth;
+                    self.pending.push(line);
+                    return;
+                }
+
+                let name = parse_item_name(self.language, trimmed, self.containers.len() > 1);
+                self.start_item(name);
+
+                if let Some(item) = &mut self.curr_item {
+                    item.has_opened_brace = info.has_opened_brace;
+                    item.header_lines = 1;
+                }
+
+                self.push_to_item(line);
+            },
+        }
+
+        // The current container is closed.
+        if self.containers.len() > 1 && self.lexer.depth < container_depth {
+            self.end_item();
+
+            while self.containers.len() > 1 && self.lexer.depth < *self.containers.last().unwrap() {
+                self.containers.pop();
+            }
+
+            return;
+        }
+
+        let Some(item) = &self.curr_item else { unreachable!() };
+
+        // A container's header is an item, and its members are items, too.
+        if self.lexer.depth == container_depth + 1
+            && info.last_code_char == Some('{')
+            && item.name.as_ref().map(|name| is_container(name)).unwrap_or(false) {
+            self.end_item();
+            self.containers.push(container_depth + 1);
+            return;
+        }
+
+        if self.lexer.depth == container_depth {
+            let has_ended = match self.language {
+                Language::Rust | Language::C => item.has_opened_brace || info.last_code_char == Some(';'),
+
+                // These languages don't need semicolons, so it has to guess whether
+                // the statement continues in the next line.
+                _ => match info.last_code_char {
+                    Some(c) => !",=([{+-*/&|.:?<>!\\".contains(c),
+                    None => false,
+                },
+            };
+
+            if has_ended {
+                self.end_item();
+            }
+        }
+    }
+
+    fn feed_python_line(&mut self, line: String) {
+        // The line is inside brackets or a multi-line string.
+        let is_continuation = self.lexer.depth > 0 || self.lexer.is_in_string() || self.curr_item.as_ref().map(
+            |item| item.lines.last().map(|line| line.trim_end().ends_with('\\')).unwrap_or(false)
+        ).unwrap_or(false);
+        let info = self.lexer.lex_line(&line);
+
+        if is_continuation || line.trim().is_empty() {
+            if self.curr_item.is_some() {
+                self.push_to_item(line);
+            } else {
+                self.pending.push(line);
+            }
+
+            return;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+
+        // A comment does not open nor close a container.
+        if !info.has_code {
+            if indent > *self.containers.last().unwrap() && self.curr_item.is_some() {
+                self.push_to_item(line);
+            } else {
+                self.end_item();
+                self.pending.push(line);
+            }
+
+            return;
+        }
+
+        if self.expect_container_body {
+            self.expect_container_body = false;
+
+            if indent > *self.containers.last().unwrap() {
+                self.end_item();
+                self.containers.push(indent);
+            }
+        }
+
+        while self.containers.len() > 1 && indent < *self.containers.last().unwrap() {
+            self.containers.pop();
+        }
+
+        // a line inside an item
+        if indent > *self.containers.last().unwrap() {
+            if self.curr_item.is_some() {
+                self.push_to_item(line);
+            } else {
+                self.pending.push(line);
+            }
+
+            return;
+        }
+
+        self.end_item();
+
+        if line.trim_start().starts_with('@') {
+            self.pending.push(line);
+            return;
+        }
+
+        let name = parse_item_name(self.language, line.trim(), self.containers.len() > 1);
+        self.expect_container_body = name.as_ref().map(|name| is_container(name)).unwrap_or(false) && info.last_code_char == Some(':');
+        self.start_item(name);
+        self.push_to_item(line);
+    }
+
+    fn start_item(&mut self, name: Option<String>) {
+        self.end_item();
+        let mut item = CodeItem::new(name);
+
+        for line in std::mem::take(&mut self.pending).into_iter() {
+            item.push(line, self.chunk_unit);
+        }
+
+        self.curr_item = Some(item);
+    }
+
+    fn push_to_item(&mut self, line: String) {
+        match &mut self.curr_item {
+            Some(item) => { item.push(line, self.chunk_unit); },
+            None => { self.pending.push(line); },
+        }
+    }
+
+    fn end_item(&mut self) {
+        if self.curr_item.is_some() {
+            self.flush_item(true);
+        }
+    }
+
+    fn flush_item(&mut self, is_complete: bool) {
+        let Some(mut item) = self.curr_item.take() else { return; };
+
+        if !item.is_flushed {
+            if self.curr_group_len > 0 && self.curr_group_len + item.len > self.chunk_size {
+                self.tokens.push(AtomicToken::PageBreak);
+                self.curr_group_len = 0;
+            }
+
+            // An item without a name also emits `ChunkExtraInfo`, so that the name of
+            // the previous item is not inherited by the chunk.
+            self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+                item: item.name.clone(),
+                ..ChunkExtraInfo::default()
+            }));
+            item.is_flushed = true;
+        }
+
+        self.curr_group_len += item.len;
+        item.len = 0;
+        let lines = std::mem::take(&mut item.lines);
+        self.push_lines(lines);
+
+        if !is_complete {
+            self.curr_item = Some(item);
+        }
+    }
+
+    fn push_lines(&mut self, lines: Vec<String>) {
+        for line in lines.into_iter() {
+            self.tokens.push(AtomicToken::String {
+                char_len: line.chars().count(),
+                data: line,
+            });
+        }
+    }
+}
+
+fn is_attribute(language: Language, line: &str) -> bool {
+    match language {
+        Language::Rust => line.starts_with("#[") || line.starts_with("#!["),
+        Language::C | Language::JavaScript => line.starts_with('@') && !line.starts_with("@interface"),
+        _ => false,
+    }
+}
+
+fn is_container(name: &str) -> bool {
+    ["impl `", "trait `", "mod `", "class `", "namespace `", "interface `"].iter().any(
+        |prefix| name.starts_with(prefix)
+    )
+}
+
+// `line` must be trimmed.
+fn parse_item_name(language: Language, line: &str, is_member: bool) -> Option<String> {
+    match language {
+        Language::Rust => {
+            if let Some(cap) = RUST_FN_RE.captures(line) {
+                Some(format!("fn `{}`", &cap[1]))
+            }
+
+            else if let Some(cap) = RUST_ITEM_RE.captures(line) {
+                Some(format!("{} `{}`", cap[1].trim_end_matches('!'), &cap[2]))
+            }
+
+            else if let Some(m) = RUST_IMPL_RE.find(line) {
+                let rest = line[m.end()..].trim_start();
+
+                // skip generic parameters: `impl<T: Clone> Foo<T>`
+                let rest = if rest.starts_with('<') {
+                    match find_closing_angle_bracket(rest) {
+                        Some(index) => rest[(index + 1)..].trim_start(),
+                        None => rest,
+                    }
+                } else {
+                    rest
+                };
+                let rest = rest.split('{').next().unwrap();
+                let rest = rest.split(" where").next().unwrap().trim();
+
+                if rest.is_empty() {
+                    None
+                } else {
+                    Some(format!("impl `{rest}`"))
+                }
+            }
+
+            else {
+                None
+            }
+        },
+        Language::Python => PYTHON_ITEM_RE.captures(line).map(
+            |cap| format!("{} `{}`", &cap[1], &cap[2])
+        ),
+        Language::JavaScript => {
+            let line = &line[JS_PREFIX_RE.find(line).map(|m| m.end()).unwrap_or(0)..];
+
+            if let Some(cap) = JS_ITEM_RE.captures(line) {
+                Some(format!("{} `{}`", cap[1].trim_end_matches('*'), &cap[2]))
+            }
+
+            else if is_member {
+                match JS_METHOD_RE.captures(line) {
+                    Some(cap) if !is_keyword(&cap[1]) => Some(format!("method `{}`", &cap[1])),
+                    _ => None,
+                }
+            }
+
+            else {
+                None
+            }
+        },
+        Language::Go => {
+            if let Some(cap) = GO_FUNC_RE.captures(line) {
+                // receiver: `func (s *Server) Serve()`
+                match cap.get(1) {
+                    Some(receiver) => match receiver.as_str().split_whitespace().last() {
+                        Some(ty) => Some(format!("func `{}.{}`", ty.trim_start_matches('*'), &cap[2])),
+                        None => Some(format!("func `{}`", &cap[2])),
+                    },
+                    None => Some(format!("func `{}`", &cap[2])),
+                }
+            }
+
+            else {
+                GO_ITEM_RE.captures(line).map(
+                    |cap| format!("{} `{}`", &cap[1], &cap[2])
+                )
+            }
+        },
+        Language::C => {
+            let type_match = C_TYPE_RE.captures(line);
+            let func_match = C_FUNC_RE.captures_iter(line).find(|cap| !is_keyword(&cap[1]));
+
+            match (type_match, func_match) {
+                // `struct foo *make_foo(void) {` is a function, not a struct
+                (Some(ty), Some(func)) if is_function_after_type(line, ty.get(0).unwrap().range(), func.get(0).unwrap().range()) => {
+                    Some(format!("function `{}`", &func[1]))
+                },
+                (Some(ty), _) => Some(format!("{} `{}`", &ty[1], &ty[2])),
+
+                // `x = foo(bar);` is not a function definition
+                (None, Some(func)) if !line[..func.get(0).unwrap().start()].contains('=') => {
+                    Some(format!("function `{}`", &func[1]))
+                },
+                _ => None,
+            }
+        },
+    }
+}
+
+fn is_function_after_type(
+    line: &str,
+    type_range: std::ops::Range<usize>,
+    func_range: std::ops::Range<usize>,
+) -> bool {
+    func_range.start < type_range.start || type_range.end <= func_range.start && !line[type_range.end..func_range.start].contains('{')
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "if" | "else" | "for" | "while" | "switch" | "return" | "sizeof" | "catch"
+        | "defined" | "__attribute__" | "function" | "new" | "throw" | "typeof"
+    )
+}
+
+fn find_closing_angle_bracket(s: &str) -> Option<usize> {
+    let mut depth = 0;
+
+    for (index, c) in s.char_indices() {
+        match c {
+            '<' => { depth += 1; },
+            '>' => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(index);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LexState {
+    Code,
+
+    // rust allows nested block comments
+    BlockComment(usize),
+    String(Quote),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Quote {
+    Double,
+    Single,
+    Backtick,
+    TripleDouble,
+    TripleSingle,
+
+    // r#"..."#: number of `#`s
+    RustRaw(usize),
+}
+
+struct LineInfo {
+    // It's false if the line is empty or only has comments.
+    has_code: bool,
+    has_opened_brace: bool,
+
+    // the last character that's not a whitespace nor a comment
+    last_code_char: Option<char>,
+}
+
+// It only cares about brackets, comments and string literals.
+struct Lexer {
+    language: Language,
+    state: LexState,
+
+    // `()`, `[]` and `{}`
+    depth: usize,
+}
+
+impl Lexer {
+    fn new(language: Language) -> Self {
+        Lexer {
+            language,
+            state: LexState::Code,
+            depth: 0,
+        }
+    }
+
+    fn is_in_string(&self) -> bool {
+        matches!(self.state, LexState::String(_))
+    }
+
+    fn lex_line(&mut self, line: &str) -> LineInfo {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut result = LineInfo {
+            has_code: false,
+            has_opened_brace: false,
+            last_code_char: None,
+        };
+        let mut index = 0;
+
+        while index < chars.len() {
+            let c = chars[index];
+            let next = chars.get(index + 1).copied();
+
+            match self.state {
+                LexState::Code => {
+                    if c.is_whitespace() {
+                        index += 1;
+                        continue;
+                    }
+
+                    match (self.language, c, next) {
+                        (Language::Python, '#', _) => { break; },
+                        (Language::Python, _, _) => {},
+                        (_, '/', Some('/')) => { break; },
+                        (_, '/', Some('*')) => {
+                            self.state = LexState::BlockComment(1);
+                            index += 2;
+                            continue;
+                        },
+                        _ => {},
+                    }
+
+                    result.has_code = true;
+                    result.last_code_char = Some(c);
+
+                    match c {
+                        '{' | '(' | '[' => {
+                            self.depth += 1;
+                            result.has_opened_brace |= c == '{';
+                        },
+                        '}' | ')' | ']' => {
+                            self.depth = self.depth.saturating_sub(1);
+                        },
+                        '"' | '\'' if self.language == Language::Python => {
+                            let triple = next == Some(c) && chars.get(index + 2) == Some(&c);
+                            self.state = LexState::String(match (c, triple) {
+                                ('"', true) => Quote::TripleDouble,
+                                ('"', false) => Quote::Double,
+                                (_, true) => Quote::TripleSingle,
+                                (_, false) => Quote::Single,
+                            });
+
+                            if triple {
+                                index += 2;
+                            }
+                        },
+                        '"' => {
+                            self.state = LexState::String(Quote::Double);
+                        },
+                        '\'' => {
+                            // In Rust, `'a` might be a lifetime.
+                            let is_char_literal = self.language != Language::Rust
+                                || next == Some('\\')
+                                || chars.get(index + 2) == Some(&'\'');
+
+                            if is_char_literal {
+                                self.state = LexState::String(Quote::Single);
+                            }
+                        },
+                        '`' if matches!(self.language, Language::JavaScript | Language::Go) => {
+                            self.state = LexState::String(Quote::Backtick);
+                        },
+                        'r' if self.language == Language::Rust && !is_ident_char(index.checked_sub(1).map(|i| chars[i])) => {
+                            let hashes = chars[(index + 1)..].iter().take_while(|c| **c == '#').count();
+
+                            if chars.get(index + 1 + hashes) == Some(&'"') {
+                                self.state = LexState::String(Quote::RustRaw(hashes));
+                                index += hashes + 1;
+                            }
+                        },
+                        _ => {},
+                    }
+
+                    index += 1;
+                },
+                LexState::BlockComment(nested) => {
+                    match (c, next) {
+                        ('*', Some('/')) => {
+                            self.state = if nested == 1 { LexState::Code } else { LexState::BlockComment(nested - 1) };
+                            index += 2;
+                        },
+                        ('/', Some('*')) if self.language == Language::Rust => {
+                            self.state = LexState::BlockComment(nested + 1);
+                            index += 2;
+                        },
+                        _ => {
+                            index += 1;
+                        },
+                    }
+                },
+                LexState::String(quote) => {
+                    result.has_code |= !c.is_whitespace();
+
+                    match quote {
+                        Quote::RustRaw(hashes) => {
+                            if c == '"' && chars[(index + 1)..].iter().take(hashes).filter(|c| **c == '#').count() == hashes {
+                                self.state = LexState::Code;
+                                index += hashes;
+                                result.last_code_char = Some('"');
+                            }
+                        },
+                        // Go's raw strings do not have escapes, but JavaScript's template literals do.
+                        Quote::Backtick if self.language == Language::Go => {
+                            if c == '`' {
+                                self.state = LexState::Code;
+                                result.last_code_char = Some('`');
+                            }
+                        },
+                        _ if c == '\\' => {
+                            index += 1;
+                        },
+                        Quote::TripleDouble | Quote::TripleSingle => {
+                            let q = if quote == Quote::TripleDouble { '"' } else { '\'' };
+
+                            if c == q && next == Some(q) && chars.get(index + 2) == Some(&q) {
+                                self.state = LexState::Code;
+                                index += 2;
+                                result.last_code_char = Some(q);
+                            }
+                        },
+                        Quote::Double | Quote::Single | Quote::Backtick => {
+                            let q = match quote {
+                                Quote::Double => '"',
+                                Quote::Single => '\'',
+                                _ => '`',
+                            };
+
+                            if c == q {
+                                self.state = LexState::Code;
+                                result.last_code_char = Some(q);
+                            }
+                        },
+                    }
+
+                    index += 1;
+                },
+            }
+        }
+
+        // Only a few kinds of strings can span multiple lines.
+        if let LexState::String(quote) = self.state {
+            let is_multi_line = match quote {
+                Quote::TripleDouble | Quote::TripleSingle | Quote::Backtick | Quote::RustRaw(_) => true,
+                Quote::Double => self.language == Language::Rust || line.trim_end().ends_with('\\'),
+                Quote::Single => line.trim_end().ends_with('\\'),
+            };
+
+            if !is_multi_line {
+                self.state = LexState::Code;
+            }
+        }
+
+        result
+    }
+}
+
+fn is_ident_char(c: Option<char>) -> bool {
+    match c {
+        Some(c) => c.is_alphanumeric() || c == '_',
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+    use super::CodeReader;
+    use crate::index::BuildConfig;
+    use ragit_fs::{WriteMode, join, remove_file, write_string};
+
+    // `extension` tells the reader the language. The file is in the temp dir, with a
+    // name that's unique to the process, so that the tests don't touch the working directory.
+    fn read_all(extension: &str, content: &str, chunk_size: usize) -> (String, Vec<Option<String>>, usize) {
+        let config = BuildConfig {
+            strict_file_reader: true,
+            chunk_size,
+            ..BuildConfig::default()
+        };
+        let path = join(
+            &std::env::temp_dir().to_string_lossy(),
+            &format!("ragit-code-reader-test-{}.{extension}", std::process::id()),
+        ).unwrap();
+        write_string(&path, content, WriteMode::AlwaysCreate).unwrap();
+        let mut reader = CodeReader::new(&path, ".", &config, &FileReaderOptions::default()).unwrap();
+        let mut tokens = vec![];
+
+        while reader.has_more_to_read() {
+            reader.load_tokens().unwrap();
+            tokens.append(&mut reader.pop_all_tokens().unwrap());
+        }
+
+        remove_file(&path).unwrap();
+        let mut data = vec![];
+        let mut items = vec![];
+        let mut page_breaks = 0;
+
+        for token in tokens.into_iter() {
+            match token {
+                AtomicToken::String { data: s, .. } => { data.push(s); },
+                AtomicToken::ChunkExtraInfo(info) => { items.push(info.item); },
+                AtomicToken::PageBreak => { page_breaks += 1; },
+                AtomicToken::SourcePosition { .. } => {},
+                _ => panic!(),
+            }
+        }
+
+        (data.concat(), items.into_iter().filter(|item| item.is_some()).collect(), page_breaks)
+    }
+
+    #[test]
+    fn code_reader_test() {
+        let rs = "use std::collections::HashMap;
+
+/// doc comment of `foo`
+#[inline]
+pub fn foo<'a>(s: &'a str) -> char {
+    let c = '{';
+    let s = r#\"}}}\"#;
+    c
+}
+
+impl<T: Clone> From<T> for Bar {
+    fn from(t: T) -> Self {
+        /* } */
+        Bar
+    }
+}
+
+pub(crate) struct Bar;
+";
+        let (data, items, _) = read_all("rs", rs, 4000);
+        assert_eq!(data, rs);
+        assert_eq!(
+            items,
+            vec![
+                Some(String::from("fn `foo`")),
+                Some(String::from("impl `From<T> for Bar`")),
+                Some(String::from("fn `from`")),
+                Some(String::from("struct `Bar`")),
+            ],
+        );
+
+        let py = "import os
+
+@decorator
+def foo(x):
+    s = \"\"\"
+def not_a_function():
+\"\"\"
+    return x
+
+class Bar:
+    \"\"\"docstring\"\"\"
+
+    def baz(self):
+        pass
+
+x = [
+    1,
+]
+";
+        let (data, items, _) = read_all("py", py, 4000);
+        assert_eq!(data, py);
+        assert_eq!(
+            items,
+            vec![
+                Some(String::from("def `foo`")),
+                Some(String::from("class `Bar`")),
+                Some(String::from("def `baz`")),
+            ],
+        );
+
+        let go = "package main
+
+func (s *Server) Serve() {
+    fmt.Println(`}`)
+}
+
+type Server struct {
+    port int
+}
+";
+        let (data, items, page_breaks) = read_all("go", go, 30);
+        assert_eq!(data, go);
+        assert_eq!(
+            items,
+            vec![
+                Some(String::from("func `Server.Serve`")),
+                Some(String::from("type `Server`")),
+            ],
+        );
+        assert_eq!(page_breaks, 2);
+    }
+}
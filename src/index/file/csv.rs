@@ -1,46 +1,105 @@
-use super::{AtomicToken, FileReaderImpl};
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
 use crate::error::Error;
-use crate::index::BuildConfig;
-use ragit_fs::FileError;
+use crate::index::{BuildConfig, ChunkUnit};
+use ragit_fs::{FileError, extension};
 use std::fs::File;
 
 // It uses a simple heuristic: it converts csv into jsonl so that
 // each chunk contains more context.
+//
+// If `format` is `markdown`, it renders rows as a markdown table instead. Rows
+// are grouped so that each group fits in a chunk, and each group starts with the
+// header, so every chunk has the header.
 pub struct CsvReader {
     iterator: csv::ByteRecordsIntoIter<File>,
-    headers: Vec<String>,
+    headers: Option<Vec<String>>,
     rows: Vec<AtomicToken>,  // an `AtomicToken` per row
+    format: CsvFormat,
     strict_mode: bool,
     is_exhausted: bool,
+    chunk_size: usize,
+    chunk_unit: ChunkUnit,
+    rows_per_chunk: Option<usize>,
+
+    // the current group ends with a `PageBreak`
+    curr_group_len: usize,
+    curr_group_rows: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CsvFormat {
+    Jsonl,
+    Markdown,
 }
 
 impl FileReaderImpl for CsvReader {
-    fn new(path: &str, _root_dir: &str, config: &BuildConfig) -> Result<Self, Error> {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("csv", &["delimiter", "quote", "quoting", "header", "rows_per_chunk", "format"])?;
+        let delimiter = match options.get_string("delimiter")? {
+            Some(d) if d.len() == 1 => d.as_bytes()[0],
+            Some(d) => {
+                return Err(Error::FileReaderError(format!("`delimiter` of `csv` must be a single ascii character, but got {d:?}.")));
+            },
+            None => match extension(path)?.unwrap_or(String::new()).to_ascii_lowercase().as_str() {
+                "tsv" => b'\t',
+                _ => b',',
+            },
+        };
+        let quote = match options.get_string("quote")? {
+            Some(q) if q.len() == 1 => q.as_bytes()[0],
+            Some(q) => {
+                return Err(Error::FileReaderError(format!("`quote` of `csv` must be a single ascii character, but got {q:?}.")));
+            },
+            None => b'"',
+        };
+        let format = match options.get_string("format")?.as_deref() {
+            Some("jsonl") | None => CsvFormat::Jsonl,
+            Some("markdown") => CsvFormat::Markdown,
+            Some(format) => {
+                return Err(Error::FileReaderError(format!("`format` of `csv` must be `jsonl` or `markdown`, but got {format:?}.")));
+            },
+        };
+        let rows_per_chunk = match options.get_usize("rows_per_chunk")? {
+            Some(0) => {
+                return Err(Error::FileReaderError(String::from("`rows_per_chunk` of `csv` must be greater than 0.")));
+            },
+            n => n,
+        };
+        let has_header = options.get_bool("header")?.unwrap_or(true);
+
         match File::open(path) {
             Ok(f) => {
                 let mut reader = csv::ReaderBuilder::new()
-                    .delimiter(b',')
+                    .delimiter(delimiter)
+                    .quote(quote)
+                    .quoting(options.get_bool("quoting")?.unwrap_or(true))
+                    .has_headers(has_header)
                     .flexible(!config.strict_file_reader)
                     .from_reader(f);
-                let mut headers = vec![];
-
-                for header in reader.byte_headers()? {
-                    match String::from_utf8(header.to_vec()) {
-                        Ok(s) => { headers.push(s); },
-                        Err(e) => if config.strict_file_reader {
-                            return Err(e.into());
-                        } else {
-                            headers.push(String::from_utf8_lossy(header).to_string());
-                        },
+                let mut headers = None;
+
+                if has_header {
+                    let mut header_strings = vec![];
+
+                    for header in reader.byte_headers()? {
+                        header_strings.push(decode_utf8(header, config.strict_file_reader)?);
                     }
+
+                    headers = Some(header_strings);
                 }
 
                 Ok(CsvReader {
                     iterator: reader.into_byte_records(),
                     headers,
                     rows: vec![],
+                    format,
                     strict_mode: config.strict_file_reader,
                     is_exhausted: false,
+                    chunk_size: config.chunk_size,
+                    chunk_unit: config.chunk_unit,
+                    rows_per_chunk,
+                    curr_group_len: 0,
+                    curr_group_rows: 0,
                 })
             },
             Err(e) => Err(FileError::from_std(e, path).into()),
@@ -54,43 +113,18 @@ impl FileReaderImpl for CsvReader {
 
         match self.iterator.next() {
             Some(Ok(records)) => {
-                let mut string_records = Vec::with_capacity(self.headers.len());
-
-                for (index, record) in records.iter().enumerate() {
-                    if index >= self.headers.len() {
-                        break;
-                    }
-
-                    match String::from_utf8(record.to_vec()) {
-                        Ok(s) => { string_records.push(s); },
-                        Err(e) => if self.strict_mode {
-                            return Err(e.into());
-                        } else {
-                            string_records.push(String::from_utf8_lossy(record).to_string());
-                        },
-                    }
-                }
-
-                let mut cells = Vec::with_capacity(self.headers.len());
-
-                for (record, header) in string_records.iter().zip(self.headers.iter()) {
-                    // heuristic: if `record` is numeric, it'd better strip off quotes
-                    let record = match record.parse::<i64>() {
-                        Ok(n) => n.to_string(),
-                        _ => match record.parse::<f64>() {
-                            Ok(f) => f.to_string(),
-                            _ => format!("{record:?}"),
-                        },
-                    };
+                let mut string_records = vec![];
+                let columns = self.headers.as_ref().map(|headers| headers.len()).unwrap_or(usize::MAX);
 
-                    cells.push(format!("{header:?}: {record}"));
+                for record in records.iter().take(columns) {
+                    string_records.push(decode_utf8(record, self.strict_mode)?);
                 }
 
-                let row = format!("{}{}{}\n", "{", cells.join(", "), "}");
-                self.rows.push(AtomicToken::String {
-                    char_len: row.chars().count(),
-                    data: row,
-                });
+                let row = match self.format {
+                    CsvFormat::Jsonl => render_jsonl_row(&string_records, self.headers.as_deref()),
+                    CsvFormat::Markdown => render_markdown_row(&string_records),
+                };
+                self.push_row(row, string_records.len());
             },
             Some(Err(e)) => if self.strict_mode {
                 return Err(e.into());
@@ -116,6 +150,101 @@ impl FileReaderImpl for CsvReader {
     }
 
     fn key(&self) -> String {
-        String::from("csv_reader_v0")
+        String::from("csv_reader_v1")
     }
 }
+
+impl CsvReader {
+    fn push_row(&mut self, row: String, columns: usize) {
+        let row_len = self.chunk_unit.measure(&row);
+        let is_group_full = match self.rows_per_chunk {
+            Some(n) if self.curr_group_rows >= n => true,
+
+            // In markdown mode, the rows have to fit in a chunk, so that the
+            // next chunk starts with the header.
+            _ => self.format == CsvFormat::Markdown && self.curr_group_rows > 0 && self.curr_group_len + row_len > self.chunk_size,
+        };
+
+        if is_group_full {
+            self.rows.push(AtomicToken::PageBreak);
+            self.curr_group_len = 0;
+            self.curr_group_rows = 0;
+        }
+
+        if self.format == CsvFormat::Markdown && self.curr_group_rows == 0 {
+            let header = render_markdown_header(self.headers.as_deref(), columns);
+            self.curr_group_len += self.chunk_unit.measure(&header);
+            self.rows.push(AtomicToken::String {
+                char_len: header.chars().count(),
+                data: header,
+            });
+        }
+
+        self.curr_group_len += row_len;
+        self.curr_group_rows += 1;
+        self.rows.push(AtomicToken::String {
+            char_len: row.chars().count(),
+            data: row,
+        });
+    }
+}
+
+fn decode_utf8(bytes: &[u8], strict_mode: bool) -> Result<String, Error> {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(s) => Ok(s),
+        Err(e) => if strict_mode {
+            Err(e.into())
+        } else {
+            Ok(String::from_utf8_lossy(bytes).to_string())
+        },
+    }
+}
+
+// `{"name": "Alice", "age": 30}`, or `["Alice", 30]` if there's no header
+fn render_jsonl_row(records: &[String], headers: Option<&[String]>) -> String {
+    let mut cells = Vec::with_capacity(records.len());
+
+    for (index, record) in records.iter().enumerate() {
+        // heuristic: if `record` is numeric, it'd better strip off quotes
+        let record = match record.parse::<i64>() {
+            Ok(n) => n.to_string(),
+            _ => match record.parse::<f64>() {
+                Ok(f) => f.to_string(),
+                _ => format!("{record:?}"),
+            },
+        };
+
+        match headers {
+            Some(headers) => { cells.push(format!("{:?}: {record}", headers[index])); },
+            None => { cells.push(record); },
+        }
+    }
+
+    match headers {
+        Some(_) => format!("{}{}{}\n", "{", cells.join(", "), "}"),
+        None => format!("[{}]\n", cells.join(", ")),
+    }
+}
+
+fn render_markdown_row(records: &[String]) -> String {
+    format!(
+        "| {} |\n",
+        records.iter().map(
+            |record| record.replace('|', "\\|").replace("\r\n", " ").replace('\n', " ")
+        ).collect::<Vec<_>>().join(" | "),
+    )
+}
+
+// If there's no header, the columns are named `column 1`, `column 2`, ...
+fn render_markdown_header(headers: Option<&[String]>, columns: usize) -> String {
+    let headers = match headers {
+        Some(headers) => headers.to_vec(),
+        None => (1..=columns).map(|i| format!("column {i}")).collect(),
+    };
+
+    format!(
+        "{}| {} |\n",
+        render_markdown_row(&headers),
+        headers.iter().map(|_| "---").collect::<Vec<_>>().join(" | "),
+    )
+}
@@ -0,0 +1,587 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions, Image};
+use super::html::{StringOrImage, render_html};
+use super::ipynb::push_lines;
+use crate::chunk::{ChunkExtraInfo, MessageInfo};
+use crate::error::Error;
+use crate::index::BuildConfig;
+use encoding_rs::{Encoding, UTF_8};
+use ragit_fs::FileError;
+use ragit_pdl::{ImageType, decode_base64};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// It reads an email (`.eml`) or a mailbox (mbox). A mailbox is split into
+/// messages, and MIME parts of each message are decoded (quoted-printable,
+/// base64 and charsets). Images in the attachments become `AtomicToken::Image`,
+/// and the other attachments are replaced with their file names.
+///
+/// Each message starts with its `From`, `To`, `Date` and `Subject`. The index
+/// of the message (1-base) and its headers (`MessageInfo`) are recorded in
+/// `ChunkSource`, and a chunk never contains more than 1 message. If `thread` option is set, the
+/// messages are grouped by threads (`Message-ID`, `In-Reply-To` and `References`),
+/// and a chunk never contains more than 1 thread.
+pub struct EmailReader {
+    path: String,
+    reader: BufReader<File>,
+    is_mbox: bool,
+    group_by_thread: bool,
+    is_exhausted: bool,
+
+    // number of messages read so far
+    cursor: usize,
+    tokens: Vec<AtomicToken>,
+    strict_mode: bool,
+}
+
+impl FileReaderImpl for EmailReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("email", &["thread"])?;
+        let mut reader = match File::open(path) {
+            Ok(f) => BufReader::new(f),
+            Err(e) => { return Err(FileError::from_std(e, path).into()); },
+        };
+
+        // An mbox file starts with a "From " line. It's not a header of the message.
+        let is_mbox = reader.fill_buf()?.starts_with(b"From ");
+
+        if is_mbox {
+            reader.read_until(b'\n', &mut vec![])?;
+        }
+
+        Ok(EmailReader {
+            path: path.to_string(),
+            reader,
+            is_mbox,
+            group_by_thread: options.get_bool("thread")?.unwrap_or(false),
+            is_exhausted: false,
+            cursor: 0,
+            tokens: vec![],
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    // It reads a message at a time. If it's grouping the messages by threads, it
+    // reads the entire mailbox at once, because a thread can be anywhere in the mailbox.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        if !self.group_by_thread {
+            if let Some(message) = self.read_message()? {
+                self.render_message(&message)?;
+                self.tokens.push(AtomicToken::PageBreak);
+            }
+
+            return Ok(());
+        }
+
+        let mut messages = vec![];
+
+        while let Some(message) = self.read_message()? {
+            messages.push(message);
+        }
+
+        let mut threads: Vec<Vec<Message>> = vec![];
+        let mut thread_by_id = HashMap::new();
+
+        for message in messages.into_iter() {
+            let thread = match message.parent_ids().iter().find_map(|id| thread_by_id.get(id)) {
+                Some(thread) => *thread,
+                None => {
+                    threads.push(vec![]);
+                    threads.len() - 1
+                },
+            };
+
+            if let Some(id) = message.header("Message-ID").map(normalize_message_id) {
+                thread_by_id.insert(id, thread);
+            }
+
+            threads[thread].push(message);
+        }
+
+        for thread in threads.iter() {
+            for message in thread.iter() {
+                self.render_message(message)?;
+            }
+
+            self.tokens.push(AtomicToken::PageBreak);
+        }
+
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("email_reader_v2")
+    }
+}
+
+impl EmailReader {
+    fn read_message(&mut self) -> Result<Option<Message>, Error> {
+        let mut bytes = vec![];
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.is_exhausted = true;
+                break;
+            }
+
+            if self.is_mbox {
+                // the beginning of the next message
+                if line.starts_with(b"From ") {
+                    break;
+                }
+
+                // ">From " in the body of a message is an escaped "From ".
+                let quotes = line.iter().take_while(|b| **b == b'>').count();
+
+                if quotes > 0 && line[quotes..].starts_with(b"From ") {
+                    line.remove(0);
+                }
+            }
+
+            bytes.extend_from_slice(&line);
+        }
+
+        if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(None);
+        }
+
+        self.cursor += 1;
+        Ok(Some(Message::parse(&bytes, self.cursor)))
+    }
+
+    fn render_message(&mut self, message: &Message) -> Result<(), Error> {
+        let mut header = String::new();
+
+        for name in ["From", "To", "Date", "Subject"] {
+            if let Some(value) = message.header(name) {
+                header.push_str(&format!("{name}: {value}\n"));
+            }
+        }
+
+        self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+            item: Some(format!("message {}", message.index)),
+            message: Some(message.info()),
+            ..ChunkExtraInfo::default()
+        }));
+        push_lines(&mut self.tokens, &format!("{header}\n"));
+
+        let mut tokens = vec![];
+        self.render_part(&message.headers, &message.body, &mut tokens)?;
+        self.tokens.append(&mut tokens);
+        Ok(())
+    }
+
+    fn render_part(&self, headers: &[(String, String)], body: &[u8], tokens: &mut Vec<AtomicToken>) -> Result<(), Error> {
+        let (content_type, params) = parse_header_value(get_header(headers, "Content-Type").unwrap_or("text/plain"));
+        let (disposition, disposition_params) = parse_header_value(get_header(headers, "Content-Disposition").unwrap_or(""));
+        let file_name = disposition_params.get("filename").or_else(|| params.get("name"));
+
+        if let Some(subtype) = content_type.strip_prefix("multipart/") {
+            let parts = match params.get("boundary") {
+                Some(boundary) => split_multipart(body, boundary),
+
+                // a broken message
+                None => vec![],
+            };
+            let parts = parts.iter().map(|part| split_headers(part)).collect::<Vec<_>>();
+
+            // It prefers `text/plain` to `text/html`.
+            if subtype == "alternative" {
+                let part = parts.iter().find(
+                    |(headers, _)| parse_header_value(get_header(headers, "Content-Type").unwrap_or("text/plain")).0 == "text/plain"
+                ).or_else(|| parts.last());
+
+                if let Some((headers, body)) = part {
+                    self.render_part(headers, body, tokens)?;
+                }
+            }
+
+            else {
+                for (headers, body) in parts.iter() {
+                    self.render_part(headers, body, tokens)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        let body = decode_transfer_encoding(get_header(headers, "Content-Transfer-Encoding").unwrap_or("7bit"), body);
+
+        if content_type == "message/rfc822" {
+            let message = Message::parse(&body, 0);
+            let mut s = String::from("Forwarded message:\n");
+
+            for name in ["From", "To", "Date", "Subject"] {
+                if let Some(value) = message.header(name) {
+                    s.push_str(&format!("{name}: {value}\n"));
+                }
+            }
+
+            push_lines(tokens, &format!("{s}\n"));
+            return self.render_part(&message.headers, &message.body, tokens);
+        }
+
+        if let Some(image_type) = content_type.strip_prefix("image/") {
+            match ImageType::from_media_type(&format!("image/{image_type}")).map_err(Error::from).and_then(|image_type| Image::new(body.clone(), image_type)) {
+                Ok(image) => {
+                    tokens.push(AtomicToken::Image(image));
+                    push_lines(tokens, "\n\n");
+                    return Ok(());
+                },
+                Err(e) => if self.strict_mode {
+                    return Err(e);
+                },
+            }
+        }
+
+        if disposition != "attachment" {
+            match content_type.as_str() {
+                "text/plain" => {
+                    let text = self.decode_charset(params.get("charset"), &body)?;
+                    push_lines(tokens, &format!("{}\n\n", text.trim_end()));
+                    return Ok(());
+                },
+                "text/html" => {
+                    let html = self.decode_charset(params.get("charset"), &body)?;
+                    let mut text = String::new();
+
+                    // Images in html mails are usually `cid:` urls to the other parts of the message.
+                    for token in render_html(&html) {
+                        match token {
+                            StringOrImage::String(s) => { text.push_str(&s); },
+                            StringOrImage::Image { alt, .. } => { text.push_str(&alt); },
+                        }
+                    }
+
+                    push_lines(tokens, &format!("{}\n\n", text.trim_end()));
+                    return Ok(());
+                },
+                _ => {},
+            }
+        }
+
+        if let Some(file_name) = file_name {
+            push_lines(tokens, &format!("[attachment: {file_name}]\n\n"));
+        }
+
+        Ok(())
+    }
+
+    fn decode_charset(&self, charset: Option<&String>, bytes: &[u8]) -> Result<String, Error> {
+        let encoding = charset.and_then(|charset| Encoding::for_label(charset.as_bytes())).unwrap_or(UTF_8);
+        let (s, is_lossy) = encoding.decode_without_bom_handling(bytes);
+
+        if is_lossy && self.strict_mode {
+            return Err(Error::FileReaderError(format!("`{}` has a message that is not a valid {} text.", self.path, encoding.name())));
+        }
+
+        Ok(s.to_string())
+    }
+}
+
+struct Message {
+    // 1-base
+    index: usize,
+
+    // Header values are decoded (see `decode_encoded_words`).
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Message {
+    fn parse(bytes: &[u8], index: usize) -> Self {
+        let (headers, body) = split_headers(bytes);
+        Message { index, headers, body }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, name)
+    }
+
+    fn info(&self) -> MessageInfo {
+        let mut to = vec![];
+
+        for name in ["To", "Cc"] {
+            if let Some(addresses) = self.header(name) {
+                to.extend(split_addresses(addresses));
+            }
+        }
+
+        MessageInfo {
+            subject: self.header("Subject").map(|s| s.to_string()),
+            from: self.header("From").map(|s| s.to_string()),
+            to,
+            date: self.header("Date").map(|s| s.to_string()),
+        }
+    }
+
+    // Message ids of the messages that this message replies to.
+    fn parent_ids(&self) -> Vec<String> {
+        let mut result = vec![];
+
+        for name in ["In-Reply-To", "References"] {
+            if let Some(ids) = self.header(name) {
+                for id in ids.split_whitespace() {
+                    result.push(normalize_message_id(id));
+                }
+            }
+        }
+
+        result
+    }
+}
+
+fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+}
+
+// `"Doe, John" <john@example.com>, jane@example.com` -> [`"Doe, John" <john@example.com>`, `jane@example.com`]
+fn split_addresses(addresses: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut curr = String::new();
+    let mut in_quote = false;
+    let mut in_angle = false;
+
+    for c in addresses.chars() {
+        match c {
+            '"' => { in_quote = !in_quote; },
+            '<' if !in_quote => { in_angle = true; },
+            '>' if !in_quote => { in_angle = false; },
+            ',' if !in_quote && !in_angle => {
+                result.push(curr.trim().to_string());
+                curr = String::new();
+                continue;
+            },
+            _ => {},
+        }
+
+        curr.push(c);
+    }
+
+    result.push(curr.trim().to_string());
+    result.into_iter().filter(|address| !address.is_empty()).collect()
+}
+
+fn normalize_message_id(id: &str) -> String {
+    id.trim().trim_start_matches('<').trim_end_matches('>').to_string()
+}
+
+// It splits a message (or a MIME part) into headers and a body. Folded header
+// lines (lines that start with a whitespace) are unfolded.
+fn split_headers(bytes: &[u8]) -> (Vec<(String, String)>, Vec<u8>) {
+    let mut headers: Vec<(String, String)> = vec![];
+    let mut cursor = 0;
+
+    while cursor < bytes.len() {
+        let line_end = bytes[cursor..].iter().position(|b| *b == b'\n').map(|i| cursor + i + 1).unwrap_or(bytes.len());
+        let line = String::from_utf8_lossy(&bytes[cursor..line_end]);
+        let line = line.trim_end_matches(['\r', '\n']);
+        cursor = line_end;
+
+        if line.is_empty() {
+            break;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        }
+
+        else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    for (_, value) in headers.iter_mut() {
+        *value = decode_encoded_words(value);
+    }
+
+    (headers, bytes[cursor..].to_vec())
+}
+
+// `text/plain; charset="utf-8"` -> ("text/plain", {"charset": "utf-8"})
+// The main value is lowercased.
+fn parse_header_value(value: &str) -> (String, HashMap<String, String>) {
+    let mut params = HashMap::new();
+    let mut segments = value.split(';');
+    let main = segments.next().unwrap_or("").trim().to_ascii_lowercase();
+
+    for segment in segments {
+        if let Some((key, value)) = segment.split_once('=') {
+            params.insert(key.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string());
+        }
+    }
+
+    (main, params)
+}
+
+fn split_multipart(body: &[u8], boundary: &str) -> Vec<Vec<u8>> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = vec![];
+    let mut curr_part: Option<Vec<u8>> = None;
+
+    for line in body.split_inclusive(|b| *b == b'\n') {
+        let trimmed = line.trim_ascii_end();
+
+        if trimmed.starts_with(delimiter.as_bytes()) {
+            if let Some(part) = curr_part.take() {
+                parts.push(part);
+            }
+
+            // the closing delimiter
+            if trimmed[delimiter.len()..].starts_with(b"--") {
+                break;
+            }
+
+            curr_part = Some(vec![]);
+        }
+
+        // Lines before the first delimiter are a preamble, which is ignored.
+        else if let Some(part) = &mut curr_part {
+            part.extend_from_slice(line);
+        }
+    }
+
+    if let Some(part) = curr_part {
+        parts.push(part);
+    }
+
+    parts
+}
+
+fn decode_transfer_encoding(encoding: &str, body: &[u8]) -> Vec<u8> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "base64" => {
+            let s = body.iter().filter(|b| !b.is_ascii_whitespace()).map(|b| *b as char).collect::<String>();
+            decode_base64(&s).unwrap_or_default()
+        },
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+// If `is_header` is set, it's the "Q" encoding of RFC 2047, where '_' is a space.
+fn decode_quoted_printable(s: &[u8], is_header: bool) -> Vec<u8> {
+    let mut result = Vec::with_capacity(s.len());
+    let mut i = 0;
+
+    while i < s.len() {
+        match s[i] {
+            b'=' => {
+                // soft line break
+                if s[(i + 1)..].starts_with(b"\r\n") {
+                    i += 3;
+                }
+
+                else if s[(i + 1)..].starts_with(b"\n") {
+                    i += 2;
+                }
+
+                else if let Some(b) = s.get((i + 1)..(i + 3)).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    result.push(b);
+                    i += 3;
+                }
+
+                else {
+                    result.push(b'=');
+                    i += 1;
+                }
+            },
+            b'_' if is_header => {
+                result.push(b' ');
+                i += 1;
+            },
+            b => {
+                result.push(b);
+                i += 1;
+            },
+        }
+    }
+
+    result
+}
+
+// `=?utf-8?B?7JWI64WV?= =?utf-8?Q?=ED=95=98?=` -> "안녕하"
+// Whitespaces between adjacent encoded words are ignored.
+fn decode_encoded_words(s: &str) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+    let mut after_encoded_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let Some((decoded, len)) = rest[start..].strip_prefix("=?").and_then(
+            |word| {
+                let (charset, word) = word.split_once('?')?;
+                let (q_or_b, word) = word.split_once('?')?;
+                let (text, _) = word.split_once("?=")?;
+                let bytes = match q_or_b {
+                    "B" | "b" => decode_base64(text).ok()?,
+                    "Q" | "q" => decode_quoted_printable(text.as_bytes(), true),
+                    _ => { return None; },
+                };
+
+                // RFC 2231 allows a language tag: `utf-8*en`
+                let encoding = Encoding::for_label(charset.split('*').next().unwrap_or("").as_bytes()).unwrap_or(UTF_8);
+
+                // `=?{charset}?{q_or_b}?{text}?=`
+                let len = charset.len() + q_or_b.len() + text.len() + 6;
+                Some((encoding.decode_without_bom_handling(&bytes).0.to_string(), len))
+            }
+        ) else {
+            result.push_str(&rest[..(start + 2)]);
+            rest = &rest[(start + 2)..];
+            after_encoded_word = false;
+            continue;
+        };
+
+        let between = &rest[..start];
+
+        if !(after_encoded_word && between.trim().is_empty()) {
+            result.push_str(between);
+        }
+
+        result.push_str(&decoded);
+        rest = &rest[(start + len)..];
+        after_encoded_word = true;
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_encoded_words, decode_quoted_printable, split_addresses, split_multipart};
+
+    #[test]
+    fn mime_test() {
+        assert_eq!(decode_encoded_words("=?utf-8?B?7JWI64WV?= =?UTF-8?Q?=ED=95=98?= world"), "안녕하 world");
+        assert_eq!(decode_encoded_words("Re: =?iso-8859-1?q?caf=E9_au_lait?="), "Re: café au lait");
+        assert_eq!(decode_encoded_words("=?broken"), "=?broken");
+        assert_eq!(split_addresses("\"Doe, John\" <john@example.com>, <a,b>, jane@example.com,"), vec!["\"Doe, John\" <john@example.com>", "<a,b>", "jane@example.com"]);
+        assert_eq!(decode_quoted_printable(b"a long =\r\nline with =3D sign", false), b"a long line with = sign");
+
+        let body = b"preamble\r\n--xyz\r\nContent-Type: text/plain\r\n\r\nfirst\r\n--xyz\r\n\r\nsecond\r\n--xyz--\r\nepilogue\r\n";
+        let parts = split_multipart(body, "xyz");
+        assert_eq!(parts, vec![b"Content-Type: text/plain\r\n\r\nfirst\r\n".to_vec(), b"\r\nsecond\r\n".to_vec()]);
+    }
+}
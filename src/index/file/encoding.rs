@@ -0,0 +1,514 @@
+use crate::error::Error;
+use encoding_rs::{
+    Decoder,
+    DecoderResult,
+    EUC_KR,
+    Encoding,
+    SHIFT_JIS,
+    UTF_16BE,
+    UTF_16LE,
+    UTF_8,
+    WINDOWS_1252,
+};
+use ragit_fs::FileError;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+// It looks at the first 64 KiB of a file to guess its encoding.
+const SAMPLE_SIZE: usize = 1 << 16;
+
+/// Ragit assumes that text files are UTF-8. If a file is not UTF-8 (e.g. a legacy Korean
+/// file in EUC-KR), it's converted to UTF-8 before it's read, and the encoding is recorded
+/// in `ChunkBuildInfo`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct FileEncoding {
+    /// Name of the encoding in the WHATWG encoding standard (e.g. "EUC-KR", "Shift_JIS", "UTF-16LE").
+    pub name: String,
+    pub detected_by: EncodingDetection,
+
+    /// Some bytes couldn't be decoded, and they're replaced with U+FFFD.
+    pub is_lossy: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingDetection {
+    /// `encoding` option of the file reader
+    Config,
+    Bom,
+
+    /// Ragit guessed the encoding from the content of the file.
+    Guess,
+
+    /// Ragit failed to guess the encoding, and it's read as windows-1252.
+    /// It's an error if `strict_file_reader` is set.
+    Fallback,
+}
+
+impl FileEncoding {
+    /// `rag check` warns about the files whose encoding is suspicious.
+    pub fn is_suspicious(&self) -> bool {
+        self.is_lossy || self.detected_by == EncodingDetection::Fallback
+    }
+}
+
+pub(super) struct TextFile {
+    pub content: Box<dyn Read + Send>,
+
+    // It's `None` if the file is UTF-8.
+    pub encoding: Option<FileEncoding>,
+
+    // If the file is UTF-8 and starts with a byte order mark, the BOM is not in `content`.
+    // It's 3 in that case, so that the readers can tell the correct byte offsets.
+    pub bom_len: usize,
+
+    // If the file is converted to UTF-8, byte offsets in `content` are not offsets in the
+    // file. It converts them.
+    pub offsets: Option<OffsetMap>,
+}
+
+/// `encoding` is the `encoding` option of the file reader. If it's not set, ragit
+/// looks at the byte order mark, and then guesses the encoding from the content.
+///
+/// Files are streamed. If a file is not UTF-8, it's converted while it's read, and
+/// `TextFile::offsets` converts the byte offsets (see `AtomicToken::SourcePosition`)
+/// in the converted text to offsets in the original file.
+pub(super) fn open_text_file(path: &str, encoding: Option<&str>, strict_mode: bool) -> Result<TextFile, Error> {
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => { return Err(FileError::from_std(e, path).into()); },
+    };
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    (&mut file).take(SAMPLE_SIZE as u64).read_to_end(&mut sample)?;
+    let is_complete = sample.len() < SAMPLE_SIZE;
+
+    let (encoding, detected_by) = match encoding {
+        Some(label) => match Encoding::for_label(label.trim().as_bytes()) {
+            Some(encoding) => (encoding, EncodingDetection::Config),
+            None => {
+                return Err(Error::FileReaderError(format!("Unknown encoding: `{label}`")));
+            },
+        },
+        None => match Encoding::for_bom(&sample) {
+            Some((encoding, _)) => (encoding, EncodingDetection::Bom),
+            None => guess_encoding(&sample, is_complete),
+        },
+    };
+
+    if detected_by == EncodingDetection::Fallback && strict_mode {
+        return Err(Error::FileReaderError(format!("Failed to guess the encoding of `{path}`. Please set `encoding` option of the file reader.")));
+    }
+
+    // A UTF-8 file with a few broken bytes is not read as it is, but decoded lossily.
+    if encoding == UTF_8 && is_valid_utf8(&sample, is_complete) {
+        let bom_len = if sample.starts_with(b"\xEF\xBB\xBF") { 3 } else { 0 };
+        sample.drain(..bom_len);
+
+        return Ok(TextFile {
+            content: Box::new(Cursor::new(sample).chain(file)),
+            encoding: None,
+            bom_len,
+            offsets: None,
+        });
+    }
+
+    // A BOM is more trustworthy than `encoding`.
+    let (encoding, bom_len, detected_by) = match Encoding::for_bom(&sample) {
+        Some((encoding, bom_len)) => (encoding, bom_len, EncodingDetection::Bom),
+        None => (encoding, 0, detected_by),
+    };
+
+    // `is_lossy` is recorded in `ChunkBuildInfo`, which is created before the file is read.
+    // So it decodes the file once, without keeping the result.
+    file.seek(SeekFrom::Start(bom_len as u64))?;
+    let is_lossy = is_lossy(&mut file, encoding)?;
+
+    if is_lossy && strict_mode {
+        return Err(Error::FileReaderError(format!("`{path}` is not a valid {} file.", encoding.name())));
+    }
+
+    file.seek(SeekFrom::Start(bom_len as u64))?;
+    let offsets = OffsetMap::new(bom_len);
+
+    Ok(TextFile {
+        content: Box::new(DecodingReader::new(Box::new(file), encoding, offsets.clone())),
+        encoding: if encoding == UTF_8 && !is_lossy {
+            None
+        } else {
+            Some(FileEncoding {
+                name: encoding.name().to_string(),
+                detected_by,
+                is_lossy,
+            })
+        },
+        bom_len: 0,
+        offsets: Some(offsets),
+    })
+}
+
+fn is_lossy(file: &mut File, encoding: &'static Encoding) -> Result<bool, Error> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut input = vec![0; 1 << 16];
+    let mut output = vec![0; 1 << 18];
+
+    loop {
+        let input_len = file.read(&mut input)?;
+        let mut remaining = &input[..input_len];
+
+        loop {
+            let (result, read, _) = decoder.decode_to_utf8_without_replacement(remaining, &mut output, input_len == 0);
+
+            match result {
+                DecoderResult::InputEmpty => { break; },
+                DecoderResult::OutputFull => { remaining = &remaining[read..]; },
+                DecoderResult::Malformed(_, _) => { return Ok(true); },
+            }
+        }
+
+        if input_len == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+/// It maps byte offsets in the text that's converted to UTF-8 to byte offsets in the
+/// original file. `DecodingReader` fills the map while it decodes the file. A file
+/// reader asks the offsets in order, so it forgets the offsets before the last one.
+#[derive(Clone)]
+pub(super) struct OffsetMap(Arc<Mutex<OffsetMapInner>>);
+
+struct OffsetMapInner {
+    // consecutive characters that have the same length in UTF-8 and in the original file
+    runs: VecDeque<OffsetRun>,
+
+    // (UTF-8 offset, original offset) of the end of the decoded text
+    end: (usize, usize),
+}
+
+struct OffsetRun {
+    utf8_start: usize,
+    original_start: usize,
+    utf8_len: usize,
+    original_len: usize,
+    count: usize,
+}
+
+impl OffsetMap {
+    // `start` is where the text starts in the original file (e.g. after a BOM).
+    fn new(start: usize) -> Self {
+        OffsetMap(Arc::new(Mutex::new(OffsetMapInner {
+            runs: VecDeque::new(),
+            end: (0, start),
+        })))
+    }
+
+    /// `offset` has to be at a character boundary of the converted text.
+    pub fn get_original_offset(&self, offset: usize) -> usize {
+        let mut inner = self.0.lock().unwrap();
+
+        while inner.runs.len() > 1 && inner.runs[1].utf8_start <= offset {
+            inner.runs.pop_front();
+        }
+
+        match inner.runs.front() {
+            Some(run) if run.utf8_start <= offset && offset < run.utf8_start + run.utf8_len * run.count => {
+                run.original_start + (offset - run.utf8_start) / run.utf8_len * run.original_len
+            },
+
+            // It has forgotten the offset. It shouldn't happen.
+            Some(run) if offset < run.utf8_start => run.original_start,
+
+            _ => inner.end.1,
+        }
+    }
+}
+
+impl OffsetMapInner {
+    fn push(&mut self, utf8_len: usize, original_len: usize) {
+        let (utf8_start, original_start) = self.end;
+        self.end = (utf8_start + utf8_len, original_start + original_len);
+
+        if let Some(run) = self.runs.back_mut().filter(|run| run.utf8_len == utf8_len && run.original_len == original_len) {
+            run.count += 1;
+            return;
+        }
+
+        self.runs.push_back(OffsetRun {
+            utf8_start,
+            original_start,
+            utf8_len,
+            original_len,
+            count: 1,
+        });
+    }
+}
+
+// It converts a file to UTF-8 while it's read. It feeds the decoder a byte at a time,
+// so that it knows which bytes of the file each character is from.
+struct DecodingReader {
+    input: Box<dyn Read + Send>,
+    decoder: Decoder,
+    offsets: OffsetMap,
+
+    // decoded, but not read yet
+    output: Vec<u8>,
+    output_cursor: usize,
+
+    // The decoder has consumed the bytes of the original file before `original_offset`,
+    // and the next character starts at `char_start`.
+    original_offset: usize,
+    char_start: usize,
+    is_eof: bool,
+}
+
+impl DecodingReader {
+    fn new(input: Box<dyn Read + Send>, encoding: &'static Encoding, offsets: OffsetMap) -> Self {
+        let start = offsets.0.lock().unwrap().end.1;
+
+        DecodingReader {
+            input,
+            decoder: encoding.new_decoder_without_bom_handling(),
+            offsets,
+            output: vec![],
+            output_cursor: 0,
+            original_offset: start,
+            char_start: start,
+            is_eof: false,
+        }
+    }
+
+    fn fill_output(&mut self) -> std::io::Result<()> {
+        let mut input = [0; 8192];
+        let input_len = self.input.read(&mut input)?;
+        let mut decoded = [0; 64];
+        let mut offsets = self.offsets.0.lock().unwrap();
+        self.output.clear();
+        self.output_cursor = 0;
+
+        for i in 0..input_len.max(1) {
+            let (_, _, written, _) = if input_len == 0 {
+                self.is_eof = true;
+                self.decoder.decode_to_utf8(&[], &mut decoded, true)
+            } else {
+                self.original_offset += 1;
+                self.decoder.decode_to_utf8(&input[i..(i + 1)], &mut decoded, false)
+            };
+
+            if written == 0 {
+                continue;
+            }
+
+            // If a byte completes more than one character (e.g. a broken sequence and the
+            // character after that), the first character gets all the bytes.
+            for (j, c) in std::str::from_utf8(&decoded[..written]).unwrap().chars().enumerate() {
+                offsets.push(c.len_utf8(), if j == 0 { self.original_offset - self.char_start } else { 0 });
+            }
+
+            self.char_start = self.original_offset;
+            self.output.extend_from_slice(&decoded[..written]);
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for DecodingReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while self.output_cursor == self.output.len() && !self.is_eof {
+            self.fill_output()?;
+        }
+
+        let len = buffer.len().min(self.output.len() - self.output_cursor);
+        buffer[..len].copy_from_slice(&self.output[self.output_cursor..(self.output_cursor + len)]);
+        self.output_cursor += len;
+        Ok(len)
+    }
+}
+
+// `sample` is the first few bytes of a file. If `is_complete` is false, `sample`
+// might end in the middle of a character.
+fn guess_encoding(sample: &[u8], is_complete: bool) -> (&'static Encoding, EncodingDetection) {
+    if is_valid_utf8(sample, is_complete) {
+        return (UTF_8, EncodingDetection::Guess);
+    }
+
+    // If there are a lot more non-ascii UTF-8 characters than invalid byte sequences,
+    // it's a UTF-8 file with a few broken bytes. Legacy encodings rarely make valid
+    // UTF-8 sequences.
+    let mut non_ascii = 0;
+    let mut invalid = 0;
+
+    for chunk in sample.utf8_chunks() {
+        non_ascii += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+
+        // consecutive invalid sequences are counted once
+        if !chunk.invalid().is_empty() && (!chunk.valid().is_empty() || invalid == 0) {
+            invalid += 1;
+        }
+    }
+
+    if non_ascii > 0 && invalid * 10 <= non_ascii {
+        return (UTF_8, EncodingDetection::Guess);
+    }
+
+    // UTF-16 without a BOM: ascii characters (spaces, newlines, digits...) have a zero byte.
+    let mut zeros_at_even = 0;
+    let mut zeros_at_odd = 0;
+
+    for (i, byte) in sample.iter().enumerate() {
+        if *byte == 0 {
+            if i % 2 == 0 { zeros_at_even += 1; } else { zeros_at_odd += 1; }
+        }
+    }
+
+    // Some non-ascii characters also have a zero byte (e.g. U+D600), so it's not that strict.
+    if zeros_at_odd * 10 > sample.len() / 2 && zeros_at_even * 4 < zeros_at_odd {
+        return (UTF_16LE, EncodingDetection::Guess);
+    }
+
+    if zeros_at_even * 10 > sample.len() / 2 && zeros_at_odd * 4 < zeros_at_even {
+        return (UTF_16BE, EncodingDetection::Guess);
+    }
+
+    // Legacy multi-byte encodings decode most byte sequences without an error,
+    // so it decodes the sample with each candidate and counts the characters that
+    // make sense in the language of the candidate. The decoded text also has to
+    // contain characters that only the language uses (hangul or kana). For example,
+    // "crème" in windows-1252 is a valid Shift_JIS text, but it's a kanji after "cr".
+    let mut best = None;
+
+    for (encoding, is_plausible, is_distinctive) in [
+        (EUC_KR, is_plausible_korean as fn(char) -> bool, is_hangul as fn(char) -> bool),
+        (SHIFT_JIS, is_plausible_japanese, is_kana),
+    ] {
+        let mut decoder = encoding.new_decoder_without_bom_handling();
+        let mut decoded = String::with_capacity(sample.len() * 3);
+
+        match decoder.decode_to_string_without_replacement(sample, &mut decoded, is_complete) {
+            (DecoderResult::InputEmpty, _) => {},
+            _ => { continue; },
+        }
+
+        let non_ascii = decoded.chars().filter(|c| !c.is_ascii()).count();
+        let plausible = decoded.chars().filter(|c| is_plausible(*c)).count();
+
+        // at least 80% of the non-ascii characters have to make sense
+        if non_ascii > 0 && plausible * 5 >= non_ascii * 4 && decoded.chars().any(is_distinctive) {
+            match best {
+                Some((_, best_plausible)) if best_plausible >= plausible => {},
+                _ => { best = Some((encoding, plausible)); },
+            }
+        }
+    }
+
+    match best {
+        Some((encoding, _)) => (encoding, EncodingDetection::Guess),
+        None => (WINDOWS_1252, EncodingDetection::Fallback),
+    }
+}
+
+fn is_valid_utf8(sample: &[u8], is_complete: bool) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => !is_complete && e.error_len().is_none(),
+    }
+}
+
+// CP949 (a superset of EUC-KR) has 8822 hangul syllables that are not in EUC-KR. They're rarely
+// used, and a Shift_JIS file decoded as CP949 is full of them. So it only counts the characters in EUC-KR.
+fn is_plausible_korean(c: char) -> bool {
+    if !matches!(c, '\u{ac00}'..='\u{d7a3}' | '\u{3131}'..='\u{318e}' | '\u{3000}'..='\u{303f}' | '\u{4e00}'..='\u{9fff}') {
+        return false;
+    }
+
+    let mut buffer = [0; 4];
+    let (bytes, _, _) = EUC_KR.encode(c.encode_utf8(&mut buffer));
+    bytes.len() == 2 && bytes[0] >= 0xa1 && bytes[1] >= 0xa1
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{ac00}'..='\u{d7a3}' | '\u{3131}'..='\u{318e}')
+}
+
+// Half-width katakana is not counted. A EUC-KR file decoded as Shift_JIS is full of them.
+fn is_plausible_japanese(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{30ff}' | '\u{4e00}'..='\u{9fff}' | '\u{ff01}'..='\u{ff5e}')
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{30ff}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodingReader, EncodingDetection, OffsetMap, guess_encoding};
+    use encoding_rs::{EUC_KR, SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn guess_encoding_test() {
+        let korean = "안녕하세요. 이것은 한국어로 쓰인 문서입니다.\n인코딩을 맞혀보세요.\n";
+        let japanese = "こんにちは。これは日本語で書かれた文書です。\nエンコーディングを当ててください。\n";
+
+        for (s, encoding) in [(korean, EUC_KR), (japanese, SHIFT_JIS)] {
+            let (bytes, _, _) = encoding.encode(s);
+            assert_eq!(guess_encoding(&bytes, true), (encoding, EncodingDetection::Guess));
+        }
+
+        let utf16 = korean.encode_utf16().flat_map(|c| c.to_le_bytes()).collect::<Vec<_>>();
+        assert_eq!(guess_encoding(&utf16, true), (UTF_16LE, EncodingDetection::Guess));
+
+        // a truncated sample
+        assert_eq!(guess_encoding(&korean.as_bytes()[..4], false), (UTF_8, EncodingDetection::Guess));
+
+        // a UTF-8 file with a broken byte
+        let mut broken = korean.as_bytes().to_vec();
+        broken[7] = 0xff;
+        assert_eq!(guess_encoding(&broken, true), (UTF_8, EncodingDetection::Guess));
+
+        // "café au lait" in windows-1252
+        assert_eq!(guess_encoding(b"caf\xe9 au lait", true), (WINDOWS_1252, EncodingDetection::Fallback));
+
+        // "crème brûlée" in windows-1252 is a valid Shift_JIS text, but it has no kana
+        assert_eq!(guess_encoding(b"Une cr\xe8me br\xfbl\xe9e, s'il vous pla\xeet.", true), (WINDOWS_1252, EncodingDetection::Fallback));
+    }
+
+    #[test]
+    fn decoding_reader_test() {
+        let text = "Hello, 안녕하세요.\nこんにちは, world! 😀\n".repeat(1000);
+
+        for (encoding, bom_len) in [(EUC_KR, 0), (SHIFT_JIS, 0), (UTF_16LE, 2)] {
+            let mut bytes = if encoding == UTF_16LE {
+                text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect::<Vec<_>>()
+            } else {
+                // EUC-KR can't encode kana, and Shift_JIS can't encode hangul, so they're `&#...;`.
+                encoding.encode(&text).0.to_vec()
+            };
+            let expected = encoding.decode_without_bom_handling(&bytes).0.to_string();
+            let offsets = OffsetMap::new(bom_len);
+            bytes.splice(0..0, vec![0; bom_len]);
+
+            let mut input = Cursor::new(bytes.clone());
+            input.set_position(bom_len as u64);
+            let mut decoded = String::new();
+            DecodingReader::new(Box::new(input), encoding, offsets.clone()).read_to_string(&mut decoded).unwrap();
+            assert_eq!(decoded, expected);
+
+            let mut prev = (0, bom_len);
+
+            for (utf8_offset, _) in decoded.match_indices('\n').map(|(i, s)| (i + s.len(), s)) {
+                let original_offset = offsets.get_original_offset(utf8_offset);
+                assert_eq!(
+                    encoding.decode_without_bom_handling(&bytes[prev.1..original_offset]).0,
+                    &decoded[prev.0..utf8_offset],
+                );
+                prev = (utf8_offset, original_offset);
+            }
+
+            assert_eq!(prev.1, bytes.len());
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::archive::{Archive, attribute, child, children, descendants, resolve_path};
+use super::html::{StringOrImage, percent_decode, render_html};
+use super::markdown::WEB_URL_RE;
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::HashMap;
+
+/// It reads xhtml chapters of an epub file in the order of the spine (the
+/// reading order in the OPF file). Each chapter is rendered by the html renderer
+/// of `HtmlReader`, and images in the chapters are loaded from the archive.
+///
+/// A chunk never contains more than 1 chapter. The number of the chapter (1-base
+/// index in the spine) and its title are recorded in `ChunkSource`. The titles
+/// come from the table of contents (nav document of epub 3 or ncx of epub 2).
+/// If a chapter is not in the table of contents, the first heading of the chapter
+/// is the title.
+pub struct EpubReader {
+    path: String,
+    archive: Archive,
+    chapters: Vec<Chapter>,
+    cursor: usize,
+    tokens: Vec<AtomicToken>,
+    strict_mode: bool,
+}
+
+struct Chapter {
+    // a path in the archive
+    path: String,
+    title: Option<String>,
+}
+
+impl FileReaderImpl for EpubReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("epub", &[])?;
+        let mut archive = Archive::open(path)?;
+        let container = archive.read_string_mandatory("META-INF/container.xml")?;
+        let container = parse_xml(&container)?;
+
+        let Some(opf_path) = descendants(container.root_element(), "rootfile").find_map(|rootfile| attribute(rootfile, "full-path")) else {
+            return Err(Error::FileReaderError(format!("`{path}` is broken: there's no rootfile in `META-INF/container.xml`.")));
+        };
+        let opf_path = opf_path.to_string();
+        let opf_dir = parent_dir(&opf_path);
+        let opf = archive.read_string_mandatory(&opf_path)?;
+        let opf = parse_xml(&opf)?;
+        let root = opf.root_element();
+
+        // id -> (path, media type)
+        let mut manifest = HashMap::new();
+        let mut nav_path = None;
+
+        if let Some(node) = child(root, "manifest") {
+            for item in children(node, "item") {
+                let (Some(id), Some(href)) = (attribute(item, "id"), attribute(item, "href")) else { continue; };
+                let item_path = resolve_href(&opf_dir, href);
+
+                if attribute(item, "properties").unwrap_or("").split_whitespace().any(|property| property == "nav") {
+                    nav_path = Some(item_path.clone());
+                }
+
+                manifest.insert(id.to_string(), (item_path, attribute(item, "media-type").unwrap_or("").to_string()));
+            }
+        }
+
+        let spine = child(root, "spine");
+        let ncx_path = spine.and_then(|spine| attribute(spine, "toc")).and_then(|id| manifest.get(id)).map(|(path, _)| path.to_string());
+
+        // Titles are optional, so a broken table of contents is not an error.
+        let titles = match (nav_path, ncx_path) {
+            (Some(nav_path), _) => read_nav_titles(&mut archive, &nav_path),
+            (None, Some(ncx_path)) => read_ncx_titles(&mut archive, &ncx_path),
+            (None, None) => HashMap::new(),
+        };
+        let mut chapters = vec![];
+
+        if let Some(spine) = spine {
+            for itemref in children(spine, "itemref") {
+                let Some((chapter_path, media_type)) = attribute(itemref, "idref").and_then(|id| manifest.get(id)) else { continue; };
+
+                // A spine may have svg pages, but they're very rare.
+                if media_type != "application/xhtml+xml" && media_type != "text/html" {
+                    continue;
+                }
+
+                chapters.push(Chapter {
+                    path: chapter_path.to_string(),
+                    title: titles.get(chapter_path).cloned(),
+                });
+            }
+        }
+
+        Ok(EpubReader {
+            path: path.to_string(),
+            archive,
+            chapters,
+            cursor: 0,
+            tokens: vec![],
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    // It reads a chapter at a time.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        let Some(chapter) = self.chapters.get(self.cursor) else { return Ok(()); };
+        let chapter_path = chapter.path.to_string();
+        let mut title = chapter.title.clone();
+        self.cursor += 1;
+
+        let bytes = match self.archive.read_bytes(&chapter_path)? {
+            Some(bytes) => bytes,
+            None => if self.strict_mode {
+                return Err(Error::FileReaderError(format!("`{}` is broken: `{chapter_path}` is missing.", self.path)));
+            } else {
+                return Ok(());
+            },
+        };
+        let html = if self.strict_mode {
+            String::from_utf8(bytes)?
+        } else {
+            String::from_utf8_lossy(&bytes).to_string()
+        };
+        let mut tokens = vec![];
+
+        for token in render_html(&html) {
+            match token {
+                StringOrImage::String(s) => {
+                    if title.is_none() {
+                        title = s.lines().find(|line| line.starts_with('#')).map(
+                            |line| line.trim_start_matches('#').trim().to_string()
+                        ).filter(|line| !line.is_empty());
+                    }
+
+                    tokens.push(AtomicToken::String {
+                        char_len: s.chars().count(),
+                        data: s,
+                    });
+                },
+                StringOrImage::Image { alt, src } => {
+                    let token = self.load_image(&chapter_path, &alt, &src)?;
+                    tokens.push(token);
+                },
+            }
+        }
+
+        // e.g. a cover page without an image
+        if tokens.iter().all(
+            |token| match token {
+                AtomicToken::String { data, .. } => data.trim().is_empty(),
+                _ => false,
+            }
+        ) {
+            return Ok(());
+        }
+
+        self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+            item: Some(format!("chapter {}", self.cursor)),
+            heading: title,
+            ..ChunkExtraInfo::default()
+        }));
+        self.tokens.append(&mut tokens);
+        self.tokens.push(AtomicToken::PageBreak);
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        self.cursor < self.chapters.len()
+    }
+
+    fn key(&self) -> String {
+        String::from("epub_reader_v1")
+    }
+}
+
+impl EpubReader {
+    // `src` is relative to the chapter.
+    fn load_image(&mut self, chapter_path: &str, alt: &str, src: &str) -> Result<AtomicToken, Error> {
+        let fallback = format!("![{alt}]({src})");
+
+        if WEB_URL_RE.is_match(src) {
+            return Ok(AtomicToken::WebImage { subst: fallback, url: src.to_string() });
+        }
+
+        let path = resolve_path(&parent_dir(chapter_path), &percent_decode(src.split(['?', '#']).next().unwrap_or("")));
+
+        match self.archive.load_image(&path, self.strict_mode)? {
+            Some(image) => Ok(image),
+            None => Ok(AtomicToken::String {
+                char_len: fallback.chars().count(),
+                data: fallback,
+            }),
+        }
+    }
+}
+
+// Epub 2 files often have `<!DOCTYPE>`.
+fn parse_xml(xml: &str) -> Result<Document<'_>, Error> {
+    Ok(Document::parse_with_options(xml, ParsingOptions { allow_dtd: true, ..ParsingOptions::default() })?)
+}
+
+fn parent_dir(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, _)) => dir.to_string(),
+        None => String::new(),
+    }
+}
+
+// `href` is relative to `dir`. It drops the fragment (e.g. `chapter1.xhtml#section2`).
+fn resolve_href(dir: &str, href: &str) -> String {
+    resolve_path(dir, &percent_decode(href.split('#').next().unwrap_or("")))
+}
+
+// A table of contents may have multiple entries for a chapter (e.g. sections of
+// the chapter). The first one is the title of the chapter.
+fn insert_title(titles: &mut HashMap<String, String>, path: String, title: &str) {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if !title.is_empty() {
+        titles.entry(path).or_insert(title);
+    }
+}
+
+fn text_content(node: Node) -> String {
+    node.descendants().filter(|node| node.is_text()).filter_map(|node| node.text()).collect::<Vec<_>>().concat()
+}
+
+// nav document of epub 3: `<nav epub:type="toc"><ol><li><a href="...">title</a></li></ol></nav>`
+fn read_nav_titles(archive: &mut Archive, nav_path: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let Ok(Some(xml)) = archive.read_string(nav_path) else { return titles; };
+    let Ok(document) = parse_xml(&xml) else { return titles; };
+    let dir = parent_dir(nav_path);
+    let navs = descendants(document.root_element(), "nav").collect::<Vec<_>>();
+    let Some(toc) = navs.iter().find(|nav| attribute(**nav, "type") == Some("toc")).or_else(|| navs.first()) else { return titles; };
+
+    for a in descendants(*toc, "a") {
+        if let Some(href) = attribute(a, "href") {
+            insert_title(&mut titles, resolve_href(&dir, href), &text_content(a));
+        }
+    }
+
+    titles
+}
+
+// ncx of epub 2: `<navPoint><navLabel><text>title</text></navLabel><content src="..."/></navPoint>`
+fn read_ncx_titles(archive: &mut Archive, ncx_path: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let Ok(Some(xml)) = archive.read_string(ncx_path) else { return titles; };
+    let Ok(document) = parse_xml(&xml) else { return titles; };
+    let dir = parent_dir(ncx_path);
+
+    for nav_point in descendants(document.root_element(), "navPoint") {
+        let (Some(label), Some(src)) = (
+            child(nav_point, "navLabel").and_then(|label| child(label, "text")),
+            child(nav_point, "content").and_then(|content| attribute(content, "src")),
+        ) else { continue; };
+
+        insert_title(&mut titles, resolve_href(&dir, src), &text_content(label));
+    }
+
+    titles
+}
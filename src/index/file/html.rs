@@ -0,0 +1,1044 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::markdown::{WEB_URL_RE, load_image_token};
+use crate::error::Error;
+use crate::index::BuildConfig;
+use ragit_fs::{exists, join, parent, read_bytes};
+
+/// It converts an html file to a markdown-like text. It's not a complete html
+/// parser, but it's generous enough to read exported documentation sites and
+/// saved web pages.
+///
+/// - It drops `<script>`, `<style>`, `<nav>` and other elements that are not
+///   meant to be read by humans.
+/// - Headings, lists, tables and code blocks are rendered like markdown.
+/// - `<img src>` is converted to an image, just like `MarkdownReader` does.
+pub struct HtmlReader {
+    path: String,
+    root_dir: String,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+}
+
+impl FileReaderImpl for HtmlReader {
+    fn new(path: &str, root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("html", &[])?;
+
+        // It checks whether the file exists. It reads the file in `load_tokens`.
+        if !exists(path) {
+            return Err(Error::FileReaderError(format!("`{path}` does not exist.")));
+        }
+
+        Ok(HtmlReader {
+            path: path.to_string(),
+            root_dir: root_dir.to_string(),
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    // Unlike other readers, it reads the entire file at once. You cannot render
+    // a table before you see `</table>`.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        let bytes = read_bytes(&self.path)?;
+        let html = if self.strict_mode {
+            String::from_utf8(bytes)?
+        } else {
+            String::from_utf8_lossy(&bytes).to_string()
+        };
+        for token in render_html(&html) {
+            match token {
+                StringOrImage::String(s) => {
+                    self.tokens.push(AtomicToken::String {
+                        char_len: s.chars().count(),
+                        data: s,
+                    });
+                },
+                StringOrImage::Image { alt, src } => {
+                    let token = self.load_image(&alt, &src)?;
+                    self.tokens.push(token);
+                },
+            }
+        }
+
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("html_reader_v0")
+    }
+}
+
+impl HtmlReader {
+    // It resolves `src` the same way `MarkdownReader` does.
+    fn load_image(&self, alt: &str, src: &str) -> Result<AtomicToken, Error> {
+        let fallback = format!("![{alt}]({src})");
+
+        // protocol-relative url
+        let mut url = if let Some(s) = src.strip_prefix("//") {
+            format!("https://{s}")
+        } else {
+            src.to_string()
+        };
+
+        if WEB_URL_RE.is_match(&url) {
+            return Ok(AtomicToken::WebImage { subst: fallback, url });
+        }
+
+        // e.g. `data:image/png;base64,...`
+        // TODO: decode base64 data urls
+        if url.starts_with("data:") {
+            return Ok(AtomicToken::String {
+                char_len: alt.chars().count(),
+                data: alt.to_string(),
+            });
+        }
+
+        // Saved web pages often have `?v=3` or `#anchor` after file names, and
+        // file names in `src` are percent-encoded.
+        url = percent_decode(url.split(['?', '#']).next().unwrap_or(""));
+
+        if !exists(&url) {
+            // Absolute path: root dir of the repository, not the root of the file system
+            if url.starts_with("/") {
+                url = join(&self.root_dir, &format!(".{}", &url))?;
+            }
+
+            // Relative path: relative to the html file
+            else {
+                url = join(&parent(&self.path)?, &url)?;
+            }
+        }
+
+        match load_image_token(&url) {
+            Ok(image) => Ok(image),
+            Err(e) => if self.strict_mode {
+                Err(e)
+            } else {
+                Ok(AtomicToken::String {
+                    char_len: fallback.chars().count(),
+                    data: fallback,
+                })
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Text(String),
+    StartTag {
+        name: String,
+        attributes: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    EndTag(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum StringOrImage {
+    String(String),
+    Image { alt: String, src: String },
+}
+
+// `EpubReader` also uses this function to render xhtml chapters.
+pub(super) fn render_html(html: &str) -> Vec<StringOrImage> {
+    let mut renderer = Renderer::new();
+
+    for token in tokenize(html) {
+        renderer.feed(token);
+    }
+
+    renderer.finish()
+}
+
+// Elements that cannot have children.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img",
+    "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+// Their contents are not parsed as html.
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "textarea", "title"];
+
+// Their contents are dropped.
+const SKIPPED_ELEMENTS: [&str; 12] = [
+    "head", "script", "style", "nav", "noscript", "template",
+    "svg", "iframe", "canvas", "select", "button", "dialog",
+];
+
+const BLOCK_ELEMENTS: [&str; 27] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "dd",
+    "details", "div", "dl", "dt", "fieldset", "figcaption", "figure",
+    "footer", "form", "header", "html", "main", "ol", "p",
+    "section", "summary", "table", "tr", "ul", "pre",
+];
+
+fn tokenize(html: &str) -> Vec<Token> {
+    // Tag names are case-insensitive. `to_ascii_lowercase` does not change byte offsets.
+    let lowered = html.to_ascii_lowercase();
+    let bytes = html.as_bytes();
+    let mut result = vec![];
+    let mut text_start = 0;
+    let mut cursor = 0;
+
+    while cursor < bytes.len() {
+        if bytes[cursor] != b'<' {
+            cursor += 1;
+            continue;
+        }
+
+        let tag_start = cursor;
+        let next = bytes.get(cursor + 1).copied();
+
+        let (token, end) = if lowered[cursor..].starts_with("<!--") {
+            match lowered[cursor + 4..].find("-->") {
+                Some(i) => (None, cursor + 4 + i + 3),
+                None => (None, bytes.len()),
+            }
+        }
+
+        // doctype, cdata and processing instructions
+        else if matches!(next, Some(b'!' | b'?')) {
+            match lowered[cursor..].find('>') {
+                Some(i) => (None, cursor + i + 1),
+                None => (None, bytes.len()),
+            }
+        }
+
+        else if next == Some(b'/') && bytes.get(cursor + 2).map(|b| b.is_ascii_alphabetic()).unwrap_or(false) {
+            let name_end = find_name_end(bytes, cursor + 2);
+            let name = lowered[cursor + 2..name_end].to_string();
+
+            match lowered[name_end..].find('>') {
+                Some(i) => (Some(Token::EndTag(name)), name_end + i + 1),
+                None => (Some(Token::EndTag(name)), bytes.len()),
+            }
+        }
+
+        else if next.map(|b| b.is_ascii_alphabetic()).unwrap_or(false) {
+            let name_end = find_name_end(bytes, cursor + 1);
+            let name = lowered[cursor + 1..name_end].to_string();
+            let (attributes, self_closing, end) = parse_attributes(html, name_end);
+            (Some(Token::StartTag { name, attributes, self_closing }), end)
+        }
+
+        // It's just a '<' character.
+        else {
+            cursor += 1;
+            continue;
+        };
+
+        if text_start < tag_start {
+            result.push(Token::Text(html[text_start..tag_start].to_string()));
+        }
+
+        cursor = end;
+        text_start = end;
+
+        if let Some(token) = token {
+            let raw_text_name = match &token {
+                Token::StartTag { name, self_closing: false, .. } if RAW_TEXT_ELEMENTS.contains(&name.as_str()) => Some(name.to_string()),
+                _ => None,
+            };
+            result.push(token);
+
+            if let Some(name) = raw_text_name {
+                let closing = format!("</{name}");
+                let raw_end = lowered[cursor..].find(&closing).map(|i| cursor + i).unwrap_or(bytes.len());
+
+                if cursor < raw_end {
+                    result.push(Token::Text(html[cursor..raw_end].to_string()));
+                }
+
+                let end = lowered[raw_end..].find('>').map(|i| raw_end + i + 1).unwrap_or(bytes.len());
+                result.push(Token::EndTag(name));
+                cursor = end;
+                text_start = end;
+            }
+        }
+    }
+
+    if text_start < bytes.len() {
+        result.push(Token::Text(html[text_start..].to_string()));
+    }
+
+    result
+}
+
+fn find_name_end(bytes: &[u8], mut cursor: usize) -> usize {
+    while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() && bytes[cursor] != b'>' && bytes[cursor] != b'/' {
+        cursor += 1;
+    }
+
+    cursor
+}
+
+// It returns (attributes, self_closing, index after `>`).
+fn parse_attributes(html: &str, mut cursor: usize) -> (Vec<(String, String)>, bool, usize) {
+    let bytes = html.as_bytes();
+    let mut attributes = vec![];
+
+    loop {
+        while cursor < bytes.len() && bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+
+        match bytes.get(cursor) {
+            None => {
+                return (attributes, false, bytes.len());
+            },
+            Some(b'>') => {
+                return (attributes, false, cursor + 1);
+            },
+            Some(b'/') => {
+                if bytes.get(cursor + 1) == Some(&b'>') {
+                    return (attributes, true, cursor + 2);
+                }
+
+                cursor += 1;
+                continue;
+            },
+            _ => {},
+        }
+
+        let name_start = cursor;
+
+        while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() && !matches!(bytes[cursor], b'=' | b'>' | b'/') {
+            cursor += 1;
+        }
+
+        let name = html[name_start..cursor].to_ascii_lowercase();
+
+        while cursor < bytes.len() && bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+
+        if bytes.get(cursor) != Some(&b'=') {
+            attributes.push((name, String::new()));
+            continue;
+        }
+
+        cursor += 1;
+
+        while cursor < bytes.len() && bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+
+        let value = match bytes.get(cursor) {
+            Some(q @ (b'"' | b'\'')) => {
+                let value_start = cursor + 1;
+                let value_end = html[value_start..].find(*q as char).map(|i| value_start + i).unwrap_or(bytes.len());
+                cursor = (value_end + 1).min(bytes.len());
+                &html[value_start..value_end]
+            },
+            _ => {
+                let value_start = cursor;
+
+                while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() && bytes[cursor] != b'>' {
+                    cursor += 1;
+                }
+
+                &html[value_start..cursor]
+            },
+        };
+
+        attributes.push((name, decode_entities(value)));
+    }
+}
+
+fn is_skipped(name: &str, attributes: &[(String, String)]) -> bool {
+    SKIPPED_ELEMENTS.contains(&name)
+    || get_attribute(attributes, "hidden").is_some()
+    || get_attribute(attributes, "aria-hidden") == Some("true")
+
+    // `<div role="navigation">` is as noisy as `<nav>`.
+    || matches!(get_attribute(attributes, "role"), Some("navigation" | "search"))
+}
+
+fn get_attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+}
+
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+enum ListKind {
+    Unordered,
+    Ordered(usize),  // next number
+}
+
+struct Renderer {
+    result: Vec<StringOrImage>,
+    buffer: String,
+
+    // If it's inside an element that has to be dropped, it counts the depth of the element.
+    skip: Option<(String, usize)>,
+
+    lists: Vec<ListKind>,
+    tables: Vec<Table>,
+    pre_depth: usize,
+
+    // If it's right after `<pre>`, it ignores a newline.
+    pre_start: bool,
+
+    // If it's right after `- `, `<p>` in `<li>` must not break the line.
+    // It's the index of `- ` in the buffer.
+    list_marker: Option<usize>,
+
+    // `<code>` inside `<pre>` is not an inline code.
+    code_depth: usize,
+}
+
+impl Renderer {
+    fn new() -> Self {
+        Renderer {
+            result: vec![],
+            buffer: String::new(),
+            skip: None,
+            lists: vec![],
+            tables: vec![],
+            pre_depth: 0,
+            pre_start: false,
+            list_marker: None,
+            code_depth: 0,
+        }
+    }
+
+    fn feed(&mut self, token: Token) {
+        if let Some((skipped, depth)) = &mut self.skip {
+            match &token {
+                Token::StartTag { name, self_closing: false, .. } if name == skipped => { *depth += 1; },
+                Token::EndTag(name) if name == skipped => {
+                    *depth -= 1;
+
+                    if *depth == 0 {
+                        self.skip = None;
+                    }
+                },
+                _ => {},
+            }
+
+            return;
+        }
+
+        match token {
+            Token::Text(s) => {
+                let s = decode_entities(&s);
+
+                if self.pre_depth > 0 {
+                    // A newline right after `<pre>` is ignored.
+                    let s = match s.strip_prefix("\n") {
+                        Some(s) if self.pre_start => s,
+                        _ => &s,
+                    };
+                    self.pre_start = false;
+                    self.write_raw(s);
+                }
+
+                else {
+                    self.write_text(&s);
+                }
+            },
+            Token::StartTag { name, attributes, self_closing } => {
+                let is_void = self_closing || VOID_ELEMENTS.contains(&name.as_str());
+
+                if !is_void && is_skipped(&name, &attributes) {
+                    self.skip = Some((name, 1));
+                    return;
+                }
+
+                self.start_tag(&name, &attributes);
+
+                if is_void {
+                    self.end_tag(&name);
+                }
+            },
+            Token::EndTag(name) => {
+                if !VOID_ELEMENTS.contains(&name.as_str()) {
+                    self.end_tag(&name);
+                }
+            },
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &[(String, String)]) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = (name.as_bytes()[1] - b'0') as usize;
+                self.block_break();
+                self.write_raw(&format!("{} ", "#".repeat(level)));
+            },
+            "br" => {
+                self.line_break();
+            },
+            "hr" => {
+                self.block_break();
+                self.write_raw("---");
+                self.block_break();
+            },
+            "ul" | "ol" | "menu" => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+
+                self.lists.push(if name == "ol" {
+                    ListKind::Ordered(get_attribute(attributes, "start").and_then(|n| n.trim().parse::<usize>().ok()).unwrap_or(1))
+                } else {
+                    ListKind::Unordered
+                });
+            },
+            "li" => {
+                self.remove_empty_list_item();
+                self.line_break();
+                let indent = "  ".repeat(self.lists.len().max(1) - 1);
+                let marker = match self.lists.last_mut() {
+                    Some(ListKind::Ordered(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    },
+                    _ => String::from("- "),
+                };
+                if self.tables.is_empty() {
+                    self.list_marker = Some(self.buffer.len());
+                }
+
+                self.write_raw(&format!("{indent}{marker}"));
+            },
+            "pre" => {
+                self.block_break();
+                let language = get_attribute(attributes, "class").and_then(get_language).unwrap_or_default();
+                self.write_raw(&format!("```{language}\n"));
+                self.pre_depth += 1;
+                self.pre_start = true;
+            },
+            "code" | "kbd" | "samp" | "tt" => {
+                if self.pre_depth == 0 {
+                    if self.code_depth == 0 {
+                        self.write_inline("`");
+                    }
+
+                    self.code_depth += 1;
+                }
+
+                // `<pre><code class="language-rust">` is more common than `<pre class="language-rust">`
+                else if let (true, Some(language)) = (
+                    self.pre_start && self.buffer.ends_with("```\n"),
+                    get_attribute(attributes, "class").and_then(get_language),
+                ) {
+                    self.buffer.pop();
+                    self.buffer.push_str(&language);
+                    self.buffer.push('\n');
+                }
+            },
+            "b" | "strong" => {
+                self.write_inline("**");
+            },
+            "i" | "em" => {
+                self.write_inline("*");
+            },
+            "table" => {
+                self.block_break();
+                self.tables.push(Table { rows: vec![] });
+            },
+            "tr" => {
+                if let Some(table) = self.tables.last_mut() {
+                    table.rows.push(vec![]);
+                }
+            },
+            "td" | "th" => {
+                if let Some(table) = self.tables.last_mut() {
+                    if table.rows.is_empty() {
+                        table.rows.push(vec![]);
+                    }
+
+                    table.rows.last_mut().unwrap().push(String::new());
+                }
+            },
+            "img" => {
+                let alt = get_attribute(attributes, "alt").unwrap_or("").trim().to_string();
+
+                match get_attribute(attributes, "src").map(|src| src.trim()) {
+                    Some(src) if !src.is_empty() => {
+                        // An image cannot be inside a table cell.
+                        if !self.tables.is_empty() {
+                            self.write_inline(&format!("![{alt}]({src})"));
+                        }
+
+                        else {
+                            self.flush_buffer();
+                            self.result.push(StringOrImage::Image { alt, src: src.to_string() });
+                        }
+                    },
+                    _ => {
+                        self.write_inline(&alt);
+                    },
+                }
+            },
+            "dd" => {
+                self.line_break();
+                self.write_raw("  ");
+            },
+            name if BLOCK_ELEMENTS.contains(&name) => {
+                self.block_break();
+            },
+            _ => {},
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block_break();
+            },
+            "ul" | "ol" | "menu" => {
+                self.remove_empty_list_item();
+                self.lists.pop();
+
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+
+                else {
+                    self.line_break();
+                }
+            },
+            "li" => {
+                self.line_break();
+            },
+            "pre" if self.pre_depth > 0 => {
+                self.pre_depth -= 1;
+                self.line_break();
+                self.write_raw("```");
+                self.block_break();
+            },
+            "code" | "kbd" | "samp" | "tt" if self.pre_depth == 0 && self.code_depth > 0 => {
+                self.code_depth -= 1;
+
+                if self.code_depth == 0 {
+                    self.write_inline("`");
+                }
+            },
+            "b" | "strong" => {
+                self.write_inline("**");
+            },
+            "i" | "em" => {
+                self.write_inline("*");
+            },
+            "table" => {
+                if let Some(table) = self.tables.pop() {
+                    let rendered = render_table(&table.rows);
+
+                    // nested table
+                    if !self.tables.is_empty() {
+                        self.write_text(&rendered);
+                    }
+
+                    else {
+                        self.block_break();
+                        self.write_raw(&rendered);
+                        self.block_break();
+                    }
+                }
+            },
+            name if BLOCK_ELEMENTS.contains(&name) => {
+                self.block_break();
+            },
+            _ => {},
+        }
+    }
+
+    // It collapses whitespaces, like browsers do.
+    fn write_text(&mut self, s: &str) {
+        let mut collapsed = String::with_capacity(s.len());
+        let mut last_is_space = self.ends_with_space();
+
+        for c in s.chars() {
+            if c.is_whitespace() {
+                if !last_is_space {
+                    collapsed.push(' ');
+                    last_is_space = true;
+                }
+            }
+
+            else {
+                collapsed.push(c);
+                last_is_space = false;
+            }
+        }
+
+        self.write_inline(&collapsed);
+    }
+
+    fn write_inline(&mut self, s: &str) {
+        if s.trim().is_empty() {
+            // whitespaces between `<tr>` and `<td>`
+            if self.tables.last().map(|table| table.rows.last().map(|row| row.is_empty()).unwrap_or(true)).unwrap_or(false) {
+                return;
+            }
+        }
+
+        else {
+            self.list_marker = None;
+        }
+
+        match self.tables.last_mut() {
+            Some(table) => {
+                if table.rows.is_empty() {
+                    table.rows.push(vec![]);
+                }
+
+                let row = table.rows.last_mut().unwrap();
+
+                if row.is_empty() {
+                    row.push(String::new());
+                }
+
+                row.last_mut().unwrap().push_str(s);
+            },
+            None => {
+                self.buffer.push_str(s);
+            },
+        }
+    }
+
+    // It doesn't collapse whitespaces, and writes to the buffer even if it's inside a table.
+    fn write_raw(&mut self, s: &str) {
+        if !self.tables.is_empty() {
+            self.write_inline(&s.replace("\n", " "));
+            return;
+        }
+
+        self.buffer.push_str(s);
+    }
+
+    fn ends_with_space(&self) -> bool {
+        match self.tables.last() {
+            Some(table) => match table.rows.last().and_then(|row| row.last()) {
+                Some(cell) => cell.is_empty() || cell.ends_with(' '),
+                None => true,
+            },
+            None => match self.buffer.chars().last() {
+                Some(c) => c.is_whitespace(),
+
+                // If the buffer is flushed, it's right after an image.
+                None => self.result.is_empty(),
+            },
+        }
+    }
+
+    fn line_break(&mut self) {
+        if self.list_marker.is_some() {
+            return;
+        }
+
+        if !self.tables.is_empty() {
+            self.write_inline(" ");
+            return;
+        }
+
+        self.trim_trailing_spaces();
+
+        if !self.buffer.is_empty() && !self.buffer.ends_with('\n') {
+            self.buffer.push('\n');
+        }
+    }
+
+    fn block_break(&mut self) {
+        if self.pre_depth > 0 || self.list_marker.is_some() {
+            return;
+        }
+
+        if !self.tables.is_empty() {
+            self.write_inline(" ");
+            return;
+        }
+
+        self.trim_trailing_spaces();
+
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        while !self.buffer.ends_with("\n\n") {
+            self.buffer.push('\n');
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        if self.pre_depth > 0 {
+            return;
+        }
+
+        while self.buffer.ends_with(' ') {
+            self.buffer.pop();
+        }
+    }
+
+    // e.g. `<li><a href="/"><img src="logo.svg"></a></li>`
+    fn remove_empty_list_item(&mut self) {
+        if let Some(index) = self.list_marker.take() {
+            self.buffer.truncate(index);
+        }
+    }
+
+    fn flush_buffer(&mut self) {
+        self.list_marker = None;
+
+        if !self.buffer.is_empty() {
+            self.result.push(StringOrImage::String(std::mem::take(&mut self.buffer)));
+        }
+    }
+
+    fn finish(mut self) -> Vec<StringOrImage> {
+        // unclosed tables
+        while !self.tables.is_empty() {
+            self.end_tag("table");
+        }
+
+        self.trim_trailing_spaces();
+
+        while self.buffer.ends_with("\n\n") {
+            self.buffer.pop();
+        }
+
+        self.flush_buffer();
+        self.result
+    }
+}
+
+// It renders a markdown table. Office file readers also use this function.
+pub(super) fn render_table(rows: &[Vec<String>]) -> String {
+    let rows = rows.iter().filter(|row| !row.is_empty()).map(
+        |row| row.iter().map(
+            |cell| cell.trim().replace("\n", " ").replace("|", "\\|")
+        ).collect::<Vec<_>>()
+    ).collect::<Vec<_>>();
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+    if columns == 0 {
+        return String::new();
+    }
+
+    let mut lines = vec![];
+
+    for (i, row) in rows.iter().enumerate() {
+        let mut cells = row.clone();
+        cells.resize(columns, String::new());
+        lines.push(format!("| {} |", cells.join(" | ")));
+
+        // The first row is always treated as a header.
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// `class="language-rust"` or `class="lang-rust"`
+fn get_language(class: &str) -> Option<String> {
+    for class in class.split_whitespace() {
+        if let Some(language) = class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")) {
+            return Some(language.to_string());
+        }
+    }
+
+    None
+}
+
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find('&') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(
+            |end| decode_entity(&rest[1..(end + 1)]).map(|c| (c, end + 2))
+        );
+
+        match decoded {
+            Some((c, len)) => {
+                result.push(c);
+                rest = &rest[len..];
+            },
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(n) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+        return u32::from_str_radix(n, 16).ok().and_then(char::from_u32);
+    }
+
+    if let Some(n) = entity.strip_prefix("#") {
+        return n.parse::<u32>().ok().and_then(char::from_u32);
+    }
+
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+
+        // It's not a whitespace, but it's rendered like one.
+        "nbsp" | "ensp" | "emsp" | "thinsp" => ' ',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "mdash" => '—',
+        "ndash" => '–',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "middot" => '·',
+        "bull" => '•',
+        "times" => '×',
+        "divide" => '÷',
+        "deg" => '°',
+        "para" => '¶',
+        "sect" => '§',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        _ => { return None; },
+    };
+
+    Some(c)
+}
+
+pub(super) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = if bytes[i] == b'%' {
+            s.get((i + 1)..(i + 3)).and_then(|h| u8::from_str_radix(h, 16).ok())
+        } else {
+            None
+        };
+
+        if let Some(b) = decoded {
+            result.push(b);
+            i += 3;
+            continue;
+        }
+
+        result.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8(result).unwrap_or(s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StringOrImage, decode_entities, render_html};
+
+    #[test]
+    fn html_test() {
+        let html = r#"<!DOCTYPE html>
+<html>
+<head><title>Title</title><style>body { color: red; }</style></head>
+<body>
+<nav><a href="/">Home</a> | <a href="/docs">Docs</a></nav>
+<script>if (a < b) { document.write("<p>not a paragraph</p>"); }</script>
+<div role="navigation"><a href="/prev">Previous</a></div>
+<h1>Getting   Started</h1>
+<p>Hello, <b>World</b>! Use <code>rag build</code> &amp; <code>rag query</code>.</p>
+<ul>
+    <li><a href="/"></a></li>
+    <li>first</li>
+    <li>second<ol start="3"><li>third</li><li>fourth</li></ol></li>
+</ul>
+<pre><code class="language-rust">fn main() {
+    println!("&lt;3");
+}
+</code></pre>
+<table>
+    <tr><th>name</th><th>age</th></tr>
+    <tr><td>Alice</td><td>30</td></tr>
+    <tr><td>Bob | Charlie</td></tr>
+</table>
+<p hidden>hidden text</p>
+<p>An image: <img src="images/a.png" alt="a cat"> and a tail.</p>
+<!-- <p>comment</p> -->
+</body>
+</html>
+"#;
+        let result = render_html(html);
+        assert_eq!(
+            result,
+            vec![
+                StringOrImage::String(String::from("# Getting Started
+
+Hello, **World**! Use `rag build` & `rag query`.
+
+- first
+- second
+  3. third
+  4. fourth
+
+```rust
+fn main() {
+    println!(\"<3\");
+}
+```
+
+| name | age |
+| --- | --- |
+| Alice | 30 |
+| Bob \\| Charlie |  |
+
+An image: ")),
+                StringOrImage::Image { alt: String::from("a cat"), src: String::from("images/a.png") },
+                StringOrImage::String(String::from(" and a tail.\n")),
+            ],
+        );
+
+        // malformed html
+        assert_eq!(
+            render_html("<p>unclosed <b>tags<div>and < stray brackets"),
+            vec![StringOrImage::String(String::from("unclosed **tags\n\nand < stray brackets"))],
+        );
+
+        assert_eq!(decode_entities("&lt;&#65;&#x42;&unknown;&"), "<AB&unknown;&");
+    }
+}
@@ -1,4 +1,4 @@
-use super::{AtomicToken, FileReaderImpl};
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
 use crate::error::Error;
 use crate::index::BuildConfig;
 use crate::uid::Uid;
@@ -93,7 +93,9 @@ pub struct ImageReader {
 }
 
 impl FileReaderImpl for ImageReader {
-    fn new(path: &str, _root_dir: &str, config: &BuildConfig) -> Result<Self, Error> {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("image", &[])?;
+
         Ok(ImageReader {
             path: path.to_string(),
             tokens: vec![],
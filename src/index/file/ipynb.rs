@@ -0,0 +1,287 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions, Image, MarkdownReader};
+use super::markdown::render_headings;
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use ragit_fs::read_string;
+use ragit_pdl::{ImageType, decode_base64};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Some cells print megabytes of logs. It doesn't make sense to index all of them.
+const MAX_OUTPUT_LEN: usize = 2000;
+
+/// It reads a jupyter notebook cell by cell. Markdown cells are read by
+/// `MarkdownReader`, code cells are rendered as fenced code blocks, and
+/// outputs of code cells are rendered after the code. Image outputs (png and
+/// jpeg) become `AtomicToken::Image`. The index of the cell (1-base) and the
+/// headings of the markdown cells above are passed to the chunk via `ChunkExtraInfo`.
+///
+/// If `outputs` option is false, it doesn't read the outputs.
+pub struct IpynbReader {
+    path: String,
+    root_dir: String,
+    config: BuildConfig,
+    cells: Vec<Cell>,
+    language: String,
+    read_outputs: bool,
+    strict_mode: bool,
+    cursor: usize,
+    tokens: Vec<AtomicToken>,
+
+    // headings of the markdown cells (see `MarkdownReader`)
+    headings: Vec<(usize, String)>,
+}
+
+#[derive(Deserialize)]
+struct Notebook {
+    #[serde(default)]
+    cells: Vec<Cell>,
+
+    #[serde(default)]
+    metadata: Value,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+
+    #[serde(default)]
+    source: MultilineString,
+
+    #[serde(default)]
+    outputs: Vec<Value>,
+
+    // file name -> mime type -> base64
+    #[serde(default)]
+    attachments: HashMap<String, HashMap<String, MultilineString>>,
+}
+
+// nbformat allows both a string and an array of strings.
+#[derive(Default, Deserialize)]
+#[serde(untagged)]
+enum MultilineString {
+    #[default]
+    Empty,
+    String(String),
+    Lines(Vec<String>),
+}
+
+impl MultilineString {
+    fn concat(&self) -> String {
+        match self {
+            MultilineString::Empty => String::new(),
+            MultilineString::String(s) => s.to_string(),
+            MultilineString::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+impl FileReaderImpl for IpynbReader {
+    fn new(path: &str, root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("ipynb", &["outputs"])?;
+        let notebook = serde_json::from_str::<Notebook>(&read_string(path)?)?;
+        let language = match (
+            notebook.metadata.get("language_info").and_then(|info| info.get("name")).and_then(|name| name.as_str()),
+            notebook.metadata.get("kernelspec").and_then(|spec| spec.get("language")).and_then(|language| language.as_str()),
+        ) {
+            (Some(language), _) | (None, Some(language)) => language.to_string(),
+            (None, None) => String::from("python"),
+        };
+
+        Ok(IpynbReader {
+            path: path.to_string(),
+            root_dir: root_dir.to_string(),
+            config: config.clone(),
+            cells: notebook.cells,
+            language,
+            read_outputs: options.get_bool("outputs")?.unwrap_or(true),
+            strict_mode: config.strict_file_reader,
+            cursor: 0,
+            tokens: vec![],
+            headings: vec![],
+        })
+    }
+
+    // It reads a cell at a time.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        let Some(cell) = self.cells.get(self.cursor) else { return Ok(()); };
+        let source = cell.source.concat();
+        let mut tokens = vec![];
+        let item = format!("cell {}", self.cursor + 1);
+        let heading = render_headings(&self.headings);
+        self.cursor += 1;
+
+        match cell.cell_type.as_str() {
+            "markdown" => {
+                let mut attachments = HashMap::new();
+
+                for (name, data) in cell.attachments.iter() {
+                    if let Some(image) = self.load_image(data)? {
+                        attachments.insert(name.to_string(), image);
+                    }
+                }
+
+                let mut reader = MarkdownReader::from_string(
+                    format!("{}\n\n", source.trim_end()),
+                    &self.path,
+                    &self.root_dir,
+                    &self.config,
+                    attachments,
+                    self.headings.clone(),
+                );
+
+                while reader.has_more_to_read() {
+                    reader.load_tokens()?;
+
+                    for token in reader.pop_all_tokens()? {
+                        match token {
+                            // a heading in the cell
+                            AtomicToken::ChunkExtraInfo(extra_info) => {
+                                tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { item: Some(item.clone()), ..extra_info }));
+                            },
+                            token => { tokens.push(token); },
+                        }
+                    }
+                }
+
+                self.headings = reader.headings().to_vec();
+            },
+            "code" => {
+                if !source.trim().is_empty() {
+                    push_lines(&mut tokens, &format!("```{}\n{}\n```\n\n", self.language, source.trim_end()));
+                }
+
+                if self.read_outputs {
+                    for output in cell.outputs.iter() {
+                        self.render_output(output, &mut tokens)?;
+                    }
+                }
+            },
+
+            // raw cells
+            _ => {
+                push_lines(&mut tokens, &format!("{}\n\n", source.trim_end()));
+            },
+        }
+
+        // an empty cell
+        if tokens.iter().all(
+            |token| match token {
+                AtomicToken::String { data, .. } => data.trim().is_empty(),
+                AtomicToken::ChunkExtraInfo(_) => true,
+                _ => false,
+            }
+        ) {
+            return Ok(());
+        }
+
+        self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { item: Some(item), heading, ..ChunkExtraInfo::default() }));
+        self.tokens.append(&mut tokens);
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        self.cursor < self.cells.len()
+    }
+
+    fn key(&self) -> String {
+        String::from("ipynb_reader_v0")
+    }
+}
+
+impl IpynbReader {
+    fn render_output(&self, output: &Value, tokens: &mut Vec<AtomicToken>) -> Result<(), Error> {
+        let text = match output.get("output_type").and_then(|t| t.as_str()) {
+            Some("stream") => output.get("text").map(value_to_string),
+            Some("execute_result" | "display_data") => {
+                let Some(data) = output.get("data") else { return Ok(()); };
+                let mut images = HashMap::new();
+
+                for mime_type in ["image/png", "image/jpeg"] {
+                    if let Some(image) = data.get(mime_type) {
+                        images.insert(mime_type.to_string(), MultilineString::String(value_to_string(image)));
+                    }
+                }
+
+                if let Some(image) = self.load_image(&images)? {
+                    tokens.push(image);
+                    tokens.push(AtomicToken::String { data: String::from("\n\n"), char_len: 2 });
+                    return Ok(());
+                }
+
+                // html outputs (e.g. pandas dataframes) usually come with plain text outputs
+                data.get("text/markdown").or_else(|| data.get("text/plain")).map(value_to_string)
+            },
+            Some("error") => Some(format!(
+                "{}: {}",
+                output.get("ename").and_then(|e| e.as_str()).unwrap_or("Error"),
+                output.get("evalue").and_then(|e| e.as_str()).unwrap_or(""),
+            )),
+            _ => None,
+        };
+
+        let Some(text) = text else { return Ok(()); };
+        let text = text.trim_end();
+
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let text = if text.chars().count() > MAX_OUTPUT_LEN {
+            format!("{}\n... (truncated)", text.chars().take(MAX_OUTPUT_LEN).collect::<String>())
+        } else {
+            text.to_string()
+        };
+
+        push_lines(tokens, &format!("```\n{text}\n```\n\n"));
+        Ok(())
+    }
+
+    // `data` is a mime bundle: mime type -> base64
+    fn load_image(&self, data: &HashMap<String, MultilineString>) -> Result<Option<AtomicToken>, Error> {
+        for (mime_type, image_type) in [("image/png", ImageType::Png), ("image/jpeg", ImageType::Jpeg)] {
+            let Some(base64) = data.get(mime_type) else { continue; };
+
+            // base64 strings in notebooks are often wrapped
+            let base64 = base64.concat().chars().filter(|c| !c.is_whitespace()).collect::<String>();
+
+            match decode_base64(&base64).map_err(Error::from).and_then(|bytes| Image::new(bytes, image_type)) {
+                Ok(image) => { return Ok(Some(AtomicToken::Image(image))); },
+                Err(e) => {
+                    if self.strict_mode {
+                        return Err(e);
+                    }
+                },
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn value_to_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.to_string(),
+        Value::Array(lines) => lines.iter().map(|line| line.as_str().unwrap_or("")).collect::<Vec<_>>().concat(),
+        _ => String::new(),
+    }
+}
+
+// Each line is a token, so that `FileReader` can split a long cell.
+// `EmailReader` also uses this function.
+pub(super) fn push_lines(tokens: &mut Vec<AtomicToken>, s: &str) {
+    for line in s.split_inclusive('\n') {
+        tokens.push(AtomicToken::String {
+            data: line.to_string(),
+            char_len: line.chars().count(),
+        });
+    }
+}
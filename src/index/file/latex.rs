@@ -0,0 +1,685 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::encoding::{FileEncoding, open_text_file};
+use super::markdown::{load_image_token, render_headings};
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::{BuildConfig, ChunkUnit};
+use ragit_fs::{exists, extension, join, parent};
+use std::io::Read;
+
+// `\input` can be nested, but not too deep.
+const MAX_INPUT_DEPTH: usize = 16;
+
+// (command, level)
+const SECTIONS: [(&str, usize); 7] = [
+    ("part", 0),
+    ("chapter", 1),
+    ("section", 2),
+    ("subsection", 3),
+    ("subsubsection", 4),
+    ("paragraph", 5),
+    ("subparagraph", 6),
+];
+
+// The contents of these environments are kept as they are.
+const VERBATIM_ENVS: [&str; 4] = ["verbatim", "Verbatim", "lstlisting", "minted"];
+
+// `\includegraphics{figures/plot}` may omit the extension.
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "svg"];
+
+/// It reads LaTeX sources. It follows `\input` and `\include`, and drops comments
+/// and the preamble (except `\title` and `\author`). `\includegraphics` is read as
+/// an image. The other commands, including math, are kept as they are.
+///
+/// Like `MarkdownReader`, each chunk knows which section it belongs to (e.g.
+/// "Method > Training"), and it prefers to split a file at `\chapter`, `\section`
+/// and `\subsection`.
+pub struct LatexReader {
+    path: String,
+
+    // comments are stripped and `\input` files are expanded
+    content: String,
+
+    // files in `\input` and `\include`
+    input_files: Vec<String>,
+    encoding: Option<FileEncoding>,
+
+    // files in `\input` are read with the same option
+    encoding_option: Option<String>,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+
+    // (level, title) of the sections that the current line belongs to
+    headings: Vec<(usize, String)>,
+
+    // the current group ends with a `PageBreak`
+    curr_group_len: usize,
+    chunk_size: usize,
+    chunk_unit: ChunkUnit,
+    image_size: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+
+    // `source` is `\section{Title}` itself
+    Section { level: usize, title: String, source: String },
+    Image { path: String, source: String },
+}
+
+impl FileReaderImpl for LatexReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("latex", &["encoding"])?;
+        let encoding_option = options.get_string("encoding")?;
+        let mut file = open_text_file(path, encoding_option.as_deref(), config.strict_file_reader)?;
+        let mut content = String::new();
+        file.content.read_to_string(&mut content)?;
+
+        let mut result = LatexReader {
+            path: path.to_string(),
+            content: String::new(),
+            input_files: vec![],
+            encoding: file.encoding,
+            encoding_option,
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+            headings: vec![],
+            curr_group_len: 0,
+            chunk_size: config.chunk_size,
+            chunk_unit: config.chunk_unit,
+            image_size: config.image_size,
+        };
+
+        // It reads all the `\input` files here, so that `rag build` can record them
+        // in `ChunkBuildInfo` before it creates chunks.
+        // Paths in `\input` and `\includegraphics` are relative to the main file,
+        // not to the file that has the command.
+        let mut input_files = vec![];
+        result.content = result.expand_inputs(
+            &strip_comments(&content),
+            &parent(path)?,
+            &mut vec![path.to_string()],
+            &mut input_files,
+        )?;
+        result.input_files = input_files;
+        Ok(result)
+    }
+
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        let base_dir = parent(&self.path)?;
+        let content = std::mem::take(&mut self.content);
+        let (preamble, body) = split_preamble(&content);
+        let mut graphics_paths = vec![String::new()];
+
+        if let Some(paths) = find_command_arg(preamble, "graphicspath") {
+            graphics_paths.extend(parse_graphics_paths(&paths));
+        }
+
+        let mut title = String::new();
+
+        for command in ["title", "author"] {
+            if let Some(arg) = find_command_arg(preamble, command) {
+                title = format!("{title}\\{command}{}{arg}{}\n", "{", "}");
+            }
+        }
+
+        if !title.is_empty() {
+            self.push_text(&format!("{title}\n"));
+        }
+
+        for piece in parse_body(body) {
+            match piece {
+                Piece::Text(s) => {
+                    self.push_text(&s);
+                },
+                Piece::Section { level, title, source } => {
+                    while self.headings.last().map(|(last_level, _)| *last_level >= level).unwrap_or(false) {
+                        self.headings.pop();
+                    }
+
+                    self.headings.push((level, title));
+
+                    if level <= 3 && self.curr_group_len >= self.chunk_size / 2 {
+                        // otherwise, the empty lines might be a chunk
+                        while let Some(AtomicToken::String { data, .. }) = self.tokens.last() {
+                            if !data.trim().is_empty() {
+                                break;
+                            }
+
+                            self.tokens.pop();
+                        }
+
+                        self.tokens.push(AtomicToken::PageBreak);
+                        self.curr_group_len = 0;
+                    }
+
+                    self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+                        heading: render_headings(&self.headings),
+                        ..ChunkExtraInfo::default()
+                    }));
+                    self.push_text(&source);
+                },
+                Piece::Image { path, source } => {
+                    match self.load_image(&base_dir, &graphics_paths, &path) {
+                        Ok(image) => {
+                            self.curr_group_len += self.image_size;
+                            self.tokens.push(image);
+                        },
+                        Err(e) => if self.strict_mode {
+                            return Err(e);
+                        } else {
+                            self.push_text(&source);
+                        },
+                    }
+                },
+            }
+        }
+
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("latex_reader_v0")
+    }
+
+    fn encoding(&self) -> Option<FileEncoding> {
+        self.encoding.clone()
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.input_files.clone()
+    }
+}
+
+impl LatexReader {
+    // A token per line, so that a chunk can be split at any line.
+    // Consecutive empty lines are merged.
+    fn push_text(&mut self, s: &str) {
+        for line in s.split_inclusive('\n') {
+            let line = if line.ends_with('\n') && line.trim().is_empty() { "\n" } else { line };
+            let is_last_line_empty = match self.tokens.last() {
+                Some(AtomicToken::String { data, .. }) => data.trim().is_empty(),
+
+                // at the start of the file or a chunk
+                Some(AtomicToken::PageBreak | AtomicToken::ChunkExtraInfo(_)) | None => true,
+                _ => false,
+            };
+
+            if line == "\n" && is_last_line_empty {
+                continue;
+            }
+
+            self.curr_group_len += self.chunk_unit.measure(line);
+            self.tokens.push(AtomicToken::String {
+                char_len: line.chars().count(),
+                data: line.to_string(),
+            });
+        }
+    }
+
+    // It replaces `\input{file}` and `\include{file}` with the contents of the files,
+    // and pushes the paths of the files to `input_files`.
+    fn expand_inputs(
+        &self,
+        content: &str,
+        base_dir: &str,
+        stack: &mut Vec<String>,
+        input_files: &mut Vec<String>,
+    ) -> Result<String, Error> {
+        let chars = content.chars().collect::<Vec<_>>();
+        let mut result = String::with_capacity(content.len());
+        let mut index = 0;
+
+        while index < chars.len() {
+            if chars[index] != '\\' {
+                result.push(chars[index]);
+                index += 1;
+                continue;
+            }
+
+            if let Some(end) = verbatim_end(&chars, index) {
+                result.extend(&chars[index..end]);
+                index = end;
+                continue;
+            }
+
+            let (name, name_end) = read_command_name(&chars, index);
+
+            if name != "input" && name != "include" {
+                result.extend(&chars[index..name_end]);
+                index = name_end;
+                continue;
+            }
+
+            // `\input{chapter1}` or `\input chapter1`
+            let (arg, arg_end) = match read_group(&chars, name_end, '{', '}') {
+                Some((arg, end)) => (arg, end),
+                None if name == "input" => {
+                    let start = skip_spaces(&chars, name_end);
+                    let mut end = start;
+
+                    while end < chars.len() && !chars[end].is_whitespace() && chars[end] != '\\' {
+                        end += 1;
+                    }
+
+                    (chars[start..end].iter().collect::<String>(), end)
+                },
+                None => {
+                    result.extend(&chars[index..name_end]);
+                    index = name_end;
+                    continue;
+                },
+            };
+            let source = chars[index..arg_end].iter().collect::<String>();
+            index = arg_end;
+
+            let path = match find_tex_file(base_dir, arg.trim())? {
+                Some(path) => path,
+                None => {
+                    if self.strict_mode {
+                        return Err(Error::FileReaderError(format!("Cannot find `{}` of `{source}`.", arg.trim())));
+                    }
+
+                    result.push_str(&source);
+                    continue;
+                },
+            };
+
+            if stack.contains(&path) || stack.len() > MAX_INPUT_DEPTH {
+                if self.strict_mode {
+                    return Err(Error::FileReaderError(format!("`{source}` is recursive or nested too deep.")));
+                }
+
+                result.push_str(&source);
+                continue;
+            }
+
+            let mut file = open_text_file(&path, self.encoding_option.as_deref(), self.strict_mode)?;
+            let mut sub_content = String::new();
+            file.content.read_to_string(&mut sub_content)?;
+
+            if !input_files.contains(&path) {
+                input_files.push(path.clone());
+            }
+
+            stack.push(path);
+            let sub_content = self.expand_inputs(&strip_comments(&sub_content), base_dir, stack, input_files)?;
+            stack.pop();
+
+            result.push('\n');
+            result.push_str(&sub_content);
+            result.push('\n');
+        }
+
+        Ok(result)
+    }
+
+    fn load_image(&self, base_dir: &str, graphics_paths: &[String], path: &str) -> Result<AtomicToken, Error> {
+        for graphics_path in graphics_paths.iter() {
+            let path = join(base_dir, &format!("{graphics_path}{path}"))?;
+
+            if exists(&path) && extension(&path)?.is_some() {
+                return load_image_token(&path);
+            }
+
+            for ext in IMAGE_EXTENSIONS.iter() {
+                let path_with_ext = format!("{path}.{ext}");
+
+                if exists(&path_with_ext) {
+                    return load_image_token(&path_with_ext);
+                }
+            }
+        }
+
+        Err(Error::FileReaderError(format!("Cannot find image `{path}` (`{}`).", self.path)))
+    }
+}
+
+// `\input{intro}` is `intro.tex` if it exists, otherwise `intro`.
+fn find_tex_file(base_dir: &str, arg: &str) -> Result<Option<String>, Error> {
+    if arg.is_empty() {
+        return Ok(None);
+    }
+
+    for candidate in [format!("{arg}.tex"), arg.to_string()] {
+        let path = join(base_dir, &candidate)?;
+
+        if exists(&path) {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+// It removes `% comments`. Like TeX, a comment also removes the newline character
+// and the indentation of the next line.
+fn strip_comments(content: &str) -> String {
+    let chars = content.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(content.len());
+    let mut index = 0;
+
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => {
+                if let Some(end) = verbatim_end(&chars, index) {
+                    result.extend(&chars[index..end]);
+                    index = end;
+                }
+
+                // `\%` is not a comment
+                else {
+                    result.extend(&chars[index..(index + 2).min(chars.len())]);
+                    index += 2;
+                }
+            },
+            '%' => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+
+                index += 1;
+
+                while index < chars.len() && (chars[index] == ' ' || chars[index] == '\t') {
+                    index += 1;
+                }
+            },
+            c => {
+                result.push(c);
+                index += 1;
+            },
+        }
+    }
+
+    result
+}
+
+// (preamble, body)
+// If there's no `\begin{document}` (e.g. a chapter of a book), the entire file is the body.
+fn split_preamble(content: &str) -> (&str, &str) {
+    match content.split_once("\\begin{document}") {
+        Some((preamble, body)) => match body.split_once("\\end{document}") {
+            Some((body, _)) => (preamble, body),
+            None => (preamble, body),
+        },
+        None => ("", content),
+    }
+}
+
+fn parse_body(body: &str) -> Vec<Piece> {
+    let chars = body.chars().collect::<Vec<_>>();
+    let mut result = vec![];
+    let mut text = String::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        if chars[index] != '\\' {
+            text.push(chars[index]);
+            index += 1;
+            continue;
+        }
+
+        if let Some(end) = verbatim_end(&chars, index) {
+            text.extend(&chars[index..end]);
+            index = end;
+            continue;
+        }
+
+        let (name, name_end) = read_command_name(&chars, index);
+        let section_level = SECTIONS.iter().find(|(command, _)| *command == name).map(|(_, level)| *level);
+
+        if section_level.is_some() || name == "includegraphics" {
+            // `\section*[short title]{title}`
+            let mut arg_start = name_end;
+
+            if chars.get(arg_start) == Some(&'*') {
+                arg_start += 1;
+            }
+
+            if let Some((_, end)) = read_group(&chars, arg_start, '[', ']') {
+                arg_start = end;
+            }
+
+            if let Some((arg, end)) = read_group(&chars, arg_start, '{', '}') {
+                let source = chars[index..end].iter().collect::<String>();
+
+                if !text.is_empty() {
+                    result.push(Piece::Text(text));
+                    text = String::new();
+                }
+
+                result.push(match section_level {
+                    Some(level) => Piece::Section {
+                        level,
+                        title: arg.split_whitespace().collect::<Vec<_>>().join(" "),
+                        source,
+                    },
+                    None => Piece::Image { path: arg.trim().to_string(), source },
+                });
+                index = end;
+                continue;
+            }
+        }
+
+        text.extend(&chars[index..name_end]);
+        index = name_end;
+    }
+
+    if !text.is_empty() {
+        result.push(Piece::Text(text));
+    }
+
+    result
+}
+
+// `\foo` -> ("foo", end), `\%` -> ("%", end)
+fn read_command_name(chars: &[char], index: usize) -> (String, usize) {
+    let mut end = index + 1;
+
+    while end < chars.len() && chars[end].is_ascii_alphabetic() {
+        end += 1;
+    }
+
+    if end == index + 1 && end < chars.len() {
+        end += 1;
+    }
+
+    (chars[(index + 1).min(end)..end].iter().collect(), end)
+}
+
+// If `\begin{verbatim}` starts at `index`, it returns where `\end{verbatim}` ends.
+fn verbatim_end(chars: &[char], index: usize) -> Option<usize> {
+    let (name, name_end) = read_command_name(chars, index);
+
+    if name != "begin" {
+        return None;
+    }
+
+    let (env, env_end) = read_group(chars, name_end, '{', '}')?;
+
+    if !VERBATIM_ENVS.contains(&env.as_str()) {
+        return None;
+    }
+
+    let end = format!("\\end{}{env}{}", "{", "}").chars().collect::<Vec<_>>();
+    let mut index = env_end;
+
+    while index < chars.len() {
+        if chars[index..].starts_with(&end) {
+            return Some(index + end.len());
+        }
+
+        index += 1;
+    }
+
+    Some(chars.len())
+}
+
+fn skip_spaces(chars: &[char], mut index: usize) -> usize {
+    while index < chars.len() && (chars[index] == ' ' || chars[index] == '\t') {
+        index += 1;
+    }
+
+    index
+}
+
+// If `{...}` (or `[...]`) starts at `index` (after spaces), it returns the content and where it ends.
+// Braces are balanced, and `\{` and `\}` are not braces.
+fn read_group(chars: &[char], index: usize, open: char, close: char) -> Option<(String, usize)> {
+    let start = skip_spaces(chars, index);
+
+    if chars.get(start) != Some(&open) {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut index = start;
+
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => {
+                index += 2;
+                continue;
+            },
+            c if c == open => {
+                depth += 1;
+            },
+            c if c == close => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some((chars[(start + 1)..index].iter().collect(), index + 1));
+                }
+            },
+            _ => {},
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+// the argument of the first `\command{...}`
+fn find_command_arg(content: &str, command: &str) -> Option<String> {
+    let chars = content.chars().collect::<Vec<_>>();
+    let mut index = 0;
+
+    while index < chars.len() {
+        if chars[index] == '\\' {
+            let (name, name_end) = read_command_name(&chars, index);
+
+            let arg = if name == command { read_group(&chars, name_end, '{', '}') } else { None };
+
+            if let Some((arg, _)) = arg {
+                return Some(arg);
+            }
+
+            index = name_end;
+        }
+
+        else {
+            index += 1;
+        }
+    }
+
+    None
+}
+
+// `{figures/}{images/}` -> ["figures/", "images/"]
+fn parse_graphics_paths(arg: &str) -> Vec<String> {
+    let chars = arg.chars().collect::<Vec<_>>();
+    let mut result = vec![];
+    let mut index = 0;
+
+    while let Some((path, end)) = read_group(&chars, index, '{', '}') {
+        let path = path.trim();
+
+        if path.ends_with('/') {
+            result.push(path.to_string());
+        } else {
+            result.push(format!("{path}/"));
+        }
+
+        index = end;
+
+        while index < chars.len() && chars[index].is_whitespace() {
+            index += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Piece, parse_body, parse_graphics_paths, split_preamble, strip_comments};
+
+    #[test]
+    fn latex_test() {
+        let content = strip_comments(r#"\documentclass{article}
+\usepackage{amsmath} % for math
+\newcommand{\R}{\mathbb{R}}
+\begin{document}
+% a comment line
+\section*[Intro]{Introduction to
+  $\R^n$}
+It costs 5\% more.%
+  Really.
+\begin{verbatim}
+% not a comment
+\section{not a section}
+\end{verbatim}
+\subsection{Setup}
+\includegraphics[width=0.5\textwidth]{figures/plot}
+$$ \{x \mid x > 0\} $$
+\end{document}
+"#);
+        let (preamble, body) = split_preamble(&content);
+
+        assert!(preamble.contains("\\newcommand{\\R}{\\mathbb{R}}"));
+        assert!(!preamble.contains("for math"));
+        assert_eq!(
+            parse_body(body),
+            vec![
+                Piece::Text(String::from("\n")),
+                Piece::Section {
+                    level: 2,
+                    title: String::from("Introduction to $\\R^n$"),
+                    source: String::from("\\section*[Intro]{Introduction to\n  $\\R^n$}"),
+                },
+                Piece::Text(String::from("\nIt costs 5\\% more.Really.\n\\begin{verbatim}\n% not a comment\n\\section{not a section}\n\\end{verbatim}\n")),
+                Piece::Section {
+                    level: 3,
+                    title: String::from("Setup"),
+                    source: String::from("\\subsection{Setup}"),
+                },
+                Piece::Text(String::from("\n")),
+                Piece::Image {
+                    path: String::from("figures/plot"),
+                    source: String::from("\\includegraphics[width=0.5\\textwidth]{figures/plot}"),
+                },
+                Piece::Text(String::from("\n$$ \\{x \\mid x > 0\\} $$\n")),
+            ],
+        );
+        assert_eq!(parse_graphics_paths("{figures/} {./images}"), vec![String::from("figures/"), String::from("./images/")]);
+    }
+}
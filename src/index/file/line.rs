@@ -1,4 +1,4 @@
-use super::{AtomicToken, BuildConfig, FileReaderImpl};
+use super::{AtomicToken, BuildConfig, FileReaderImpl, FileReaderOptions};
 use crate::error::Error;
 use ragit_fs::FileError;
 use std::io::{BufRead, BufReader};
@@ -11,11 +11,13 @@ pub struct LineReader {
 }
 
 impl FileReaderImpl for LineReader {
-    fn new(path: &str, _root_dir: &str, _config: &BuildConfig) -> Result<Self, Error> {
+    fn new(path: &str, _root_dir: &str, _config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("line", &[])?;
+
         match File::open(path) {
             Ok(f) => Ok(LineReader {
                 lines: BufReader::new(f),
-                tokens: vec![],
+                tokens: vec![AtomicToken::SourcePosition { line: 1, byte: 0 }],
                 is_exhausted: false,
             }),
             Err(e) => Err(FileError::from_std(e, path).into()),
@@ -1,29 +1,57 @@
-use super::{AtomicToken, FileReaderImpl, Image};
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions, Image};
+use super::encoding::{FileEncoding, OffsetMap, open_text_file};
+use crate::chunk::ChunkExtraInfo;
 use crate::error::Error;
-use crate::index::BuildConfig;
+use crate::index::{BuildConfig, ChunkUnit};
 use lazy_static::lazy_static;
-use ragit_fs::{FileError, exists, extension, join, parent, read_bytes};
+use ragit_fs::{exists, extension, join, parent, read_bytes};
 use ragit_pdl::ImageType;
 use regex::Regex;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Cursor};
 
 lazy_static! {
     static ref FENCE_RE: Regex = Regex::new(r"(\s*)(\`{3,}|\~{3,})([^`]*)").unwrap();
     static ref DEF_RE: Regex = Regex::new(r"\s{0,3}\[([^\[\]]{1,999})\]\s?\:\s?(.+)").unwrap();
-    static ref WEB_URL_RE: Regex = Regex::new(r"[a-zA-Z]+\:\/\/.+\/.+").unwrap();
+    pub(super) static ref WEB_URL_RE: Regex = Regex::new(r"[a-zA-Z]+\:\/\/.+\/.+").unwrap();
 }
 
+/// Each chunk knows which section it belongs to. The path of the headings
+/// (e.g. "Guide > Install > Linux") is passed to the chunk via `ChunkExtraInfo`.
+///
+/// It prefers to split a file at `#` and `##` headings. If a top-level section
+/// is long enough (`chunk_size / 2`), the next `#` or `##` heading starts a new
+/// chunk (`AtomicToken::PageBreak`). Smaller sections are grouped together.
+///
+/// Setext headings (underlined with `===` or `---`) are not recognized.
+///
+/// If the file is not UTF-8, it's converted to UTF-8 before it's read (see `PlainTextReader`).
 pub struct MarkdownReader {
     path: String,
     root_dir: String,
-    lines: BufReader<File>,
+    lines: Box<dyn BufRead + Send>,
+    encoding: Option<FileEncoding>,
+    offsets: Option<OffsetMap>,
     tokens: Vec<AtomicToken>,
     is_exhausted: bool,
     strict_mode: bool,
     curr_parse_state: ParseState,
     link_reference_definitions: HashMap<String, String>,
+
+    // `![image](attachment:name.png)` in a jupyter notebook
+    attachments: HashMap<String, AtomicToken>,
+
+    // (level, title) of the headings that the current line belongs to
+    headings: Vec<(usize, String)>,
+
+    // the current group ends with a `PageBreak`
+    curr_group_len: usize,
+    chunk_size: usize,
+    chunk_unit: ChunkUnit,
+    image_size: usize,
+
+    // (line, byte) of the next line, if the markdown is a file (see `AtomicToken::SourcePosition`)
+    curr_position: Option<(usize, usize)>,
 }
 
 impl FileReaderImpl for MarkdownReader {
@@ -31,20 +59,30 @@ impl FileReaderImpl for MarkdownReader {
         path: &str,
         root_dir: &str,
         config: &BuildConfig,
+        options: &FileReaderOptions,
     ) -> Result<Self, Error> {
-        match File::open(path) {
-            Ok(f) => Ok(MarkdownReader {
-                path: path.to_string(),
-                root_dir: root_dir.to_string(),
-                lines: BufReader::new(f),
-                tokens: vec![],
-                is_exhausted: false,
-                strict_mode: config.strict_file_reader,
-                curr_parse_state: ParseState::Paragraph,
-                link_reference_definitions: HashMap::new(),
-            }),
-            Err(e) => Err(FileError::from_std(e, path).into()),
-        }
+        options.check_keys("markdown", &["encoding"])?;
+        let file = open_text_file(path, options.get_string("encoding")?.as_deref(), config.strict_file_reader)?;
+
+        Ok(MarkdownReader {
+            path: path.to_string(),
+            root_dir: root_dir.to_string(),
+            lines: Box::new(BufReader::new(file.content)),
+            encoding: file.encoding,
+            offsets: file.offsets,
+            tokens: vec![AtomicToken::SourcePosition { line: 1, byte: file.bom_len }],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+            curr_parse_state: ParseState::Paragraph,
+            link_reference_definitions: HashMap::new(),
+            attachments: HashMap::new(),
+            headings: vec![],
+            curr_group_len: 0,
+            chunk_size: config.chunk_size,
+            chunk_unit: config.chunk_unit,
+            image_size: config.image_size,
+            curr_position: Some((1, file.bom_len)),
+        })
     }
 
     fn load_tokens(&mut self) -> Result<(), Error> {
@@ -64,21 +102,40 @@ impl FileReaderImpl for MarkdownReader {
                 break;
             }
 
+            if let Some((line_no, byte)) = &mut self.curr_position {
+                *line_no += 1;
+                *byte += line.len();
+            }
+
             match &self.curr_parse_state {
                 ParseState::Paragraph => match parse_code_fence(&line) {
                     Some(fence) => {
                         self.curr_parse_state = ParseState::CodeFence(fence);
                     },
                     None => {
+                        if let Some((level, title)) = parse_heading(&line) {
+                            buffer.push(StringOrImage::Heading { level, title });
+                        }
+
                         if let Some((label, destination)) = parse_link_reference_definition(&line) {
                             self.link_reference_definitions.insert(label, destination);
+                            self.push_position(&mut buffer);
                             continue;
                         }
 
-                        for token in parse_markdown_images(&line)? {
+                        let tokens = parse_markdown_images(&line)?;
+
+                        // An image syntax is not copied verbatim, so `FileReader` loses track of the position.
+                        let has_image = tokens.iter().any(|token| !matches!(token, StringOrImage::String(_)));
+
+                        for token in tokens {
                             buffer.push(token);
                         }
 
+                        if has_image {
+                            self.push_position(&mut buffer);
+                        }
+
                         continue;
                     },
                 },
@@ -114,21 +171,101 @@ impl FileReaderImpl for MarkdownReader {
     }
 
     fn key(&self) -> String {
-        String::from("markdown_reader_v0")
+        String::from("markdown_reader_v2")
+    }
+
+    fn encoding(&self) -> Option<FileEncoding> {
+        self.encoding.clone()
+    }
+
+    fn get_original_offset(&self, byte: usize) -> usize {
+        match &self.offsets {
+            Some(offsets) => offsets.get_original_offset(byte),
+            None => byte,
+        }
     }
 }
 
 impl MarkdownReader {
+    /// It reads markdown that is not a file (e.g. a markdown cell of a jupyter notebook).
+    /// `path` is used to resolve relative paths of images. `headings` are the headings
+    /// of the previous markdown, if the markdown is a continuation of another one.
+    pub(super) fn from_string(
+        content: String,
+        path: &str,
+        root_dir: &str,
+        config: &BuildConfig,
+        attachments: HashMap<String, AtomicToken>,
+        headings: Vec<(usize, String)>,
+    ) -> Self {
+        MarkdownReader {
+            path: path.to_string(),
+            root_dir: root_dir.to_string(),
+            lines: Box::new(Cursor::new(content.into_bytes())),
+            encoding: None,
+            offsets: None,
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+            curr_parse_state: ParseState::Paragraph,
+            link_reference_definitions: HashMap::new(),
+            attachments,
+            headings,
+
+            // A markdown that's not a file doesn't have to be split at headings.
+            curr_group_len: 0,
+            chunk_size: usize::MAX,
+            chunk_unit: config.chunk_unit,
+            image_size: config.image_size,
+
+            // A position in `content` is meaningless to `FileReader`.
+            curr_position: None,
+        }
+    }
+
+    pub(super) fn headings(&self) -> &[(usize, String)] {
+        &self.headings
+    }
+
+    fn push_position(&self, buffer: &mut Vec<StringOrImage>) {
+        if let Some((line, byte)) = self.curr_position {
+            buffer.push(StringOrImage::SourcePosition { line, byte });
+        }
+    }
+
     fn consume_buffer(&mut self, buffer: Vec<StringOrImage>) -> Result<(), Error> {
         for token in buffer.into_iter() {
             match token {
                 StringOrImage::String(s) => {
+                    self.curr_group_len += self.chunk_unit.measure(&s);
                     self.tokens.push(AtomicToken::String {
                         char_len: s.chars().count(),
                         data: s,
                     });
                 },
+                StringOrImage::Heading { level, title } => {
+                    while self.headings.last().map(|(last_level, _)| *last_level >= level).unwrap_or(false) {
+                        self.headings.pop();
+                    }
+
+                    self.headings.push((level, title));
+
+                    if level <= 2 && self.curr_group_len >= self.chunk_size / 2 {
+                        self.tokens.push(AtomicToken::PageBreak);
+                        self.curr_group_len = 0;
+                    }
+
+                    self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+                        heading: render_headings(&self.headings),
+                        ..ChunkExtraInfo::default()
+                    }));
+                },
+                StringOrImage::SourcePosition { line, byte } => {
+                    self.tokens.push(AtomicToken::SourcePosition { line, byte });
+                },
                 _ => {
+                    self.curr_group_len += self.image_size;
+
                     let (desc, mut url) = match token {
                         StringOrImage::ImageUrl { desc, url } => (desc, url),
                         StringOrImage::ImageRef { desc, r#ref } => match self.link_reference_definitions.get(&r#ref) {
@@ -149,6 +286,26 @@ impl MarkdownReader {
                         _ => unreachable!(),
                     };
 
+                    match url.strip_prefix("attachment:").map(|name| self.attachments.get(name)) {
+                        Some(Some(image)) => {
+                            self.tokens.push(image.clone());
+                            continue;
+                        },
+                        Some(None) => {
+                            if self.strict_mode {
+                                return Err(Error::FileReaderError(format!("Cannot find attachment: {url:?}")));
+                            }
+
+                            let fallback = format!("![{desc}]({url})");
+                            self.tokens.push(AtomicToken::String {
+                                char_len: fallback.chars().count(),
+                                data: fallback,
+                            });
+                            continue;
+                        },
+                        None => {},
+                    }
+
                     if WEB_URL_RE.is_match(&url) {
                         self.tokens.push(AtomicToken::WebImage { subst: format!("![{desc}]({url})"), url: url.to_string() });
                         continue;
@@ -205,6 +362,60 @@ enum StringOrImage {
     String(String),
     ImageUrl { desc: String, url: String },    // ![desc](url)
     ImageRef { desc: String, r#ref: String },  // ![ref] or ![desc][ref]
+
+    // It's followed by the heading line itself.
+    Heading { level: usize, title: String },
+
+    // where the next line starts
+    SourcePosition { line: usize, byte: usize },
+}
+
+// https://github.github.com/gfm/#atx-headings
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let line = line.trim_end();
+    let indent = line.len() - line.trim_start_matches(' ').len();
+
+    if indent > 3 {
+        return None;
+    }
+
+    let line = &line[indent..];
+    let level = line.chars().take_while(|c| *c == '#').count();
+
+    if level == 0 || level > 6 {
+        return None;
+    }
+
+    let title = &line[level..];
+
+    if !title.is_empty() && !title.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // an optional closing sequence: `## Title ##`
+    let mut title = title.trim();
+    let without_closing = title.trim_end_matches('#');
+
+    if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        title = without_closing.trim();
+    }
+
+    Some((level, title.to_string()))
+}
+
+/// e.g. "Guide > Install > Linux"
+pub(super) fn render_headings(headings: &[(usize, String)]) -> Option<String> {
+    let titles = headings.iter().filter(
+        |(_, title)| !title.is_empty()
+    ).map(
+        |(_, title)| title.to_string()
+    ).collect::<Vec<_>>();
+
+    if titles.is_empty() {
+        None
+    } else {
+        Some(titles.join(" > "))
+    }
 }
 
 // https://github.github.com/gfm/#fenced-code-blocks
@@ -465,7 +676,7 @@ fn get_matching_bracket_index(chars: &[char], mut index: usize) -> Option<usize>
     }
 }
 
-fn load_image_token(url: &str) -> Result<AtomicToken, Error> {
+pub(super) fn load_image_token(url: &str) -> Result<AtomicToken, Error> {
     let bytes = read_bytes(url)?;
     let image_type = ImageType::from_extension(&extension(&url).unwrap_or(Some(String::from("png"))).unwrap_or(String::from("png")))?;
     Ok(AtomicToken::Image(Image::new(bytes, image_type)?))
@@ -473,7 +684,7 @@ fn load_image_token(url: &str) -> Result<AtomicToken, Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::super::{AtomicToken, FileReaderImpl};
+    use super::super::{AtomicToken, FileReader, FileReaderImpl, FileReaderOptions};
     use super::MarkdownReader;
     use crate::index::BuildConfig;
     use ragit_fs::{WriteMode, remove_file, write_string};
@@ -491,7 +702,7 @@ This is a markdown file that has no image.
 ![This is a broken image
 ";
         write_string("__tmp_test.md", md1, WriteMode::AlwaysCreate).unwrap();
-        let mut md_reader = MarkdownReader::new("__tmp_test.md", ".", &config_strict).unwrap();
+        let mut md_reader = MarkdownReader::new("__tmp_test.md", ".", &config_strict, &FileReaderOptions::default()).unwrap();
 
         while md_reader.has_more_to_read() {
             md_reader.load_tokens().unwrap();
@@ -502,6 +713,8 @@ This is a markdown file that has no image.
             md1_tokens.iter().map(
                 |token| match token {
                     AtomicToken::String { data, .. } => data.to_string(),
+                    AtomicToken::ChunkExtraInfo(_)
+                    | AtomicToken::SourcePosition { .. } => String::new(),
                     _ => panic!(),
                 }
             ).collect::<Vec<_>>().concat(),
@@ -509,4 +722,108 @@ This is a markdown file that has no image.
         );
         remove_file("__tmp_test.md").unwrap();
     }
+
+    #[test]
+    fn heading_test() {
+        let config = BuildConfig { chunk_size: 100, ..BuildConfig::default() };
+        let md = format!(
+            "# Guide\n\nintro\n\n## Install\n\n{}\n\n### Linux ###\n\n```sh\n# not a heading\n```\n\n#not a heading\n\n    # not a heading\n\n## Usage\n\nshort\n\n## Config\n\nshort\n",
+            "long text ".repeat(10),
+        );
+        write_string("__tmp_heading_test.md", &md, WriteMode::AlwaysCreate).unwrap();
+        let mut md_reader = MarkdownReader::new("__tmp_heading_test.md", ".", &config, &FileReaderOptions::default()).unwrap();
+        let mut tokens = vec![];
+
+        while md_reader.has_more_to_read() {
+            md_reader.load_tokens().unwrap();
+            tokens.append(&mut md_reader.pop_all_tokens().unwrap());
+        }
+
+        remove_file("__tmp_heading_test.md").unwrap();
+        let mut data = vec![];
+        let mut headings = vec![];
+        let mut page_breaks = vec![];
+
+        for token in tokens.iter() {
+            match token {
+                AtomicToken::String { data: s, .. } => { data.push(s.to_string()); },
+                AtomicToken::ChunkExtraInfo(info) => { headings.push(info.heading.clone().unwrap()); },
+                AtomicToken::PageBreak => { page_breaks.push(headings.last().unwrap().to_string()); },
+                AtomicToken::SourcePosition { .. } => {},
+                _ => panic!(),
+            }
+        }
+
+        assert_eq!(data.concat(), md);
+        assert_eq!(
+            headings,
+            vec![
+                "Guide",
+                "Guide > Install",
+                "Guide > Install > Linux",
+                "Guide > Usage",
+                "Guide > Config",
+            ],
+        );
+
+        // "Install" section is long enough, so "Usage" starts a new chunk.
+        // "Usage" section is short, so "Config" is grouped with "Usage".
+        // `PageBreak` comes before `ChunkExtraInfo`, so `headings.last()` is the previous one.
+        assert_eq!(page_breaks, vec!["Guide > Install > Linux"]);
+    }
+
+    #[test]
+    fn source_range_test() {
+        let config = BuildConfig { chunk_size: 60, slide_len: 10, ..BuildConfig::default() };
+        let md = "# Title
+
+The first paragraph is long enough to be split into multiple chunks, hopefully 3 or 4 chunks.
+
+[logo]: ./nowhere.png
+![a broken image](./nowhere.png) and some text after the image
+
+```rust
+fn main() {}
+```
+
+
+The last paragraph is here, and the image above doesn't confuse the line numbers.
+";
+        write_string("__tmp_source_range_test.md", md, WriteMode::AlwaysCreate).unwrap();
+        let mut file_reader = FileReader::new(
+            String::from("__tmp_source_range_test.md"),
+            String::from("__tmp_source_range_test.md"),
+            ".",
+            config,
+        ).unwrap();
+        let mut chunks = vec![];
+
+        while file_reader.can_generate_chunk() {
+            let (tokens, extra_info) = file_reader.next_chunk().unwrap();
+            let data = tokens.iter().map(
+                |token| match token {
+                    AtomicToken::String { data, .. } => data.to_string(),
+                    _ => String::new(),
+                }
+            ).collect::<Vec<_>>().concat();
+            chunks.push((data, extra_info.unwrap().range.unwrap()));
+        }
+
+        remove_file("__tmp_source_range_test.md").unwrap();
+        let line_of = |byte: usize| md[..byte].matches('\n').count() + 1;
+        assert!(chunks.len() > 3);
+
+        for (data, range) in chunks.iter() {
+            // The fallback of the broken image is not in the file.
+            if data.contains("![") {
+                continue;
+            }
+
+            assert_eq!(&md[range.start_byte..range.end_byte], data);
+            assert_eq!(range.end_line, line_of(range.end_byte - 1));
+            assert_eq!(range.start_line, line_of(range.start_byte + data.len() - data.trim_start_matches('\n').len()).min(range.end_line));
+        }
+
+        assert_eq!(chunks.last().unwrap().1.end_line, md.lines().count());
+    }
 }
@@ -0,0 +1,115 @@
+use super::AtomicToken;
+use super::archive::{
+    Archive,
+    attribute,
+    child,
+    children,
+    descendants,
+    resolve_path,
+};
+use crate::error::Error;
+use roxmltree::Node;
+use std::collections::HashMap;
+
+mod docx;
+mod odt;
+mod pptx;
+mod xlsx;
+
+pub use docx::DocxReader;
+pub use odt::OdtReader;
+pub use pptx::PptxReader;
+pub use xlsx::XlsxReader;
+
+impl Archive {
+    /// `part` is a path of an xml file in the archive (e.g. `word/document.xml`).
+    /// It reads the relationships of the part (e.g. `word/_rels/document.xml.rels`),
+    /// and returns a map from relationship ids to relationships.
+    /// External relationships (e.g. hyperlinks) are not included.
+    pub fn read_relationships(&mut self, part: &str) -> Result<HashMap<String, Relationship>, Error> {
+        let (dir, file) = match part.rsplit_once('/') {
+            Some((dir, file)) => (dir, file),
+            None => ("", part),
+        };
+        let rels_path = if dir.is_empty() {
+            format!("_rels/{file}.rels")
+        } else {
+            format!("{dir}/_rels/{file}.rels")
+        };
+        let xml = match self.read_string(&rels_path)? {
+            Some(xml) => xml,
+            None => {
+                return Ok(HashMap::new());
+            },
+        };
+        let document = roxmltree::Document::parse(&xml)?;
+        let mut result = HashMap::new();
+
+        for node in document.descendants().filter(|node| node.tag_name().name() == "Relationship") {
+            if node.attribute("TargetMode") == Some("External") {
+                continue;
+            }
+
+            if let (Some(id), Some(target)) = (node.attribute("Id"), node.attribute("Target")) {
+                result.insert(
+                    id.to_string(),
+                    Relationship {
+                        r#type: node.attribute("Type").unwrap_or("").to_string(),
+                        path: resolve_path(dir, target),
+                    },
+                );
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub(super) struct Relationship {
+    pub r#type: String,
+
+    // a path in the archive
+    pub path: String,
+}
+
+// Attributes are a different story. For example, `<p:sldId id="256" r:id="rId2"/>`
+// has 2 attributes with the same local name. It looks for an attribute whose
+// namespace is `.../relationships`.
+pub(super) fn relationship_id<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes().find(
+        |attribute| attribute.name() == name && attribute.namespace().map(|ns| ns.ends_with("/relationships")).unwrap_or(false)
+    ).map(|attribute| attribute.value())
+}
+
+/// It buffers strings so that adjacent strings become a single `AtomicToken::String`.
+#[derive(Default)]
+pub(super) struct TokenBuffer {
+    tokens: Vec<AtomicToken>,
+    buffer: String,
+}
+
+impl TokenBuffer {
+    pub fn push_str(&mut self, s: &str) {
+        self.buffer.push_str(s);
+    }
+
+    pub fn push(&mut self, token: AtomicToken) {
+        self.flush();
+        self.tokens.push(token);
+    }
+
+    pub fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let s = std::mem::take(&mut self.buffer);
+            self.tokens.push(AtomicToken::String {
+                char_len: s.chars().count(),
+                data: s,
+            });
+        }
+    }
+
+    pub fn into_tokens(mut self) -> Vec<AtomicToken> {
+        self.flush();
+        self.tokens
+    }
+}
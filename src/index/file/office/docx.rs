@@ -0,0 +1,307 @@
+use super::{
+    Archive,
+    Relationship,
+    TokenBuffer,
+    attribute,
+    child,
+    children,
+    descendants,
+    relationship_id,
+};
+use super::super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::super::html::render_table;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+
+/// It reads paragraphs, headings, lists and tables of a docx file. Images in
+/// the document are converted to `AtomicToken::Image`. Headers, footers and
+/// comments are not read.
+pub struct DocxReader {
+    archive: Archive,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+}
+
+impl FileReaderImpl for DocxReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("docx", &[])?;
+
+        Ok(DocxReader {
+            archive: Archive::open(path)?,
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        let xml = self.archive.read_string_mandatory("word/document.xml")?;
+        let relationships = self.archive.read_relationships("word/document.xml")?;
+        let heading_styles = match self.archive.read_string("word/styles.xml")? {
+            Some(styles) => parse_heading_styles(&styles)?,
+            None => HashMap::new(),
+        };
+        let document = Document::parse(&xml)?;
+        let mut context = Context {
+            archive: &mut self.archive,
+            relationships,
+            heading_styles,
+            strict_mode: self.strict_mode,
+            tokens: TokenBuffer::default(),
+        };
+
+        if let Some(body) = descendants(document.root_element(), "body").next() {
+            context.render_blocks(body)?;
+        }
+
+        self.tokens = context.tokens.into_tokens();
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("docx_reader_v0")
+    }
+}
+
+struct Context<'a> {
+    archive: &'a mut Archive,
+    relationships: HashMap<String, Relationship>,
+
+    // style id -> heading level
+    heading_styles: HashMap<String, usize>,
+    strict_mode: bool,
+    tokens: TokenBuffer,
+}
+
+enum Inline {
+    Text(String),
+    Image(String),  // path in the archive
+}
+
+impl Context<'_> {
+    fn render_blocks(&mut self, node: Node) -> Result<(), Error> {
+        for block in node.children() {
+            match block.tag_name().name() {
+                "p" => { self.render_paragraph(block)?; },
+                "tbl" => { self.render_table(block)?; },
+
+                // content controls and custom xml elements wrap paragraphs
+                "sdt" => {
+                    if let Some(content) = child(block, "sdtContent") {
+                        self.render_blocks(content)?;
+                    }
+                },
+                "customXml" => { self.render_blocks(block)?; },
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_paragraph(&mut self, paragraph: Node) -> Result<(), Error> {
+        let mut inlines = vec![];
+        collect_inlines(paragraph, &self.relationships, &mut inlines);
+
+        if inlines.iter().all(|inline| matches!(inline, Inline::Text(s) if s.trim().is_empty())) {
+            return Ok(());
+        }
+
+        let prefix = match child(paragraph, "pPr") {
+            Some(properties) => {
+                let heading_level = child(properties, "pStyle").and_then(
+                    |style| attribute(style, "val")
+                ).and_then(
+                    |style| self.heading_styles.get(style).copied()
+                ).or_else(
+                    || child(properties, "outlineLvl").and_then(|level| attribute(level, "val")).and_then(parse_outline_level)
+                );
+
+                match (heading_level, child(properties, "numPr")) {
+                    (Some(level), _) => format!("{} ", "#".repeat(level)),
+                    (None, Some(numbering)) => {
+                        let level = child(numbering, "ilvl").and_then(|level| attribute(level, "val")).and_then(|level| level.parse::<usize>().ok()).unwrap_or(0);
+                        format!("{}- ", "  ".repeat(level))
+                    },
+                    (None, None) => String::new(),
+                }
+            },
+            None => String::new(),
+        };
+
+        self.tokens.push_str(&prefix);
+
+        for inline in inlines.iter() {
+            match inline {
+                Inline::Text(s) => { self.tokens.push_str(s); },
+                Inline::Image(path) => {
+                    if let Some(image) = self.archive.load_image(path, self.strict_mode)? {
+                        self.tokens.push(image);
+                    }
+                },
+            }
+        }
+
+        // list items are not separated by blank lines
+        if prefix.ends_with("- ") {
+            self.tokens.push_str("\n");
+        }
+
+        else {
+            self.tokens.push_str("\n\n");
+        }
+
+        Ok(())
+    }
+
+    fn render_table(&mut self, table: Node) -> Result<(), Error> {
+        let mut rows = vec![];
+
+        // A table cell cannot contain an image, so images are rendered after the table.
+        let mut images = vec![];
+
+        for row in children(table, "tr") {
+            let mut cells = vec![];
+
+            for cell in children(row, "tc") {
+                let mut paragraphs = vec![];
+
+                for paragraph in descendants(cell, "p") {
+                    let mut inlines = vec![];
+                    collect_inlines(paragraph, &self.relationships, &mut inlines);
+                    let mut text = String::new();
+
+                    for inline in inlines.into_iter() {
+                        match inline {
+                            Inline::Text(s) => { text.push_str(&s); },
+                            Inline::Image(path) => { images.push(path); },
+                        }
+                    }
+
+                    if !text.trim().is_empty() {
+                        paragraphs.push(text);
+                    }
+                }
+
+                cells.push(paragraphs.join(" "));
+            }
+
+            rows.push(cells);
+        }
+
+        self.tokens.push_str(&render_table(&rows));
+        self.tokens.push_str("\n\n");
+
+        for path in images.iter() {
+            if let Some(image) = self.archive.load_image(path, self.strict_mode)? {
+                self.tokens.push(image);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn collect_inlines(node: Node, relationships: &HashMap<String, Relationship>, inlines: &mut Vec<Inline>) {
+    for child in node.children() {
+        match child.tag_name().name() {
+            "t" => {
+                push_text(inlines, child.text().unwrap_or(""));
+            },
+            "tab" => { push_text(inlines, "\t"); },
+            "br" | "cr" => { push_text(inlines, "\n"); },
+            "blip" => {
+                if let Some(relationship) = relationship_id(child, "embed").and_then(|id| relationships.get(id)) {
+                    inlines.push(Inline::Image(relationship.path.to_string()));
+                }
+            },
+
+            // legacy (vml) images
+            "imagedata" => {
+                if let Some(relationship) = relationship_id(child, "id").and_then(|id| relationships.get(id)) {
+                    inlines.push(Inline::Image(relationship.path.to_string()));
+                }
+            },
+
+            // A paragraph in a text box
+            "p" => {
+                collect_inlines(child, relationships, inlines);
+                push_text(inlines, "\n");
+            },
+
+            // properties, field codes, deleted texts (track changes) and fallbacks of `mc:AlternateContent`
+            "pPr" | "rPr" | "instrText" | "delText" | "Fallback" => {},
+            _ => {
+                collect_inlines(child, relationships, inlines);
+            },
+        }
+    }
+}
+
+fn push_text(inlines: &mut Vec<Inline>, s: &str) {
+    match inlines.last_mut() {
+        Some(Inline::Text(last)) => { last.push_str(s); },
+        _ => { inlines.push(Inline::Text(s.to_string())); },
+    }
+}
+
+// `w:outlineLvl` is 0-based, and 9 means "body text".
+fn parse_outline_level(level: &str) -> Option<usize> {
+    match level.parse::<usize>() {
+        Ok(level) if level < 9 => Some((level + 1).min(6)),
+        _ => None,
+    }
+}
+
+// Style ids are localized (e.g. "1" instead of "Heading1" in some languages),
+// so it reads `styles.xml` to find which styles are headings.
+fn parse_heading_styles(xml: &str) -> Result<HashMap<String, usize>, Error> {
+    let document = Document::parse(xml)?;
+    let mut result = HashMap::new();
+
+    for style in descendants(document.root_element(), "style") {
+        if attribute(style, "type") != Some("paragraph") {
+            continue;
+        }
+
+        let Some(id) = attribute(style, "styleId") else { continue; };
+        let name = child(style, "name").and_then(|name| attribute(name, "val")).unwrap_or("").to_ascii_lowercase();
+        let level = match name.as_str() {
+            "title" => Some(1),
+            name => match name.strip_prefix("heading ") {
+                Some(level) => level.parse::<usize>().ok(),
+                None => child(style, "pPr").and_then(
+                    |properties| child(properties, "outlineLvl")
+                ).and_then(
+                    |level| attribute(level, "val")
+                ).and_then(
+                    parse_outline_level
+                ),
+            },
+        };
+
+        if let Some(level) = level {
+            result.insert(id.to_string(), level.clamp(1, 6));
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,271 @@
+use super::{Archive, TokenBuffer, attribute, children, descendants};
+use super::super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::super::html::render_table;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use roxmltree::{Document, Node};
+
+/// It's like `DocxReader`, but for OpenDocument text files.
+pub struct OdtReader {
+    archive: Archive,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+}
+
+impl FileReaderImpl for OdtReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("odt", &[])?;
+
+        Ok(OdtReader {
+            archive: Archive::open(path)?,
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        let xml = self.archive.read_string_mandatory("content.xml")?;
+        let document = Document::parse(&xml)?;
+        let mut context = Context {
+            archive: &mut self.archive,
+            strict_mode: self.strict_mode,
+            tokens: TokenBuffer::default(),
+        };
+
+        // `<office:body><office:text>...</office:text></office:body>`
+        if let Some(body) = descendants(document.root_element(), "body").next() {
+            for text in children(body, "text") {
+                context.render_blocks(text, 0)?;
+            }
+        }
+
+        self.tokens = context.tokens.into_tokens();
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("odt_reader_v0")
+    }
+}
+
+struct Context<'a> {
+    archive: &'a mut Archive,
+    strict_mode: bool,
+    tokens: TokenBuffer,
+}
+
+enum Inline {
+    Text(String),
+    Image(String),  // path in the archive
+}
+
+impl Context<'_> {
+    // `list_depth` is 0 if it's not in a list.
+    fn render_blocks(&mut self, node: Node, list_depth: usize) -> Result<(), Error> {
+        for block in node.children() {
+            match block.tag_name().name() {
+                "h" => {
+                    let level = attribute(block, "outline-level").and_then(|level| level.parse::<usize>().ok()).unwrap_or(1);
+                    self.render_paragraph(block, &format!("{} ", "#".repeat(level.clamp(1, 6))), "\n\n")?;
+                },
+                "p" => {
+                    if list_depth == 0 {
+                        self.render_paragraph(block, "", "\n\n")?;
+                    }
+
+                    else {
+                        self.render_paragraph(block, &"  ".repeat(list_depth), "\n")?;
+                    }
+                },
+                "list" => {
+                    for item in block.children().filter(|item| matches!(item.tag_name().name(), "list-item" | "list-header")) {
+                        self.render_list_item(item, list_depth + 1)?;
+                    }
+
+                    if list_depth == 0 {
+                        self.tokens.push_str("\n");
+                    }
+                },
+                "table" => { self.render_table(block)?; },
+                "section" => { self.render_blocks(block, list_depth)?; },
+
+                // e.g. `table-of-content`, `sequence-decls` and `tracked-changes`
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_list_item(&mut self, item: Node, depth: usize) -> Result<(), Error> {
+        let mut is_first = true;
+
+        for block in item.children() {
+            match block.tag_name().name() {
+                "p" | "h" if is_first => {
+                    self.render_paragraph(block, &format!("{}- ", "  ".repeat(depth - 1)), "\n")?;
+                    is_first = false;
+                },
+                "list" => {
+                    for item in block.children().filter(|item| matches!(item.tag_name().name(), "list-item" | "list-header")) {
+                        self.render_list_item(item, depth + 1)?;
+                    }
+                },
+                "p" | "h" => {
+                    self.render_paragraph(block, &"  ".repeat(depth), "\n")?;
+                },
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_paragraph(&mut self, paragraph: Node, prefix: &str, suffix: &str) -> Result<(), Error> {
+        let mut inlines = vec![];
+        collect_inlines(paragraph, &mut inlines);
+
+        if inlines.iter().all(|inline| matches!(inline, Inline::Text(s) if s.trim().is_empty())) {
+            return Ok(());
+        }
+
+        self.tokens.push_str(prefix);
+
+        for inline in inlines.iter() {
+            match inline {
+                Inline::Text(s) => { self.tokens.push_str(s); },
+                Inline::Image(path) => {
+                    if let Some(image) = self.archive.load_image(path, self.strict_mode)? {
+                        self.tokens.push(image);
+                    }
+                },
+            }
+        }
+
+        self.tokens.push_str(suffix);
+        Ok(())
+    }
+
+    fn render_table(&mut self, table: Node) -> Result<(), Error> {
+        let mut rows = vec![];
+
+        // A table cell cannot contain an image, so images are rendered after the table.
+        let mut images = vec![];
+
+        // rows can be wrapped by `table-header-rows`, `table-rows` or `table-row-group`
+        for row in descendants(table, "table-row") {
+            // a row of a nested table
+            if row.ancestors().find(|node| node.tag_name().name() == "table") != Some(table) {
+                continue;
+            }
+
+            let mut cells = vec![];
+
+            for cell in children(row, "table-cell") {
+                let mut inlines = vec![];
+                collect_inlines(cell, &mut inlines);
+                let mut text = String::new();
+
+                for inline in inlines.into_iter() {
+                    match inline {
+                        Inline::Text(s) => { text.push_str(&s); },
+                        Inline::Image(path) => { images.push(path); },
+                    }
+                }
+
+                cells.push(text);
+            }
+
+            rows.push(cells);
+        }
+
+        self.tokens.push_str(&render_table(&rows));
+        self.tokens.push_str("\n\n");
+
+        for path in images.iter() {
+            if let Some(image) = self.archive.load_image(path, self.strict_mode)? {
+                self.tokens.push(image);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn collect_inlines(node: Node, inlines: &mut Vec<Inline>) {
+    for child in node.children() {
+        if child.is_text() {
+            // Consecutive whitespaces in OpenDocument are collapsed. `<text:s/>` is used for multiple spaces.
+            let mut text = String::new();
+
+            for c in child.text().unwrap_or("").chars() {
+                if !c.is_whitespace() {
+                    text.push(c);
+                }
+
+                else if !text.ends_with(' ') {
+                    text.push(' ');
+                }
+            }
+
+            push_text(inlines, &text);
+            continue;
+        }
+
+        match child.tag_name().name() {
+            "s" => {
+                let count = attribute(child, "c").and_then(|c| c.parse::<usize>().ok()).unwrap_or(1);
+                push_text(inlines, &" ".repeat(count));
+            },
+            "tab" => { push_text(inlines, "\t"); },
+            "line-break" => { push_text(inlines, "\n"); },
+            "image" => {
+                if let Some(href) = attribute(child, "href") {
+                    // e.g. `Pictures/1000000000000.png`
+                    if !href.contains("://") {
+                        inlines.push(Inline::Image(href.trim_start_matches("./").to_string()));
+                    }
+                }
+            },
+
+            // A paragraph in a table cell or a text box
+            "p" | "h" => {
+                if !inlines.is_empty() {
+                    push_text(inlines, " ");
+                }
+
+                collect_inlines(child, inlines);
+            },
+
+            // footnotes, comments and the fallback images of objects
+            "note" | "annotation" | "annotation-end" => {},
+            _ => {
+                collect_inlines(child, inlines);
+            },
+        }
+    }
+}
+
+fn push_text(inlines: &mut Vec<Inline>, s: &str) {
+    match inlines.last_mut() {
+        Some(Inline::Text(last)) => { last.push_str(s); },
+        _ => { inlines.push(Inline::Text(s.to_string())); },
+    }
+}
@@ -0,0 +1,223 @@
+use super::{
+    Archive,
+    Relationship,
+    TokenBuffer,
+    attribute,
+    child,
+    children,
+    descendants,
+    relationship_id,
+};
+use super::super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::super::html::render_table;
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+
+/// It reads texts, tables, images and speaker notes of each slide. A chunk never
+/// contains more than 1 slide, and the slide number is recorded in `ChunkSource`,
+/// like page numbers of `PdfReader`.
+pub struct PptxReader {
+    archive: Archive,
+    slides: Vec<String>,  // paths in the archive
+    cursor: usize,
+    tokens: Vec<AtomicToken>,
+    strict_mode: bool,
+}
+
+impl FileReaderImpl for PptxReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("pptx", &[])?;
+        let mut archive = Archive::open(path)?;
+        let xml = archive.read_string_mandatory("ppt/presentation.xml")?;
+        let relationships = archive.read_relationships("ppt/presentation.xml")?;
+        let document = Document::parse(&xml)?;
+        let mut slides = vec![];
+
+        // `<p:sldIdLst>` has the order of the slides.
+        for slide in descendants(document.root_element(), "sldId") {
+            if let Some(relationship) = relationship_id(slide, "id").and_then(|id| relationships.get(id)) {
+                slides.push(relationship.path.to_string());
+            }
+        }
+
+        Ok(PptxReader {
+            archive,
+            slides,
+            cursor: 0,
+            tokens: vec![],
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    // It reads a slide at a time.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        let Some(slide) = self.slides.get(self.cursor).map(|slide| slide.to_string()) else { return Ok(()); };
+        self.cursor += 1;
+
+        let xml = self.archive.read_string_mandatory(&slide)?;
+        let relationships = self.archive.read_relationships(&slide)?;
+        let document = Document::parse(&xml)?;
+        let mut context = Context {
+            archive: &mut self.archive,
+            relationships: &relationships,
+            strict_mode: self.strict_mode,
+            tokens: TokenBuffer::default(),
+        };
+
+        if let Some(shapes) = descendants(document.root_element(), "spTree").next() {
+            context.render_shapes(shapes)?;
+        }
+
+        let notes = match relationships.values().find(|relationship| relationship.r#type.ends_with("/notesSlide")) {
+            Some(relationship) => context.archive.read_string(&relationship.path)?,
+            None => None,
+        };
+
+        if let Some(xml) = notes {
+            let document = Document::parse(&xml)?;
+            let mut lines = vec![];
+
+            // The notes slide also has a placeholder for the slide image and the slide number.
+            for shape in descendants(document.root_element(), "sp") {
+                if get_placeholder_type(shape) == Some("body") {
+                    lines.append(&mut get_paragraphs(shape));
+                }
+            }
+
+            if !lines.is_empty() {
+                context.tokens.push_str(&format!("Notes:\n{}\n\n", lines.join("\n")));
+            }
+        }
+
+        let tokens = context.tokens.into_tokens();
+
+        if !tokens.is_empty() {
+            // 1-base
+            self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { page_no: Some(self.cursor), ..ChunkExtraInfo::default() }));
+            self.tokens.extend(tokens);
+            self.tokens.push(AtomicToken::PageBreak);
+        }
+
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        self.cursor < self.slides.len()
+    }
+
+    fn key(&self) -> String {
+        String::from("pptx_reader_v1")
+    }
+}
+
+struct Context<'a> {
+    archive: &'a mut Archive,
+    relationships: &'a HashMap<String, Relationship>,
+    strict_mode: bool,
+    tokens: TokenBuffer,
+}
+
+impl Context<'_> {
+    fn render_shapes(&mut self, node: Node) -> Result<(), Error> {
+        for shape in node.children() {
+            match shape.tag_name().name() {
+                "sp" => {
+                    let paragraphs = get_paragraphs(shape);
+
+                    if paragraphs.is_empty() {
+                        continue;
+                    }
+
+                    match get_placeholder_type(shape) {
+                        Some("title" | "ctrTitle") => {
+                            self.tokens.push_str(&format!("# {}\n\n", paragraphs.join(" ")));
+                        },
+                        _ => {
+                            self.tokens.push_str(&format!("{}\n\n", paragraphs.join("\n")));
+                        },
+                    }
+                },
+                "grpSp" => { self.render_shapes(shape)?; },
+                "graphicFrame" => {
+                    if let Some(table) = descendants(shape, "tbl").next() {
+                        let mut rows = vec![];
+
+                        for row in children(table, "tr") {
+                            rows.push(children(row, "tc").map(|cell| get_paragraphs(cell).join(" ")).collect::<Vec<_>>());
+                        }
+
+                        self.tokens.push_str(&format!("{}\n\n", render_table(&rows)));
+                    }
+                },
+                "pic" => {
+                    let path = descendants(shape, "blip").next().and_then(
+                        |blip| relationship_id(blip, "embed")
+                    ).and_then(
+                        |id| self.relationships.get(id)
+                    ).map(
+                        |relationship| relationship.path.to_string()
+                    );
+                    let image = match path {
+                        Some(path) => self.archive.load_image(&path, self.strict_mode)?,
+                        None => None,
+                    };
+
+                    if let Some(image) = image {
+                        self.tokens.push(image);
+                        self.tokens.push_str("\n\n");
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// e.g. `<p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>`
+fn get_placeholder_type<'a>(shape: Node<'a, '_>) -> Option<&'a str> {
+    let placeholder = child(shape, "nvSpPr").and_then(|properties| child(properties, "nvPr")).and_then(|properties| child(properties, "ph"))?;
+
+    // A placeholder without a type is a body.
+    Some(attribute(placeholder, "type").unwrap_or("body"))
+}
+
+// It returns non-empty paragraphs (`<a:p>`) in the node. Nested list items are indented.
+fn get_paragraphs(node: Node) -> Vec<String> {
+    let mut result = vec![];
+
+    for paragraph in descendants(node, "p") {
+        let mut text = String::new();
+
+        for run in paragraph.children() {
+            match run.tag_name().name() {
+                "r" | "fld" => {
+                    if let Some(t) = child(run, "t") {
+                        text.push_str(t.text().unwrap_or(""));
+                    }
+                },
+                "br" => { text.push('\n'); },
+                _ => {},
+            }
+        }
+
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let level = child(paragraph, "pPr").and_then(|properties| attribute(properties, "lvl")).and_then(|level| level.parse::<usize>().ok()).unwrap_or(0);
+        result.push(format!("{}{text}", "  ".repeat(level)));
+    }
+
+    result
+}
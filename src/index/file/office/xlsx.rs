@@ -0,0 +1,209 @@
+use super::{Archive, attribute, child, children, descendants, relationship_id};
+use super::super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use crate::chunk::ChunkExtraInfo;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+
+/// It converts each sheet to jsonl, like `CsvReader`. The first non-empty row
+/// of a sheet is the header. A chunk never contains more than 1 sheet, and the
+/// name of the sheet is recorded in `ChunkSource`.
+pub struct XlsxReader {
+    archive: Archive,
+    sheets: Vec<(String, String)>,  // (name, path in the archive)
+    shared_strings: Vec<String>,
+    cursor: usize,
+    tokens: Vec<AtomicToken>,
+}
+
+impl FileReaderImpl for XlsxReader {
+    fn new(path: &str, _root_dir: &str, _config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("xlsx", &[])?;
+        let mut archive = Archive::open(path)?;
+        let xml = archive.read_string_mandatory("xl/workbook.xml")?;
+        let relationships = archive.read_relationships("xl/workbook.xml")?;
+        let document = Document::parse(&xml)?;
+        let mut sheets = vec![];
+
+        for sheet in descendants(document.root_element(), "sheet") {
+            if let (Some(name), Some(relationship)) = (
+                attribute(sheet, "name"),
+                relationship_id(sheet, "id").and_then(|id| relationships.get(id)),
+            ) {
+                sheets.push((name.to_string(), relationship.path.to_string()));
+            }
+        }
+
+        // Most strings in cells are stored in `sharedStrings.xml`, and cells only have indexes of the strings.
+        let shared_strings = match archive.read_string("xl/sharedStrings.xml")? {
+            Some(xml) => {
+                let document = Document::parse(&xml)?;
+                children(document.root_element(), "si").map(get_text).collect()
+            },
+            None => vec![],
+        };
+
+        Ok(XlsxReader {
+            archive,
+            sheets,
+            shared_strings,
+            cursor: 0,
+            tokens: vec![],
+        })
+    }
+
+    // It reads a sheet at a time.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        let Some((name, path)) = self.sheets.get(self.cursor).map(|(name, path)| (name.to_string(), path.to_string())) else { return Ok(()); };
+        self.cursor += 1;
+
+        let xml = self.archive.read_string_mandatory(&path)?;
+        let document = Document::parse(&xml)?;
+        let mut headers: Option<BTreeMap<usize, String>> = None;
+        let mut rows = vec![];
+
+        for row in descendants(document.root_element(), "row") {
+            let mut cells = BTreeMap::new();
+
+            for (index, cell) in children(row, "c").enumerate() {
+                // `r` is optional
+                let column = attribute(cell, "r").and_then(parse_column).unwrap_or(index);
+                let value = self.get_cell_value(cell);
+
+                if !value.is_empty() {
+                    cells.insert(column, value);
+                }
+            }
+
+            if cells.is_empty() {
+                continue;
+            }
+
+            match &headers {
+                None => {
+                    headers = Some(cells);
+                },
+                Some(headers) => {
+                    let mut fields = Vec::with_capacity(cells.len());
+
+                    for (column, value) in cells.iter() {
+                        let header = match headers.get(column) {
+                            Some(header) => header.to_string(),
+                            None => render_column(*column),
+                        };
+
+                        // The same format as `CsvReader`.
+                        let value = match value.parse::<i64>() {
+                            Ok(n) => n.to_string(),
+                            _ => match value.parse::<f64>() {
+                                Ok(f) => f.to_string(),
+                                _ => format!("{value:?}"),
+                            },
+                        };
+
+                        fields.push(format!("{header:?}: {value}"));
+                    }
+
+                    let row = format!("{}{}{}\n", "{", fields.join(", "), "}");
+                    rows.push(AtomicToken::String {
+                        char_len: row.chars().count(),
+                        data: row,
+                    });
+                },
+            }
+        }
+
+        // A sheet with only a header row.
+        if let (true, Some(headers)) = (rows.is_empty(), &headers) {
+            let row = format!("{}\n", headers.values().map(|header| format!("{header:?}")).collect::<Vec<_>>().join(", "));
+            rows.push(AtomicToken::String {
+                char_len: row.chars().count(),
+                data: row,
+            });
+        }
+
+        if !rows.is_empty() {
+            self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { item: Some(format!("sheet `{name}`")), ..ChunkExtraInfo::default() }));
+            self.tokens.extend(rows);
+            self.tokens.push(AtomicToken::PageBreak);
+        }
+
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        self.cursor < self.sheets.len()
+    }
+
+    fn key(&self) -> String {
+        String::from("xlsx_reader_v1")
+    }
+}
+
+impl XlsxReader {
+    // Dates are stored as numbers, and it doesn't format them.
+    fn get_cell_value(&self, cell: Node) -> String {
+        let value = child(cell, "v").and_then(|v| v.text()).unwrap_or("");
+
+        match attribute(cell, "t") {
+            Some("s") => value.trim().parse::<usize>().ok().and_then(
+                |index| self.shared_strings.get(index)
+            ).map(
+                |s| s.to_string()
+            ).unwrap_or_default(),
+            Some("inlineStr") => child(cell, "is").map(get_text).unwrap_or_default(),
+            Some("b") => if value.trim() == "1" { String::from("TRUE") } else { String::from("FALSE") },
+
+            // numbers, formula results and errors (e.g. `#DIV/0!`)
+            _ => value.to_string(),
+        }
+    }
+}
+
+// A rich text has multiple `<t>`s. `<rPh>` is a phonetic hint and has to be ignored.
+fn get_text(node: Node) -> String {
+    descendants(node, "t").filter(
+        |t| !t.ancestors().any(|ancestor| ancestor.tag_name().name() == "rPh")
+    ).map(
+        |t| t.text().unwrap_or("")
+    ).collect::<Vec<_>>().concat()
+}
+
+// "C7" -> 2
+fn parse_column(reference: &str) -> Option<usize> {
+    let mut result = 0;
+    let mut has_letter = false;
+
+    for c in reference.chars() {
+        if c.is_ascii_alphabetic() {
+            result = result * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1);
+            has_letter = true;
+        }
+
+        else {
+            break;
+        }
+    }
+
+    if has_letter { Some(result - 1) } else { None }
+}
+
+// 2 -> "C"
+fn render_column(mut column: usize) -> String {
+    let mut result = vec![];
+    column += 1;
+
+    while column > 0 {
+        result.push((b'A' + ((column - 1) % 26) as u8) as char);
+        column = (column - 1) / 26;
+    }
+
+    result.iter().rev().collect()
+}
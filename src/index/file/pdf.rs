@@ -1,10 +1,16 @@
-use super::{AtomicToken, FileReaderImpl, Image};
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions, Image};
 use crate::chunk::ChunkExtraInfo;
 use crate::error::Error;
-use crate::index::BuildConfig;
-use mupdf::{Colorspace, Document, ImageFormat, Matrix};
+use crate::index::{BuildConfig, PdfMode};
+use mupdf::{Colorspace, Document, ImageFormat, Matrix, Page, TextBlockType, TextPageFlags};
 use ragit_pdl::ImageType;
 
+// If a page has less characters than this, it's likely to be a scanned page.
+const MIN_TEXT_LEN: usize = 100;
+
+// If images in a page cover more than 10% of the page, the page has a figure.
+const MIN_FIGURE_RATIO: f32 = 0.1;
+
 // `PdfReader` has to be `Send`, but `mupdf::Document` is `!Send`.
 // So it takes a bit inefficient route. It opens `Document`, converts
 // 64 pages, and drops the `Document`. I chose the number 64 because
@@ -12,82 +18,162 @@ use ragit_pdl::ImageType;
 //    bottleneck.
 // 2. If it opens the `Document` only once and loads all the pages, it'd use
 //    too much memory if the pdf file is very large.
+//
+// There are 3 modes (`BuildConfig::pdf_mode`).
+// 1. `image`: each page is converted to an image, and the vision model reads the image.
+// 2. `text`: it extracts the text layer of each page. It doesn't need a vision model,
+//    but it cannot read scanned pages and figures.
+// 3. `hybrid`: it extracts the text layer of each page. If a page has (almost) no text
+//    or has a figure, the page is also converted to an image.
 pub struct PdfReader {
     path: String,
-    images: Vec<(AtomicToken, usize /* page_no */)>,
+    mode: PdfMode,
+    pages: Vec<(Vec<AtomicToken>, usize /* page_no */)>,
     page_count: usize,
     cursor: usize,
 }
 
 impl FileReaderImpl for PdfReader {
-    fn new(path: &str, _root_dir: &str, _config: &BuildConfig) -> Result<Self, Error> {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("pdf", &[])?;
         let document = Document::open(path)?;
         let page_count = document.page_count()?.max(0) as usize;
         let mut result = PdfReader {
-            images: vec![],
             path: path.to_string(),
+            mode: config.pdf_mode,
+            pages: vec![],
             page_count,
             cursor: 0,
         };
 
-        if result.cursor < result.page_count {
-            for _ in 0..64 {
-                result.images.push((convert_page(&document, result.cursor as i32)?, result.cursor + 1));
-                result.cursor += 1;
-
-                if result.cursor >= result.page_count {
-                    break;
-                }
-            }
-        }
-
+        result.load_pages(&document)?;
         Ok(result)
     }
 
     fn load_tokens(&mut self) -> Result<(), Error> {
         if self.cursor < self.page_count {
             let document = Document::open(&self.path)?;
-
-            for _ in 0..64 {
-                self.images.push((convert_page(&document, self.cursor as i32)?, self.cursor + 1));
-                self.cursor += 1;
-
-                if self.cursor >= self.page_count {
-                    break;
-                }
-            }
+            self.load_pages(&document)?;
         }
 
         Ok(())
     }
 
     fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
-        let mut result = Vec::with_capacity(self.images.len());
+        let mut result = vec![];
 
-        for (image, page_no) in self.images.iter() {
-            result.push(image.clone());
-            result.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { page_no: Some(*page_no) }));
+        for (tokens, page_no) in self.pages.iter() {
+            // A page may be split into multiple chunks in text mode, so `ChunkExtraInfo`
+            // has to come before the page.
+            result.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo { page_no: Some(*page_no), ..ChunkExtraInfo::default() }));
+            result.extend(tokens.iter().cloned());
             result.push(AtomicToken::PageBreak);
         }
 
-        self.images = vec![];
+        self.pages = vec![];
         Ok(result)
     }
 
     fn has_more_to_read(&self) -> bool {
-        self.cursor < self.page_count || !self.images.is_empty()
+        self.cursor < self.page_count || !self.pages.is_empty()
     }
 
     fn key(&self) -> String {
-        String::from("pdf_reader_v1")
+        match self.mode {
+            PdfMode::Image => String::from("pdf_reader_v2"),
+            PdfMode::Text => String::from("pdf_reader_v2_text"),
+            PdfMode::Hybrid => String::from("pdf_reader_v2_hybrid"),
+        }
+    }
+}
+
+impl PdfReader {
+    fn load_pages(&mut self, document: &Document) -> Result<(), Error> {
+        for _ in 0..64 {
+            if self.cursor >= self.page_count {
+                break;
+            }
+
+            let tokens = convert_page(document, self.cursor as i32, self.mode)?;
+
+            // an empty page in text mode
+            if !tokens.is_empty() {
+                self.pages.push((tokens, self.cursor + 1));
+            }
+
+            self.cursor += 1;
+        }
+
+        Ok(())
     }
 }
 
 fn convert_page(
     document: &Document,
     page: i32,
-) -> Result<AtomicToken, Error> {
+    mode: PdfMode,
+) -> Result<Vec<AtomicToken>, Error> {
     let page = document.load_page(page)?;
+
+    match mode {
+        PdfMode::Image => Ok(vec![render_page(&page)?]),
+        PdfMode::Text | PdfMode::Hybrid => {
+            let (text, has_figure) = extract_text(&page)?;
+            let mut result = vec![];
+
+            if !text.is_empty() {
+                result.push(AtomicToken::String {
+                    char_len: text.chars().count(),
+                    data: text.clone(),
+                });
+            }
+
+            if mode == PdfMode::Hybrid && (has_figure || text.chars().filter(|c| !c.is_whitespace()).count() < MIN_TEXT_LEN) {
+                result.push(render_page(&page)?);
+            }
+
+            Ok(result)
+        },
+    }
+}
+
+// It returns the text layer of the page and whether the page has a figure.
+// It only detects raster images. Figures that are drawn with vector graphics
+// (e.g. most charts) are not detected.
+fn extract_text(page: &Page) -> Result<(String, bool), Error> {
+    let bounds = page.bounds()?;
+    let page_area = ((bounds.x1 - bounds.x0) * (bounds.y1 - bounds.y0)).max(0.1);
+    let text_page = page.to_text_page(TextPageFlags::PRESERVE_IMAGES)?;
+    let mut image_area = 0.0;
+
+    for block in text_page.blocks() {
+        if let TextBlockType::Image = block.r#type() {
+            let bounds = block.bounds();
+            image_area += (bounds.x1 - bounds.x0).max(0.0) * (bounds.y1 - bounds.y0).max(0.0);
+        }
+    }
+
+    let mut text = String::new();
+
+    // mupdf puts a newline after each line and an empty line after each block.
+    for line in text_page.to_text()?.lines() {
+        let line = line.trim_end();
+
+        if line.is_empty() && (text.is_empty() || text.ends_with("\n\n")) {
+            continue;
+        }
+
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    let text = text.trim_end().to_string();
+    let text = if text.is_empty() { text } else { format!("{text}\n\n") };
+
+    Ok((text, image_area / page_area > MIN_FIGURE_RATIO))
+}
+
+fn render_page(page: &Page) -> Result<AtomicToken, Error> {
     let bounds = page.bounds()?;
     let width = bounds.x1 - bounds.x0;
     let height = bounds.y1 - bounds.y0;
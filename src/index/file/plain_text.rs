@@ -1,28 +1,33 @@
-use super::{AtomicToken, FileReaderImpl};
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::encoding::{FileEncoding, OffsetMap, open_text_file};
 use crate::error::Error;
 use crate::index::BuildConfig;
-use ragit_fs::FileError;
-use std::fs::File;
-use std::io::{Bytes, Read};
+use std::io::{BufReader, Bytes, Read};
 
+/// If the file is not UTF-8, it's converted to UTF-8 before it's read. You can
+/// set the encoding with `encoding` option, otherwise ragit guesses the encoding.
 pub struct PlainTextReader {
-    bytes: Bytes<File>,
+    bytes: Bytes<BufReader<Box<dyn Read + Send>>>,
+    encoding: Option<FileEncoding>,
+    offsets: Option<OffsetMap>,
     tokens: Vec<AtomicToken>,
     is_exhausted: bool,
     strict_mode: bool,
 }
 
 impl FileReaderImpl for PlainTextReader {
-    fn new(path: &str, _root_dir: &str, config: &BuildConfig) -> Result<Self, Error> {
-        match File::open(path) {
-            Ok(f) => Ok(PlainTextReader {
-                bytes: f.bytes(),
-                tokens: vec![],
-                is_exhausted: false,
-                strict_mode: config.strict_file_reader,
-            }),
-            Err(e) => Err(FileError::from_std(e, path).into()),
-        }
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("plain_text", &["encoding"])?;
+        let file = open_text_file(path, options.get_string("encoding")?.as_deref(), config.strict_file_reader)?;
+
+        Ok(PlainTextReader {
+            bytes: BufReader::new(file.content).bytes(),
+            encoding: file.encoding,
+            offsets: file.offsets,
+            tokens: vec![AtomicToken::SourcePosition { line: 1, byte: file.bom_len }],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+        })
     }
 
     fn load_tokens(&mut self) -> Result<(), Error> {
@@ -98,4 +103,15 @@ impl FileReaderImpl for PlainTextReader {
     fn key(&self) -> String {
         String::from("plain_text_reader_v0")
     }
+
+    fn encoding(&self) -> Option<FileEncoding> {
+        self.encoding.clone()
+    }
+
+    fn get_original_offset(&self, byte: usize) -> usize {
+        match &self.offsets {
+            Some(offsets) => offsets.get_original_offset(byte),
+            None => byte,
+        }
+    }
 }
@@ -0,0 +1,532 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::encoding::{FileEncoding, open_text_file};
+use super::ipynb::push_lines;
+use crate::error::Error;
+use crate::index::BuildConfig;
+use ragit_fs::extension;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+use std::io::Read;
+
+// A leaf group is split into multiple rows if it's longer than this.
+const MAX_ROW_LEN: usize = 2000;
+
+// The parsers have their own recursion limits, but toml's dotted keys
+// (`a.b.c.d = 1`) are not limited. It makes sure that `render_rows` doesn't
+// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// It reads json, yaml and toml files. It walks the document tree and converts
+/// each leaf group (scalar values that share the same parent) to a jsonl-style row,
+/// like `CsvReader` does. Each value in a row has its full key path, so a chunk of a
+/// large config file (or an OpenAPI spec) knows where it's from, even if it's split.
+///
+/// ```text
+/// {"spec.template.containers[0].name": "web", "spec.template.containers[0].image": "nginx"}
+/// ```
+///
+/// The format is inferred from the extension. You can set it with `format` option
+/// (`json`, `yaml` or `toml`). If the file is broken (e.g. json with comments), it's
+/// read like a plain text file, unless it's strict mode. Yaml needs "yaml" feature.
+pub struct StructuredReader {
+    path: String,
+    content: String,
+    format: Format,
+    encoding: Option<FileEncoding>,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+    strict_mode: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// A document tree. The values of json, yaml and toml are converted to this, so
+/// that it can render them the same way.
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    /// a scalar value rendered in json (e.g. `"nginx"`, `80`, `true` or `null`)
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn string(s: &str) -> Self {
+        Node::Scalar(serde_json::to_string(s).unwrap())
+    }
+
+    fn null() -> Self {
+        Node::Scalar(String::from("null"))
+    }
+
+    // scalars, empty containers and lists of scalars
+    fn is_leaf(&self) -> bool {
+        match self {
+            Node::Scalar(_) => true,
+            Node::List(items) => items.iter().all(|item| matches!(item, Node::Scalar(_))),
+            Node::Map(entries) => entries.is_empty(),
+        }
+    }
+
+    // It only renders leaves.
+    fn render_leaf(&self) -> String {
+        match self {
+            Node::Scalar(s) => s.to_string(),
+            Node::List(items) => format!("[{}]", items.iter().map(|item| item.render_leaf()).collect::<Vec<_>>().join(", ")),
+            Node::Map(_) => String::from("{}"),
+        }
+    }
+}
+
+impl FileReaderImpl for StructuredReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("structured", &["format", "encoding"])?;
+        let format = match options.get_string("format")? {
+            Some(format) => match format.to_ascii_lowercase().as_str() {
+                "json" => Format::Json,
+                "yaml" | "yml" => Format::Yaml,
+                "toml" => Format::Toml,
+                _ => {
+                    return Err(Error::FileReaderError(format!("`format` of `structured` must be `json`, `yaml` or `toml`, but got {format:?}.")));
+                },
+            },
+            None => match extension(path)?.unwrap_or(String::new()).to_ascii_lowercase().as_str() {
+                "yaml" | "yml" => Format::Yaml,
+                "toml" => Format::Toml,
+                _ => Format::Json,
+            },
+        };
+
+        #[cfg(not(feature = "yaml"))]
+        if format == Format::Yaml {
+            return Err(Error::FeatureNotEnabled { feature: String::from("yaml"), action: format!("read `{path}`") });
+        }
+
+        let mut file = open_text_file(path, options.get_string("encoding")?.as_deref(), config.strict_file_reader)?;
+        let mut content = String::new();
+        file.content.read_to_string(&mut content)?;
+
+        Ok(StructuredReader {
+            path: path.to_string(),
+            content,
+            format,
+            encoding: file.encoding,
+            tokens: vec![],
+            is_exhausted: false,
+            strict_mode: config.strict_file_reader,
+        })
+    }
+
+    // It has to parse the entire file before it walks the tree.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        let tree = match self.format {
+            Format::Json => parse_json(&self.content),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => parse_yaml(&self.content),
+            #[cfg(not(feature = "yaml"))]
+            Format::Yaml => unreachable!(),
+            Format::Toml => parse_toml(&self.content),
+        };
+
+        match tree {
+            Ok(tree) => {
+                let mut rows = vec![];
+                render_rows(&tree, "", &mut rows);
+
+                for row in rows.into_iter() {
+                    self.tokens.push(AtomicToken::String {
+                        char_len: row.chars().count(),
+                        data: row,
+                    });
+                }
+            },
+            Err(e) => if self.strict_mode {
+                return Err(Error::FileReaderError(format!("failed to parse `{}` as {:?}: {e}", self.path, self.format)));
+            } else {
+                push_lines(&mut self.tokens, &self.content);
+            },
+        }
+
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("structured_reader_v0")
+    }
+
+    fn encoding(&self) -> Option<FileEncoding> {
+        self.encoding.clone()
+    }
+}
+
+// `spec.template`, `paths["/pets/{id}"]` or `containers[0]`
+fn join_key(path: &str, key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    } else {
+        format!("{path}[{}]", serde_json::to_string(key).unwrap())
+    }
+}
+
+fn render_rows(node: &Node, path: &str, rows: &mut Vec<String>) {
+    let children = match node {
+        Node::Map(entries) if !entries.is_empty() => entries.iter().map(|(key, value)| (join_key(path, key), value)).collect::<Vec<_>>(),
+        Node::List(items) if !node.is_leaf() => items.iter().enumerate().map(|(i, item)| (format!("{path}[{i}]"), item)).collect(),
+
+        // The root is a leaf.
+        _ => {
+            rows.push(format!("{}\n", node.render_leaf()));
+            return;
+        },
+    };
+    let mut group = vec![];
+    let mut group_len = 0;
+
+    for (child_path, child) in children.iter() {
+        if child.is_leaf() {
+            let cell = format!("{}: {}", serde_json::to_string(child_path).unwrap(), child.render_leaf());
+            group_len += cell.chars().count();
+            group.push(cell);
+
+            if group_len > MAX_ROW_LEN {
+                flush_group(&mut group, rows);
+                group_len = 0;
+            }
+        }
+
+        else {
+            flush_group(&mut group, rows);
+            group_len = 0;
+            render_rows(child, child_path, rows);
+        }
+    }
+
+    flush_group(&mut group, rows);
+}
+
+fn flush_group(group: &mut Vec<String>, rows: &mut Vec<String>) {
+    if !group.is_empty() {
+        rows.push(format!("{}{}{}\n", "{", group.join(", "), "}"));
+        group.clear();
+    }
+}
+
+// serde_json has its own recursion limit, so it doesn't have to check the depth.
+fn parse_json(s: &str) -> Result<Node, String> {
+    serde_json::from_str::<Node>(s).map_err(|e| e.to_string())
+}
+
+// A yaml file may have multiple documents. If so, each document is an element of the root list.
+#[cfg(feature = "yaml")]
+fn parse_yaml(s: &str) -> Result<Node, String> {
+    let mut documents = vec![];
+
+    for document in serde_yaml::Deserializer::from_str(s) {
+        let value = serde_yaml::Value::deserialize(document).map_err(|e| e.to_string())?;
+        documents.push(yaml_to_node(value, 0)?);
+    }
+
+    match documents.len() {
+        0 => Ok(Node::null()),
+        1 => Ok(documents.pop().unwrap()),
+        _ => Ok(Node::List(documents)),
+    }
+}
+
+fn parse_toml(s: &str) -> Result<Node, String> {
+    let table = toml::from_str::<toml::Table>(s).map_err(|e| e.to_string())?;
+    toml_to_node(toml::Value::Table(table), 0)
+}
+
+fn check_depth(depth: usize) -> Result<(), String> {
+    if depth > MAX_DEPTH {
+        Err(format!("the document is nested deeper than {MAX_DEPTH} levels"))
+    } else {
+        Ok(())
+    }
+}
+
+// `serde_json::Map` sorts the keys, unless serde_json's "preserve_order" feature is
+// enabled. But the feature would change every json map in ragit, so it deserializes
+// json to `Node` directly.
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+// With "arbitrary_precision" feature, serde_json gives a number as a map with this key.
+const JSON_NUMBER_KEY: &str = "$serde_json::private::Number";
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a json value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Node, E> {
+        Ok(Node::Scalar(b.to_string()))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Node, E> {
+        Ok(Node::Scalar(n.to_string()))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Node, E> {
+        Ok(Node::Scalar(n.to_string()))
+    }
+
+    fn visit_f64<E>(self, f: f64) -> Result<Node, E> {
+        Ok(render_float(f))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Node, E> {
+        Ok(Node::string(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<Node, E> {
+        Ok(Node::null())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = vec![];
+
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+
+        Ok(Node::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = vec![];
+
+        while let Some(key) = map.next_key::<String>()? {
+            if entries.is_empty() && key == JSON_NUMBER_KEY {
+                return Ok(Node::Scalar(map.next_value::<String>()?));
+            }
+
+            entries.push((key, map.next_value()?));
+        }
+
+        Ok(Node::Map(entries))
+    }
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_to_node(value: serde_yaml::Value, depth: usize) -> Result<Node, String> {
+    check_depth(depth)?;
+
+    match value {
+        serde_yaml::Value::Null => Ok(Node::null()),
+        serde_yaml::Value::Bool(b) => Ok(Node::Scalar(b.to_string())),
+        serde_yaml::Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => Ok(render_float(f)),
+            _ => Ok(Node::Scalar(n.to_string())),
+        },
+        serde_yaml::Value::String(s) => Ok(Node::string(&s)),
+        serde_yaml::Value::Sequence(items) => Ok(Node::List(items.into_iter().map(|item| yaml_to_node(item, depth + 1)).collect::<Result<Vec<_>, _>>()?)),
+        serde_yaml::Value::Mapping(entries) => {
+            let mut result = Vec::with_capacity(entries.len());
+
+            for (key, value) in entries.into_iter() {
+                let key = match key {
+                    serde_yaml::Value::String(s) => s,
+                    serde_yaml::Value::Null => String::from("null"),
+                    serde_yaml::Value::Bool(b) => b.to_string(),
+                    serde_yaml::Value::Number(n) => n.to_string(),
+
+                    // e.g. `? [a, b]\n: c`
+                    key => serde_yaml::to_string(&key).map_err(|e| e.to_string())?.trim().to_string(),
+                };
+
+                result.push((key, yaml_to_node(value, depth + 1)?));
+            }
+
+            Ok(Node::Map(result))
+        },
+
+        // Tags are ignored.
+        serde_yaml::Value::Tagged(tagged) => yaml_to_node(tagged.value, depth),
+    }
+}
+
+fn toml_to_node(value: toml::Value, depth: usize) -> Result<Node, String> {
+    check_depth(depth)?;
+
+    match value {
+        toml::Value::String(s) => Ok(Node::string(&s)),
+        toml::Value::Integer(n) => Ok(Node::Scalar(n.to_string())),
+        toml::Value::Float(f) => Ok(render_float(f)),
+        toml::Value::Boolean(b) => Ok(Node::Scalar(b.to_string())),
+        toml::Value::Datetime(d) => Ok(Node::string(&d.to_string())),
+        toml::Value::Array(items) => Ok(Node::List(items.into_iter().map(|item| toml_to_node(item, depth + 1)).collect::<Result<Vec<_>, _>>()?)),
+        toml::Value::Table(entries) => Ok(Node::Map(entries.into_iter().map(|(key, value)| toml_to_node(value, depth + 1).map(|value| (key, value))).collect::<Result<Vec<_>, _>>()?)),
+    }
+}
+
+// `inf` and `nan` are not valid json numbers, so they're rendered as strings.
+fn render_float(f: f64) -> Node {
+    match serde_json::Number::from_f64(f) {
+        Some(n) => Node::Scalar(n.to_string()),
+        None => Node::string(&f.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_json, parse_toml, render_rows};
+
+    #[cfg(feature = "yaml")]
+    use super::parse_yaml;
+
+    const ROWS: &str = r#"{"spec.replicas": 3}
+{"spec.template.containers[0].name": "web", "spec.template.containers[0].image": "nginx:1.25", "spec.template.containers[0].args": ["--port", 80]}
+{"spec.template.containers[1].name": "sidecar"}
+{"spec.template.labels": {}}
+{"spec.paused": false}
+{"paths[\"/pets/{id}\"].get.summary": "Find a pet", "paths[\"/pets/{id}\"].get.deprecated": null}
+"#;
+
+    fn render(tree: super::Node) -> String {
+        let mut rows = vec![];
+        render_rows(&tree, "", &mut rows);
+        rows.concat()
+    }
+
+    #[test]
+    fn structured_test() {
+        let json = r#"{
+            "spec": {
+                "replicas": 3,
+                "template": {
+                    "containers": [
+                        {"name": "web", "image": "nginx:1.25", "args": ["--port", 80]},
+                        {"name": "sidecar"}
+                    ],
+                    "labels": {}
+                },
+                "paused": false
+            },
+            "paths": {"/pets/{id}": {"get": {"summary": "Find a pet", "deprecated": null}}}
+        }"#;
+        assert_eq!(render(parse_json(json).unwrap()), ROWS);
+
+        let toml = r#"
+paths = { "/pets/{id}" = { get = { summary = "Find a pet" } } }
+
+[spec]
+replicas = 3
+
+[[spec.template.containers]]
+name = "web"
+image = 'nginx:1.25'
+args = [
+    "--port",
+    80,  # trailing comma
+]
+
+[[spec.template.containers]]
+name = "sidecar"
+"#;
+        assert_eq!(
+            render(parse_toml(toml).unwrap()),
+            r#"{"paths[\"/pets/{id}\"].get.summary": "Find a pet"}
+{"spec.replicas": 3}
+{"spec.template.containers[0].name": "web", "spec.template.containers[0].image": "nginx:1.25", "spec.template.containers[0].args": ["--port", 80]}
+{"spec.template.containers[1].name": "sidecar"}
+"#,
+        );
+
+        // the order of the keys is kept
+        assert_eq!(render(parse_json("{\"b\": 1, \"a\": [2.50, -3]}").unwrap()), "{\"b\": 1, \"a\": [2.50, -3]}\n");
+
+        assert!(parse_json("{\"a\": 1,}").is_err());
+        assert!(parse_toml("a = ").is_err());
+
+        // too deep
+        assert!(parse_json(&format!("{}{}", "[".repeat(1000), "]".repeat(1000))).is_err());
+        assert!(parse_toml(&format!("{} = 1", vec!["a"; 1000].join("."))).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn structured_yaml_test() {
+        let yaml = r#"
+# a comment
+spec:
+  replicas: 3
+  template:
+    containers:
+    - name: web   # another comment
+      image: "nginx:1.25"
+      args: [--port, 80]
+    - name: sidecar
+    labels: {}
+  paused: false
+paths:
+  "/pets/{id}":
+    get:
+      summary: Find
+        a pet
+      deprecated: ~
+"#;
+        assert_eq!(render(parse_yaml(yaml).unwrap()), ROWS);
+
+        let yaml = r#"
+description: |
+  first line
+  second line
+
+folded: >-
+  a b
+  c
+list:
+  - a
+  -
+    - b
+    - c
+empty:
+---
+second: document
+"#;
+        assert_eq!(
+            render(parse_yaml(yaml).unwrap()),
+            r#"{"[0].description": "first line\nsecond line\n", "[0].folded": "a b c"}
+{"[0].list[0]": "a", "[0].list[1]": ["b", "c"]}
+{"[0].empty": null}
+{"[1].second": "document"}
+"#,
+        );
+
+        // too deep
+        assert!(parse_yaml(&format!("{}{}", "[".repeat(1000), "]".repeat(1000))).is_err());
+    }
+}
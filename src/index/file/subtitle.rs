@@ -0,0 +1,280 @@
+use super::{AtomicToken, FileReaderImpl, FileReaderOptions};
+use super::encoding::{FileEncoding, open_text_file};
+use crate::chunk::{ChunkExtraInfo, TimeRange, render_timestamp};
+use crate::error::Error;
+use crate::index::BuildConfig;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::Read;
+
+lazy_static! {
+    // `00:01:02,500 --> 00:01:04,000` (srt) or `01:02.500 --> 01:04.000 align:start` (vtt)
+    static ref TIMING_RE: Regex = Regex::new(r"^\s*(\S+)\s*-->\s*(\S+)").unwrap();
+
+    // `[00:12:03] Alice: Hello` or `00:12:03 - Hello`
+    static ref TRANSCRIPT_LINE_RE: Regex = Regex::new(r"^\s*[\[(]?((?:\d+:)?\d{1,2}:\d{2}(?:[.,]\d{1,3})?)[\])]?\s*-?\s*(.*)$").unwrap();
+
+    // vtt cue text can have tags: `<v Alice>`, `<c.yellow>`, `<00:00:01.000>`, `<i>` ...
+    static ref VTT_TAG_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+/// It reads subtitles (`.srt` and `.vtt`) and timestamped transcripts. A
+/// transcript is a text file where each line starts with a timestamp, like
+/// `[00:12:03] Alice: Hello`. Lines without timestamps belong to the previous line.
+///
+/// Each cue is a line that starts with its start time, and the start and end
+/// time of the cues in a chunk are recorded in `ChunkSource`. A cue in a
+/// transcript ends where the next cue starts.
+pub struct SubtitleReader {
+    content: String,
+    encoding: Option<FileEncoding>,
+    tokens: Vec<AtomicToken>,
+    is_exhausted: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Cue {
+    start_ms: u64,
+    end_ms: u64,
+    text: String,
+}
+
+impl FileReaderImpl for SubtitleReader {
+    fn new(path: &str, _root_dir: &str, config: &BuildConfig, options: &FileReaderOptions) -> Result<Self, Error> {
+        options.check_keys("subtitle", &["encoding"])?;
+        let mut file = open_text_file(path, options.get_string("encoding")?.as_deref(), config.strict_file_reader)?;
+        let mut content = String::new();
+        file.content.read_to_string(&mut content)?;
+
+        Ok(SubtitleReader {
+            content,
+            encoding: file.encoding,
+            tokens: vec![],
+            is_exhausted: false,
+        })
+    }
+
+    // Subtitle files are small enough to be read at once.
+    fn load_tokens(&mut self) -> Result<(), Error> {
+        if self.is_exhausted {
+            return Ok(());
+        }
+
+        for cue in parse_cues(&self.content) {
+            self.tokens.push(AtomicToken::ChunkExtraInfo(ChunkExtraInfo {
+                time: Some(TimeRange { start_ms: cue.start_ms, end_ms: cue.end_ms }),
+                ..ChunkExtraInfo::default()
+            }));
+
+            let line = format!("[{}] {}\n", render_timestamp(cue.start_ms), cue.text);
+            self.tokens.push(AtomicToken::String {
+                char_len: line.chars().count(),
+                data: line,
+            });
+        }
+
+        self.is_exhausted = true;
+        Ok(())
+    }
+
+    fn pop_all_tokens(&mut self) -> Result<Vec<AtomicToken>, Error> {
+        let mut result = vec![];
+        std::mem::swap(&mut self.tokens, &mut result);
+        Ok(result)
+    }
+
+    fn has_more_to_read(&self) -> bool {
+        !self.is_exhausted
+    }
+
+    fn key(&self) -> String {
+        String::from("subtitle_reader_v0")
+    }
+
+    fn encoding(&self) -> Option<FileEncoding> {
+        self.encoding.clone()
+    }
+}
+
+fn parse_cues(content: &str) -> Vec<Cue> {
+    let mut cues = if content.lines().any(|line| TIMING_RE.is_match(line)) {
+        parse_srt_or_vtt(content)
+    } else {
+        parse_transcript(content)
+    };
+
+    // Auto-generated subtitles often repeat the same text in consecutive cues.
+    cues.dedup_by(
+        |curr, prev| if curr.text == prev.text {
+            prev.end_ms = prev.end_ms.max(curr.end_ms);
+            true
+        } else {
+            false
+        }
+    );
+
+    cues
+}
+
+// A cue is a block of lines: an optional identifier, the timing and the text.
+// Blocks are separated by empty lines. Blocks without timing (e.g. `WEBVTT`,
+// `NOTE` and `STYLE` of vtt) are not cues.
+fn parse_srt_or_vtt(content: &str) -> Vec<Cue> {
+    let mut cues = vec![];
+    let mut curr_cue: Option<Cue> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            if let Some(cue) = curr_cue.take() {
+                push_cue(&mut cues, cue);
+            }
+
+            continue;
+        }
+
+        if let Some(cue) = &mut curr_cue {
+            if !cue.text.is_empty() {
+                cue.text.push(' ');
+            }
+
+            cue.text.push_str(&render_vtt_tags(line));
+            continue;
+        }
+
+        // Lines before the timing (e.g. the index of an srt cue) are ignored.
+        curr_cue = TIMING_RE.captures(line).and_then(
+            |cap| match (parse_timestamp(&cap[1]), parse_timestamp(&cap[2])) {
+                (Some(start_ms), Some(end_ms)) => Some(Cue { start_ms, end_ms, text: String::new() }),
+                _ => None,
+            }
+        );
+    }
+
+    if let Some(cue) = curr_cue {
+        push_cue(&mut cues, cue);
+    }
+
+    cues
+}
+
+fn parse_transcript(content: &str) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = vec![];
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match TRANSCRIPT_LINE_RE.captures(line).and_then(|cap| parse_timestamp(&cap[1]).map(|start_ms| (start_ms, cap[2].to_string()))) {
+            Some((start_ms, text)) => {
+                if let Some(prev) = cues.last_mut() {
+                    prev.end_ms = prev.end_ms.max(start_ms);
+                }
+
+                cues.push(Cue { start_ms, end_ms: start_ms, text });
+            },
+
+            // a line without a timestamp belongs to the previous cue
+            None => match cues.last_mut() {
+                Some(prev) => {
+                    prev.text.push(' ');
+                    prev.text.push_str(line);
+                },
+
+                // e.g. a title of the transcript
+                None => {
+                    cues.push(Cue { start_ms: 0, end_ms: 0, text: line.to_string() });
+                },
+            },
+        }
+    }
+
+    cues.retain(|cue| !cue.text.trim().is_empty());
+    cues
+}
+
+fn push_cue(cues: &mut Vec<Cue>, cue: Cue) {
+    if !cue.text.trim().is_empty() {
+        cues.push(cue);
+    }
+}
+
+// `<v Alice>Hello` -> `Alice: Hello`, and the other tags are removed.
+fn render_vtt_tags(line: &str) -> String {
+    let line = match line.strip_prefix("<v").and_then(|rest| rest.split_once('>')) {
+        // `<v.loud Alice>` has a class
+        Some((voice, text)) if voice.starts_with([' ', '.']) => match voice.split_once(' ').map(|(_, speaker)| speaker.trim()) {
+            Some(speaker) if !speaker.is_empty() => format!("{speaker}: {text}"),
+            _ => text.to_string(),
+        },
+        _ => line.to_string(),
+    };
+
+    VTT_TAG_RE.replace_all(&line, "").replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", " ")
+}
+
+// `01:02:03,500`, `01:02:03.500`, `02:03.500` or `02:03`
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (s, fraction) = match s.split_once([',', '.']) {
+        Some((s, fraction)) => (s, fraction),
+        None => (s, "0"),
+    };
+
+    if fraction.is_empty() || fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let ms = fraction.parse::<u64>().ok()? * 10u64.pow(3 - fraction.len() as u32);
+    let mut seconds = 0;
+    let parts = s.split(':').collect::<Vec<_>>();
+
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+
+    for part in parts.iter() {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+
+    Some(seconds * 1000 + ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cue, parse_cues, parse_timestamp};
+
+    #[test]
+    fn subtitle_test() {
+        assert_eq!(parse_timestamp("01:02:03,500"), Some(3_723_500));
+        assert_eq!(parse_timestamp("02:03.5"), Some(123_500));
+        assert_eq!(parse_timestamp("02:03"), Some(123_000));
+        assert_eq!(parse_timestamp("2023"), None);
+
+        let vtt = "WEBVTT\n\nNOTE a comment\n\nintro\n00:00:01.000 --> 00:00:04.000 align:start\n<v.loud Alice>Hello, <i>everyone</i>.\n\n00:04.000 --> 00:06.000\n<v Bob>Hi!\nHow are you?\n\n00:06.000 --> 00:07.000\n<v Bob>Hi!\nHow are you?\n";
+        assert_eq!(
+            parse_cues(vtt),
+            vec![
+                Cue { start_ms: 1_000, end_ms: 4_000, text: String::from("Alice: Hello, everyone.") },
+                Cue { start_ms: 4_000, end_ms: 7_000, text: String::from("Bob: Hi! How are you?") },
+            ],
+        );
+
+        let transcript = "Weekly Meeting\n[00:00:05] Alice: Let's start.\nIt's about the release.\n(00:01:10) Bob: Sure.\n";
+        assert_eq!(
+            parse_cues(transcript),
+            vec![
+                Cue { start_ms: 0, end_ms: 5_000, text: String::from("Weekly Meeting") },
+                Cue { start_ms: 5_000, end_ms: 70_000, text: String::from("Alice: Let's start. It's about the release.") },
+                Cue { start_ms: 70_000, end_ms: 70_000, text: String::from("Bob: Sure.") },
+            ],
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// (script, characters per token)
+// These numbers are from BPE tokenizers of popular models. An english word is
+// 1 ~ 2 tokens, and a CJK character is about a token.
+const CHARS_PER_TOKEN: [(Script, usize); 7] = [
+    (Script::Alphabet, 5),
+    (Script::Digit, 3),
+    (Script::Punctuation, 2),
+    (Script::Letter, 2),
+    (Script::Hangul, 1),
+    (Script::Cjk, 1),
+    (Script::Other, 1),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Script {
+    Whitespace,
+
+    // ascii alphabets
+    Alphabet,
+    Digit,
+    Punctuation,
+
+    // non-ascii alphabets: accented latin, greek, cyrillic, arabic, ...
+    Letter,
+    Hangul,
+
+    // Han, Hiragana and Katakana
+    Cjk,
+
+    // emojis and the other symbols
+    Other,
+}
+
+impl Script {
+    fn of(c: char) -> Script {
+        match c {
+            _ if c.is_whitespace() => Script::Whitespace,
+            'a'..='z' | 'A'..='Z' => Script::Alphabet,
+            '0'..='9' => Script::Digit,
+            _ if c.is_ascii_punctuation() => Script::Punctuation,
+            '\u{1100}'..='\u{11ff}'
+            | '\u{3130}'..='\u{318f}'
+            | '\u{ac00}'..='\u{d7af}' => Script::Hangul,
+            '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{f900}'..='\u{faff}' => Script::Cjk,
+            _ if c.is_alphabetic() => Script::Letter,
+            _ => Script::Other,
+        }
+    }
+
+    fn chars_per_token(&self) -> usize {
+        CHARS_PER_TOKEN.iter().find(|(script, _)| script == self).map(|(_, n)| *n).unwrap_or(1)
+    }
+}
+
+/// It estimates the number of tokens of `s` without a vocabulary. It's used when
+/// `chunk_unit` is `token`.
+///
+/// It splits `s` into runs of the same script, and each run costs `len / chars_per_token`
+/// tokens (rounded up). A single space costs nothing because BPE tokenizers merge it
+/// with the next word. The other runs of whitespaces (e.g. newlines and indentations)
+/// cost a token.
+pub fn estimate_token_count(s: &str) -> usize {
+    let mut result = 0;
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let script = Script::of(c);
+        let mut run = String::from(c);
+
+        while let Some(next) = chars.peek() {
+            if Script::of(*next) != script {
+                break;
+            }
+
+            run.push(*next);
+            chars.next();
+        }
+
+        result += match script {
+            Script::Whitespace => if run == " " { 0 } else { 1 },
+            _ => run.chars().count().div_ceil(script.chars_per_token()),
+        };
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::estimate_token_count;
+
+    #[test]
+    fn token_count_test() {
+        assert_eq!(estimate_token_count(""), 0);
+        assert_eq!(estimate_token_count("Hello, World!"), 4);
+        assert_eq!(estimate_token_count("fn main() {\n    println!(\"{}\", 12345);\n}\n"), 17);
+
+        // a Korean or Chinese character is a token
+        assert_eq!(estimate_token_count("안녕하세요"), 5);
+        assert_eq!(estimate_token_count("你好，世界"), 5);
+
+        // An english text has much less tokens than characters, but a CJK text doesn't.
+        let english = "The quick brown fox jumps over the lazy dog.";
+        let korean = "다람쥐 헌 쳇바퀴에 타고파.";
+        assert!(estimate_token_count(english) * 3 < english.chars().count());
+        assert!(estimate_token_count(korean) * 3 > korean.chars().count() * 2);
+    }
+}
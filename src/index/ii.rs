@@ -1,35 +1,65 @@
 // Inverted Index Implementation
 // Inverted Index is still very naive and lacking many features.
 //
-// 1. You can only build ii from scratch. There's no incremental build.
-// 2. You can only remove the entire ii. There's no removing a single file or a chunk.
-// 3. If something goes wrong while an ii is building, you have to build it from scratch.
+// 1. `rag ii-build` builds an ii from scratch. Once it's complete, `rag build`, `rag remove`,
+//    `rag merge` and `rag pull` add and remove the postings of each chunk, so that the
+//    ii stays complete.
+// 2. If something goes wrong while an ii is building or updating, you have to build it from scratch.
+//
+// An ii is a list of segments (see `segment.rs`). Postings of a term have the positions of
+// the term in each chunk. Phrase and proximity queries use the positions.
+//
+// An older version of ragit used to store each term in a separate file (`ii/prefix/suffix`).
+// Such an ii is not used anymore, and `rag ii-build` builds a new one.
 
 use super::Index;
 use crate::index::commands::erase_lines;
 use crate::constant::{II_DIR_NAME, INDEX_DIR_NAME};
 use crate::error::Error;
-use crate::uid::{self, Uid, UidWriteMode};
+use crate::uid::Uid;
 use ragit_fs::{
-    exists,
-    file_name,
     is_dir,
-    join,
     join3,
-    parent,
     read_dir,
     remove_dir_all,
-    try_create_dir,
+    remove_file,
 };
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::collections::hash_map::{Entry, HashMap};
 
+mod segment;
+
+use segment::{
+    Posting,
+    SEGMENT_EXTENSIONS,
+    SEGMENT_LIST_FILE_NAME,
+    SegmentReader,
+    SegmentWriter,
+    get_segment_file_name,
+    load_segment_list,
+    remove_segment_files,
+    save_removed_chunks,
+    save_segment_list,
+    write_segment,
+};
+
 pub type Term = String;
 pub type Weight = f32;
-const AUTO_FLUSH: usize = 65536;  // TODO: make it configurable
+
+/// Index of a term in the tokenized haystack of a chunk.
+pub type Position = u32;
+
+/// sha3-256 of a term. Segments find terms by their hashes.
+pub(crate) type TermHash = [u8; 32];
+
+// ii-build buffers postings, with positions, before it flushes them to a segment.
+type IIBuffer = HashMap<Term, Vec<(Uid, Vec<Position>)>>;
+
+// If there are more segments than this, it merges the small ones.
+const MAX_SEGMENTS: usize = 16;
 
 // It takes too long to iterate all the terms and chunks.
 const CHECK_II_LIMIT: usize = 512;
@@ -67,12 +97,13 @@ impl Index {
         limit: usize,
     ) -> Result<Vec<Uid>, Error> {
         let mut result: HashMap<Uid, f32> = HashMap::new();
+        let segments = self.open_ii_segments()?;
 
         for (term, weight) in terms.iter() {
-            let chunk_uids = self.search_ii_by_term(term)?;
-            let score = weight * ((self.chunk_count + 1) as f32 / (chunk_uids.len() + 1) as f32).log2();
+            let postings = search_segments(&segments, term)?;
+            let score = weight * ((self.chunk_count + 1) as f32 / (postings.len() + 1) as f32).log2();
 
-            for chunk_uid in chunk_uids.iter() {
+            for (chunk_uid, _) in postings.iter() {
                 match result.entry(*chunk_uid) {
                     Entry::Occupied(mut score_) => {
                         *score_.get_mut() += score;
@@ -96,16 +127,33 @@ impl Index {
         Ok(result.into_iter().map(|(uid, _)| uid).collect())
     }
 
-    pub fn search_ii_by_term(&self, term: &Term) -> Result<Vec<Uid>, Error> {
-        let ii_path = Index::get_ii_path(&self.root_dir, hash(term));
+    /// It returns the number of chunks that contain each term. Unlike `search_ii_by_term`,
+    /// it opens the segments once and doesn't decode the postings.
+    pub fn get_document_frequencies<'a>(&self, terms: impl Iterator<Item = &'a Term>) -> Result<HashMap<Term, usize>, Error> {
+        let segments = self.open_ii_segments()?;
+        let mut result = HashMap::new();
 
-        if exists(&ii_path) {
-            Ok(uid::load_from_file(&ii_path)?)
-        }
+        for term in terms {
+            let term_hash = hash(term);
+            let mut document_frequency = 0;
 
-        else {
-            Ok(vec![])
+            for segment in segments.iter() {
+                document_frequency += segment.get_document_frequency(&term_hash)?;
+            }
+
+            result.insert(term.to_string(), document_frequency);
         }
+
+        Ok(result)
+    }
+
+    pub fn search_ii_by_term(&self, term: &Term) -> Result<Vec<Uid>, Error> {
+        Ok(self.search_ii_positions_by_term(term)?.into_iter().map(|(uid, _)| uid).collect())
+    }
+
+    /// It returns (uid, positions) of each chunk that contains the term.
+    pub fn search_ii_positions_by_term(&self, term: &Term) -> Result<Vec<(Uid, Vec<Position>)>, Error> {
+        search_segments(&self.open_ii_segments()?, term)
     }
 
     pub fn build_ii(&mut self, quiet: bool) -> Result<(), Error> {
@@ -122,7 +170,7 @@ impl Index {
             },
         }
 
-        let mut buffer = HashMap::with_capacity(AUTO_FLUSH);
+        let mut buffer = HashMap::new();
         let mut state = IIBuildState::default();
         let mut uid_check_point = None;
         let mut has_to_erase_lines = false;
@@ -142,13 +190,13 @@ impl Index {
                 has_to_erase_lines = true;
             }
 
-            if buffer.len() > AUTO_FLUSH {
+            if buffer.len() > self.build_config.ii_flush_threshold {
                 self.ii_status = IIStatus::Ongoing(uid_check_point.unwrap());
                 uid_check_point = None;
                 self.save_to_file()?;
 
                 self.flush_ii_buffer(buffer)?;
-                buffer = HashMap::with_capacity(AUTO_FLUSH);
+                buffer = HashMap::new();
                 state.buffer_uid = 0;
                 state.buffer_flush += 1;
             }
@@ -164,6 +212,9 @@ impl Index {
             state.buffer_flush += 1;
         }
 
+        // A freshly built ii doesn't have to be split.
+        self.compact_ii()?;
+
         if !quiet {
             self.render_ii_build_dashboard(&state, has_to_erase_lines);
         }
@@ -174,11 +225,7 @@ impl Index {
     }
 
     pub fn reset_ii(&mut self) -> Result<(), Error> {
-        let ii_path = join3(
-            &self.root_dir,
-            INDEX_DIR_NAME,
-            II_DIR_NAME,
-        )?;
+        let ii_path = self.get_ii_dir()?;
 
         // It's kinda WAL.
         // It might fail while `reset_ii`. In that case,
@@ -186,9 +233,13 @@ impl Index {
         self.ii_status = IIStatus::Outdated;
         self.save_to_file()?;
 
-        for dir in read_dir(&ii_path, false)? {
-            if is_dir(&dir) {
-                remove_dir_all(&dir)?;
+        for file in read_dir(&ii_path, false)? {
+            if is_dir(&file) {
+                remove_dir_all(&file)?;
+            }
+
+            else {
+                remove_file(&file)?;
             }
         }
 
@@ -197,114 +248,185 @@ impl Index {
         Ok(())
     }
 
-    pub fn check_ii(&self) -> Result<(), Error> {
-        let mut term_hash_map: HashMap<String, String> = HashMap::with_capacity(1024);
-        let mut from_ii: HashMap<String, Vec<Uid>> = HashMap::with_capacity(1024);
-        let mut from_tfidf: HashMap<String, Vec<Uid>> = HashMap::with_capacity(1024);
+    /// It removes `removed` chunks from the ii and adds `added` chunks to it.
+    /// `added` chunks must have tfidf files. The caller has to make sure that the
+    /// ii was complete before the chunks were removed or added. The commands mark
+    /// the ii outdated before they remove chunks, so it cannot check `self.ii_status`.
+    pub(crate) fn update_ii(&mut self, removed: &[Uid], added: &[Uid]) -> Result<(), Error> {
+        // It's kinda WAL. If it fails while updating the ii,
+        // the ii has to be built from scratch.
+        self.ii_status = IIStatus::Outdated;
+        self.save_to_file()?;
+
+        self.remove_chunks_from_ii(removed)?;
+        let mut buffer = HashMap::new();
+
+        for uid in added.iter() {
+            self.update_ii_buffer(&mut buffer, *uid)?;
+
+            if buffer.len() > self.build_config.ii_flush_threshold {
+                self.flush_ii_buffer(buffer)?;
+                buffer = HashMap::new();
+            }
+        }
+
+        if !buffer.is_empty() {
+            self.flush_ii_buffer(buffer)?;
+        }
 
-        'outer: for internal in read_dir(&join3(
+        self.merge_small_ii_segments()?;
+        self.ii_status = IIStatus::Complete;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// It merges all the segments into one, without the removed chunks.
+    pub(crate) fn compact_ii(&self) -> Result<(), Error> {
+        let segment_list = load_segment_list(&self.get_ii_dir()?)?.unwrap_or_default();
+
+        match segment_list.segments.as_slice() {
+            [] => Ok(()),
+            [segment] if segment.removed_chunk_count == 0 => Ok(()),
+            segments => self.merge_ii_segments(&segments.iter().map(|segment| segment.id).collect::<Vec<_>>()),
+        }
+    }
+
+    /// Files in `.ragit/ii/` that are a part of the ii. Anything else is garbage.
+    pub(crate) fn get_ii_files(&self) -> Result<HashSet<String>, Error> {
+        let ii_dir = self.get_ii_dir()?;
+        let mut result = HashSet::new();
+
+        if let Some(segment_list) = load_segment_list(&ii_dir)? {
+            result.insert(SEGMENT_LIST_FILE_NAME.to_string());
+
+            for segment in segment_list.segments.iter() {
+                for ext in SEGMENT_EXTENSIONS.iter() {
+                    result.insert(get_segment_file_name(segment.id, ext));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub(crate) fn get_ii_dir(&self) -> Result<String, Error> {
+        Ok(join3(
             &self.root_dir,
             INDEX_DIR_NAME,
             II_DIR_NAME,
-        )?, false)? {
-            let prefix = file_name(&internal)?;
-
-            for ii_path in read_dir(&internal, false)? {
-                let suffix = file_name(&ii_path)?;
-                let term_hash = format!("{prefix}{suffix}");
-                let uids = uid::load_from_file(&ii_path)?;
-                from_ii.insert(term_hash, uids);
-
-                // It takes too long to iterate all the terms...
-                if from_ii.len() == CHECK_II_LIMIT {
-                    break 'outer;
+        )?)
+    }
+
+    pub fn check_ii(&self) -> Result<(), Error> {
+        if self.has_old_ii() {
+            return Err(Error::BrokenII(String::from("The inverted index is built by an older version of ragit. Please run `rag ii-reset` and `rag ii-build`.")));
+        }
+
+        let chunk_uids = self.get_all_chunk_uids()?;
+        let sampled_chunks = chunk_uids.iter().take(CHECK_II_LIMIT).copied().collect::<HashSet<_>>();
+        let segments = self.open_ii_segments()?;
+        let mut live_uids = HashSet::with_capacity(chunk_uids.len());
+
+        // uids of the first `CHECK_II_LIMIT` terms
+        let mut from_ii: HashMap<TermHash, HashSet<Uid>> = HashMap::with_capacity(CHECK_II_LIMIT);
+
+        // positions of the terms in the first `CHECK_II_LIMIT` chunks
+        let mut sampled_positions: HashMap<(Uid, TermHash), Vec<Position>> = HashMap::new();
+
+        for segment in segments.iter() {
+            if segment.removed.len() != segment.info.removed_chunk_count {
+                return Err(Error::BrokenII(format!("segment {} is supposed to have {} removed chunks, but has {}.", segment.info.id, segment.info.removed_chunk_count, segment.removed.len())));
+            }
+
+            for uid in segment.removed.iter() {
+                if segment.uids.binary_search(uid).is_err() {
+                    return Err(Error::BrokenII(format!("segment {} removes `{uid}`, which it doesn't have.", segment.info.id)));
+                }
+            }
+
+            for uid in segment.live_uids() {
+                if !live_uids.insert(uid) {
+                    return Err(Error::BrokenII(format!("`{uid}` is in more than 1 segment.")));
+                }
+            }
+
+            // It reads and decodes every term in the segment.
+            for term in segment.iter_terms()? {
+                let (term_hash, postings) = term?;
+
+                if from_ii.len() < CHECK_II_LIMIT || from_ii.contains_key(&term_hash) {
+                    from_ii.entry(term_hash).or_default().extend(postings.iter().map(|(uid, _)| *uid));
+                }
+
+                for (uid, positions) in postings.into_iter() {
+                    if sampled_chunks.contains(&uid) {
+                        sampled_positions.insert((uid, term_hash), positions);
+                    }
                 }
             }
         }
 
-        for (uid_index, uid) in self.get_all_chunk_uids()?.into_iter().enumerate() {
-            let tfidf = self.get_tfidf_by_chunk_uid(uid)?;
+        let mut from_tfidf: HashMap<TermHash, HashSet<Uid>> = HashMap::with_capacity(CHECK_II_LIMIT);
+        let mut terms: HashMap<TermHash, Term> = HashMap::with_capacity(CHECK_II_LIMIT);
+
+        for uid in chunk_uids.iter() {
+            if !live_uids.contains(uid) {
+                return Err(Error::BrokenII(format!("`{uid}` is not in ii.")));
+            }
+
+            let tfidf = self.get_tfidf_by_chunk_uid(*uid)?;
+            let chunk_positions = if sampled_chunks.contains(uid) {
+                Some(self.get_term_positions_by_chunk_uid(*uid, &tfidf)?)
+            } else {
+                None
+            };
 
             for term in tfidf.term_frequency.keys() {
                 let term_hash = hash(term);
 
                 if from_ii.contains_key(&term_hash) {
-                    term_hash_map.insert(term_hash.to_string(), term.to_string());
-
-                    match from_tfidf.entry(term_hash.to_string()) {
-                        Entry::Occupied(mut uids) => {
-                            uids.get_mut().push(uid);
-                        },
-                        Entry::Vacant(e) => {
-                            e.insert(vec![uid]);
-                        },
-                    }
+                    terms.insert(term_hash, term.to_string());
+                    from_tfidf.entry(term_hash).or_default().insert(*uid);
                 }
 
                 // it takes too long to iterate all the chunks...
-                if uid_index < CHECK_II_LIMIT {
-                    let prefix = term_hash.get(0..2).unwrap().to_string();
-                    let suffix = term_hash.get(2..).unwrap().to_string();
-                    let ii_at = join(
-                        &join(
-                            &self.root_dir,
-                            INDEX_DIR_NAME,
-                        )?,
-                        &join3(
-                            II_DIR_NAME,
-                            &prefix,
-                            &suffix,
-                        )?,
-                    )?;
-
-                    if exists(&ii_at) {
-                        let ii_uids = uid::load_from_file(&ii_at)?;
-
-                        if !ii_uids.contains(&uid) {
-                            return Err(Error::BrokenII(format!("`{term}` is in `{uid}`, but not in ii.")));
-                        }
-                    }
-
-                    else {
+                if let Some(chunk_positions) = &chunk_positions {
+                    let Some(positions) = sampled_positions.remove(&(*uid, term_hash)) else {
                         return Err(Error::BrokenII(format!("`{term}` is in `{uid}`, but not in ii.")));
+                    };
+                    let expected = chunk_positions.get(term).map(|positions| positions.as_slice()).unwrap_or(&[]);
+
+                    if positions.as_slice() != expected {
+                        return Err(Error::BrokenII(format!("ii has wrong positions of `{term}` in `{uid}`.")));
                     }
                 }
             }
         }
 
-        for (term_hash, term) in term_hash_map.iter() {
-            match from_ii.get(term_hash) {
-                Some(uids_from_ii) => {
-                    let uids_from_ii = uids_from_ii.iter().map(
-                        |uid| *uid
-                    ).collect::<HashSet<_>>();
-                    let uids_from_tfidf = from_tfidf.get(term_hash).unwrap().iter().map(
-                        |uid| *uid
-                    ).collect::<HashSet<_>>();
-
-                    for uid in uids_from_ii.iter() {
-                        if !uids_from_tfidf.contains(uid) {
-                            return Err(Error::BrokenII(format!("ii says `{uid}` contains `{term}`, but its tfidf doesn't.")));
-                        }
-                    }
+        if let Some((uid, _)) = sampled_positions.keys().next() {
+            return Err(Error::BrokenII(format!("ii says `{uid}` contains a term, but its tfidf doesn't.")));
+        }
 
-                    for uid in uids_from_tfidf.iter() {
-                        if !uids_from_ii.contains(uid) {
-                            return Err(Error::BrokenII(format!("`{term}` is in `{uid}`, but not in ii.")));
-                        }
-                    }
-                },
-                // if `from_ii.len() < CHECK_II_LIMIT`, `from_ii` contains all the terms
-                None if from_ii.len() < CHECK_II_LIMIT => {
-                    return Err(Error::BrokenII(format!("`{term}` is in the knowledge-base, but not in ii.")));
-                },
-                _ => {},
-            }
+        if live_uids.len() != chunk_uids.len() {
+            return Err(Error::BrokenII(format!("ii has {} chunks, but the knowledge-base has {} chunks.", live_uids.len(), chunk_uids.len())));
         }
 
-        for term_hash in from_ii.keys() {
-            if !term_hash_map.contains_key(term_hash) {
-                return Err(Error::BrokenII(format!("ii has a term_hash `{term_hash}`, but it's not found in actual tfidf files.")));
+        for (term_hash, uids_from_ii) in from_ii.iter() {
+            let Some(term) = terms.get(term_hash) else {
+                return Err(Error::BrokenII(format!("ii has a term_hash `{}`, but it's not found in actual tfidf files.", hash_to_string(term_hash))));
+            };
+            let uids_from_tfidf = from_tfidf.get(term_hash).unwrap();
+
+            for uid in uids_from_ii.iter() {
+                if !uids_from_tfidf.contains(uid) {
+                    return Err(Error::BrokenII(format!("ii says `{uid}` contains `{term}`, but its tfidf doesn't.")));
+                }
+            }
+
+            for uid in uids_from_tfidf.iter() {
+                if !uids_from_ii.contains(uid) {
+                    return Err(Error::BrokenII(format!("`{term}` is in `{uid}`, but not in ii.")));
+                }
             }
         }
 
@@ -315,16 +437,28 @@ impl Index {
         self.ii_status == IIStatus::Complete
     }
 
-    pub(crate) fn update_ii_buffer(&self, buffer: &mut HashMap<Term, Vec<Uid>>, uid: Uid) -> Result<(), Error> {
+    /// An older version of ragit stored each term in a separate file, in
+    /// `ii/prefix/suffix`. Segments don't have directories.
+    pub(crate) fn has_old_ii(&self) -> bool {
+        match self.get_ii_dir().map(|ii_dir| read_dir(&ii_dir, false)) {
+            Ok(Ok(files)) => files.iter().any(|file| is_dir(file)),
+            _ => false,
+        }
+    }
+
+    fn update_ii_buffer(&self, buffer: &mut IIBuffer, uid: Uid) -> Result<(), Error> {
         let tfidf = self.get_tfidf_by_chunk_uid(uid)?;
+        let mut positions = self.get_term_positions_by_chunk_uid(uid, &tfidf)?;
 
         for term in tfidf.term_frequency.keys() {
+            let posting = (uid, positions.remove(term).unwrap_or_default());
+
             match buffer.entry(term.to_string()) {
-                Entry::Occupied(mut uids) => {
-                    uids.get_mut().push(uid);
+                Entry::Occupied(mut postings) => {
+                    postings.get_mut().push(posting);
                 },
                 Entry::Vacant(e) => {
-                    e.insert(vec![uid]);
+                    e.insert(vec![posting]);
                 },
             }
         }
@@ -332,27 +466,154 @@ impl Index {
         Ok(())
     }
 
-    pub(crate) fn flush_ii_buffer(&self, buffer: HashMap<Term, Vec<Uid>>) -> Result<(), Error> {
-        for (term, uids) in buffer.into_iter() {
-            let term_hash = hash(&term);
-            let ii_path = Index::get_ii_path(&self.root_dir, term_hash);
-            let parent_path = parent(&ii_path)?;
+    /// It writes the buffer to a new segment.
+    fn flush_ii_buffer(&self, buffer: IIBuffer) -> Result<(), Error> {
+        let ii_dir = self.get_ii_dir()?;
+        let mut segment_list = load_segment_list(&ii_dir)?.unwrap_or_default();
+        let terms = buffer.into_iter().map(
+            |(term, postings)| (hash(&term), postings)
+        ).collect::<BTreeMap<_, _>>();
+        let segment = write_segment(&ii_dir, segment_list.next_id, terms)?;
+
+        segment_list.next_id += 1;
+        segment_list.segments.push(segment);
+        save_segment_list(&ii_dir, &segment_list)?;
+        Ok(())
+    }
+
+    fn open_ii_segments(&self) -> Result<Vec<SegmentReader>, Error> {
+        let ii_dir = self.get_ii_dir()?;
+        let mut result = vec![];
+
+        for segment in load_segment_list(&ii_dir)?.unwrap_or_default().segments.into_iter() {
+            result.push(SegmentReader::open(&ii_dir, segment)?);
+        }
 
-            if !exists(&parent_path) {
-                try_create_dir(&parent_path)?;
+        Ok(result)
+    }
+
+    // Segments are immutable, so it only marks the chunks removed. The postings
+    // are removed when the segment is merged.
+    fn remove_chunks_from_ii(&self, removed: &[Uid]) -> Result<(), Error> {
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        let ii_dir = self.get_ii_dir()?;
+        let mut segment_list = load_segment_list(&ii_dir)?.unwrap_or_default();
+        let removed = removed.iter().copied().collect::<HashSet<_>>();
+
+        for segment in segment_list.segments.iter_mut() {
+            let reader = SegmentReader::open(&ii_dir, *segment)?;
+            let mut removed_chunks = reader.removed;
+            let prev_count = removed_chunks.len();
+
+            for uid in reader.uids.iter() {
+                if removed.contains(uid) {
+                    removed_chunks.insert(*uid);
+                }
             }
 
-            let uids = if exists(&ii_path) {
-                let mut prev_uids = uid::load_from_file(&ii_path)?;
-                prev_uids.extend(uids);
-                prev_uids
+            if removed_chunks.len() > prev_count {
+                save_removed_chunks(&ii_dir, segment.id, &removed_chunks)?;
+                segment.removed_chunk_count = removed_chunks.len();
             }
+        }
 
-            else {
-                uids
-            };
+        save_segment_list(&ii_dir, &segment_list)
+    }
+
+    // If there are too many segments, it merges the small ones. A segment
+    // whose chunks are all removed is just removed.
+    fn merge_small_ii_segments(&self) -> Result<(), Error> {
+        let ii_dir = self.get_ii_dir()?;
+        let mut segment_list = load_segment_list(&ii_dir)?.unwrap_or_default();
+        let (empty_segments, mut segments): (Vec<_>, Vec<_>) = segment_list.segments.clone().into_iter().partition(
+            |segment| segment.live_chunk_count() == 0
+        );
+
+        if !empty_segments.is_empty() {
+            segment_list.segments = segments.clone();
+            save_segment_list(&ii_dir, &segment_list)?;
+
+            for segment in empty_segments.iter() {
+                remove_segment_files(&ii_dir, segment.id)?;
+            }
+        }
 
-            uid::save_to_file(&ii_path, &uids, UidWriteMode::Compact)?;
+        if segments.len() <= MAX_SEGMENTS {
+            return Ok(());
+        }
+
+        segments.sort_by_key(|segment| (segment.live_chunk_count(), segment.id));
+        let small_segments = segments[..(segments.len() - MAX_SEGMENTS / 2)].iter().map(
+            |segment| segment.id
+        ).collect::<Vec<_>>();
+
+        self.merge_ii_segments(&small_segments)
+    }
+
+    // It merges the segments into a new one. It reads the segments a term at a time, so
+    // that it doesn't have to load all the postings at once.
+    fn merge_ii_segments(&self, ids: &[u64]) -> Result<(), Error> {
+        let ii_dir = self.get_ii_dir()?;
+        let mut segment_list = load_segment_list(&ii_dir)?.unwrap_or_default();
+        let mut readers = vec![];
+
+        for segment in segment_list.segments.iter() {
+            if ids.contains(&segment.id) {
+                readers.push(SegmentReader::open(&ii_dir, *segment)?);
+            }
+        }
+
+        let uids = readers.iter().flat_map(|reader| reader.live_uids()).collect::<Vec<_>>();
+        let new_id = segment_list.next_id;
+        let mut writer = SegmentWriter::new(&ii_dir, new_id, uids)?;
+        let mut iters = vec![];
+        let mut curr_terms = vec![];
+
+        for reader in readers.iter() {
+            let mut iter = reader.iter_terms()?;
+            curr_terms.push(iter.next().transpose()?);
+            iters.push(iter);
+        }
+
+        // k-way merge of the sorted terms
+        while let Some(min_hash) = curr_terms.iter().filter_map(|term| term.as_ref().map(|(hash, _)| *hash)).min() {
+            let mut postings: Vec<Posting> = vec![];
+
+            for (iter, curr_term) in iters.iter_mut().zip(curr_terms.iter_mut()) {
+                let is_min = match curr_term {
+                    Some((hash, _)) => *hash == min_hash,
+                    None => false,
+                };
+
+                if is_min {
+                    let (_, curr_postings) = curr_term.take().unwrap();
+                    postings.extend(curr_postings);
+                    *curr_term = iter.next().transpose()?;
+                }
+            }
+
+            writer.push(min_hash, &postings)?;
+        }
+
+        let new_segment = writer.finish()?;
+        segment_list.next_id += 1;
+        segment_list.segments.retain(|segment| !ids.contains(&segment.id));
+
+        if new_segment.chunk_count > 0 {
+            segment_list.segments.push(new_segment);
+        }
+
+        save_segment_list(&ii_dir, &segment_list)?;
+
+        if new_segment.chunk_count == 0 {
+            remove_segment_files(&ii_dir, new_id)?;
+        }
+
+        for id in ids.iter() {
+            remove_segment_files(&ii_dir, *id)?;
         }
 
         Ok(())
@@ -376,8 +637,26 @@ impl Index {
     }
 }
 
-fn hash(term: &Term) -> String {
+// A chunk is in at most 1 segment, so it doesn't have to deduplicate the postings.
+fn search_segments(segments: &[SegmentReader], term: &Term) -> Result<Vec<Posting>, Error> {
+    let term_hash = hash(term);
+    let mut result = vec![];
+
+    for segment in segments.iter() {
+        result.extend(segment.get(&term_hash)?);
+    }
+
+    Ok(result)
+}
+
+fn hash(term: &Term) -> TermHash {
     let mut hasher = Sha3_256::new();
     hasher.update(term.as_bytes());
-    format!("{:064x}", hasher.finalize())
+    let mut result = [0; 32];
+    result.copy_from_slice(&hasher.finalize());
+    result
+}
+
+fn hash_to_string(term_hash: &TermHash) -> String {
+    term_hash.iter().map(|byte| format!("{byte:02x}")).collect()
 }
@@ -0,0 +1,659 @@
+// An ii is a list of segments. Each segment is immutable, except for its list of removed
+// chunks. `rag build` (and the other commands that add chunks) writes a new segment, and
+// small segments are merged into a bigger one from time to time.
+//
+// A segment has 4 files in `.ragit/ii/`.
+//
+// 1. `segment-{id}.uids`: uids of the chunks in the segment (`UidWriteMode::Compact`, which
+//    sorts the uids). A posting refers to a chunk by its index in this list.
+// 2. `segment-{id}.dict`: terms, sorted by their hashes. Each entry is `DICT_ENTRY_SIZE` bytes:
+//    hash of the term (32 bytes), offset (u64) and length (u32) of its postings in the `.post`
+//    file and the number of chunks (u32) that contain the term. Integers are little endian.
+//    It's read with binary search, an entry at a time, so it never loads the entire dictionary.
+// 3. `segment-{id}.post`: postings of the terms. Postings of a term are the number of chunks,
+//    and (index delta, number of positions, position deltas) of each chunk. All the numbers
+//    are varints.
+// 4. `segment-{id}.del`: uids of the removed chunks. It doesn't exist if no chunk is removed.
+//
+// `segments.json` is the list of the segments. A file that's not in the list (e.g. `rag build`
+// was killed while merging segments) is garbage, and `rag gc --ii` removes it.
+
+use super::{Position, TermHash};
+use crate::error::Error;
+use crate::uid::{self, Uid, UidWriteMode};
+use ragit_fs::{
+    WriteMode,
+    exists,
+    file_size,
+    join,
+    read_bytes_offset,
+    read_string,
+    remove_file,
+    write_bytes,
+    write_string,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub const SEGMENT_LIST_FILE_NAME: &str = "segments.json";
+pub const SEGMENT_EXTENSIONS: [&str; 4] = ["uids", "dict", "post", "del"];
+const DICT_ENTRY_SIZE: u64 = 48;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SegmentList {
+    pub next_id: u64,
+    pub segments: Vec<SegmentInfo>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SegmentInfo {
+    pub id: u64,
+    pub chunk_count: usize,
+    pub term_count: usize,
+    pub removed_chunk_count: usize,
+}
+
+impl SegmentInfo {
+    pub fn live_chunk_count(&self) -> usize {
+        self.chunk_count - self.removed_chunk_count
+    }
+}
+
+/// A posting of a term in a chunk.
+pub type Posting = (Uid, Vec<Position>);
+
+pub struct SegmentReader {
+    pub info: SegmentInfo,
+    pub uids: Vec<Uid>,
+    pub removed: HashSet<Uid>,
+    dict_path: String,
+    post_path: String,
+}
+
+impl SegmentReader {
+    pub fn open(ii_dir: &str, info: SegmentInfo) -> Result<Self, Error> {
+        let uids = uid::load_from_file(&get_segment_path(ii_dir, info.id, "uids")?)?;
+        let removed_path = get_segment_path(ii_dir, info.id, "del")?;
+        let removed = if exists(&removed_path) {
+            uid::load_from_file(&removed_path)?.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+
+        if uids.len() != info.chunk_count {
+            return Err(Error::BrokenII(format!("segment {} is supposed to have {} chunks, but has {}.", info.id, info.chunk_count, uids.len())));
+        }
+
+        Ok(SegmentReader {
+            info,
+            uids,
+            removed,
+            dict_path: get_segment_path(ii_dir, info.id, "dict")?,
+            post_path: get_segment_path(ii_dir, info.id, "post")?,
+        })
+    }
+
+    /// It returns the postings of a term, except the ones of the removed chunks.
+    pub fn get(&self, term_hash: &TermHash) -> Result<Vec<Posting>, Error> {
+        match self.find_dict_entry(term_hash)? {
+            Some((offset, length, _)) => {
+                let bytes = read_bytes_offset(&self.post_path, offset, offset + length)?;
+                self.decode_postings(&bytes)
+            },
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The number of chunks that contain the term, except the removed ones. It only reads
+    /// the dictionary, unless the segment has removed chunks.
+    pub fn get_document_frequency(&self, term_hash: &TermHash) -> Result<usize, Error> {
+        if !self.removed.is_empty() {
+            return Ok(self.get(term_hash)?.len());
+        }
+
+        Ok(self.find_dict_entry(term_hash)?.map(|(_, _, chunk_count)| chunk_count as usize).unwrap_or(0))
+    }
+
+    // (offset, length, number of chunks) of the postings of a term
+    fn find_dict_entry(&self, term_hash: &TermHash) -> Result<Option<(u64, u64, u32)>, Error> {
+        let mut low = 0;
+        let mut high = self.info.term_count;
+
+        while low < high {
+            let mid = (low + high) / 2;
+            let entry = read_bytes_offset(
+                &self.dict_path,
+                mid as u64 * DICT_ENTRY_SIZE,
+                (mid as u64 + 1) * DICT_ENTRY_SIZE,
+            )?;
+            let (hash, offset, length, chunk_count) = decode_dict_entry(&entry)?;
+
+            if hash == *term_hash {
+                return Ok(Some((offset, length, chunk_count)));
+            }
+
+            else if hash < *term_hash {
+                low = mid + 1;
+            }
+
+            else {
+                high = mid;
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// It iterates all the terms in the segment, sorted by their hashes. Removed chunks
+    /// are not included, and a term that's only in removed chunks is skipped.
+    /// It reads the files a block at a time, so it never loads the entire segment.
+    pub fn iter_terms(&self) -> Result<SegmentTerms<'_>, Error> {
+        let dict_size = file_size(&self.dict_path)?;
+
+        if dict_size != self.info.term_count as u64 * DICT_ENTRY_SIZE {
+            return Err(Error::BrokenII(format!("segment {} is supposed to have {} terms, but its dictionary is {dict_size} bytes.", self.info.id, self.info.term_count)));
+        }
+
+        Ok(SegmentTerms {
+            segment: self,
+            dict: BlockReader::new(&self.dict_path)?,
+            postings: BlockReader::new(&self.post_path)?,
+            cursor: 0,
+            prev_hash: None,
+        })
+    }
+
+    /// Uids of the chunks that are not removed.
+    pub fn live_uids(&self) -> Vec<Uid> {
+        self.uids.iter().filter(|uid| !self.removed.contains(uid)).copied().collect()
+    }
+
+    fn decode_postings(&self, bytes: &[u8]) -> Result<Vec<Posting>, Error> {
+        self.resolve_postings(decode_postings(bytes)?)
+    }
+
+    // chunk index -> uid
+    fn resolve_postings(&self, postings: Vec<(usize, Vec<Position>)>) -> Result<Vec<Posting>, Error> {
+        let mut result = Vec::with_capacity(postings.len());
+
+        for (index, positions) in postings.into_iter() {
+            let Some(uid) = self.uids.get(index) else {
+                return Err(Error::BrokenII(format!("segment {} has {} chunks, but a posting points to chunk {index}.", self.info.id, self.uids.len())));
+            };
+
+            if !self.removed.contains(uid) {
+                result.push((*uid, positions));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub struct SegmentTerms<'a> {
+    segment: &'a SegmentReader,
+    dict: BlockReader,
+    postings: BlockReader,
+    cursor: u64,
+    prev_hash: Option<TermHash>,
+}
+
+impl SegmentTerms<'_> {
+    fn next_term(&mut self) -> Result<Option<(TermHash, Vec<Posting>)>, Error> {
+        loop {
+            let Some(entry) = self.dict.read(self.cursor, DICT_ENTRY_SIZE)? else {
+                return Ok(None);
+            };
+            self.cursor += DICT_ENTRY_SIZE;
+            let (hash, offset, length, chunk_count) = decode_dict_entry(entry)?;
+            let id = self.segment.info.id;
+
+            if self.prev_hash.is_some_and(|prev_hash| prev_hash >= hash) {
+                return Err(Error::BrokenII(format!("the dictionary of segment {id} is not sorted.")));
+            }
+
+            self.prev_hash = Some(hash);
+
+            let Some(bytes) = self.postings.read(offset, length)? else {
+                return Err(Error::BrokenII(format!("segment {id} has postings out of range.")));
+            };
+            let decoded = decode_postings(bytes)?;
+
+            if decoded.len() != chunk_count as usize {
+                return Err(Error::BrokenII(format!("segment {id} says a term is in {chunk_count} chunks, but it's in {} chunks.", decoded.len())));
+            }
+
+            let postings = self.segment.resolve_postings(decoded)?;
+
+            if !postings.is_empty() {
+                return Ok(Some((hash, postings)));
+            }
+        }
+    }
+}
+
+impl Iterator for SegmentTerms<'_> {
+    type Item = Result<(TermHash, Vec<Posting>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_term().transpose()
+    }
+}
+
+// `SegmentTerms` reads the files sequentially. It reads this many bytes at once.
+const READ_BLOCK_SIZE: u64 = 1 << 20;
+
+// It reads a file a block at a time. Reading a range that's not in the current
+// block loads a new block that starts at the range.
+struct BlockReader {
+    path: String,
+    size: u64,
+    block: Vec<u8>,
+    block_start: u64,
+}
+
+impl BlockReader {
+    fn new(path: &str) -> Result<Self, Error> {
+        Ok(BlockReader {
+            path: path.to_string(),
+            size: file_size(path)?,
+            block: vec![],
+            block_start: 0,
+        })
+    }
+
+    // It returns `None` if the range is out of the file.
+    fn read(&mut self, offset: u64, length: u64) -> Result<Option<&[u8]>, Error> {
+        if offset + length > self.size {
+            return Ok(None);
+        }
+
+        let block_end = self.block_start + self.block.len() as u64;
+
+        if offset < self.block_start || offset + length > block_end {
+            let to = (offset + length.max(READ_BLOCK_SIZE)).min(self.size);
+            self.block = read_bytes_offset(&self.path, offset, to)?;
+            self.block_start = offset;
+
+            if (self.block.len() as u64) < length {
+                return Ok(None);
+            }
+        }
+
+        let start = (offset - self.block_start) as usize;
+        Ok(Some(&self.block[start..(start + length as usize)]))
+    }
+}
+
+/// It writes a segment, a term at a time. Terms must be pushed in the order of their hashes.
+/// It doesn't add the segment to the segment list.
+pub struct SegmentWriter {
+    id: u64,
+    uid_indexes: HashMap<Uid, usize>,
+    dict: Vec<u8>,
+    dict_path: String,
+    post_path: String,
+    postings: Vec<u8>,
+    postings_offset: u64,
+    prev_hash: Option<TermHash>,
+    term_count: usize,
+}
+
+// The writer flushes the dictionary and the postings when its buffer gets bigger than this (in bytes).
+const WRITE_BUFFER_SIZE: usize = 1 << 20;
+
+impl SegmentWriter {
+    /// `uids` are all the chunks in the segment.
+    pub fn new(ii_dir: &str, id: u64, mut uids: Vec<Uid>) -> Result<Self, Error> {
+        uids.sort();
+        uids.dedup();
+        uid::save_to_file(&get_segment_path(ii_dir, id, "uids")?, &uids, UidWriteMode::Compact)?;
+        let dict_path = get_segment_path(ii_dir, id, "dict")?;
+        let post_path = get_segment_path(ii_dir, id, "post")?;
+        write_bytes(&dict_path, &[], WriteMode::CreateOrTruncate)?;
+        write_bytes(&post_path, &[], WriteMode::CreateOrTruncate)?;
+
+        Ok(SegmentWriter {
+            id,
+            uid_indexes: uids.into_iter().enumerate().map(|(index, uid)| (uid, index)).collect(),
+            dict: vec![],
+            dict_path,
+            post_path,
+            postings: vec![],
+            postings_offset: 0,
+            prev_hash: None,
+            term_count: 0,
+        })
+    }
+
+    pub fn push(&mut self, hash: TermHash, postings: &[Posting]) -> Result<(), Error> {
+        if self.prev_hash.is_some_and(|prev_hash| prev_hash >= hash) {
+            return Err(Error::BrokenII(format!("terms are pushed to segment {} in a wrong order.", self.id)));
+        }
+
+        let mut indexed_postings = Vec::with_capacity(postings.len());
+
+        for (uid, positions) in postings.iter() {
+            let Some(index) = self.uid_indexes.get(uid) else {
+                return Err(Error::BrokenII(format!("`{uid}` is not in segment {}.", self.id)));
+            };
+
+            indexed_postings.push((*index, positions.clone()));
+        }
+
+        indexed_postings.sort_by_key(|(index, _)| *index);
+        indexed_postings.dedup_by_key(|(index, _)| *index);
+
+        let encoded = encode_postings(&indexed_postings);
+        self.dict.extend_from_slice(&hash);
+        self.dict.extend_from_slice(&(self.postings_offset + self.postings.len() as u64).to_le_bytes());
+        self.dict.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        self.dict.extend_from_slice(&(indexed_postings.len() as u32).to_le_bytes());
+        self.postings.extend(encoded);
+        self.prev_hash = Some(hash);
+        self.term_count += 1;
+
+        if self.postings.len() > WRITE_BUFFER_SIZE || self.dict.len() > WRITE_BUFFER_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<SegmentInfo, Error> {
+        self.flush()?;
+
+        Ok(SegmentInfo {
+            id: self.id,
+            chunk_count: self.uid_indexes.len(),
+            term_count: self.term_count,
+            removed_chunk_count: 0,
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        write_bytes(&self.dict_path, &self.dict, WriteMode::AlwaysAppend)?;
+        write_bytes(&self.post_path, &self.postings, WriteMode::AlwaysAppend)?;
+        self.postings_offset += self.postings.len() as u64;
+        self.dict = vec![];
+        self.postings = vec![];
+        Ok(())
+    }
+}
+
+pub fn load_segment_list(ii_dir: &str) -> Result<Option<SegmentList>, Error> {
+    let path = join(ii_dir, SEGMENT_LIST_FILE_NAME)?;
+
+    if !exists(&path) {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&read_string(&path)?)?))
+}
+
+pub fn save_segment_list(ii_dir: &str, segment_list: &SegmentList) -> Result<(), Error> {
+    Ok(write_string(
+        &join(ii_dir, SEGMENT_LIST_FILE_NAME)?,
+        &serde_json::to_string_pretty(segment_list)?,
+        WriteMode::Atomic,
+    )?)
+}
+
+/// It writes a new segment from in-memory postings. It doesn't add the segment to the segment list.
+pub fn write_segment(ii_dir: &str, id: u64, terms: BTreeMap<TermHash, Vec<Posting>>) -> Result<SegmentInfo, Error> {
+    let uids = terms.values().flat_map(|postings| postings.iter().map(|(uid, _)| *uid)).collect::<Vec<_>>();
+    let mut writer = SegmentWriter::new(ii_dir, id, uids)?;
+
+    for (hash, postings) in terms.iter() {
+        writer.push(*hash, postings)?;
+    }
+
+    writer.finish()
+}
+
+pub fn save_removed_chunks(ii_dir: &str, id: u64, removed: &HashSet<Uid>) -> Result<(), Error> {
+    let removed = removed.iter().copied().collect::<Vec<_>>();
+    uid::save_to_file(&get_segment_path(ii_dir, id, "del")?, &removed, UidWriteMode::Compact)
+}
+
+pub fn remove_segment_files(ii_dir: &str, id: u64) -> Result<(), Error> {
+    for ext in SEGMENT_EXTENSIONS.iter() {
+        let path = get_segment_path(ii_dir, id, ext)?;
+
+        if exists(&path) {
+            remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_segment_path(ii_dir: &str, id: u64, ext: &str) -> Result<String, Error> {
+    Ok(join(ii_dir, &get_segment_file_name(id, ext))?)
+}
+
+pub fn get_segment_file_name(id: u64, ext: &str) -> String {
+    format!("segment-{id:06}.{ext}")
+}
+
+fn decode_dict_entry(entry: &[u8]) -> Result<(TermHash, u64, u64, u32), Error> {
+    if entry.len() != DICT_ENTRY_SIZE as usize {
+        return Err(Error::BrokenII(format!("a dictionary entry is supposed to be {DICT_ENTRY_SIZE} bytes, but is {} bytes.", entry.len())));
+    }
+
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&entry[0..32]);
+    let offset = u64::from_le_bytes(entry[32..40].try_into().unwrap());
+    let length = u32::from_le_bytes(entry[40..44].try_into().unwrap()) as u64;
+    let chunk_count = u32::from_le_bytes(entry[44..48].try_into().unwrap());
+
+    Ok((hash, offset, length, chunk_count))
+}
+
+// `postings` must be sorted by the chunk index, and positions of each chunk must be sorted.
+fn encode_postings(postings: &[(usize, Vec<Position>)]) -> Vec<u8> {
+    let mut result = vec![];
+    let mut prev_index = 0;
+    push_varint(&mut result, postings.len() as u64);
+
+    for (index, positions) in postings.iter() {
+        push_varint(&mut result, (*index - prev_index) as u64);
+        push_varint(&mut result, positions.len() as u64);
+        prev_index = *index;
+        let mut prev_position = 0;
+
+        for position in positions.iter() {
+            push_varint(&mut result, (*position - prev_position) as u64);
+            prev_position = *position;
+        }
+    }
+
+    result
+}
+
+fn decode_postings(bytes: &[u8]) -> Result<Vec<(usize, Vec<Position>)>, Error> {
+    let mut cursor = 0;
+    let count = read_varint(bytes, &mut cursor)? as usize;
+    let mut result = Vec::with_capacity(count.min(bytes.len()));
+    let mut index = 0;
+
+    for posting_index in 0..count {
+        let delta = read_varint(bytes, &mut cursor)? as usize;
+
+        // chunk indexes must be strictly increasing
+        if posting_index > 0 && delta == 0 {
+            return Err(Error::BrokenII(String::from("postings have a duplicate chunk.")));
+        }
+
+        index += delta;
+        let position_count = read_varint(bytes, &mut cursor)? as usize;
+        let mut positions = Vec::with_capacity(position_count.min(bytes.len()));
+        let mut position = 0;
+
+        for _ in 0..position_count {
+            position += read_varint(bytes, &mut cursor)? as Position;
+            positions.push(position);
+        }
+
+        result.push((index, positions));
+    }
+
+    if cursor != bytes.len() {
+        return Err(Error::BrokenII(format!("postings have {} trailing bytes.", bytes.len() - cursor)));
+    }
+
+    Ok(result)
+}
+
+fn push_varint(buffer: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buffer.push((n & 0x7f) as u8 | 0x80);
+        n >>= 7;
+    }
+
+    buffer.push(n as u8);
+}
+
+fn read_varint(bytes: &[u8], cursor: &mut usize) -> Result<u64, Error> {
+    let mut result = 0;
+    let mut shift = 0;
+
+    loop {
+        let Some(byte) = bytes.get(*cursor) else {
+            return Err(Error::BrokenII(String::from("unexpected end of postings.")));
+        };
+        *cursor += 1;
+
+        if shift >= 64 {
+            return Err(Error::BrokenII(String::from("a varint in postings is too big.")));
+        }
+
+        result |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BlockReader,
+        READ_BLOCK_SIZE,
+        SegmentReader,
+        decode_postings,
+        encode_postings,
+        push_varint,
+        read_varint,
+        write_segment,
+    };
+    use crate::uid::Uid;
+    use ragit_fs::{WriteMode, create_dir_all, join, remove_dir_all, temp_dir, write_bytes};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn varint_test() {
+        for n in [0, 1, 127, 128, 300, 16383, 16384, u32::MAX as u64, u64::MAX] {
+            let mut buffer = vec![];
+            push_varint(&mut buffer, n);
+            let mut cursor = 0;
+            assert_eq!(read_varint(&buffer, &mut cursor).unwrap(), n);
+            assert_eq!(cursor, buffer.len());
+        }
+
+        // small numbers take a byte
+        let mut buffer = vec![];
+        push_varint(&mut buffer, 127);
+        assert_eq!(buffer.len(), 1);
+
+        // truncated
+        assert!(read_varint(&[0x80], &mut 0).is_err());
+    }
+
+    #[test]
+    fn postings_test() {
+        let postings = vec![
+            (0, vec![3, 7, 200]),
+            (5, vec![0]),
+            (1000, vec![]),
+        ];
+        let encoded = encode_postings(&postings);
+        assert_eq!(decode_postings(&encoded).unwrap(), postings);
+        assert_eq!(decode_postings(&encode_postings(&[])).unwrap(), vec![]);
+
+        // trailing bytes
+        let mut broken = encoded.clone();
+        broken.push(0);
+        assert!(decode_postings(&broken).is_err());
+
+        // duplicate chunk
+        let duplicate = encode_postings(&[(3, vec![1]), (3, vec![2])]);
+        assert!(decode_postings(&duplicate).is_err());
+    }
+
+    #[test]
+    fn block_reader_test() {
+        let dir = join(&temp_dir().unwrap(), &format!("ragit-block-reader-test-{}", std::process::id())).unwrap();
+        create_dir_all(&dir).unwrap();
+        let path = join(&dir, "bytes").unwrap();
+        let bytes = (0..(READ_BLOCK_SIZE * 2 + 100)).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+        write_bytes(&path, &bytes, WriteMode::CreateOrTruncate).unwrap();
+        let mut reader = BlockReader::new(&path).unwrap();
+
+        for (offset, length) in [
+            (0, 10),
+            (READ_BLOCK_SIZE - 5, 10),  // crosses a block
+            (3, 4),  // goes back
+            (READ_BLOCK_SIZE * 2, 100),
+            (0, READ_BLOCK_SIZE * 2 + 100),  // bigger than a block
+        ] {
+            assert_eq!(
+                reader.read(offset, length).unwrap().unwrap(),
+                &bytes[offset as usize..(offset + length) as usize],
+            );
+        }
+
+        assert!(reader.read(READ_BLOCK_SIZE * 2 + 50, 51).unwrap().is_none());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_test() {
+        let dir = join(&temp_dir().unwrap(), &format!("ragit-segment-test-{}", std::process::id())).unwrap();
+        create_dir_all(&dir).unwrap();
+        let uids = (0..20u8).map(|n| Uid::new_image(&[n])).collect::<Vec<_>>();
+        let mut terms = BTreeMap::new();
+
+        for n in 0..300u32 {
+            let mut hash = [0; 32];
+            hash[0..4].copy_from_slice(&n.to_be_bytes());
+            let postings = uids.iter().enumerate().filter(
+                |(index, _)| (*index as u32).is_multiple_of(n % 7 + 1)
+            ).map(
+                |(index, uid)| (*uid, vec![index as u32, n + 100])
+            ).collect::<Vec<_>>();
+            terms.insert(hash, postings);
+        }
+
+        let info = write_segment(&dir, 0, terms.clone()).unwrap();
+        let reader = SegmentReader::open(&dir, info).unwrap();
+        let expected = terms.iter().map(
+            |(hash, postings)| {
+                let mut postings = postings.clone();
+                postings.sort_by_key(|(uid, _)| *uid);
+                (*hash, postings)
+            }
+        ).collect::<Vec<_>>();
+
+        assert_eq!(reader.iter_terms().unwrap().collect::<Result<Vec<_>, _>>().unwrap(), expected);
+
+        for (hash, postings) in expected.iter() {
+            assert_eq!(&reader.get(hash).unwrap(), postings);
+        }
+
+        assert!(reader.get(&[255; 32]).unwrap().is_empty());
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+use crate::chunk::{Chunk, ChunkBuildInfo};
+use crate::error::Error;
+use crate::index::Index;
+use crate::uid::Uid;
+use ragit_fs::{WriteMode, exists, read_bytes, write_bytes};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// A shingle is this many characters.
+const SHINGLE_SIZE: usize = 8;
+
+// A signature has `BANDS * ROWS` bins. Two chunks are compared only if
+// they have at least one identical band (locality-sensitive hashing).
+const BANDS: usize = 16;
+const ROWS: usize = 8;
+const SIGNATURE_SIZE: usize = BANDS * ROWS;
+
+/// If the estimated jaccard similarity (in percent) of the shingles of two chunks
+/// is at least this, they're near-duplicates.
+pub const NEAR_DUPLICATE_THRESHOLD: usize = 90;
+
+/// A MinHash signature of a text. It uses one-permutation hashing: each shingle
+/// is hashed once, and each bin keeps the minimum hash of the shingles that fall
+/// into the bin. So it's O(n), even for big chunks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MinHash(Vec<Option<u64>>);
+
+impl MinHash {
+    pub fn new(s: &str) -> Self {
+        // Differences in cases and whitespaces don't matter.
+        let chars = s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase().chars().collect::<Vec<_>>();
+        let mut bins = vec![None; SIGNATURE_SIZE];
+
+        if chars.is_empty() {
+            return MinHash(bins);
+        }
+
+        for shingle in chars.windows(SHINGLE_SIZE.min(chars.len())) {
+            let hash = mix(fnv1a(shingle.iter().flat_map(|c| (*c as u32).to_le_bytes())));
+            let bin = &mut bins[(hash % SIGNATURE_SIZE as u64) as usize];
+            let value = hash / SIGNATURE_SIZE as u64;
+
+            match bin {
+                Some(prev) if *prev <= value => {},
+                _ => { *bin = Some(value); },
+            }
+        }
+
+        MinHash(bins)
+    }
+
+    /// Estimated jaccard similarity in percent. Empty bins of both signatures are ignored.
+    pub fn similarity(&self, other: &MinHash) -> usize {
+        let mut filled = 0;
+        let mut same: usize = 0;
+
+        for (a, b) in self.0.iter().zip(other.0.iter()) {
+            if a.is_none() && b.is_none() {
+                continue;
+            }
+
+            filled += 1;
+
+            if a == b {
+                same += 1;
+            }
+        }
+
+        (same * 100).checked_div(filled).unwrap_or(0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|bin| bin.is_none())
+    }
+
+    // A bin is a u64 in little endian. A value in a bin is at most `u64::MAX / SIGNATURE_SIZE`,
+    // so `u64::MAX` is an empty bin.
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        for bin in self.0.iter() {
+            buffer.extend(bin.unwrap_or(u64::MAX).to_le_bytes());
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        MinHash(bytes.chunks(8).map(
+            |bin| match u64::from_le_bytes(bin.try_into().unwrap()) {
+                u64::MAX => None,
+                value => Some(value),
+            }
+        ).collect())
+    }
+
+    fn bands(&self) -> Vec<(usize, u64)> {
+        self.0.chunks(ROWS).enumerate().map(
+            |(band, rows)| (band, fnv1a(rows.iter().flat_map(|row| row.unwrap_or(u64::MAX).to_le_bytes())))
+        ).collect()
+    }
+}
+
+// The signatures are saved in `.ragit/minhash`, so the hash must not change between
+// builds of ragit. `DefaultHasher` doesn't guarantee that, but FNV-1a does.
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+// splitmix64: lower bits of FNV-1a have to be mixed before they're used as a bin index.
+fn mix(hash: u64) -> u64 {
+    let mut x = hash.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Default)]
+struct NearDuplicatesInner {
+    signatures: HashMap<Uid, MinHash>,
+
+    // (band index, hash of the band) -> chunks
+    buckets: HashMap<(usize, u64), Vec<Uid>>,
+}
+
+/// It remembers the signatures of chunks and finds a near-duplicate of a new
+/// chunk. The workers of `rag build` share an instance, so that a chunk can
+/// find its twin in the chunks that are built by the other workers.
+#[derive(Default)]
+pub struct NearDuplicates {
+    inner: Mutex<NearDuplicatesInner>,
+}
+
+impl NearDuplicates {
+    pub fn new() -> Self {
+        NearDuplicates::default()
+    }
+
+    pub fn insert(&self, uid: Uid, data: &str) {
+        if data.is_empty() {
+            return;
+        }
+
+        self.insert_signature(uid, MinHash::new(data));
+    }
+
+    pub fn insert_signature(&self, uid: Uid, signature: MinHash) {
+        if signature.is_empty() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+
+        for band in signature.bands() {
+            inner.buckets.entry(band).or_default().push(uid);
+        }
+
+        inner.signatures.insert(uid, signature);
+    }
+
+    pub fn get_signatures(&self) -> HashMap<Uid, MinHash> {
+        self.inner.lock().unwrap().signatures.clone()
+    }
+
+    /// It returns a chunk that's a near-duplicate of `data`, if there's any.
+    /// The twin must be built with the same model and the same prompt, because
+    /// its title and summary are going to be reused.
+    pub fn find_twin(&self, index: &Index, data: &str, build_info: &ChunkBuildInfo) -> Option<Chunk> {
+        if data.is_empty() {
+            return None;
+        }
+
+        for uid in self.get_candidates(&MinHash::new(data)) {
+            // The twin might have been removed, e.g. a build of its file has failed.
+            let Ok(twin) = index.get_chunk_by_uid(uid) else {
+                continue;
+            };
+
+            if twin.build_info.model == build_info.model && twin.build_info.prompt_hash == build_info.prompt_hash {
+                return Some(twin);
+            }
+        }
+
+        None
+    }
+
+    // the most similar one comes first
+    fn get_candidates(&self, signature: &MinHash) -> Vec<Uid> {
+        let inner = self.inner.lock().unwrap();
+        let mut candidates = vec![];
+
+        for band in signature.bands() {
+            for uid in inner.buckets.get(&band).map(|uids| uids.as_slice()).unwrap_or(&[]) {
+                if candidates.iter().any(|(candidate, _)| candidate == uid) {
+                    continue;
+                }
+
+                let similarity = signature.similarity(inner.signatures.get(uid).unwrap());
+
+                if similarity >= NEAR_DUPLICATE_THRESHOLD {
+                    candidates.push((*uid, similarity));
+                }
+            }
+        }
+
+        candidates.sort_by_key(|(uid, similarity)| (usize::MAX - *similarity, *uid));
+        candidates.into_iter().map(|(uid, _)| uid).collect()
+    }
+}
+
+// `rag build` caches the signatures of the chunks in `.ragit/minhash`, so that it
+// doesn't have to load all the chunks every time. A signature of a chunk never
+// changes, and the signatures of removed chunks are removed by the next `rag build`.
+// The file starts with `MAGIC`, and each record is a uid (32 bytes) and a signature
+// (`SIGNATURE_SIZE` u64s). If the hash function changes, `MAGIC` has to be changed too.
+const MAGIC: &[u8] = b"minhash1";
+const RECORD_SIZE: usize = 32 + SIGNATURE_SIZE * 8;
+
+pub(crate) fn load_signatures(path: &str) -> Result<HashMap<Uid, MinHash>, Error> {
+    let mut result = HashMap::new();
+
+    if !exists(path) {
+        return Ok(result);
+    }
+
+    let bytes = read_bytes(path)?;
+
+    // It's created by an older version of ragit, whose signatures are not compatible.
+    // `rag build` calculates the signatures again and overwrites the file.
+    let Some(records) = bytes.strip_prefix(MAGIC) else {
+        return Ok(result);
+    };
+
+    if !records.len().is_multiple_of(RECORD_SIZE) {
+        return Err(Error::BrokenIndex(format!("`{path}` is broken.")));
+    }
+
+    for record in records.chunks(RECORD_SIZE) {
+        result.insert(Uid::decode(&record[..32])?, MinHash::decode(&record[32..]));
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn save_signatures(path: &str, signatures: &HashMap<Uid, MinHash>) -> Result<(), Error> {
+    let mut uids = signatures.keys().copied().collect::<Vec<_>>();
+    uids.sort();
+    let mut bytes = Vec::with_capacity(MAGIC.len() + uids.len() * RECORD_SIZE);
+    bytes.extend(MAGIC);
+
+    for uid in uids.iter() {
+        uid.encode(&mut bytes);
+        signatures.get(uid).unwrap().encode(&mut bytes);
+    }
+
+    Ok(write_bytes(path, &bytes, WriteMode::Atomic)?)
+}
+
+/// It removes near-duplicates from `chunks`. If there are near-duplicates,
+/// only the first one survives, so the order of `chunks` has to be by relevance.
+pub fn collapse_near_duplicates(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut result: Vec<(Chunk, MinHash)> = Vec::with_capacity(chunks.len());
+
+    for chunk in chunks.into_iter() {
+        let signature = MinHash::new(&chunk.data);
+
+        if result.iter().any(
+            |(prev, prev_signature)| get_original(prev) == get_original(&chunk) || (
+                !chunk.data.is_empty() && prev_signature.similarity(&signature) >= NEAR_DUPLICATE_THRESHOLD
+            )
+        ) {
+            continue;
+        }
+
+        result.push((chunk, signature));
+    }
+
+    result.into_iter().map(|(chunk, _)| chunk).collect()
+}
+
+fn get_original(chunk: &Chunk) -> Uid {
+    chunk.build_info.duplicate_of.unwrap_or(chunk.uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MinHash, NEAR_DUPLICATE_THRESHOLD, fnv1a};
+
+    #[test]
+    fn minhash_test() {
+        let license = "Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the \"Software\"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions: The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software. THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED.";
+        let reformatted = license.replace(", ", ",\n    ").to_uppercase();
+        let modified = license.replace("free of charge", "free of any charge");
+        let different = "Ragit is a git-like software that turns your local files into a knowledge-base. You can add files, build a knowledge-base and ask questions about it.";
+
+        let license = MinHash::new(license);
+        assert_eq!(license.similarity(&license), 100);
+        assert_eq!(license.similarity(&MinHash::new(&reformatted)), 100);
+        assert!(license.similarity(&MinHash::new(&modified)) >= NEAR_DUPLICATE_THRESHOLD);
+        assert!(license.similarity(&MinHash::new(different)) < 30);
+
+        // empty texts are not similar to anything
+        assert_eq!(MinHash::new("").similarity(&MinHash::new("")), 0);
+
+        // the signatures are saved, so the hash must be stable
+        assert_eq!(fnv1a(b"".iter().copied()), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a".iter().copied()), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar".iter().copied()), 0x85944171f73967e8);
+
+        for signature in [license, MinHash::new("")] {
+            let mut bytes = vec![];
+            signature.encode(&mut bytes);
+            assert_eq!(MinHash::decode(&bytes), signature);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+// Phrase and proximity queries
+//
+// `"write lock"` matches chunks where `write` is right before `lock`, and
+// `error NEAR/3 "write lock"` matches chunks where there are at most 3 terms
+// between `error` and `write lock` (in any order). Both sides are tokenized
+// like tfidf keywords, so `"Write Locks"` also matches `write lock`.
+
+use super::Index;
+use super::ii::{Position, Term};
+use super::tfidf::{self, ProcessedDoc, TfidfResult, get_term_positions, tokenize};
+use crate::error::Error;
+use crate::query::Keywords;
+use crate::uid::Uid;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PositionalQuery {
+    /// The terms appear in this order, right next to each other.
+    Phrase(Vec<Term>),
+
+    /// There are at most `distance` terms between `left` and `right`, in any order.
+    /// Each side is a phrase.
+    Near {
+        left: Vec<Term>,
+        right: Vec<Term>,
+        distance: usize,
+    },
+}
+
+impl PositionalQuery {
+    /// It returns `None` if `s` has no terms (e.g. it's all punctuation).
+    pub fn phrase(s: &str) -> Option<Self> {
+        let terms = tokenize(s);
+
+        if terms.is_empty() {
+            None
+        } else {
+            Some(PositionalQuery::Phrase(terms))
+        }
+    }
+
+    pub fn terms(&self) -> Vec<Term> {
+        match self {
+            PositionalQuery::Phrase(terms) => terms.clone(),
+            PositionalQuery::Near { left, right, .. } => [left.clone(), right.clone()].concat(),
+        }
+    }
+
+    /// If a chunk with `positions` matches the query, it returns the smallest gap
+    /// between the two sides of `NEAR/k` (0 for phrases). `positions` must be sorted.
+    pub fn get_gap(&self, positions: &HashMap<Term, Vec<Position>>) -> Option<usize> {
+        match self {
+            PositionalQuery::Phrase(terms) => if find_phrase(terms, positions).is_empty() { None } else { Some(0) },
+            PositionalQuery::Near { left, right, distance } => {
+                let lefts = find_phrase(left, positions);
+                let rights = find_phrase(right, positions);
+                let mut result = None;
+
+                // Both are sorted, so it walks `rights` once. `rights[..cursor]` end before `l`.
+                let mut cursor = 0;
+
+                for l in lefts.iter().map(|l| *l as usize) {
+                    while cursor < rights.len() && rights[cursor] as usize + right.len() <= l {
+                        cursor += 1;
+                    }
+
+                    // the closest right side that ends before `l`
+                    let before = if cursor > 0 { Some(l - rights[cursor - 1] as usize - right.len()) } else { None };
+
+                    // The closest right side that starts after the left side ends. The ones
+                    // in between overlap the left side, and there are only a few of them.
+                    let after = rights[cursor..].iter().map(|r| *r as usize).find(|r| l + left.len() <= *r).map(|r| r - l - left.len());
+
+                    for gap in [before, after].into_iter().flatten() {
+                        if gap <= *distance {
+                            result = Some(result.map_or(gap, |prev: usize| prev.min(gap)));
+                        }
+                    }
+                }
+
+                result
+            },
+        }
+    }
+}
+
+/// It parses quoted phrases and `NEAR/k` in `query`. It returns the positional queries
+/// and the keywords. The keywords include the terms of the positional queries, so that
+/// tfidf can score the matched chunks.
+pub fn parse_positional_query(query: &str) -> (Vec<PositionalQuery>, Vec<String>) {
+    let mut units = vec![];
+    let mut in_quote = false;
+    let mut curr_quote = String::new();
+    let mut curr_word = String::new();
+
+    for c in query.chars() {
+        if in_quote {
+            if c == '"' {
+                units.push(QueryUnit::Quoted(curr_quote));
+                curr_quote = String::new();
+                in_quote = false;
+            }
+
+            else {
+                curr_quote.push(c);
+            }
+        }
+
+        else if c == '"' || c.is_whitespace() {
+            if !curr_word.is_empty() {
+                units.push(QueryUnit::from_word(&curr_word));
+                curr_word = String::new();
+            }
+
+            in_quote = c == '"';
+        }
+
+        else {
+            curr_word.push(c);
+        }
+    }
+
+    // an unclosed quote is just a word
+    if in_quote {
+        curr_word.push_str(&curr_quote);
+    }
+
+    if !curr_word.is_empty() {
+        units.push(QueryUnit::from_word(&curr_word));
+    }
+
+    let mut queries = vec![];
+    let mut keywords = vec![];
+    let mut cursor = 0;
+
+    while cursor < units.len() {
+        match (&units[cursor], units.get(cursor + 1), units.get(cursor + 2)) {
+            (
+                QueryUnit::Word(left) | QueryUnit::Quoted(left),
+                Some(QueryUnit::Near(distance)),
+                Some(QueryUnit::Word(right) | QueryUnit::Quoted(right)),
+            ) => {
+                let (left_terms, right_terms) = (tokenize(left), tokenize(right));
+
+                if !left_terms.is_empty() && !right_terms.is_empty() {
+                    queries.push(PositionalQuery::Near {
+                        left: left_terms,
+                        right: right_terms,
+                        distance: *distance,
+                    });
+                }
+
+                keywords.push(left.to_string());
+                keywords.push(right.to_string());
+                cursor += 3;
+            },
+            (QueryUnit::Quoted(phrase), _, _) => {
+                if let Some(query) = PositionalQuery::phrase(phrase) {
+                    queries.push(query);
+                }
+
+                keywords.push(phrase.to_string());
+                cursor += 1;
+            },
+            (QueryUnit::Word(word), _, _) => {
+                keywords.push(word.to_string());
+                cursor += 1;
+            },
+            // `NEAR/k` without operands is just a word
+            (QueryUnit::Near(distance), _, _) => {
+                keywords.push(format!("NEAR/{distance}"));
+                cursor += 1;
+            },
+        }
+    }
+
+    (queries, keywords)
+}
+
+enum QueryUnit {
+    Word(String),
+    Quoted(String),
+    Near(usize),
+}
+
+impl QueryUnit {
+    fn from_word(word: &str) -> Self {
+        match word.strip_prefix("NEAR/").map(|distance| distance.parse::<usize>()) {
+            Some(Ok(distance)) => QueryUnit::Near(distance),
+            _ => QueryUnit::Word(word.to_string()),
+        }
+    }
+}
+
+// A phrase match doubles the score, and a `NEAR/k` match gets less as the gap grows.
+fn get_boost(gap: usize) -> f32 {
+    1.0 + 1.0 / (1 + gap) as f32
+}
+
+// start positions of `terms` in a chunk
+fn find_phrase(terms: &[Term], positions: &HashMap<Term, Vec<Position>>) -> Vec<Position> {
+    let Some(first) = terms.first().and_then(|term| positions.get(term)) else {
+        return vec![];
+    };
+
+    first.iter().filter(
+        |start| terms.iter().enumerate().skip(1).all(
+            |(i, term)| match positions.get(term) {
+                Some(positions) => positions.binary_search(&(**start + i as Position)).is_ok(),
+                None => false,
+            }
+        )
+    ).copied().collect()
+}
+
+impl Index {
+    /// Like `run_tfidf`, but the result only has chunks that match all the `positional_queries`.
+    /// Chunks whose `NEAR/k` terms are closer get higher scores.
+    pub fn run_tfidf_with_positions(
+        &self,
+        keywords: Keywords,
+        positional_queries: &[PositionalQuery],
+        limit: usize,
+    ) -> Result<Vec<TfidfResult<Uid>>, Error> {
+        if positional_queries.is_empty() {
+            return self.run_tfidf(keywords, limit);
+        }
+
+        let matches = self.search_positional(positional_queries)?;
+        let mut uids = matches.keys().copied().collect::<Vec<_>>();
+        uids.sort();
+
+        let mut result = self.run_tfidf_on(&uids, keywords, uids.len())?;
+
+        for r in result.iter_mut() {
+            r.score *= get_boost(matches.get(&r.id).copied().unwrap_or(0));
+        }
+
+        result.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        result.truncate(limit);
+        Ok(result)
+    }
+
+    /// Like `run_tfidf`, but the chunks that match all the `positional_queries` get higher
+    /// scores. Unlike `run_tfidf_with_positions`, it doesn't drop the chunks that don't match.
+    pub fn run_tfidf_with_phrase_boost(
+        &self,
+        keywords: Keywords,
+        positional_queries: &[PositionalQuery],
+        limit: usize,
+    ) -> Result<Vec<TfidfResult<Uid>>, Error> {
+        if positional_queries.is_empty() {
+            return self.run_tfidf(keywords, limit);
+        }
+
+        let boost = self.search_positional(positional_queries)?.into_iter().map(
+            |(uid, gap)| (uid, get_boost(gap))
+        ).collect::<HashMap<_, _>>();
+
+        self.run_tfidf_with_boost(keywords, limit, &boost)
+    }
+
+    /// It returns the chunks that match all the queries, with the sum of their gaps
+    /// (see `PositionalQuery::get_gap`).
+    pub fn search_positional(&self, queries: &[PositionalQuery]) -> Result<HashMap<Uid, usize>, Error> {
+        let mut terms = queries.iter().map(|query| query.terms()).collect::<Vec<_>>().concat();
+        terms.sort();
+        terms.dedup();
+
+        // uid -> term -> positions
+        let mut candidates: HashMap<Uid, HashMap<Term, Vec<Position>>> = HashMap::new();
+
+        // The postings have positions, so it doesn't have to read any chunk that doesn't match.
+        if self.query_config.enable_ii && self.is_ii_built() {
+            for (term_index, term) in terms.iter().enumerate() {
+                let postings = self.search_ii_positions_by_term(term)?;
+
+                if term_index == 0 {
+                    for (uid, positions) in postings.into_iter() {
+                        candidates.entry(uid).or_default().insert(term.to_string(), positions);
+                    }
+                }
+
+                else {
+                    let mut new_candidates = HashMap::with_capacity(candidates.len());
+
+                    for (uid, positions) in postings.into_iter() {
+                        if let Some(mut prev) = candidates.remove(&uid) {
+                            prev.insert(term.to_string(), positions);
+                            new_candidates.insert(uid, prev);
+                        }
+                    }
+
+                    candidates = new_candidates;
+                }
+            }
+        }
+
+        else {
+            for tfidf_file in self.get_all_tfidf_files()? {
+                let tfidf = tfidf::load_from_file(&tfidf_file)?;
+
+                if !terms.iter().all(|term| tfidf.contains_term(term)) {
+                    continue;
+                }
+
+                let Some(uid) = tfidf.uid else {
+                    continue;
+                };
+
+                candidates.insert(uid, self.get_term_positions_by_chunk_uid(uid, &tfidf)?);
+            }
+        }
+
+        let mut result = HashMap::new();
+
+        'candidates: for (uid, positions) in candidates.into_iter() {
+            let mut gap_sum = 0;
+
+            for query in queries.iter() {
+                match query.get_gap(&positions) {
+                    Some(gap) => { gap_sum += gap; },
+                    None => { continue 'candidates; },
+                }
+            }
+
+            result.insert(uid, gap_sum);
+        }
+
+        Ok(result)
+    }
+
+    /// A tfidf file created by an older version of ragit doesn't have positions.
+    /// In that case, it tokenizes the chunk again.
+    pub(crate) fn get_term_positions_by_chunk_uid(&self, uid: Uid, tfidf: &ProcessedDoc) -> Result<HashMap<Term, Vec<Position>>, Error> {
+        match tfidf.get_positions() {
+            Some(positions) => Ok(positions.clone()),
+            None => {
+                let chunk = self.get_chunk_by_uid(uid)?;
+
+                if chunk.searchable {
+                    Ok(get_term_positions(&chunk.get_tfidf_fields(&self.root_dir)?))
+                } else {
+                    Ok(HashMap::new())
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PositionalQuery, parse_positional_query};
+    use crate::index::tfidf::get_term_positions;
+
+    #[test]
+    fn positional_query_test() {
+        let (queries, keywords) = parse_positional_query("\"write lock\" deadlock error NEAR/2 \"Write Locks\" NEAR/x");
+        assert_eq!(
+            queries,
+            vec![
+                PositionalQuery::Phrase(vec![String::from("write"), String::from("lock")]),
+                PositionalQuery::Near {
+                    left: vec![String::from("error")],
+                    right: vec![String::from("write"), String::from("lock")],
+                    distance: 2,
+                },
+            ],
+        );
+        assert_eq!(keywords, vec!["write lock", "deadlock", "error", "Write Locks", "NEAR/x"]);
+
+        // an unclosed quote is not a phrase
+        assert_eq!(parse_positional_query("\"write lock").0, vec![]);
+
+        let positions = get_term_positions(&["Failed to acquire a write lock: an error occurred. Lock the write-ahead log."]);
+        let phrase = |s: &str| PositionalQuery::phrase(s).unwrap();
+        let near = |left: &str, right: &str, distance: usize| PositionalQuery::Near {
+            left: vec![left.to_string()],
+            right: vec![right.to_string()],
+            distance,
+        };
+
+        assert_eq!(phrase("write lock").get_gap(&positions), Some(0));
+        assert_eq!(phrase("WRITE-LOCK").get_gap(&positions), Some(0));
+        assert_eq!(phrase("lock write").get_gap(&positions), None);
+        assert_eq!(phrase("acquire write").get_gap(&positions), None);
+
+        // `lock` and `error` have 1 term (`an`) between them
+        assert_eq!(near("lock", "error", 1).get_gap(&positions), Some(1));
+        assert_eq!(near("error", "lock", 1).get_gap(&positions), Some(1));
+        assert_eq!(near("lock", "error", 0).get_gap(&positions), None);
+        assert_eq!(near("fail", "log", 5).get_gap(&positions), None);
+
+        // it finds the closest pair among many matches
+        let positions = get_term_positions(&["lock error a b c lock d e f g error h i lock j k error"]);
+        assert_eq!(near("error", "lock", 1).get_gap(&positions), Some(0));
+        assert_eq!(near("lock", "error", 10).get_gap(&positions), Some(0));
+        assert_eq!(phrase("lock error").get_gap(&positions), Some(0));
+        assert_eq!(near("a", "lock", 2).get_gap(&positions), Some(1));
+        assert_eq!(near("lock", "k", 0).get_gap(&positions), None);
+        assert_eq!(near("lock", "k", 1).get_gap(&positions), Some(1));
+
+        // a phrase never spans 2 fields (e.g. the summary and the data of a chunk)
+        let positions = get_term_positions(&["Someone asked for a write", "lock. There was a deadlock."]);
+        assert_eq!(phrase("write lock").get_gap(&positions), None);
+        assert_eq!(near("write", "deadlock", 5).get_gap(&positions), None);
+    }
+}
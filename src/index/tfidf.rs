@@ -1,6 +1,7 @@
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkSource};
 use crate::error::Error;
 use crate::index::{IMAGE_DIR_NAME, Index};
+use crate::index::ii::Position;
 use crate::query::Keywords;
 use crate::uid::Uid;
 use flate2::Compression;
@@ -22,12 +23,57 @@ use std::io::Read;
 type Term = String;
 type Weight = f32;
 
+// https://en.wikipedia.org/wiki/Okapi_BM25
+// Positions of 2 adjacent fields (e.g. the summary and the data of a chunk) are this far
+// from each other, so that a phrase (or `NEAR/k` with a small k) never spans 2 fields.
+const FIELD_GAP: usize = 1024;
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+// (weight, b) of title, summary and body for BM25F. A title is short and dense,
+// so its length is normalized less.
+const BM25F_FIELDS: [(f32, f32); 3] = [
+    (3.0, 0.5),
+    (1.5, 0.75),
+    (1.0, 0.75),
+];
+
+/// How `TfidfState` scores documents.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ranking {
+    /// It's what ragit has been using: a BM25-like term-frequency with a `log2` idf,
+    /// on a haystack that mixes the title, the summary and the data of a chunk.
+    #[default]
+    Tfidf,
+
+    /// Okapi BM25. If the inverted-index is used, idf is calculated with the entire
+    /// knowledge-base, not with the candidates.
+    Bm25,
+
+    /// BM25F. The title, the summary and the body (source and data) of a chunk are
+    /// different fields, and each field has its own weight and length normalization.
+    /// It falls back to BM25 for tfidf files that don't know their fields.
+    Bm25f,
+}
+
 pub struct TfidfState<DocId> {
     pub terms: HashMap<Term, Weight>,
     term_frequency: HashMap<(DocId, Term), usize>,
     document_frequency: HashMap<Term, usize>,
     document_len: HashMap<DocId, usize>,
     docs: Vec<DocId>,
+    ranking: Ranking,
+
+    // (title, summary) of each document, for BM25F
+    field_frequency: HashMap<(DocId, Term), [usize; 2]>,
+    field_len: HashMap<DocId, [usize; 2]>,
+
+    // If it's set, BM25 uses this (number of documents, document-frequency) instead of
+    // the consumed documents. The inverted-index knows the entire knowledge-base,
+    // while it consumes only a subset of it.
+    corpus_stat: Option<(usize, HashMap<Term, usize>)>,
 }
 
 #[derive(Clone)]
@@ -44,6 +90,44 @@ pub struct ProcessedDoc {
     pub uid: Option<Uid>,
     pub term_frequency: HashMap<Term, usize>,
     length: usize,
+
+    // Term-frequencies of the title and the summary of the chunk. BM25F uses them
+    // to tell the fields apart. They're `None` if the file is created by an older
+    // version of ragit, or the doc is made of multiple chunks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<FieldFrequency>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<FieldFrequency>,
+
+    // Where each term appears in the haystack (see `Chunk::into_tfidf_haystack`).
+    // Phrase and proximity queries use them. It's `None` if the file is created
+    // by an older version of ragit, or the doc is made of multiple chunks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    positions: Option<HashMap<Term, Vec<Position>>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Serialize, PartialEq)]
+struct FieldFrequency {
+    term_frequency: HashMap<Term, usize>,
+    length: usize,
+}
+
+impl FieldFrequency {
+    fn new(s: &str) -> Self {
+        let mut term_frequency = HashMap::new();
+        let mut length = 0;
+
+        for term in tokenize(s) {
+            length += 1;
+            *term_frequency.entry(term).or_insert(0) += 1;
+        }
+
+        FieldFrequency { term_frequency, length }
+    }
+
+    fn get(&self, term: &str) -> usize {
+        self.term_frequency.get(term).copied().unwrap_or(0)
+    }
 }
 
 // tfidf files are always compressed
@@ -58,12 +142,20 @@ pub fn load_from_file(path: &str) -> Result<ProcessedDoc, Error> {
 
 pub fn save_to_file(path: &str, chunk: &Chunk, root_dir: &str) -> Result<(), Error> {
     let tfidf = if chunk.searchable {
-        ProcessedDoc::new(chunk.uid.clone(), &chunk.into_tfidf_haystack(root_dir)?)
+        let fields = chunk.get_tfidf_fields(root_dir)?;
+        let mut tfidf = ProcessedDoc::new(chunk.uid, &fields.join("\n"));
+        tfidf.title = Some(FieldFrequency::new(&chunk.title));
+        tfidf.summary = Some(FieldFrequency::new(&chunk.summary));
+        tfidf.positions = Some(get_term_positions(&fields));
+        tfidf
     } else {
         ProcessedDoc {
             uid: Some(chunk.uid),
             term_frequency: HashMap::new(),
             length: 0,
+            title: None,
+            summary: None,
+            positions: None,
         }
     };
     let result = serde_json::to_vec(&tfidf)?;
@@ -114,6 +206,9 @@ impl ProcessedDoc {
             uid: Some(uid),
             length,
             term_frequency,
+            title: None,
+            summary: None,
+            positions: None,
         }
     }
 
@@ -122,6 +217,9 @@ impl ProcessedDoc {
             uid: None,
             length: 0,
             term_frequency: HashMap::new(),
+            title: None,
+            summary: None,
+            positions: None,
         }
     }
 
@@ -130,6 +228,11 @@ impl ProcessedDoc {
             self.uid = None;
         }
 
+        // fields and positions of different chunks cannot be merged
+        self.title = None;
+        self.summary = None;
+        self.positions = None;
+
         self.length += other.length;
 
         for (term, count) in other.term_frequency.iter() {
@@ -156,6 +259,27 @@ impl ProcessedDoc {
         self.length
     }
 
+    /// It's `None` if the doc doesn't know the positions of its terms.
+    pub fn get_positions(&self) -> Option<&HashMap<Term, Vec<Position>>> {
+        self.positions.as_ref()
+    }
+
+    // (title, summary)
+    fn get_field_frequency(&self, term: &str) -> Option<[usize; 2]> {
+        match (&self.title, &self.summary) {
+            (Some(title), Some(summary)) => Some([title.get(term), summary.get(term)]),
+            _ => None,
+        }
+    }
+
+    // (title, summary)
+    fn get_field_len(&self) -> Option<[usize; 2]> {
+        match (&self.title, &self.summary) {
+            (Some(title), Some(summary)) => Some([title.length, summary.length]),
+            _ => None,
+        }
+    }
+
     pub fn render(&self, term_only: bool, stat_only: bool, json_mode: bool) -> String {
         let mut lines = vec![];
 
@@ -212,9 +336,24 @@ impl<DocId: Clone + Eq + Hash> TfidfState<DocId> {
             document_frequency: HashMap::new(),
             document_len: HashMap::new(),
             docs: vec![],
+            ranking: Ranking::Tfidf,
+            field_frequency: HashMap::new(),
+            field_len: HashMap::new(),
+            corpus_stat: None,
         }
     }
 
+    pub fn with_ranking(mut self, ranking: Ranking) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    /// `document_frequency` is the document-frequency of each term in `self.terms`, in
+    /// the entire corpus (`doc_count` documents). It's used only by BM25 and BM25F.
+    pub fn set_corpus_stat(&mut self, doc_count: usize, document_frequency: HashMap<Term, usize>) {
+        self.corpus_stat = Some((doc_count, document_frequency));
+    }
+
     pub fn consume(
         &mut self,
         doc_id: DocId,
@@ -237,6 +376,14 @@ impl<DocId: Clone + Eq + Hash> TfidfState<DocId> {
                 (doc_id.clone(), term.to_string()),
                 processed_doc.get(term).unwrap_or(0),
             );
+
+            if let Some(field_frequency) = processed_doc.get_field_frequency(term) {
+                self.field_frequency.insert((doc_id.clone(), term.to_string()), field_frequency);
+            }
+        }
+
+        if let Some(field_len) = processed_doc.get_field_len() {
+            self.field_len.insert(doc_id.clone(), field_len);
         }
 
         self.document_len.insert(doc_id.clone(), processed_doc.length());
@@ -246,19 +393,21 @@ impl<DocId: Clone + Eq + Hash> TfidfState<DocId> {
     pub fn get_top(&self, limit: usize) -> Vec<TfidfResult<DocId>> {
         let mut tfidfs: HashMap<DocId, f32> = HashMap::new();
 
-        // https://en.wikipedia.org/wiki/Okapi_BM25
-        let k = 1.2;
-        let b = 0.75;
-
         if self.document_len.is_empty() {
             return vec![];
         }
 
         let avg_len = self.document_len.values().sum::<usize>() as f32 / self.document_len.len() as f32;
+        let avg_field_len = self.get_avg_field_len();
 
         for (term, weight) in self.terms.iter() {
-            let idf = ((self.docs.len() + 1) as f32 / (*self.document_frequency.get(term).unwrap_or(&0) + 1) as f32).log2();
-            let idf = idf.max(0.1);
+            let idf = match self.ranking {
+                Ranking::Tfidf => {
+                    let idf = ((self.docs.len() + 1) as f32 / (*self.document_frequency.get(term).unwrap_or(&0) + 1) as f32).log2();
+                    idf.max(0.1)
+                },
+                Ranking::Bm25 | Ranking::Bm25f => self.get_bm25_idf(term),
+            };
 
             for doc in self.docs.iter() {
                 let t = *self.term_frequency.get(&(doc.clone(), term.to_string())).unwrap_or(&0) as f32;
@@ -268,7 +417,32 @@ impl<DocId: Clone + Eq + Hash> TfidfState<DocId> {
                 }
 
                 let len = *self.document_len.get(doc).unwrap() as f32;
-                let tf = (t * (k + 1.0)) / (t + k * (1.0 - b + b * (len / avg_len)));
+                let tf = match (self.ranking, self.field_len.get(doc)) {
+                    (Ranking::Bm25f, Some(field_len)) => {
+                        let [title, summary] = self.field_frequency.get(&(doc.clone(), term.to_string())).copied().unwrap_or([0, 0]);
+                        let [title_len, summary_len] = *field_len;
+
+                        // The haystack has the title twice. See `Chunk::into_tfidf_haystack`.
+                        let fields = [
+                            (title as f32, title_len as f32),
+                            (summary as f32, summary_len as f32),
+                            (
+                                t - (title * 2 + summary) as f32,
+                                len - (title_len * 2 + summary_len) as f32,
+                            ),
+                        ];
+                        let mut pseudo_tf = 0.0;
+
+                        for (i, (field_tf, field_len)) in fields.into_iter().enumerate() {
+                            let (field_weight, b) = BM25F_FIELDS[i];
+                            let norm = if avg_field_len[i] > 0.0 { 1.0 - b + b * (field_len.max(0.0) / avg_field_len[i]) } else { 1.0 };
+                            pseudo_tf += field_weight * field_tf.max(0.0) / norm;
+                        }
+
+                        (pseudo_tf * (K1 + 1.0)) / (pseudo_tf + K1)
+                    },
+                    _ => (t * (K1 + 1.0)) / (t + K1 * (1.0 - B + B * (len / avg_len))),
+                };
                 let tfidf = tf * idf;
 
                 match tfidfs.entry(doc.clone()) {
@@ -291,6 +465,44 @@ impl<DocId: Clone + Eq + Hash> TfidfState<DocId> {
             tfidfs
         }
     }
+
+    // https://en.wikipedia.org/wiki/Okapi_BM25
+    fn get_bm25_idf(&self, term: &str) -> f32 {
+        let (doc_count, document_frequency) = match &self.corpus_stat {
+            Some((doc_count, document_frequency)) => (
+                *doc_count,
+                document_frequency.get(term).copied().unwrap_or(0),
+            ),
+            None => (
+                self.docs.len(),
+                self.document_frequency.get(term).copied().unwrap_or(0),
+            ),
+        };
+
+        // `doc_count` might be smaller than `document_frequency` if the corpus stat is outdated.
+        let doc_count = doc_count.max(document_frequency) as f32;
+        let document_frequency = document_frequency as f32;
+
+        (1.0 + (doc_count - document_frequency + 0.5) / (document_frequency + 0.5)).ln()
+    }
+
+    // average length of title, summary and body of the documents that know their fields
+    fn get_avg_field_len(&self) -> [f32; 3] {
+        if self.field_len.is_empty() {
+            return [0.0; 3];
+        }
+
+        let mut result = [0.0; 3];
+
+        for (doc, [title, summary]) in self.field_len.iter() {
+            let len = *self.document_len.get(doc).unwrap();
+            result[0] += *title as f32;
+            result[1] += *summary as f32;
+            result[2] += len.saturating_sub(title * 2 + summary) as f32;
+        }
+
+        result.map(|len| len / self.field_len.len() as f32)
+    }
 }
 
 pub fn tokenize(s: &str) -> Vec<String> {
@@ -327,15 +539,40 @@ pub fn tokenize(s: &str) -> Vec<String> {
     result
 }
 
+/// Positions of each term in `tokenize(field)` of each field. The positions are sorted.
+/// There's a gap (`FIELD_GAP`) between the fields.
+pub fn get_term_positions<S: AsRef<str>>(fields: &[S]) -> HashMap<Term, Vec<Position>> {
+    let mut result: HashMap<Term, Vec<Position>> = HashMap::new();
+    let mut offset = 0;
+
+    for field in fields.iter() {
+        let terms = tokenize(field.as_ref());
+        let term_count = terms.len();
+
+        for (position, term) in terms.into_iter().enumerate() {
+            result.entry(term).or_default().push((offset + position) as Position);
+        }
+
+        offset += term_count + FIELD_GAP;
+    }
+
+    result
+}
+
 impl Chunk {
     // very naive heuristic
     // 1. `self.title` is very important, so it's included twice
-    // 2. `self.file` might have an information.
+    // 2. `self.source` might have an information (e.g. the name of the function or the section).
     // 3. `self.summary` has constraints that are not in `self.data`.
     //     - It has explanations on images
     //     - It's always English
     // 4. Images have to be replaced with its description.
     pub fn into_tfidf_haystack(&self, root_dir: &str) -> Result<String, Error> {
+        Ok(self.get_tfidf_fields(root_dir)?.join("\n"))
+    }
+
+    // source, title (twice), summary and data (with image descriptions)
+    pub(crate) fn get_tfidf_fields(&self, root_dir: &str) -> Result<Vec<String>, Error> {
         let mut data = self.data.clone();
 
         for image in self.images.iter() {
@@ -368,13 +605,17 @@ impl Chunk {
             );
         }
 
-        Ok(format!(
-            "{}\n{}\n{}\n{}\n{}",
-            self.render_source(),
-            self.title,
-            self.title,
-            self.summary,
+        // Line numbers (e.g. "src/foo.rs:120-188") are not keywords.
+        let mut source = self.source.clone();
+        let ChunkSource::File { range, .. } = &mut source;
+        *range = None;
+
+        Ok(vec![
+            source.render(),
+            self.title.clone(),
+            self.title.clone(),
+            self.summary.clone(),
             data,
-        ))
+        ])
     }
 }
@@ -0,0 +1,337 @@
+use super::{Index, Path};
+use crate::constant::{INDEX_DIR_NAME, VIRTUAL_FILE_DIR_NAME, VIRTUAL_FILE_SEPARATOR};
+use crate::error::Error;
+use crate::uid::Uid;
+use ragit_fs::{
+    WriteMode,
+    create_dir_all,
+    exists,
+    join,
+    join3,
+    parent,
+    remove_dir_all,
+    write_bytes,
+};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+#[cfg(feature = "archive")]
+use ragit_fs::FileError;
+
+#[cfg(feature = "archive")]
+use std::fs::File;
+
+// Files in an archive (`.zip`, `.tar`, `.tar.gz` or `.tgz`) are staged as virtual files,
+// whose paths look like `bundle.zip!/docs/a.md`. A virtual file is like any other file,
+// except that it's extracted to `.ragit/virtual_files/` while it's being built, because
+// file readers only read real files.
+
+/// `bundle.zip!/docs/a.md` -> `Some(("bundle.zip", "docs/a.md"))`
+pub fn split_virtual_path(rel_path: &str) -> Option<(&str, &str)> {
+    rel_path.split_once(VIRTUAL_FILE_SEPARATOR)
+}
+
+/// If it's false, `rag add` stages the archive itself, not the files in the archive.
+pub fn is_archive(path: &str) -> bool {
+    cfg!(feature = "archive") && archive_kind(path).is_some()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_kind(path: &str) -> Option<ArchiveKind> {
+    let path = path.to_ascii_lowercase();
+
+    if path.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if path.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+// macos junks and nested archives
+fn is_skipped(member: &str) -> bool {
+    member.starts_with("__MACOSX/")
+    || member == ".DS_Store"
+    || member.ends_with("/.DS_Store")
+    || archive_kind(member).is_some()
+}
+
+impl Index {
+    /// `rag add bundle.zip` stages the files in the archive, not the archive itself.
+    /// It also returns the uids of the files, so that `rag add` doesn't have to read
+    /// the archive again for each file.
+    pub(crate) fn unfold_archive(&self, rel_path: &str) -> Result<Vec<(Path, Uid)>, Error> {
+        let real_path = Index::get_data_path(&self.root_dir, &rel_path.to_string())?;
+        let mut result = vec![];
+
+        walk_archive(
+            &real_path,
+            |member, reader| {
+                if !is_skipped(member) {
+                    let path = format!("{rel_path}{VIRTUAL_FILE_SEPARATOR}{member}");
+                    let mut bytes = vec![];
+                    reader.read_to_end(&mut bytes)?;
+                    let uid = Uid::new_virtual_file(&path, &bytes);
+                    result.push((path, uid));
+                }
+
+                Ok(true)
+            },
+        )?;
+
+        Ok(result)
+    }
+
+    /// It works with both real files and virtual files. It reads the entire archive
+    /// to find a virtual file, so if you need uids of many files in an archive, use
+    /// `unfold_archive` or `extract_virtual_files`.
+    pub(crate) fn get_file_uid(&self, rel_path: &str) -> Result<Uid, Error> {
+        match split_virtual_path(rel_path) {
+            Some((archive, member)) => match read_member(&Index::get_data_path(&self.root_dir, &archive.to_string())?, member)? {
+                Some(bytes) => Ok(Uid::new_virtual_file(rel_path, &bytes)),
+                None => Err(Error::NoSuchFile { path: Some(rel_path.to_string()), uid: None }),
+            },
+            None => Uid::new_file(&self.root_dir, &Index::get_data_path(&self.root_dir, &rel_path.to_string())?),
+        }
+    }
+
+    /// It returns the files that do not exist. It works with both real files and
+    /// virtual files, and reads each archive only once.
+    pub(crate) fn get_missing_files(root_dir: &Path, files: Vec<Path>) -> Result<Vec<Path>, Error> {
+        let mut archive_members: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut result = vec![];
+
+        for file in files.into_iter() {
+            let file_exists = match split_virtual_path(&file) {
+                Some((archive, member)) => {
+                    if !archive_members.contains_key(archive) {
+                        archive_members.insert(archive.to_string(), get_archive_members(root_dir, archive)?);
+                    }
+
+                    archive_members.get(archive).unwrap().contains(member)
+                },
+                None => exists(&Index::get_data_path(root_dir, &file)?),
+            };
+
+            if !file_exists {
+                result.push(file);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// File readers need a real file. If it's a virtual file, it returns a path
+    /// in `.ragit/virtual_files/`, and extracts the file if it's not extracted yet.
+    pub(crate) fn get_readable_path(root_dir: &Path, rel_path: &Path) -> Result<Path, Error> {
+        match split_virtual_path(rel_path) {
+            Some((archive, member)) => {
+                let path = join(&Index::get_virtual_file_dir(root_dir, archive)?, member)?;
+
+                if !exists(&path) {
+                    match read_member(&Index::get_data_path(root_dir, &archive.to_string())?, member)? {
+                        Some(bytes) => {
+                            create_dir_all(&parent(&path)?)?;
+                            write_bytes(&path, &bytes, WriteMode::CreateOrTruncate)?;
+                        },
+                        None => {
+                            return Err(Error::NoSuchFile { path: Some(rel_path.to_string()), uid: None });
+                        },
+                    }
+                }
+
+                Ok(path)
+            },
+            None => Index::get_data_path(root_dir, rel_path),
+        }
+    }
+
+    /// It extracts the archives of the staged virtual files before `rag build`, so
+    /// that a file can refer to the other files in the same archive (e.g. images
+    /// of a markdown file). If an archive is broken, it skips the archive, and the
+    /// error is reported when its files are built.
+    ///
+    /// It returns the uids of the extracted files, so that `rag build` doesn't have
+    /// to read the archives again.
+    pub(crate) fn extract_virtual_files(&self) -> Result<HashMap<Path, Uid>, Error> {
+        let archives = self.staged_files.iter().filter_map(
+            |file| split_virtual_path(file).map(|(archive, _)| archive.to_string())
+        ).collect::<HashSet<_>>();
+        let mut result = HashMap::new();
+
+        for archive in archives.iter() {
+            let dir = Index::get_virtual_file_dir(&self.root_dir, archive)?;
+            let _ = walk_archive(
+                &Index::get_data_path(&self.root_dir, archive)?,
+                |member, reader| {
+                    let path = join(&dir, member)?;
+                    let mut bytes = vec![];
+                    reader.read_to_end(&mut bytes)?;
+                    create_dir_all(&parent(&path)?)?;
+                    write_bytes(&path, &bytes, WriteMode::CreateOrTruncate)?;
+
+                    let rel_path = format!("{archive}{VIRTUAL_FILE_SEPARATOR}{member}");
+                    let uid = Uid::new_virtual_file(&rel_path, &bytes);
+                    result.insert(rel_path, uid);
+                    Ok(true)
+                },
+            );
+        }
+
+        Ok(result)
+    }
+
+    /// Extracted files are only needed while building.
+    pub(crate) fn clean_up_virtual_files(&self) -> Result<(), Error> {
+        let dir = join3(&self.root_dir, INDEX_DIR_NAME, VIRTUAL_FILE_DIR_NAME)?;
+
+        if exists(&dir) {
+            remove_dir_all(&dir)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_virtual_file_dir(root_dir: &Path, archive: &str) -> Result<Path, Error> {
+        Ok(join(
+            &join3(root_dir, INDEX_DIR_NAME, VIRTUAL_FILE_DIR_NAME)?,
+            archive,
+        )?)
+    }
+}
+
+// An archive that doesn't exist has no members.
+fn get_archive_members(root_dir: &Path, archive: &str) -> Result<HashSet<String>, Error> {
+    let archive = Index::get_data_path(root_dir, &archive.to_string())?;
+    let mut result = HashSet::new();
+
+    if !exists(&archive) {
+        return Ok(result);
+    }
+
+    walk_archive(
+        &archive,
+        |member, _| {
+            result.insert(member.to_string());
+            Ok(true)
+        },
+    )?;
+
+    Ok(result)
+}
+
+fn read_member(archive: &str, member: &str) -> Result<Option<Vec<u8>>, Error> {
+    let mut result = None;
+
+    walk_archive(
+        archive,
+        |name, reader| {
+            if name == member {
+                let mut bytes = vec![];
+                reader.read_to_end(&mut bytes)?;
+                result = Some(bytes);
+                Ok(false)
+            }
+
+            else {
+                Ok(true)
+            }
+        },
+    )?;
+
+    Ok(result)
+}
+
+// It calls `f` with the path and the content of each file in the archive, until `f`
+// returns false. Directories, symlinks and files with unsafe paths (e.g. `../a.md`)
+// are skipped.
+#[cfg(feature = "archive")]
+fn walk_archive<F: FnMut(&str, &mut dyn Read) -> Result<bool, Error>>(path: &str, mut f: F) -> Result<(), Error> {
+    let file = File::open(path).map_err(|e| FileError::from_std(e, path))?;
+
+    match archive_kind(path) {
+        Some(ArchiveKind::Zip) => {
+            let mut zip = zip::ZipArchive::new(file)?;
+
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+
+                if !file.is_file() {
+                    continue;
+                }
+
+                let name = match file.enclosed_name().and_then(|name| normalize_member_path(&name)) {
+                    Some(name) => name,
+                    None => { continue; },
+                };
+
+                if !f(&name, &mut file)? {
+                    break;
+                }
+            }
+        },
+        Some(kind @ (ArchiveKind::Tar | ArchiveKind::TarGz)) => {
+            let reader: Box<dyn Read> = if kind == ArchiveKind::TarGz {
+                Box::new(flate2::read::GzDecoder::new(file))
+            } else {
+                Box::new(file)
+            };
+            let mut tar = tar::Archive::new(reader);
+
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+
+                let name = match entry.path().ok().and_then(|name| normalize_member_path(&name)) {
+                    Some(name) => name,
+                    None => { continue; },
+                };
+
+                if !f(&name, &mut entry)? {
+                    break;
+                }
+            }
+        },
+        None => {
+            return Err(Error::FileReaderError(format!("`{path}` is not an archive.")));
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "archive"))]
+fn walk_archive<F: FnMut(&str, &mut dyn Read) -> Result<bool, Error>>(path: &str, _f: F) -> Result<(), Error> {
+    Err(Error::FeatureNotEnabled { feature: String::from("archive"), action: format!("read `{path}`") })
+}
+
+#[cfg(feature = "archive")]
+fn normalize_member_path(path: &std::path::Path) -> Option<String> {
+    let mut components = vec![];
+
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(s) => { components.push(s.to_str()?); },
+            std::path::Component::CurDir => {},
+            _ => { return None; },
+        }
+    }
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components.join("/"))
+    }
+}
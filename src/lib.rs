@@ -25,6 +25,8 @@ pub use chunk::{
     ChunkBuildInfo,
     ChunkSource,
     MultiModalContent,
+    SourceRange,
+    TimeRange,
     into_multi_modal_contents,
     merge_and_convert_chunks,
 };
@@ -35,16 +37,29 @@ pub use index::{
     AddResult,
     Audit,
     BuildConfig,
+    BuildOverrides,
     BuildResult,
+    BuildRule,
+    ChunkUnit,
+    Embeddings,
+    EncodingDetection,
+    FileEncoding,
+    FileReaderKind,
+    FileReaderRule,
     IIStatus,
     ImageDescription,
     Index,
     LoadMode,
     MergeMode,
     MergeResult,
+    MinHash,
+    NearDuplicates,
+    PdfMode,
+    PositionalQuery,
     ProcessedDoc,
     PullResult,
     PushResult,
+    Ranking,
     RecoverResult,
     RemoveResult,
     Summary,
@@ -52,7 +67,11 @@ pub use index::{
     TfidfResult,
     UidOrStagedFile,
     VersionInfo,
+    collapse_near_duplicates,
+    estimate_token_count,
     get_compatibility_warning,
+    parse_positional_query,
+    reciprocal_rank_fusion,
 };
 pub use query::{
     Keywords,
@@ -98,10 +117,14 @@ pub fn get_build_options() -> BuildOptions {
         version: VERSION.to_string(),
         profile: profile.to_string(),
         features: vec![
+            (String::from("archive"), cfg!(feature = "archive")),
             (String::from("csv"), cfg!(feature = "csv")),
+            (String::from("epub"), cfg!(feature = "epub")),
             (String::from("korean"), cfg!(feature = "korean")),
+            (String::from("office"), cfg!(feature = "office")),
             (String::from("pdf"), cfg!(feature = "pdf")),
             (String::from("svg"), cfg!(feature = "svg")),
+            (String::from("yaml"), cfg!(feature = "yaml")),
         ].into_iter().collect(),
     }
 }
@@ -16,6 +16,7 @@ use ragit::{
     PullResult,
     PushResult,
     QueryTurn,
+    Ranking,
     RemoveResult,
     SummaryMode,
     UidOrStagedFile,
@@ -23,6 +24,7 @@ use ragit::{
     get_build_options,
     get_compatibility_warning,
     into_multi_modal_contents,
+    parse_positional_query,
     render_query_turns,
 };
 use ragit::schema::{ChunkSchema, Prettify};
@@ -117,6 +119,9 @@ async fn main() {
                 Error::DeprecatedConfig { key, message } => {
                     eprintln!("Config `{key}` is deprecated!\n{message}");
                 },
+                Error::InvalidBuildRule { pattern, message } => {
+                    eprintln!("Build rule `{pattern}` is invalid: {message}");
+                },
                 Error::CliError { message, span } => {
                     eprintln!("cli error: {message}{}",
                         if let Some(span) = span {
@@ -430,6 +435,7 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
         Some("cat-file") => {
             let parsed_args = ArgParser::new()
                 .optional_flag(&["--json"])
+                .optional_flag(&["--source"])
                 .short_flag(&["--json"])
                 .args(ArgType::String, ArgCount::Exact(1))  // uid or path
                 .parse(&args, 2)?;
@@ -443,6 +449,7 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
             let query = parsed_args.get_args_exact(1)?.clone();
             let query_result = index.uid_query(&args, UidQueryConfig::new())?;
             let json_mode = parsed_args.get_flag(0).is_some();
+            let show_source = parsed_args.get_flag(1).is_some();
 
             if query_result.has_multiple_matches() {
                 return Err(Error::UidQueryError(format!("There're multiple file/chunk that match `{}`. Please give more specific query.", query[0])));
@@ -451,6 +458,10 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
             else if let Some(uid) = query_result.get_chunk_uid() {
                 let chunk = index.get_chunk_by_uid(uid)?;
 
+                if show_source && !json_mode {
+                    println!("{}\n", chunk.render_source());
+                }
+
                 if json_mode {
                     println!("{}", serde_json::to_string_pretty(&into_multi_modal_contents(&chunk.data, &chunk.images))?);
                 }
@@ -543,6 +554,16 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                     match index.check() {
                         Ok(()) => {
                             println!("everything is fine!");
+
+                            for (path, encoding) in index.get_files_with_suspicious_encoding()? {
+                                if encoding.is_lossy {
+                                    println!("warning: `{path}` has bytes that are not valid {}. They're replaced with U+FFFD.", encoding.name);
+                                }
+
+                                else {
+                                    println!("warning: ragit failed to guess the encoding of `{path}`, and it's read as {}.", encoding.name);
+                                }
+                            }
                         },
                         Err(e) => if recover {
                             let recover_result = index.recover()?;
@@ -766,7 +787,7 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
         },
         Some("gc") => {
             let parsed_args = ArgParser::new()
-                .flag(&["--logs", "--images", "--audit", "--all"])
+                .flag(&["--logs", "--images", "--audit", "--ii", "--all"])
                 .parse(&args, 2)?;
 
             if parsed_args.show_help() {
@@ -789,11 +810,16 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                     index.gc_audit()?;
                     println!("removed audit logs");
                 },
+                "--ii" => {
+                    let removed = index.gc_ii()?;
+                    println!("removed {removed} inverted-index files");
+                },
                 "--all" => {
                     let removed_logs = index.gc_logs()?;
                     let removed_images = index.gc_images()?;
+                    let removed_ii_files = index.gc_ii()?;
                     index.gc_audit()?;
-                    println!("removed {removed_logs} log files, {removed_images} images and audit logs");
+                    println!("removed {removed_logs} log files, {removed_images} images, {removed_ii_files} inverted-index files and audit logs");
                 },
                 _ => unreachable!(),
             }
@@ -1018,6 +1044,7 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                     println!("{}", chunk.render_source());
                     println!("uid: {}", chunk.uid.abbrev(abbrev));
                     println!("character_len: {}", chunk.char_len);
+                    println!("token_len: {}", chunk.token_len());
                     println!("title: {}", chunk.title);
                     println!("summary: {}", chunk.summary);
                 }
@@ -2704,6 +2731,7 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                 .flag_with_default(&["--keyword", "--query"])
                 .arg_flag_with_default("--limit", "10", ArgType::uinteger())
                 .arg_flag_with_default("--abbrev", "9", ArgType::integer_between(Some(4), Some(64)))
+                .optional_arg_flag("--ranking", ArgType::enum_(&["tfidf", "bm25", "bm25f"]))
                 .short_flag(&["--json"])
                 .args(ArgType::String, ArgCount::Exact(1))
                 .parse(&args, 2)?;
@@ -2718,9 +2746,16 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
             let query_mode = parsed_args.get_flag(2).unwrap_or(String::new()) == "--query";
             let abbrev = parsed_args.arg_flags.get("--abbrev").unwrap().parse::<usize>().unwrap();
 
-            let index = Index::load(root_dir?, LoadMode::OnlyJson)?;
+            let mut index = Index::load(root_dir?, LoadMode::OnlyJson)?;
+
+            // It's okay to change the config because we're not gonna save this.
+            if let Some(ranking) = parsed_args.arg_flags.get("--ranking") {
+                index.query_config.ranking = serde_json::from_value::<Ranking>(Value::String(ranking.to_string()))?;
+            }
+
             let started_at = std::time::Instant::now();
-            let keywords = if query_mode {
+            let (positional_queries, raw_keywords) = parse_positional_query(&parsed_args.get_args_exact(1)?[0]);
+            let mut keywords = if query_mode {
                 let keywords = index.extract_keywords(&parsed_args.get_args_exact(1)?[0]).await?;
 
                 if keywords.is_empty() {
@@ -2731,9 +2766,17 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                 else {
                     keywords
                 }
+            } else if !positional_queries.is_empty() {
+                Keywords::from_raw(raw_keywords.clone())
             } else {
                 Keywords::from_raw(parsed_args.get_args())
             };
+
+            // see `Index::load_chunks_or_tfidf`
+            if query_mode && !positional_queries.is_empty() {
+                keywords.extra.extend(raw_keywords);
+            }
+
             let tokenized_keywords = keywords.tokenize();
             let limit = parsed_args.arg_flags.get("--limit").map(|n| n.parse::<usize>().unwrap()).unwrap();
 
@@ -2741,6 +2784,10 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                 println!("search keywords: {:?}", parsed_args.get_args());
                 println!("tokenized keywords: {:?}", tokenized_keywords.iter().map(|(token, _)| token).collect::<Vec<_>>());
 
+                if !positional_queries.is_empty() {
+                    println!("positional queries: {positional_queries:?}");
+                }
+
                 match index.ii_status {
                     IIStatus::None => if index.query_config.enable_ii {
                         println!("inverted-index not found");
@@ -2761,8 +2808,9 @@ async fn run(args: Vec<String>) -> Result<(), Error> {
                 }
             }
 
-            let tfidf_results = index.run_tfidf(
+            let tfidf_results = index.run_tfidf_with_positions(
                 keywords,
+                &positional_queries,
                 limit,
             )?;
             let mut chunks = Vec::with_capacity(tfidf_results.len());
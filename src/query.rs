@@ -3,7 +3,7 @@ use crate::agent::AgentResponse;
 use crate::chunk::{Chunk, merge_and_convert_chunks};
 use crate::constant::QUERY_HISTORY_DIR_NAME;
 use crate::error::Error;
-use crate::index::Index;
+use crate::index::{Index, collapse_near_duplicates};
 use crate::uid::Uid;
 use ragit_api::Request;
 use ragit_fs::{
@@ -109,6 +109,10 @@ impl Index {
         let tfidf_limit = if super_rerank { max_summaries * 4 } else { max_summaries };
         let mut chunks = self.load_chunks_or_tfidf(query, tfidf_limit).await?;
 
+        if self.query_config.collapse_near_duplicates {
+            chunks = collapse_near_duplicates(chunks);
+        }
+
         // Let's say `max_summaries` is 10 and `chunks.len()` is 40. That means the LLM can handle at most 10 chunks at a time,
         // but 40 chunks are given. So, it calls LLMs 4 times: the first call with the first 10 chunks, the next call with the next
         // 10 chunks, ... In each call, the LLM is asked to select at most 5 relevant chunks in the given 10 chunks.
@@ -1,3 +1,4 @@
+use crate::index::Ranking;
 use serde::{Deserialize, Serialize};
 
 // This struct is used for loading partial configurations from ~/.config/ragit/query.json
@@ -9,6 +10,8 @@ pub struct PartialQueryConfig {
     pub enable_ii: Option<bool>,
     pub enable_rag: Option<bool>,
     pub super_rerank: Option<bool>,
+    pub collapse_near_duplicates: Option<bool>,
+    pub ranking: Option<Ranking>,
 }
 
 impl PartialQueryConfig {
@@ -29,6 +32,12 @@ impl PartialQueryConfig {
         if let Some(super_rerank) = self.super_rerank {
             config.super_rerank = super_rerank;
         }
+        if let Some(collapse_near_duplicates) = self.collapse_near_duplicates {
+            config.collapse_near_duplicates = collapse_near_duplicates;
+        }
+        if let Some(ranking) = self.ranking {
+            config.ranking = ranking;
+        }
     }
 }
 
@@ -61,6 +70,17 @@ pub struct QueryConfig {
     /// It takes more time and money, but is likely to yield better result.
     #[serde(default = "_false")]
     pub super_rerank: bool,
+
+    /// If it's set, near-duplicate chunks (e.g. copies of the same README) are
+    /// collapsed to one before reranking, so the LLM never sees the same text twice.
+    /// It's off by default, like `detect_near_duplicates`, because it changes the
+    /// results of existing knowledge-bases.
+    #[serde(default = "_false")]
+    pub collapse_near_duplicates: bool,
+
+    /// How tfidf search scores chunks: `tfidf`, `bm25` or `bm25f`. See `Ranking`.
+    #[serde(default)]
+    pub ranking: Ranking,
 }
 
 fn _false() -> bool {
@@ -80,6 +100,8 @@ impl Default for QueryConfig {
             enable_ii: true,
             enable_rag: true,
             super_rerank: false,
+            collapse_near_duplicates: false,
+            ranking: Ranking::Tfidf,
         }
     }
 }
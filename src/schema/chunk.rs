@@ -24,6 +24,13 @@ impl Prettify for ChunkSchema {
                 None => {},
             }
 
+            if let Some(uid) = obj.get_mut("build_info").and_then(|build_info| build_info.get_mut("duplicate_of")) {
+                *uid = prettify_uid(uid);
+            }
+
+            // It's not a field of `Chunk`, but it's useful.
+            obj.insert(String::from("token_len"), Value::from(self.token_len()));
+
             match obj.get_mut("timestamp") {
                 Some(timestamp) => { *timestamp = prettify_timestamp(timestamp); },
                 None => {},
@@ -245,9 +245,6 @@ impl Uid {
     pub fn new_file(root_dir: &str, path: &str) -> Result<Self, Error> {
         let size = file_size(path)?;
         let rel_path = get_relative_path(&root_dir.to_string(), &path.to_string())?;
-        let mut file_path_hasher = Sha3_256::new();
-        file_path_hasher.update(rel_path.as_bytes());
-        let file_path_uid = format!("{:064x}", file_path_hasher.finalize()).parse::<Uid>().unwrap();
         let mut file_content_hasher = Sha3_256::new();
 
         if size < 32 * 1024 * 1024 {
@@ -270,12 +267,29 @@ impl Uid {
             }
         }
 
+        Ok(Uid::from_file_content_hasher(&rel_path, file_content_hasher, size))
+    }
+
+    /// A file in an archive (e.g. `bundle.zip!/docs/a.md`). It's the same as
+    /// `Uid::new_file` of the file, if the file were extracted to `rel_path`.
+    pub fn new_virtual_file(rel_path: &str, bytes: &[u8]) -> Self {
+        let mut file_content_hasher = Sha3_256::new();
+        file_content_hasher.update(bytes);
+        Uid::from_file_content_hasher(rel_path, file_content_hasher, bytes.len() as u64)
+    }
+
+    // A file uid is a hash of its content, xor-ed with a hash of its path.
+    fn from_file_content_hasher(rel_path: &str, file_content_hasher: Sha3_256, size: u64) -> Self {
+        let mut file_path_hasher = Sha3_256::new();
+        file_path_hasher.update(rel_path.as_bytes());
+        let file_path_uid = format!("{:064x}", file_path_hasher.finalize()).parse::<Uid>().unwrap();
+
         let mut result = format!("{:064x}", file_content_hasher.finalize()).parse::<Uid>().unwrap();
         result ^= file_path_uid;
         result = result.clear_metadata();
         result.low |= Uid::FILE_TYPE;
         result.low |= (size as u128) & 0xffff_ffff;
-        Ok(result)
+        result
     }
 
     pub fn new_group(uids: &[Uid]) -> Self {
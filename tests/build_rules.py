@@ -0,0 +1,90 @@
+import json
+import os
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def build_rules():
+    goto_root()
+    mk_and_cd_tmp_dir()
+    os.mkdir("logs")
+    os.mkdir("specs")
+
+    for path in ["logs/server.txt", "specs/api.txt", "notes.txt"]:
+        write_string(path, "".join(f"line {i}: something happened.\n" for i in range(400)))
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+
+    # there's no rule by default
+    assert json.loads(cargo_run(["config", "--get", "build_rules"], stdout=True)) == []
+    assert cargo_run(["config", "--set", "build_rules", "[{\"chunk_size\": 1000}]"], check=False) != 0
+
+    # a typo is not silently ignored
+    assert cargo_run(["config", "--set", "build_rules", "[{\"pattern\": \"txt\", \"chunk_sise\": 1000}]"], check=False) != 0
+
+    # `slide_len` (1000 by default) has to be smaller than `chunk_size`
+    assert cargo_run(["config", "--set", "build_rules", "[{\"pattern\": \"txt\", \"chunk_size\": 800}]"], check=False) != 0
+    assert json.loads(cargo_run(["config", "--get", "build_rules"], stdout=True)) == []
+
+    rules = [
+        {"pattern": "logs/**", "chunk_size": 1500, "slide_len": 0},
+
+        # all the matching rules are applied, and the later one wins
+        {"pattern": "txt", "max_summary_len": 2000},
+        {"pattern": "specs/*.txt", "chunk_size": 2000, "max_summary_len": 1500},
+    ]
+    cargo_run(["config", "--set", "build_rules", json.dumps(rules)])
+    assert json.loads(cargo_run(["config", "--get", "build_rules"], stdout=True)) == rules
+    assert "build_rules" in cargo_run(["config", "--get-all"], stdout=True)
+
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+
+    def get_chunks(path: str) -> list[dict]:
+        return json.loads(cargo_run(["ls-chunks", "--json", path], stdout=True))
+
+    expected_overrides = {
+        "logs/server.txt": {"chunk_size": 1500, "slide_len": 0, "max_summary_len": 2000},
+        "specs/api.txt": {"chunk_size": 2000, "max_summary_len": 1500},
+        "notes.txt": {"max_summary_len": 2000},
+    }
+
+    for path, overrides in expected_overrides.items():
+        assert all(chunk["build_info"]["build_overrides"] == overrides for chunk in get_chunks(path))
+
+    # smaller chunks, and no sliding window
+    log_chunks = get_chunks("logs/server.txt")
+    assert len(log_chunks) > len(get_chunks("notes.txt"))
+    assert len(get_chunks("specs/api.txt")) > len(get_chunks("notes.txt"))
+    assert sum(len(chunk["data"]) for chunk in log_chunks) == len("".join(f"line {i}: something happened.\n" for i in range(400)))
+
+    # If a rule changes, only the files that the rule applies to are staged again.
+    rules[0]["chunk_size"] = 1200
+    cargo_run(["config", "--set", "build_rules", json.dumps(rules)])
+    cargo_run(["add", "--all"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True)) == ["logs/server.txt"]
+
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert all(chunk["build_info"]["build_overrides"]["chunk_size"] == 1200 for chunk in get_chunks("logs/server.txt"))
+
+    # a file that no longer matches any rule is also staged again, and so is `logs/server.txt`,
+    # because it loses `max_summary_len` of the 2nd rule
+    cargo_run(["config", "--set", "build_rules", json.dumps(rules[:1])])
+    cargo_run(["add", "--all"])
+    assert sorted(json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True))) == ["logs/server.txt", "notes.txt", "specs/api.txt"]
+
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert all("build_overrides" not in chunk["build_info"] for chunk in get_chunks("notes.txt"))
+
+    # changing a global config doesn't stage anything
+    cargo_run(["config", "--set", "chunk_size", "3000"])
+    cargo_run(["add", "--all"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True)) == []
@@ -0,0 +1,53 @@
+import json
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def chunk_unit():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    english = "The quick brown fox jumps over the lazy dog.\n" * 200
+    korean = "다람쥐 헌 쳇바퀴에 타고파. 키스의 고유조건은 입술끼리 만나야 하고 특별한 기술은 필요치 않다.\n" * 160
+    write_string("english.txt", english)
+    write_string("korean.txt", korean)
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    assert cargo_run(["config", "--get", "chunk_unit"], stdout=True).strip() == "char"
+    assert cargo_run(["config", "--set", "chunk_unit", "byte"], check=False) != 0
+
+    def build() -> tuple[list[dict], list[dict]]:
+        cargo_run(["add", "english.txt", "korean.txt"])
+        cargo_run(["build"])
+        cargo_run(["check"])
+        return (
+            json.loads(cargo_run(["ls-chunks", "--json", "english.txt"], stdout=True)),
+            json.loads(cargo_run(["ls-chunks", "--json", "korean.txt"], stdout=True)),
+        )
+
+    # In characters, the 2 files are split into similar number of chunks.
+    cargo_run(["config", "--set", "chunk_size", "2000"])
+    cargo_run(["config", "--set", "slide_len", "400"])
+    english_chunks, korean_chunks = build()
+    assert abs(len(english_chunks) - len(korean_chunks)) <= 1
+    assert all(chunk["char_len"] <= 4000 for chunk in english_chunks + korean_chunks)
+
+    # ls-chunks shows the number of tokens, and a Korean text has much more tokens.
+    assert all(chunk["token_len"] > 0 for chunk in english_chunks + korean_chunks)
+    assert sum(chunk["token_len"] for chunk in korean_chunks) > sum(chunk["token_len"] for chunk in english_chunks) * 2
+    assert "token_len: " in cargo_run(["ls-chunks", "english.txt"], stdout=True)
+
+    # In tokens, the Korean text has more chunks.
+    cargo_run(["config", "--set", "chunk_unit", "token"])
+    cargo_run(["config", "--set", "chunk_size", "1000"])
+    cargo_run(["config", "--set", "slide_len", "200"])
+    cargo_run(["remove", "--all"])
+    english_chunks, korean_chunks = build()
+    assert len(korean_chunks) > len(english_chunks) * 2
+    assert all(chunk["token_len"] <= 2000 for chunk in english_chunks + korean_chunks)
+
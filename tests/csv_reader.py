@@ -68,3 +68,42 @@ def csv_reader():
 
     for file in ["normal.csv", "empty.csv", "malformed.csv"]:
         assert cargo_run(["cat-file", file], stdout=True).strip() == read_string(file).strip()
+
+    # options: delimiter, quoting, header, rows_per_chunk and markdown tables
+    cargo_run(["remove", "--all"])
+    cargo_run(["config", "--set", "chunk_size", "1000"])
+    cargo_run(["config", "--set", "slide_len", "200"])
+    write_string("catalog.tsv", "table\tcolumn\tdescription\n" + "".join(f"orders\tcol_{i}\tThe {i}th column of the orders table, which is quite long.\n" for i in range(100)))
+    write_string("european.csv", "name;price\n\"Müller; Sohn\";3,50\nSchmidt;2,00\n")
+    write_string("no_header.csv", "Alice,30\n'Bob, Jr.',25\n")
+    write_string("grouped.csv", "id,name\n" + "".join(f"{i},name {i}\n" for i in range(20)))
+    cargo_run(["config", "--set", "file_readers", json.dumps([
+        {"pattern": "catalog.tsv", "reader": "csv", "options": {"format": "markdown"}},
+        {"pattern": "european.csv", "reader": "csv", "options": {"delimiter": ";"}},
+        {"pattern": "no_header.csv", "reader": "csv", "options": {"header": False, "quote": "'"}},
+        {"pattern": "grouped.csv", "reader": "csv", "options": {"rows_per_chunk": 6}},
+    ])])
+    cargo_run(["add", "catalog.tsv", "european.csv", "no_header.csv", "grouped.csv"])
+    cargo_run(["build"], features=["csv"])
+    cargo_run(["check"])
+
+    # every chunk of the markdown table starts with the header
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "catalog.tsv"], stdout=True))
+    assert len(chunks) > 3
+
+    for chunk in chunks:
+        assert chunk["data"].startswith("| table | column | description |\n| --- | --- | --- |\n| orders | col_")
+
+    rows = [line for chunk in chunks for line in chunk["data"].split("\n") if line.startswith("| orders")]
+    assert len(rows) == 100
+
+    content = cargo_run(["cat-file", "european.csv"], stdout=True)
+    assert [json.loads(line) for line in content.split("\n") if line] == [{"name": "Müller; Sohn", "price": "3,50"}, {"name": "Schmidt", "price": "2,00"}]
+
+    content = cargo_run(["cat-file", "no_header.csv"], stdout=True)
+    assert [json.loads(line) for line in content.split("\n") if line] == [["Alice", 30], ["Bob, Jr.", 25]]
+
+    # a chunk never contains more than 6 rows
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "grouped.csv"], stdout=True))
+    assert len(chunks) == 4
+    assert sorted(len([line for line in chunk["data"].split("\n") if line]) for chunk in chunks) == [2, 6, 6, 6]
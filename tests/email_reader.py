@@ -0,0 +1,137 @@
+import base64
+import json
+from utils import (
+    cargo_run,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def email_reader():
+    goto_root()
+    green = base64.b64encode(open("tests/images/green.png", "rb").read()).decode("ascii")
+    green = "\n".join(green[i:(i + 76)] for i in range(0, len(green), 76))
+    mk_and_cd_tmp_dir()
+
+    write_string("sample.eml", f"""From: Alice <alice@example.com>
+To: Bob <bob@example.com>
+Subject: =?utf-8?B?7ZqM7J2YIOyekOujjA==?= (meeting notes)
+Date: Mon, 3 Mar 2025 10:00:00 +0900
+Message-ID: <1@example.com>
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary="outer"
+
+This is a multi-part message in MIME format.
+--outer
+Content-Type: multipart/alternative;
+ boundary="inner"
+
+--inner
+Content-Type: text/plain; charset="euc-kr"
+Content-Transfer-Encoding: quoted-printable
+
+=C8=B8=C0=C7=B7=CF: the release is postponed to a very, very, very long =
+soft-broken line.
+--inner
+Content-Type: text/html; charset="utf-8"
+
+<p>html version of the mail</p>
+--inner--
+--outer
+Content-Type: image/png; name="chart.png"
+Content-Disposition: attachment; filename="chart.png"
+Content-Transfer-Encoding: base64
+
+{green}
+--outer
+Content-Type: application/pdf; name="report.pdf"
+Content-Disposition: attachment; filename="report.pdf"
+Content-Transfer-Encoding: base64
+
+JVBERi0xLjQK
+--outer--
+""")
+
+    write_string("list.mbox", """From alice@example.com Mon Mar  3 10:00:00 2025
+From: Alice <alice@example.com>
+To: list@example.com
+Subject: Release schedule
+Date: Mon, 3 Mar 2025 10:00:00 +0900
+Message-ID: <a@example.com>
+
+When is the next release?
+
+From bob@example.com Mon Mar  3 11:00:00 2025
+From: Bob <bob@example.com>
+To: list@example.com
+Subject: Build is broken
+Date: Mon, 3 Mar 2025 11:00:00 +0900
+Message-ID: <b@example.com>
+
+The build is broken on Windows.
+>From now on, please run the tests.
+
+From carol@example.com Mon Mar  3 12:00:00 2025
+From: Carol <carol@example.com>
+To: list@example.com
+Subject: Re: Release schedule
+Date: Mon, 3 Mar 2025 12:00:00 +0900
+Message-ID: <c@example.com>
+In-Reply-To: <a@example.com>
+References: <a@example.com>
+
+Next Monday.
+""")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["add", "sample.eml", "list.mbox"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert count_images() == 1
+
+    content = cargo_run(["cat-file", "sample.eml"], stdout=True)
+    assert "From: Alice <alice@example.com>\nTo: Bob <bob@example.com>\nDate: Mon, 3 Mar 2025 10:00:00 +0900\nSubject: 회의 자료 (meeting notes)" in content
+    assert "회의록: the release is postponed to a very, very, very long soft-broken line." in content
+    assert "html version" not in content
+    assert "[attachment: report.pdf]" in content
+    assert "multi-part message" not in content
+    assert "JVBERi0xLjQK" not in content
+    assert green[:40] not in content
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.eml"], stdout=True))
+    assert chunks[0]["source"]["item"] == "message 1"
+    assert "heading" not in chunks[0]["source"]
+    assert chunks[0]["source"]["message"] == {
+        "subject": "회의 자료 (meeting notes)",
+        "from": "Alice <alice@example.com>",
+        "to": ["Bob <bob@example.com>"],
+        "date": "Mon, 3 Mar 2025 10:00:00 +0900",
+    }
+
+    # a chunk never contains more than 1 message
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "list.mbox"], stdout=True))
+    assert len(chunks) == 3
+    chunks = {chunk["source"]["item"]: chunk for chunk in chunks}
+    assert chunks["message 1"]["source"]["message"]["subject"] == "Release schedule"
+    assert chunks["message 2"]["source"]["message"]["from"] == "Bob <bob@example.com>"
+    assert "When is the next release?" in chunks["message 1"]["data"]
+    assert "From now on, please run the tests." in chunks["message 2"]["data"]
+    assert ">From" not in chunks["message 2"]["data"]
+    assert "From bob@example.com" not in chunks["message 2"]["data"]
+    assert "Next Monday." in chunks["message 3"]["data"]
+
+    # a chunk never contains more than 1 thread
+    cargo_run(["rm", "list.mbox"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "mbox", "reader": "email", "options": {"thread": True}}])])
+    cargo_run(["add", "list.mbox"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "list.mbox"], stdout=True))
+    assert len(chunks) == 2
+    chunks = {chunk["source"]["item"]: chunk for chunk in chunks}
+    assert chunks["message 1, message 3"]["source"]["message"]["subject"] == "Release schedule"
+    assert chunks["message 1, message 3"]["data"].index("When is the next release?") < chunks["message 1, message 3"]["data"].index("Next Monday.")
+    assert "The build is broken" in chunks["message 2"]["data"]
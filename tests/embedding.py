@@ -0,0 +1,102 @@
+import json
+import os
+from utils import (
+    cargo_run,
+    count_chunks,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    read_string,
+    write_string,
+)
+
+# see the comments in `src/index/embedding.rs`
+def count_embeddings() -> int:
+    if not os.path.exists(".ragit/embeddings/vectors"):
+        return 0
+
+    with open(".ragit/embeddings/vectors", "rb") as f:
+        bytes = f.read()
+
+    model_len = int.from_bytes(bytes[0:4], "little")
+    dimension = int.from_bytes(bytes[(4 + model_len):(8 + model_len)], "little")
+    return (len(bytes) - 8 - model_len) // (32 + dimension * 4)
+
+def embedding():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    write_string("zebra.txt", "A zebra is a zebra. Zebras live in Africa with the other zebras.")
+    write_string("stripes.txt", "A zebra has black and white stripes.")
+
+    for i in range(10):
+        write_string(f"filler-{i}.txt", f"This is filler number {i}. It talks about lions and giraffes.")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+
+    def retrieve(query: str, limit: int) -> list[str]:
+        # If `max_summaries` and `max_retrieval` are the same, it doesn't rerank the chunks.
+        return json.loads(cargo_run([
+            "retrieve-chunks", query,
+            "--max-summaries", str(limit),
+            "--max-retrieval", str(limit),
+            "--uid-only", "--json",
+        ], stdout=True))
+
+    # keyword-only is the default
+    assert cargo_run(["config", "--get", "embedding_model"], stdout=True).strip() == "null"
+    assert count_embeddings() == 0
+    keyword_only = retrieve("zebra", 3)
+
+    # tfidf only finds the chunks that have the keyword
+    assert len(keyword_only) == 2
+
+    # `rag build` embeds the chunks that were built before `embedding_model` is set
+    cargo_run(["config", "--set", "embedding_model", "dummy"])
+    assert cargo_run(["config", "--get", "embedding_model"], stdout=True).strip() == "dummy"
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert count_embeddings() == count_chunks()
+
+    # Vector search ranks every chunk, so the fusion fills the rest of the slots.
+    hybrid = retrieve("zebra", 3)
+    assert len(hybrid) == 3
+    assert set(keyword_only) < set(hybrid)
+
+    # an embedding of a removed chunk is ignored, and the next `rag build` removes it
+    cargo_run(["remove", "stripes.txt"])
+    assert len(retrieve("zebra", 3)) == 3
+    cargo_run(["build"])
+    assert count_embeddings() == count_chunks()
+
+    # If the embedding api is down, `rag build` and `rag query` fall back to tfidf.
+    models = json.loads(read_string(".ragit/models.json"))
+    models.append({
+        "name": "offline-embedding-model",
+        "api_provider": "openai",
+        "api_url": "http://127.0.0.1:11435/v1/embeddings",
+        "api_name": "offline",
+        "can_read_images": False,
+        "input_price": 0.0,
+        "output_price": 0.0,
+    })
+    write_string(".ragit/models.json", json.dumps(models))
+    cargo_run(["config", "--set", "embedding_model", "offline-embedding-model"])
+    cargo_run(["config", "--set", "max_retry", "0"])
+    write_string("stripes.txt", "A zebra has black and white stripes.")
+    cargo_run(["add", "stripes.txt"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert count_embeddings() == 0
+    assert len(retrieve("zebra", 3)) == 2
+
+    # an invalid embedding model is an error
+    cargo_run(["config", "--set", "embedding_model", "invalid-model-name"])
+    assert cargo_run(["retrieve-chunks", "zebra", "--max-summaries", "3", "--max-retrieval", "3"], check=False) != 0
+
+    # back to keyword-only
+    cargo_run(["config", "--set", "embedding_model", "null"])
+    assert len(retrieve("zebra", 3)) == 2
@@ -0,0 +1,139 @@
+import json
+import zipfile
+from utils import (
+    cargo_run,
+    count_files,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+)
+
+CONTAINER = '''<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+    <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>'''
+
+def xhtml(body: str) -> str:
+    return f'''<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>Sample Book</title></head>
+<body>{body}</body>
+</html>'''
+
+def write_epub(path: str, files: dict[str, str | bytes]):
+    with zipfile.ZipFile(path, "w") as f:
+        f.writestr("mimetype", "application/epub+zip", compress_type=zipfile.ZIP_STORED)
+        f.writestr("META-INF/container.xml", CONTAINER)
+
+        for name, content in files.items():
+            f.writestr(name, content)
+
+def epub_reader():
+    goto_root()
+    image = open("tests/images/green.png", "rb").read()
+    mk_and_cd_tmp_dir()
+
+    # epub 3: the spine order is different from the manifest order, and the
+    # titles come from the nav document
+    write_epub("book3.epub", {
+        "OEBPS/content.opf": '''<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+    <manifest>
+        <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        <item id="ch2" href="text/chapter%202.xhtml" media-type="application/xhtml+xml"/>
+        <item id="ch1" href="text/chapter1.xhtml" media-type="application/xhtml+xml"/>
+        <item id="cover" href="text/cover.xhtml" media-type="application/xhtml+xml"/>
+        <item id="img" href="images/green.png" media-type="image/png"/>
+    </manifest>
+    <spine>
+        <itemref idref="cover"/>
+        <itemref idref="ch1"/>
+        <itemref idref="ch2"/>
+    </spine>
+</package>''',
+        "OEBPS/nav.xhtml": xhtml('''<nav epub:type="toc"><ol>
+            <li><a href="text/chapter1.xhtml">The   Beginning</a></li>
+            <li><a href="text/chapter1.xhtml#part2">Part 2 of Chapter 1</a></li>
+        </ol></nav>'''),
+
+        # an empty page
+        "OEBPS/text/cover.xhtml": xhtml("<div> </div>"),
+        "OEBPS/text/chapter1.xhtml": xhtml('''<h1>Chapter One</h1>
+            <p>It was a bright cold day in April.</p>
+            <p><img src="../images/green.png" alt="a green square"/></p>'''),
+        "OEBPS/text/chapter 2.xhtml": xhtml('''<h2>The Second Chapter</h2><p>The clocks were striking thirteen.</p>'''),
+        "OEBPS/images/green.png": image,
+    })
+
+    # epub 2: the titles come from the ncx file
+    write_epub("book2.epub", {
+        "OEBPS/content.opf": '''<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+    <manifest>
+        <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+        <item id="a" href="a.html" media-type="application/xhtml+xml"/>
+        <item id="b" href="b.html" media-type="application/xhtml+xml"/>
+    </manifest>
+    <spine toc="ncx">
+        <itemref idref="a"/>
+        <itemref idref="b"/>
+    </spine>
+</package>''',
+        "OEBPS/toc.ncx": '''<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE ncx PUBLIC "-//NISO//DTD ncx 2005-1//EN" "http://www.daisy.org/z3986/2005/ncx-2005-1.dtd">
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+    <navMap>
+        <navPoint id="p1" playOrder="1"><navLabel><text>Prologue</text></navLabel><content src="a.html"/></navPoint>
+        <navPoint id="p2" playOrder="2"><navLabel><text>Epilogue</text></navLabel><content src="b.html"/></navPoint>
+    </navMap>
+</ncx>''',
+        "OEBPS/a.html": xhtml("<p>Once upon a time.</p>"),
+        "OEBPS/b.html": xhtml("<p>The end.</p>"),
+    })
+
+    with open("broken.epub", "w") as f:
+        f.write("not a zip file")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["add", "book3.epub", "book2.epub"])
+
+    # make sure that it doesn't work without "epub" feature
+    assert "FeatureNotEnabled" in cargo_run(["build"], features=[], stdout=True)
+    assert count_files() == (2, 2, 0)  # (total, staged, processed)
+
+    cargo_run(["build"], features=["epub"])
+    cargo_run(["check"])
+    assert count_files() == (2, 0, 2)
+    assert count_images() == 1
+
+    # chapters are read in the spine order
+    content = cargo_run(["cat-file", "book3.epub"], stdout=True)
+    assert "Sample Book" not in content
+    assert content.index("Chapter One") < content.index("bright cold day") < content.index("striking thirteen")
+
+    # a chunk never contains more than 1 chapter, and the empty cover page has no chunk
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "book3.epub"], stdout=True))
+    assert len(chunks) == 2
+    chunks = {chunk["source"]["item"]: chunk for chunk in chunks}
+    assert chunks["chapter 2"]["source"]["heading"] == "The Beginning"
+    assert "bright cold day" in chunks["chapter 2"]["data"]
+    assert len(chunks["chapter 2"]["images"]) == 1
+
+    # it's not in the table of contents, so the first heading is the title
+    assert chunks["chapter 3"]["source"]["heading"] == "The Second Chapter"
+    assert "striking thirteen" in chunks["chapter 3"]["data"]
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "book2.epub"], stdout=True))
+    chunks = {chunk["source"]["item"]: chunk for chunk in chunks}
+    assert chunks["chapter 1"]["source"]["heading"] == "Prologue"
+    assert chunks["chapter 2"]["source"]["heading"] == "Epilogue"
+    assert "The end." in chunks["chapter 2"]["data"]
+
+    # a broken file
+    cargo_run(["add", "broken.epub"])
+    cargo_run(["build"], features=["epub"])
+    assert count_files() == (3, 1, 2)
+    cargo_run(["check"])
@@ -0,0 +1,76 @@
+import json
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def file_readers():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    write_string("data.tsv", "name\tage\nAlice\t30\nBob\t25\n")
+    write_string("lib.foo", "fn foo() -> u32 {\n    0\n}\n\nfn bar() -> u32 {\n    1\n}\n")
+    write_string("notes.txt", "Hello, World!\n")
+    write_string("notes.foo", "Hello, World!\n")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+
+    # there's no rule by default
+    assert json.loads(cargo_run(["config", "--get", "file_readers"], stdout=True)) == []
+
+    # a rule has to be a valid json array
+    assert cargo_run(["config", "--set", "file_readers", "this is not an array"], check=False) != 0
+    assert cargo_run(["config", "--set", "file_readers", "{}"], check=False) != 0
+
+    # an unknown reader
+    assert cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "tsv", "reader": "no_such_reader"}])], check=False) != 0
+    assert json.loads(cargo_run(["config", "--get", "file_readers"], stdout=True)) == []
+
+    rules = [
+        # a bare extension is treated as `*.tsv`
+        {"pattern": "tsv", "reader": "csv", "options": {"delimiter": "\t"}},
+
+        # the first matching rule wins
+        {"pattern": "notes.foo", "reader": "plain_text"},
+        {"pattern": "*.foo", "reader": "code", "options": {"language": "rust"}},
+    ]
+    cargo_run(["config", "--set", "file_readers", json.dumps(rules)])
+    assert json.loads(cargo_run(["config", "--get", "file_readers"], stdout=True)) == rules
+
+    cargo_run(["add", "--all"])
+    cargo_run(["build"], features=["csv"])
+    cargo_run(["check"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True)) == []
+
+    # csv reader converts the data to jsonl
+    rows = [json.loads(line) for line in cargo_run(["cat-file", "data.tsv"], stdout=True).split("\n") if line.strip()]
+    assert rows == [{"name": "Alice", "age": 30}, {"name": "Bob", "age": 25}]
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "lib.foo"], stdout=True))
+    assert all(chunk["build_info"]["file_reader_key"].startswith("code_reader") for chunk in chunks)
+    assert "foo" in chunks[0]["source"]["item"]
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "notes.foo"], stdout=True))
+    assert all(chunk["build_info"]["file_reader_key"].startswith("plain_text_reader") for chunk in chunks)
+
+    # options are part of `file_reader_key`, so that chunks with different options are distinguished
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "data.tsv"], stdout=True))
+    assert all("delimiter" in chunk["build_info"]["file_reader_key"] for chunk in chunks)
+
+    # an unknown option is an error
+    cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "txt", "reader": "plain_text", "options": {"no_such_option": 1}}])])
+    cargo_run(["remove", "notes.txt"])
+    cargo_run(["add", "notes.txt"])
+    cargo_run(["build"], features=["csv"])
+    assert "notes.txt" not in json.loads(cargo_run(["ls-files", "--processed", "--name-only", "--json"], stdout=True))
+    assert "notes.txt" in json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True))
+
+    # it goes back to the default reader
+    cargo_run(["config", "--set", "file_readers", "[]"])
+    cargo_run(["build"], features=["csv"])
+    cargo_run(["check"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True)) == []
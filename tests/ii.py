@@ -37,6 +37,34 @@ def ii():
     cargo_run(["ii-reset"])
     assert cargo_run(["ii-status"], stdout=True).strip() == "not initialized"
     ii_worker()
+
+    # a small `ii_flush_threshold` makes many segments, and they're merged
+    cargo_run(["config", "--set", "ii_flush_threshold", "64"])
+    cargo_run(["ii-reset"])
+    cargo_run(["ii-build"])
+    cargo_run(["check"])
+    assert count_segments() == 1
+
+    words = [rand_word() for _ in range(20)]
+
+    for i in range(20):
+        write_string(f"segment-{i}.txt", f"This is segment number {i}: {words[i]}")
+        cargo_run(["add", f"segment-{i}.txt"])
+        cargo_run(["build"])
+
+    cargo_run(["remove", "segment-0.txt"])
+    cargo_run(["check"])
+    assert 1 < count_segments() <= 16
+
+    # `rag gc --ii` merges the segments and removes garbage files
+    write_string(".ragit/ii/garbage", "")
+    cargo_run(["gc", "--ii"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+    assert count_segments() == 1
+    assert "garbage" not in os.listdir(".ragit/ii")
+    assert "segment-1.txt" in cargo_run(["tfidf", words[1]], stdout=True)
+    assert "segment-0.txt" not in cargo_run(["tfidf", words[0]], stdout=True)
     os.chdir("..")
 
     # step 2: clone remote knowledge-bases
@@ -127,6 +155,46 @@ def ii_worker():
     assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
     assert "self-introduction.txt" in cargo_run(["tfidf", "Hi, my name is baehyunsol."], stdout=True)
 
+    # `rag build` removes the postings of the old chunks of a modified file
+    old_word, new_word = rand_word(), rand_word()
+    write_string("self-introduction.txt", f"Hi, my name is {old_word}.")
+    cargo_run(["add", "self-introduction.txt"])
+    cargo_run(["build"])
+    write_string("self-introduction.txt", f"Hi, my name is {new_word}.")
+    cargo_run(["add", "self-introduction.txt"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+    assert "self-introduction.txt" not in cargo_run(["tfidf", old_word], stdout=True)
+    assert "self-introduction.txt" in cargo_run(["tfidf", new_word], stdout=True)
+
+    # `rag remove` removes the postings of the removed chunks
+    cargo_run(["remove", "self-introduction.txt"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+    assert "self-introduction.txt" not in cargo_run(["tfidf", new_word], stdout=True)
+
+    # `rag merge` adds the postings of the merged chunks
+    base_dir = os.getcwd()
+    merged_dir = os.path.join("..", f"merged-{rand_word()}")
+    merged_word = rand_word()
+    os.mkdir(merged_dir)
+    os.chdir(merged_dir)
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    write_string("merged.txt", f"This file is from another knowledge-base: {merged_word}.")
+    cargo_run(["add", "merged.txt"])
+    cargo_run(["build"])
+    merged_dir = os.getcwd()
+    os.chdir(base_dir)
+    cargo_run(["merge", merged_dir, "--prefix=merged"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+    assert "merged.txt" in cargo_run(["tfidf", merged_word], stdout=True)
+
+def count_segments() -> int:
+    return len([f for f in os.listdir(".ragit/ii") if f.endswith(".dict")])
+
 def generate_terms():
     dictionary = []
     words = set()
@@ -0,0 +1,128 @@
+import base64
+import json
+from utils import (
+    cargo_run,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def ipynb_reader():
+    goto_root()
+    green = base64.b64encode(open("tests/images/green.png", "rb").read()).decode("ascii")
+    red = base64.b64encode(open("tests/images/red.jpg", "rb").read()).decode("ascii")
+    mk_and_cd_tmp_dir()
+
+    notebook = {
+        "nbformat": 4,
+        "nbformat_minor": 5,
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}, "language_info": {"name": "python"}},
+        "cells": [
+            {
+                "cell_type": "markdown",
+                "metadata": {},
+                "source": ["# Analysis of Sales\n", "\n", "This notebook analyzes sales.\n", "![chart](attachment:chart.png)"],
+                "attachments": {"chart.png": {"image/png": green}},
+            },
+            {
+                "cell_type": "code",
+                "execution_count": 1,
+                "metadata": {},
+                "source": "import pandas as pd\nprint('loaded')",
+                "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["loaded\n"]},
+                ],
+            },
+            # an empty cell
+            {"cell_type": "code", "execution_count": None, "metadata": {}, "source": [], "outputs": []},
+            {
+                "cell_type": "code",
+                "execution_count": 2,
+                "metadata": {},
+                "source": ["df.plot()"],
+                "outputs": [
+                    {
+                        "output_type": "display_data",
+                        "metadata": {},
+                        # the base64 string is wrapped
+                        "data": {"image/jpeg": red[:40] + "\n" + red[40:], "text/plain": ["<Figure size 640x480 with 1 Axes>"]},
+                    },
+                    {
+                        "output_type": "execute_result",
+                        "execution_count": 2,
+                        "metadata": {},
+                        "data": {"text/html": ["<table>...</table>"], "text/plain": ["   revenue\n", "0      100"]},
+                    },
+                ],
+            },
+            {
+                "cell_type": "code",
+                "execution_count": 3,
+                "metadata": {},
+                "source": ["1 / 0"],
+                "outputs": [
+                    {
+                        "output_type": "error",
+                        "ename": "ZeroDivisionError",
+                        "evalue": "division by zero",
+                        "traceback": ["\u001b[0;31m---------------------------------------------------------------------------\u001b[0m"],
+                    },
+                    {"output_type": "stream", "name": "stdout", "text": "training... " * 2000},
+                ],
+            },
+            {"cell_type": "raw", "metadata": {}, "source": "raw text"},
+        ],
+    }
+    write_string("sample.ipynb", json.dumps(notebook, indent=1))
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["add", "sample.ipynb"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+
+    # 1 attachment + 1 output
+    assert count_images() == 2
+
+    content = cargo_run(["cat-file", "sample.ipynb"], stdout=True)
+    assert "# Analysis of Sales" in content
+    assert "```python\nimport pandas as pd\nprint('loaded')\n```\n\n```\nloaded\n```" in content
+    assert "   revenue\n0      100" in content
+    assert "<table>" not in content
+    assert "ZeroDivisionError: division by zero" in content
+    assert "\u001b" not in content
+    assert "(truncated)" in content
+    assert content.count("training...") < 1000
+    assert "raw text" in content
+
+    # base64 strings are not indexed
+    assert green[:40] not in content
+    assert red[:40] not in content
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.ipynb"], stdout=True))
+    assert all("cell" in chunk["source"]["item"] for chunk in chunks)
+    assert "cell 1" in chunks[0]["source"]["item"]
+
+    # the empty cell (cell 3) is skipped
+    assert all("cell 3" not in chunk["source"]["item"].split(", ") for chunk in chunks)
+
+    # it doesn't read the outputs
+    cargo_run(["rm", "sample.ipynb"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "ipynb", "reader": "ipynb", "options": {"outputs": False}}])])
+    cargo_run(["add", "sample.ipynb"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    content = cargo_run(["cat-file", "sample.ipynb"], stdout=True)
+    assert "```python\nimport pandas as pd" in content
+    assert "ZeroDivisionError" not in content
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.ipynb"], stdout=True))
+    assert sum(len(chunk["images"]) for chunk in chunks) == 1
+
+    # a broken notebook
+    write_string("broken.ipynb", "{\"cells\": [")
+    cargo_run(["add", "broken.ipynb"])
+    cargo_run(["build"])
+    assert "broken.ipynb" in json.loads(cargo_run(["ls-files", "--staged", "--name-only", "--json"], stdout=True))
+    cargo_run(["check"])
@@ -0,0 +1,115 @@
+import json
+import os
+import shutil
+from utils import (
+    cargo_run,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def latex_reader():
+    goto_root()
+    image = os.path.abspath("tests/images/green.png")
+    mk_and_cd_tmp_dir()
+    os.mkdir("sections")
+    os.mkdir("figures")
+    shutil.copyfile(image, "figures/plot.png")
+
+    write_string("macros.tex", "\\newcommand{\\R}{\\mathbb{R}}\n\\DeclareMathOperator*{\\argmax}{arg\\,max}\n")
+    write_string("paper.tex", """\\documentclass[11pt]{article}
+\\usepackage{amsmath}
+\\usepackage{graphicx}  % for figures
+\\graphicspath{{figures/}}
+\\input{macros}
+
+\\title{Learning to Rank with Sparse Signals}
+\\author{Alice and Bob}
+
+\\begin{document}
+\\maketitle
+
+% TODO: rewrite the introduction
+\\input{sections/intro}
+\\include{sections/method}
+
+\\section{Conclusion}
+We proposed a method. It costs 5\\% more.
+\\end{document}
+""")
+    write_string("sections/intro.tex", "\\section{Introduction}\n" + "Ranking is important. " * 60 + "\n")
+    write_string("sections/method.tex", "\\section{Method}\n" + "Our method is simple. " * 60 + """
+\\subsection{Training}
+We minimize $\\mathcal{L}(\\theta) = \\sum_{i} \\log p(y_i \\mid x_i)$ over $\\theta \\in \\R^d$.
+\\begin{figure}
+\\includegraphics[width=0.8\\linewidth]{plot}
+\\caption{Training loss.}
+\\end{figure}
+\\begin{verbatim}
+% this is not a comment
+\\end{verbatim}
+""")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["config", "--set", "chunk_size", "1200"])
+    cargo_run(["config", "--set", "slide_len", "200"])
+
+    cargo_run(["add", "paper.tex"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+
+    chunks = json.loads(cargo_run(["ls-chunks", "paper.tex", "--json"], stdout=True))
+    chunks.sort(key=lambda chunk: chunk["source"]["index"])
+    content = "".join(chunk["data"] for chunk in chunks)
+    headings = [chunk["source"].get("heading") for chunk in chunks]
+
+    # the preamble and comments are gone, but the title is not
+    assert chunks[0]["data"].startswith("\\title{Learning to Rank with Sparse Signals}\n\\author{Alice and Bob}")
+    assert "usepackage" not in content
+    assert "newcommand" not in content
+    assert "TODO" not in content
+    assert "5\\% more" in content
+    assert "% this is not a comment" in content
+
+    # math is kept as it is
+    assert "$\\mathcal{L}(\\theta) = \\sum_{i} \\log p(y_i \\mid x_i)$" in content
+
+    # sections start new chunks, and there's no empty chunk between sections
+    assert all(chunk["data"].strip() != "" for chunk in chunks)
+    assert any(chunk["data"].startswith("\\section{Method}") for chunk in chunks)
+    assert "Introduction" in headings
+    assert "Method > Training" in headings
+    assert headings[-1] == "Conclusion"
+    assert all("Ranking" not in chunk["data"] for chunk in chunks if chunk["source"].get("heading") == "Method > Training")
+
+    # `\includegraphics{plot}` is `figures/plot.png`
+    assert count_images() == 1
+
+    # the input files are recorded in the chunks
+    assert sorted(path for path, _ in chunks[0]["build_info"]["dependencies"]) == ["macros.tex", "sections/intro.tex", "sections/method.tex"]
+
+    # if an input file changes, `rag add` stages the main file again
+    cargo_run(["add", "paper.tex"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--json", "--name-only"], stdout=True)) == []
+    write_string("sections/intro.tex", "\\section{Introduction}\n" + "Ranking is very important. " * 60 + "\n")
+    cargo_run(["add", "paper.tex"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--json", "--name-only"], stdout=True)) == ["paper.tex"]
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert "Ranking is very important." in cargo_run(["cat-file", "paper.tex"], stdout=True)
+
+    # a missing file is an error in strict mode
+    write_string("broken.tex", "\\input{missing}\n\\includegraphics{missing}\n")
+    cargo_run(["add", "broken.tex"])
+    cargo_run(["build"])
+    assert json.loads(cargo_run(["ls-files", "--staged", "--json", "--name-only"], stdout=True)) == ["broken.tex"]
+
+    cargo_run(["config", "--set", "strict_file_reader", "false"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    content = cargo_run(["cat-file", "broken.tex"], stdout=True)
+    assert "\\input{missing}" in content
+    assert "\\includegraphics{missing}" in content
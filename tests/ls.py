@@ -111,7 +111,9 @@ def ls():
             continue
 
         lines = chunk.split("\n")
-        file_name = lines[0]
+
+        # e.g. "no_image.md:1-12", it strips the line range
+        file_name = re.sub(r"\:\d+(\-\d+)?$", "", lines[0])
 
         for line in lines:
             if (r := re.match(r"^uid\:\s([a-f0-9]{64})$", line)) is not None:
@@ -1,3 +1,4 @@
+import json
 from utils import (
     cargo_run,
     goto_root,
@@ -134,3 +135,52 @@ def markdown_reader():
 
     # If pdl-escaping was successful, summay of its chunk must have this substring
     assert "<|media(PATH/TO/YOUR/MEDIA/FILE)|>" in cargo_run(["ls-chunks", "sample5.md"], stdout=True)
+
+    # Each chunk knows which section it's from, and `#` and `##` headings are preferred chunk boundaries.
+    sections = [
+        ("## Install", "installation " * 150),
+        ("### Linux", "apt-get " * 150),
+        ("## Usage", "usage " * 300),
+    ]
+    write_string("sectioned.md", "# Guide\n\n" + "".join(f"{heading}\n\n{body}\n\n" for heading, body in sections))
+    cargo_run(["config", "--set", "chunk_size", "2000"])
+    cargo_run(["config", "--set", "slide_len", "400"])
+    cargo_run(["add", "sectioned.md"])
+    cargo_run(["build"], timeout=20.0)
+    cargo_run(["check"])
+
+    chunks = json.loads(cargo_run(["ls-chunks", "sectioned.md", "--json"], stdout=True))
+    chunks.sort(key=lambda chunk: chunk["source"]["index"])
+    headings = [chunk["source"]["heading"] for chunk in chunks]
+    assert headings[0] == "Guide"
+    assert "Guide > Install > Linux" in headings
+    assert headings[-1] == "Guide > Usage"
+
+    # "Usage" section starts a new chunk
+    assert any(chunk["data"].startswith("## Usage") for chunk in chunks)
+    assert all("installation" not in chunk["data"] for chunk in chunks if chunk["source"]["heading"] == "Guide > Usage")
+
+    # the headings are in `ChunkSource::render` and tfidf haystack
+    assert "section `Guide > Usage` in sectioned.md" in cargo_run(["ls-chunks", "sectioned.md"], stdout=True)
+    tfidf_result = json.loads(cargo_run(["tfidf", "Linux", "--json"], stdout=True))
+    assert any("Linux" in r["source"] for r in tfidf_result)
+
+    # Each chunk knows its line range in the file.
+    content = open("sectioned.md", "rb").read()
+    usage_line = content.decode("utf-8").split("\n").index("## Usage") + 1
+
+    for chunk in chunks:
+        range = chunk["source"]["range"]
+        assert content[range["start_byte"]:range["end_byte"]].decode("utf-8") == chunk["data"]
+
+        if chunk["data"].startswith("## Usage"):
+            assert range["start_line"] == usage_line
+
+    last_range = chunks[-1]["source"]["range"]
+    assert last_range["end_byte"] == len(content)
+    assert last_range["end_line"] == content[:-1].count(b"\n") + 1
+    assert f"sectioned.md:{last_range['start_line']}-{last_range['end_line']}" in cargo_run(["cat-file", "--source", chunks[-1]["uid"]], stdout=True)
+
+    # line numbers are in the source, but they're not keywords
+    tfidf_result = json.loads(cargo_run(["tfidf", str(last_range["end_line"]), "--json"], stdout=True))
+    assert all("sectioned.md" not in r["source"] for r in tfidf_result)
@@ -0,0 +1,107 @@
+import json
+import os
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def near_duplicates():
+    goto_root()
+    mk_and_cd_tmp_dir()
+    license = "\n".join([
+        "Permission is hereby granted, free of charge, to any person obtaining a copy",
+        "of this software and associated documentation files (the \"Software\"), to deal",
+        "in the Software without restriction, including without limitation the rights",
+        "to use, copy, modify, merge, publish, distribute, sublicense, and/or sell",
+        "copies of the Software, and to permit persons to whom the Software is",
+        "furnished to do so, subject to the following conditions:",
+        "",
+        "The above copyright notice and this permission notice shall be included in all",
+        "copies or substantial portions of the Software.",
+        "",
+        "THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR",
+        "IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,",
+        "FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.",
+    ])
+
+    for dir in ["a", "b", "c", "d"]:
+        os.mkdir(dir)
+
+    write_string("a/LICENSE", license)
+    write_string("b/LICENSE", license)
+
+    # differences in cases and whitespaces don't matter
+    write_string("c/LICENSE", license.upper().replace("\n", "\n    "))
+
+    # a small modification is still a near-duplicate
+    write_string("d/LICENSE", license.replace("free of charge", "free of any charge"))
+    write_string("notes.txt", "Ragit is a git-like software that turns your local files into a knowledge-base.")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    assert cargo_run(["config", "--get", "detect_near_duplicates"], stdout=True).strip() == "false"
+    assert cargo_run(["config", "--get", "collapse_near_duplicates"], stdout=True).strip() == "false"
+
+    def get_chunks() -> dict[str, dict]:
+        chunks = json.loads(cargo_run(["ls-chunks", "--json"], stdout=True))
+        return {chunk["source"]["path"]: chunk for chunk in chunks}
+
+    # it's disabled by default
+    cargo_run(["add", "a/LICENSE", "b/LICENSE"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert all("duplicate_of" not in chunk["build_info"] for chunk in get_chunks().values())
+
+    # The new files are near-duplicates of the chunks that are already built.
+    cargo_run(["config", "--set", "detect_near_duplicates", "true"])
+    cargo_run(["add", "c/LICENSE", "d/LICENSE", "notes.txt"])
+    cargo_run(["build", "--jobs=2"])
+    cargo_run(["check"])
+    chunks = get_chunks()
+    originals = [chunks["a/LICENSE"]["uid"], chunks["b/LICENSE"]["uid"]]
+
+    for path in ["c/LICENSE", "d/LICENSE"]:
+        original = chunks[path]["build_info"]["duplicate_of"]
+        assert original in originals
+
+        # the title and the summary are copied from the twin
+        twin = [chunk for chunk in chunks.values() if chunk["uid"] == original][0]
+        assert chunks[path]["title"] == twin["title"]
+        assert chunks[path]["summary"] == twin["summary"]
+
+    assert "duplicate_of" not in chunks["notes.txt"]["build_info"]
+
+    # In a single build, duplicates are found in the chunks that are built by the other workers.
+    cargo_run(["rm", "--all"])
+    cargo_run(["add", "--all"])
+    cargo_run(["build", "--jobs=3"])
+    cargo_run(["check"])
+    chunks = get_chunks()
+    licenses = [chunk for path, chunk in chunks.items() if path.endswith("LICENSE")]
+    assert len(licenses) == 4
+    assert 1 <= len([chunk for chunk in licenses if "duplicate_of" not in chunk["build_info"]]) <= 3
+    assert all(
+        chunk["build_info"]["duplicate_of"] in [c["uid"] for c in licenses]
+        for chunk in licenses if "duplicate_of" in chunk["build_info"]
+    )
+
+    # the signatures are cached
+    assert os.path.getsize(".ragit/minhash") == len("minhash1") + len(chunks) * (32 + 128 * 8)
+
+    # a cache of an older version (without a header) is calculated again by the next build
+    with open(".ragit/minhash", "rb") as f:
+        cache = f.read()
+
+    with open(".ragit/minhash", "wb") as f:
+        f.write(cache[len("minhash1"):])
+
+    # A chunk of a staged file cannot be a twin, because it's removed when the file is rebuilt.
+    write_string("notes.txt", "Ragit is a git-like software that turns your local files into a knowledge-base!")
+    cargo_run(["add", "notes.txt"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert "duplicate_of" not in get_chunks()["notes.txt"]["build_info"]
+    assert os.path.getsize(".ragit/minhash") == len("minhash1") + len(chunks) * (32 + 128 * 8)
@@ -0,0 +1,142 @@
+import json
+import zipfile
+from utils import (
+    cargo_run,
+    count_files,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+)
+
+W = 'xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"'
+P = 'xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"'
+X = 'xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"'
+O = 'xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" xmlns:xlink="http://www.w3.org/1999/xlink"'
+
+def rels(relationships: list[tuple[str, str, str]]) -> str:
+    return '<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">' + "".join(
+        f'<Relationship Id="{id}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/{type}" Target="{target}"/>'
+        for id, type, target in relationships
+    ) + '</Relationships>'
+
+def write_zip(path: str, files: dict[str, str | bytes]):
+    with zipfile.ZipFile(path, "w") as f:
+        for name, content in files.items():
+            f.writestr(name, content)
+
+def office_readers():
+    goto_root()
+    image = open("tests/images/green.png", "rb").read()
+    mk_and_cd_tmp_dir()
+
+    # style id "1" is "heading 1" (style ids are localized in some languages)
+    write_zip("sample.docx", {
+        "word/document.xml": f'''<w:document {W}><w:body>
+            <w:p><w:pPr><w:pStyle w:val="1"/></w:pPr><w:r><w:t>Introduction</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Hello, </w:t></w:r><w:r><w:t>World!</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>first item</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="1"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>nested item</w:t></w:r></w:p>
+            <w:tbl>
+                <w:tr><w:tc><w:p><w:r><w:t>name</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>age</w:t></w:r></w:p></w:tc></w:tr>
+                <w:tr><w:tc><w:p><w:r><w:t>Alice</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>30</w:t></w:r></w:p></w:tc></w:tr>
+            </w:tbl>
+            <w:p><w:r><w:drawing><a:graphic><a:graphicData><a:blip r:embed="rId1"/></a:graphicData></a:graphic></w:drawing></w:r></w:p>
+            <w:p><w:r><w:delText>deleted text</w:delText></w:r></w:p>
+        </w:body></w:document>''',
+        "word/styles.xml": f'<w:styles {W}><w:style w:type="paragraph" w:styleId="1"><w:name w:val="heading 1"/></w:style></w:styles>',
+        "word/_rels/document.xml.rels": rels([("rId1", "image", "media/image1.png")]),
+        "word/media/image1.png": image,
+    })
+
+    # `slide2.xml` comes before `slide1.xml` in the presentation
+    write_zip("sample.pptx", {
+        "ppt/presentation.xml": f'<p:presentation {P}><p:sldIdLst><p:sldId id="256" r:id="rId2"/><p:sldId id="257" r:id="rId1"/></p:sldIdLst></p:presentation>',
+        "ppt/_rels/presentation.xml.rels": rels([("rId1", "slide", "slides/slide1.xml"), ("rId2", "slide", "slides/slide2.xml")]),
+        "ppt/slides/slide2.xml": f'''<p:sld {P}><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>First slide</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr/></p:nvSpPr><p:txBody><a:p><a:r><a:t>bullet one</a:t></a:r></a:p><a:p><a:pPr lvl="1"/><a:r><a:t>bullet two</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:sld>''',
+        "ppt/slides/_rels/slide2.xml.rels": rels([("rId1", "notesSlide", "../notesSlides/notesSlide1.xml")]),
+        "ppt/notesSlides/notesSlide1.xml": f'<p:notes {P}><p:cSld><p:spTree><p:sp><p:nvSpPr><p:nvPr><p:ph type="body"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>speaker notes</a:t></a:r></a:p></p:txBody></p:sp></p:spTree></p:cSld></p:notes>',
+        "ppt/slides/slide1.xml": f'''<p:sld {P}><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Second slide</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:pic><p:blipFill><a:blip r:embed="rId1"/></p:blipFill></p:pic>
+        </p:spTree></p:cSld></p:sld>''',
+        "ppt/slides/_rels/slide1.xml.rels": rels([("rId1", "image", "../media/image1.png")]),
+        "ppt/media/image1.png": image,
+    })
+
+    write_zip("sample.xlsx", {
+        "xl/workbook.xml": f'<workbook {X}><sheets><sheet name="People" sheetId="1" r:id="rId1"/><sheet name="Empty" sheetId="2" r:id="rId2"/><sheet name="Cities" sheetId="3" r:id="rId3"/></sheets></workbook>',
+        "xl/_rels/workbook.xml.rels": rels([("rId1", "worksheet", "worksheets/sheet1.xml"), ("rId2", "worksheet", "worksheets/sheet2.xml"), ("rId3", "worksheet", "/xl/worksheets/sheet3.xml")]),
+        "xl/sharedStrings.xml": f'<sst {X}><si><t>name</t></si><si><t>age</t></si><si><r><t>Al</t></r><r><t>ice</t></r></si><si><t>Bob</t></si></sst>',
+        "xl/worksheets/sheet1.xml": f'''<worksheet {X}><sheetData>
+            <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c></row>
+            <row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2"><v>30</v></c></row>
+            <row r="3"><c r="A3" t="s"><v>3</v></c><c r="C3" t="b"><v>1</v></c></row>
+        </sheetData></worksheet>''',
+        "xl/worksheets/sheet2.xml": f'<worksheet {X}><sheetData/></worksheet>',
+        "xl/worksheets/sheet3.xml": f'<worksheet {X}><sheetData><row r="1"><c r="A1" t="inlineStr"><is><t>city</t></is></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>Seoul</t></is></c></row></sheetData></worksheet>',
+    })
+
+    write_zip("sample.odt", {
+        "content.xml": f'''<office:document-content {O}><office:body><office:text>
+            <text:h text:outline-level="2">Overview</text:h>
+            <text:p>Hello,   <text:span>OpenDocument</text:span>!<text:s text:c="2"/>spaces</text:p>
+            <text:list><text:list-item><text:p>apple</text:p><text:list><text:list-item><text:p>banana</text:p></text:list-item></text:list></text:list-item></text:list>
+            <table:table><table:table-row><table:table-cell><text:p>key</text:p></table:table-cell><table:table-cell><text:p>value</text:p></table:table-cell></table:table-row></table:table>
+            <text:p><draw:frame><draw:image xlink:href="Pictures/image1.png"/></draw:frame></text:p>
+        </office:text></office:body></office:document-content>''',
+        "Pictures/image1.png": image,
+    })
+
+    write_zip("broken.docx", {"hello.txt": "This is not a docx file."})
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["add", "sample.docx", "sample.pptx", "sample.xlsx", "sample.odt"])
+
+    # make sure that it doesn't work without "office" feature
+    assert "FeatureNotEnabled" in cargo_run(["build"], features=[], stdout=True)
+    assert count_files() == (4, 4, 0)  # (total, staged, processed)
+
+    cargo_run(["build"], features=["office"])
+    cargo_run(["check"])
+    assert count_files() == (4, 0, 4)
+
+    # the same image in 3 files
+    assert count_images() == 1
+
+    docx = cargo_run(["cat-file", "sample.docx"], stdout=True)
+    assert "# Introduction\n\nHello, World!\n\n- first item\n  - nested item\n" in docx
+    assert "| name | age |\n| --- | --- |\n| Alice | 30 |" in docx
+    assert "deleted text" not in docx
+
+    odt = cargo_run(["cat-file", "sample.odt"], stdout=True)
+    assert "## Overview\n\nHello, OpenDocument!  spaces\n\n- apple\n  - banana\n" in odt
+    assert "| key | value |" in odt
+
+    # a chunk never contains more than 1 slide
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.pptx"], stdout=True))
+    assert len(chunks) == 2
+    chunks = {chunk["source"]["page"]: chunk for chunk in chunks}
+    assert "First slide" in chunks[1]["data"]
+    assert "  bullet two" in chunks[1]["data"]
+    assert "speaker notes" in chunks[1]["data"]
+    assert "Second slide" in chunks[2]["data"]
+    assert len(chunks[2]["images"]) == 1
+
+    # a chunk never contains more than 1 sheet, and an empty sheet has no chunk
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.xlsx"], stdout=True))
+    assert len(chunks) == 2
+    chunks = {chunk["source"]["item"]: chunk for chunk in chunks}
+    rows = [json.loads(row) for row in chunks["sheet `People`"]["data"].split("\n") if row.strip()]
+    assert rows == [{"name": "Alice", "age": 30}, {"name": "Bob", "C": "TRUE"}]
+    assert json.loads(chunks["sheet `Cities`"]["data"].strip()) == {"city": "Seoul"}
+
+    # a broken file
+    cargo_run(["add", "broken.docx"])
+    cargo_run(["build"], features=["office"])
+    assert count_files() == (5, 1, 4)
+    cargo_run(["check"])
@@ -64,3 +64,109 @@ def pdf(test_model: str):
                 if pdf["query"] is not None:
                     query, answer = pdf["query"]
                     assert answer in cargo_run(["query", query], stdout=True)
+
+    # `pdf_mode`: the dummy model can read the text layer of the pdf files
+    goto_root()
+    mk_and_cd_tmp_dir()
+    cargo_run(["init"])
+    shutil.copyfile("../tests/pdfs/landscape.pdf", "landscape.pdf")
+    shutil.copyfile("../tests/pdfs/portrait.pdf", "portrait.pdf")
+    cargo_run(["add", "landscape.pdf", "portrait.pdf"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    assert cargo_run(["config", "--set", "pdf_mode", "ocr"], check=False) != 0
+    cargo_run(["config", "--set", "pdf_mode", "text"])
+    cargo_run(["build"], features=["pdf"])
+    cargo_run(["check"])
+
+    for pdf in pdfs:
+        chunks = json.loads(cargo_run(["ls-chunks", pdf["name"], "--json"], stdout=True))
+
+        # a page may be split into multiple chunks, but a chunk never contains more than 1 page
+        page_nos = sorted(set(chunk["source"]["page"] for chunk in chunks))
+        assert page_nos == list(range(1, pdf["pages"] + 1))
+        assert all(len(chunk["images"]) == 0 for chunk in chunks)
+
+        for keyword in pdf["keywords"]:
+            search_result = json.loads(cargo_run(["tfidf", keyword, "--json"], stdout=True))
+            assert any([pdf["name"] in r["source"] for r in search_result])
+
+    # changing `pdf_mode` doesn't rebuild processed files
+    chunk_uids = sorted(chunk["uid"] for chunk in json.loads(cargo_run(["ls-chunks", "--json"], stdout=True)))
+    cargo_run(["config", "--set", "pdf_mode", "hybrid"])
+    cargo_run(["add", "landscape.pdf", "portrait.pdf"])
+    assert count_files() == (2, 0, 2)
+    cargo_run(["build"], features=["pdf"])
+    cargo_run(["check"])
+    assert chunk_uids == sorted(chunk["uid"] for chunk in json.loads(cargo_run(["ls-chunks", "--json"], stdout=True)))
+
+    # `hybrid` mode: a page with a figure is also converted to an image
+    write_figure_pdf("figure.pdf", "../tests/images/red.jpg")
+    cargo_run(["add", "figure.pdf"])
+    cargo_run(["build"], features=["pdf"])
+    cargo_run(["check"])
+    chunks = json.loads(cargo_run(["ls-chunks", "figure.pdf", "--json"], stdout=True))
+    images = {}
+
+    for chunk in chunks:
+        page_no = chunk["source"]["page"]
+        images[page_no] = images.get(page_no, 0) + len(chunk["images"])
+
+    # page 1 has a figure and page 2 has only text
+    assert images[1] > 0
+    assert images[2] == 0
+
+# It writes a 2-page pdf file. The first page has a jpeg image and the second
+# page has only text. Both pages have enough text to pass `MIN_TEXT_LEN`.
+def write_figure_pdf(path: str, jpeg_path: str):
+    with open(jpeg_path, "rb") as f:
+        jpeg = f.read()
+
+    width, height = jpeg_size(jpeg)
+    text = "BT /F1 11 Tf 14 TL 72 740 Td " + " ".join(
+        f"(This is line {i} of the text layer, which is long enough for the text mode.) '"
+        for i in range(5)
+    ) + " ET"
+    pages = [
+        (text + " q 300 0 0 400 150 150 cm /Im1 Do Q").encode("ascii"),
+        text.encode("ascii"),
+    ]
+    objects = [
+        b"<< /Type /Catalog /Pages 2 0 R >>",
+        b"<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+        b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 5 0 R >> /XObject << /Im1 6 0 R >> >> /Contents 7 0 R >>",
+        b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 5 0 R >> >> /Contents 8 0 R >>",
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+        f"<< /Type /XObject /Subtype /Image /Width {width} /Height {height} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {len(jpeg)} >>\nstream\n".encode("ascii") + jpeg + b"\nendstream",
+        *[f"<< /Length {len(page)} >>\nstream\n".encode("ascii") + page + b"\nendstream" for page in pages],
+    ]
+    result = b"%PDF-1.4\n"
+    offsets = []
+
+    for i, obj in enumerate(objects):
+        offsets.append(len(result))
+        result += f"{i + 1} 0 obj\n".encode("ascii") + obj + b"\nendobj\n"
+
+    xref = len(result)
+    result += f"xref\n0 {len(objects) + 1}\n0000000000 65535 f \n".encode("ascii")
+    result += b"".join(f"{offset:010} 00000 n \n".encode("ascii") for offset in offsets)
+    result += f"trailer\n<< /Size {len(objects) + 1} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n".encode("ascii")
+
+    with open(path, "wb") as f:
+        f.write(result)
+
+# (width, height) from the SOF marker of a jpeg file
+def jpeg_size(jpeg: bytes) -> tuple[int, int]:
+    i = 2
+
+    while i < len(jpeg):
+        marker = jpeg[i + 1]
+        length = int.from_bytes(jpeg[i + 2:i + 4], "big")
+
+        if marker in [0xc0, 0xc1, 0xc2]:
+            height = int.from_bytes(jpeg[i + 5:i + 7], "big")
+            width = int.from_bytes(jpeg[i + 7:i + 9], "big")
+            return width, height
+
+        i += 2 + length
+
+    raise ValueError("invalid jpeg file")
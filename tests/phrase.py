@@ -0,0 +1,88 @@
+import json
+import os
+import re
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def get_segment_files() -> list[str]:
+    return [f for f in os.listdir(".ragit/ii") if f.startswith("segment-")]
+
+def phrase():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    write_string("exact.txt", "Failed to acquire a write lock because of a deadlock.")
+    write_string("scattered.txt", "The lock was released before the write finished.")
+    write_string("far.txt", "There was a deadlock. " + " ".join(f"Then step {i} happened." for i in range(10)) + " Finally, someone asked for a write lock.")
+
+    for i in range(5):
+        write_string(f"filler-{i}.txt", f"This is filler number {i}. It talks about lions and giraffes.")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+
+    # the inverted-index is stored in segments
+    assert "segments.json" in os.listdir(".ragit/ii")
+    assert len(get_segment_files()) > 0
+
+    # e.g. "1st chunk of exact.txt:1" -> "exact.txt"
+    def get_file_name(source: str) -> str:
+        return re.sub(r"\:\d+(\-\d+)?$", "", source.split(" of ")[1])
+
+    def search(keywords: str) -> set[str]:
+        result = json.loads(cargo_run(["tfidf", keywords, "--json"], stdout=True))
+        return {get_file_name(r["source"]) for r in result}
+
+    for enable_ii in ["true", "false"]:
+        cargo_run(["config", "--set", "enable_ii", enable_ii])
+
+        assert search("write lock") == {"exact.txt", "scattered.txt", "far.txt"}
+        assert search("\"write lock\"") == {"exact.txt", "far.txt"}
+        assert search("\"Write-Locks\"") == {"exact.txt", "far.txt"}
+        assert search("\"lock write\"") == set()
+        assert search("deadlock NEAR/5 \"write lock\"") == {"exact.txt"}
+        assert search("\"write lock\" NEAR/5 deadlock") == {"exact.txt"}
+        assert search("deadlock NEAR/100 \"write lock\"") == {"exact.txt", "far.txt"}
+
+        # a closer match gets a higher score
+        result = json.loads(cargo_run(["tfidf", "deadlock NEAR/100 \"write lock\"", "--json"], stdout=True))
+        assert get_file_name(result[0]["source"]) == "exact.txt"
+
+        # `rag retrieve-chunks` ranks the chunks that match the phrases higher
+        result = json.loads(cargo_run([
+            "retrieve-chunks", "What does \"write lock\" mean?",
+            "--max-summaries", "2",
+            "--max-retrieval", "2",
+            "--json",
+        ], stdout=True))
+        assert {chunk["source"]["path"] for chunk in result} == {"exact.txt", "far.txt"}
+
+        # ... but it doesn't filter out the chunks that don't match
+        result = json.loads(cargo_run([
+            "retrieve-chunks", "Why did the \"lock write\" happen?",
+            "--max-summaries", "3",
+            "--max-retrieval", "3",
+            "--json",
+        ], stdout=True))
+        assert "scattered.txt" in {chunk["source"]["path"] for chunk in result}
+
+    # an ii built by an older version of ragit (a file per term) is outdated
+    cargo_run(["config", "--set", "enable_ii", "true"])
+    os.mkdir(".ragit/ii/ab")
+    write_string(".ragit/ii/ab/cdef", "")
+    assert cargo_run(["ii-status"], stdout=True).strip() == "outdated"
+    assert search("\"write lock\"") == {"exact.txt", "far.txt"}
+
+    cargo_run(["ii-build"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+    assert "ab" not in os.listdir(".ragit/ii")
+    assert search("\"write lock\"") == {"exact.txt", "far.txt"}
@@ -0,0 +1,55 @@
+import json
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def prefer_boundaries():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    paragraphs = []
+
+    for i in range(40):
+        sentences = [f"Sentence {j} of paragraph {i} has {'many ' * (j % 5)}words in it." for j in range(3 + i % 4)]
+        paragraphs.append(" ".join(sentences))
+
+    write_string("sample.txt", "\n\n".join(paragraphs) + "\n")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["config", "--set", "chunk_size", "1000"])
+    cargo_run(["config", "--set", "slide_len", "200"])
+    assert cargo_run(["config", "--get", "prefer_boundaries"], stdout=True).strip() == "false"
+
+    def build() -> list[str]:
+        cargo_run(["add", "sample.txt"])
+        cargo_run(["build"])
+        cargo_run(["check"])
+        chunks = json.loads(cargo_run(["ls-chunks", "--json", "sample.txt"], stdout=True))
+        chunks.sort(key=lambda chunk: chunk["source"]["index"])
+        return [chunk["data"] for chunk in chunks]
+
+    # By default, chunks are cut in the middle of sentences.
+    chunks = build()
+    assert any(not chunk.rstrip().endswith(".") for chunk in chunks[:-1])
+
+    cargo_run(["config", "--set", "prefer_boundaries", "true"])
+    cargo_run(["remove", "--all"])
+    chunks = build()
+    assert len(chunks) > 1
+
+    for chunk in chunks:
+        # chunks and sliding windows start and end at sentences
+        assert chunk.strip()[0] == "S"
+        assert chunk.rstrip().endswith(".")
+
+        # a chunk is a bit smaller or bigger than `chunk_size`, but not too much
+        assert len(chunk) <= 1200
+
+    # a paragraph is preferred to a sentence
+    paragraph_ends = [chunk for chunk in chunks[:-1] if any(chunk.rstrip().endswith(paragraph) for paragraph in paragraphs)]
+    assert len(paragraph_ends) >= len(chunks) - 2
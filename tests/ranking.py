@@ -0,0 +1,53 @@
+import json
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def ranking():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    # `dense.txt` is short and has many zebras, so every ranking has to put it first.
+    write_string("dense.txt", "A zebra is a zebra. Zebras live in Africa with the other zebras.")
+    write_string("sparse.txt", " ".join(f"Animal number {i} is not what we're looking for." for i in range(60)) + " A zebra is here.")
+    write_string("unrelated.txt", "Ragit is a git-like software that turns your local files into a knowledge-base.")
+
+    for i in range(10):
+        write_string(f"filler-{i}.txt", f"This is filler number {i}. It talks about lions and giraffes.")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    assert cargo_run(["config", "--get", "ranking"], stdout=True).strip() == "tfidf"
+
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert cargo_run(["ii-status"], stdout=True).strip() == "complete"
+
+    def search(keywords: str, ranking: str) -> list[dict]:
+        return json.loads(cargo_run(["tfidf", keywords, "--ranking", ranking, "--json"], stdout=True))
+
+    assert cargo_run(["tfidf", "zebra", "--ranking", "cosine"], check=False) != 0
+    results = {}
+
+    for enable_ii in ["true", "false"]:
+        cargo_run(["config", "--set", "enable_ii", enable_ii])
+
+        for ranking in ["tfidf", "bm25", "bm25f"]:
+            result = search("zebra", ranking)
+            assert [r["source"] for r in result] == ["1st chunk of dense.txt:1", "1st chunk of sparse.txt:1"]
+            results[(enable_ii, ranking)] = result
+
+        # `--ranking` doesn't change the config
+        assert cargo_run(["config", "--get", "ranking"], stdout=True).strip() == "tfidf"
+
+    # bm25 has a different idf than tfidf
+    assert [r["score"] for r in results[("true", "tfidf")]] != [r["score"] for r in results[("true", "bm25")]]
+
+    # `rag tfidf` uses the config if `--ranking` is not set
+    cargo_run(["config", "--set", "ranking", "bm25f"])
+    assert cargo_run(["config", "--get", "ranking"], stdout=True).strip() == "bm25f"
+    assert [r["uid"] for r in json.loads(cargo_run(["tfidf", "zebra", "--json"], stdout=True))] == [r["uid"] for r in results[("true", "bm25f")]]
@@ -0,0 +1,123 @@
+import json
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def structured_reader():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    # an OpenAPI spec that's too big for a chunk
+    spec = {
+        "openapi": "3.0.0",
+        "info": {"title": "Pet Store", "version": "1.0.0"},
+        "paths": {
+            f"/pets/{i}/{{id}}": {
+                "get": {
+                    "summary": f"Find pet number {i} by its id",
+                    "parameters": [{"name": "id", "in": "path", "required": True}],
+                    "responses": {"200": {"description": f"pet number {i}"}},
+                },
+            } for i in range(80)
+        },
+    }
+    write_string("openapi.json", json.dumps(spec, indent=4))
+    write_string("deployment.yaml", """# a kubernetes deployment
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 3
+  template:
+    spec:
+      containers:
+      - name: web
+        image: nginx:1.25
+        ports:
+        - containerPort: 80
+      - name: sidecar
+        image: "envoy:1.30"
+        args: [--config, /etc/envoy.yaml]
+---
+apiVersion: v1
+kind: Service
+metadata:
+  name: web-service
+""")
+    write_string("app.toml", """[package]
+name = "sample"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+
+[[bin]]
+name = "sample-cli"
+path = "src/main.rs"
+""")
+
+    # json with comments is not a valid json
+    write_string("tsconfig.json", """{
+    // compiler options
+    "compilerOptions": { "strict": true }
+}""")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["config", "--set", "chunk_size", "2000"])
+    cargo_run(["config", "--set", "slide_len", "400"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "*.spec", "reader": "structured", "options": {"format": "yaml"}}])])
+    write_string("compose.spec", "services:\n  db:\n    image: postgres\n")
+    cargo_run(["add", "openapi.json", "deployment.yaml", "app.toml", "tsconfig.json", "compose.spec"])
+
+    # it cannot read yaml files without "yaml" feature
+    assert "FeatureNotEnabled" in cargo_run(["build"], features=[], stdout=True)
+    cargo_run(["check"])
+    staged_files = json.loads(cargo_run(["ls-files", "--staged", "--json", "--name-only"], stdout=True))
+    assert sorted(staged_files) == ["compose.spec", "deployment.yaml", "tsconfig.json"]
+
+    cargo_run(["build"], features=["yaml"])
+    cargo_run(["check"])
+
+    # it cannot parse tsconfig.json in strict mode
+    staged_files = json.loads(cargo_run(["ls-files", "--staged", "--json", "--name-only"], stdout=True))
+    assert staged_files == ["tsconfig.json"]
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "openapi.json"], stdout=True))
+    assert len(chunks) > 1
+
+    # each chunk knows which path it's from
+    for chunk in chunks:
+        for line in chunk["data"].split("\n"):
+            if "summary" in line:
+                i = int(line.split("Find pet number ")[1].split(" ")[0])
+                assert f'"paths[\\"/pets/{i}/{{id}}\\"].get.summary"' in line
+
+    content = cargo_run(["cat-file", "openapi.json"], stdout=True)
+    assert '{"openapi": "3.0.0"}\n{"info.title": "Pet Store", "info.version": "1.0.0"}' in content
+    assert '{"paths[\\"/pets/0/{id}\\"].get.parameters[0].name": "id", "paths[\\"/pets/0/{id}\\"].get.parameters[0].in": "path", "paths[\\"/pets/0/{id}\\"].get.parameters[0].required": true}' in content
+
+    content = cargo_run(["cat-file", "deployment.yaml"], stdout=True)
+    assert '{"[0].spec.template.spec.containers[0].name": "web", "[0].spec.template.spec.containers[0].image": "nginx:1.25"}' in content
+    assert '{"[0].spec.template.spec.containers[0].ports[0].containerPort": 80}' in content
+    assert '"[0].spec.template.spec.containers[1].args": ["--config", "/etc/envoy.yaml"]' in content
+    assert '{"[1].metadata.name": "web-service"}' in content
+    assert "kubernetes" not in content
+
+    content = cargo_run(["cat-file", "app.toml"], stdout=True)
+    assert '{"package.name": "sample", "package.version": "0.1.0"}' in content
+    assert '{"dependencies.serde.version": "1.0", "dependencies.serde.features": ["derive"]}' in content
+    assert '{"bin[0].name": "sample-cli", "bin[0].path": "src/main.rs"}' in content
+
+    assert '{"services.db.image": "postgres"}' in cargo_run(["cat-file", "compose.spec"], stdout=True)
+
+    # a broken file is read as a plain text file
+    cargo_run(["config", "--set", "strict_file_reader", "false"])
+    cargo_run(["build"], features=["yaml"])
+    cargo_run(["check"])
+    assert "// compiler options" in cargo_run(["cat-file", "tsconfig.json"], stdout=True)
@@ -0,0 +1,72 @@
+import json
+import re
+from utils import (
+    cargo_run,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+# 3723 -> "01:02:03"
+def hms(seconds: int) -> str:
+    return f"{seconds // 3600:02}:{seconds // 60 % 60:02}:{seconds % 60:02}"
+
+def subtitle_reader():
+    goto_root()
+    mk_and_cd_tmp_dir()
+
+    # a cue every 10 seconds, for about an hour
+    sentences = [f"This is sentence number {i} of the keynote, and it talks about topic {i // 20}." for i in range(360)]
+    write_string("keynote.srt", "\n".join(
+        f"{i + 1}\n{hms(i * 10)},000 --> {hms(i * 10 + 8)},000\n{sentence}\n"
+        for i, sentence in enumerate(sentences)
+    ))
+    write_string("keynote.vtt", "WEBVTT\n\nNOTE this is a comment\n\n" + "\n".join(
+        f"{hms(i * 10)}.000 --> {hms(i * 10 + 8)}.000 align:start\n<v Alice>{sentence}\n"
+        for i, sentence in enumerate(sentences)
+    ))
+    write_string("meeting.txt", """Weekly Meeting
+[00:00:05] Alice: Let's start. This week, we'll talk about the release.
+[00:01:10] Bob: The build is broken on Windows.
+It fails to find the linker.
+[00:02:30] Alice: Let's fix it before Friday.
+""")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([{"pattern": "meeting.txt", "reader": "subtitle"}])])
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+
+    for file in ["keynote.srt", "keynote.vtt"]:
+        chunks = json.loads(cargo_run(["ls-chunks", "--json", file], stdout=True))
+        chunks.sort(key=lambda chunk: chunk["source"]["index"])
+        assert len(chunks) > 1
+
+        # srt/vtt syntax is not in the chunks
+        assert all("-->" not in chunk["data"] and "<v" not in chunk["data"] for chunk in chunks)
+        assert chunks[0]["data"].startswith("[00:00:00] ")
+        assert "sentence number 0 " in chunks[0]["data"]
+
+        for chunk in chunks:
+            time = chunk["source"]["time"]
+            numbers = [int(n) for n in re.findall(r"sentence number (\d+)", chunk["data"])]
+
+            # the time range covers all the cues in the chunk
+            assert time["start_ms"] <= min(numbers) * 10000
+            assert time["end_ms"] >= max(numbers) * 10000 + 8000
+            assert time["start_ms"] < time["end_ms"]
+
+        assert chunks[-1]["source"]["time"]["end_ms"] == 3598000
+        time = chunks[1]["source"]["time"]
+        assert f"{hms(time['start_ms'] // 1000)}–{hms(time['end_ms'] // 1000)} of {file}" in cargo_run(["cat-file", "--source", chunks[1]["uid"]], stdout=True)
+
+    content = cargo_run(["cat-file", "keynote.vtt"], stdout=True)
+    assert "Alice: This is sentence number 0" in content
+
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "meeting.txt"], stdout=True))
+    assert len(chunks) == 1
+    assert chunks[0]["source"]["time"] == {"start_ms": 0, "end_ms": 150000}
+    assert "[00:01:10] Bob: The build is broken on Windows. It fails to find the linker." in chunks[0]["data"]
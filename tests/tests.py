@@ -15,14 +15,26 @@ from clone_empty import clone_empty
 from config import config
 from csv_reader import csv_reader
 from empty import empty
+from email_reader import email_reader
 from end_to_end import end_to_end
+from epub_reader import epub_reader
 from external_bases import external_bases
 from extract_keywords import extract_keywords
 from fetch_models import fetch_models
+from file_readers import file_readers
+from build_rules import build_rules
+from chunk_unit import chunk_unit
+from prefer_boundaries import prefer_boundaries
+from near_duplicates import near_duplicates
+from ranking import ranking
+from embedding import embedding
+from phrase import phrase
 from generous_file_reader import generous_file_reader
 from gh_issue_20 import gh_issue_20
 from ignore import ignore
 from ii import ii
+from ipynb_reader import ipynb_reader
+from latex_reader import latex_reader
 from images import images
 from images2 import images2
 from images3 import images3
@@ -40,6 +52,7 @@ from migrate import migrate
 from migrate2 import migrate2
 from migrate3 import migrate3
 from models_init import models_init, test_home_config_override
+from office_readers import office_readers
 from orphan_process import orphan_process
 from outside import outside
 from pdf import pdf
@@ -60,11 +73,15 @@ from server_ai_model import server_ai_model
 from server_chat import server_chat
 from server_file_tree import server_file_tree
 from server_permission import server_permission
+from structured_reader import structured_reader
 from subdir import subdir
+from subtitle_reader import subtitle_reader
 from summary import summary
 from svg import svg
 from symlink import symlink
+from text_encoding import text_encoding
 from tfidf import tfidf
+from virtual_files import virtual_files
 from web_images import web_images
 from write_lock import write_lock
 
@@ -360,6 +377,70 @@ Commands
 
     csv_reader                  run `csv_reader` test
 
+    file_readers                run `file_readers` test
+
+    build_rules                 run `build_rules` test
+                                It builds files with different chunk sizes, and checks
+                                that changing a rule stages only the affected files.
+
+    chunk_unit                  run `chunk_unit` test
+                                It builds english and Korean files with `chunk_unit`
+                                "char" and "token", and compares the chunks.
+
+    prefer_boundaries           run `prefer_boundaries` test
+                                It checks that chunks and sliding windows start and end
+                                at sentences and paragraphs.
+
+    near_duplicates             run `near_duplicates` test
+                                It builds copies of a license file and checks that
+                                near-duplicates reuse the summaries of their twins.
+
+    ranking                     run `ranking` test
+                                It runs `rag tfidf` with `tfidf`, `bm25` and `bm25f`,
+                                with and without an inverted-index.
+
+    embedding                   run `embedding` test
+                                It embeds chunks with the dummy model and checks that
+                                `rag retrieve-chunks` fuses vector and tfidf results.
+
+    phrase                      run `phrase` test
+                                It runs phrase and `NEAR/k` queries, with and without
+                                an inverted-index.
+
+    ipynb_reader                run `ipynb_reader` test
+
+    office_readers              run `office_readers` test
+                                It creates docx, pptx, xlsx and odt files and builds a
+                                knowledge-base with/without "office" feature.
+
+    epub_reader                 run `epub_reader` test
+                                It creates epub 2 and epub 3 files and builds a
+                                knowledge-base with/without "epub" feature.
+
+    email_reader                run `email_reader` test
+                                It creates an eml file and an mbox file with MIME parts
+                                and checks how they're split into chunks.
+
+    subtitle_reader             run `subtitle_reader` test
+                                It creates srt, vtt and transcript files and checks the
+                                time ranges of the chunks.
+
+    structured_reader           run `structured_reader` test
+                                It creates json, yaml and toml files and checks whether
+                                each row has the full key path.
+
+    latex_reader                run `latex_reader` test
+                                It creates a paper with `\\input`, `\\include` and figures, and
+                                checks the sections, the preamble and the images of the chunks.
+
+    virtual_files               run `virtual_files` test
+                                It adds zip and tar archives with/without "archive" feature,
+                                and adds, builds and removes the files in the archives.
+
+    text_encoding               run `text_encoding` test
+                                It creates text files in EUC-KR, Shift_JIS, UTF-16 and more,
+                                and checks whether ragit converts them to UTF-8.
+
     real_repos [repo=all]       run `real_repos` test
                                 It clones real git repos from the web and build knowledge-base
                                 of the repos.
@@ -668,6 +749,57 @@ if __name__ == "__main__":
         elif command == "csv_reader":
             csv_reader()
 
+        elif command == "file_readers":
+            file_readers()
+
+        elif command == "build_rules":
+            build_rules()
+
+        elif command == "chunk_unit":
+            chunk_unit()
+
+        elif command == "prefer_boundaries":
+            prefer_boundaries()
+
+        elif command == "near_duplicates":
+            near_duplicates()
+
+        elif command == "ranking":
+            ranking()
+
+        elif command == "embedding":
+            embedding()
+
+        elif command == "phrase":
+            phrase()
+
+        elif command == "ipynb_reader":
+            ipynb_reader()
+
+        elif command == "office_readers":
+            office_readers()
+
+        elif command == "epub_reader":
+            epub_reader()
+
+        elif command == "email_reader":
+            email_reader()
+
+        elif command == "subtitle_reader":
+            subtitle_reader()
+
+        elif command == "structured_reader":
+            structured_reader()
+
+        elif command == "latex_reader":
+            latex_reader()
+
+        elif command == "virtual_files":
+            virtual_files()
+
+        elif command == "text_encoding":
+            text_encoding()
+
         elif command == "real_repos":
             repo = "all" if len(args) < 3 else args[2]
             real_repos(repo=repo)
@@ -755,6 +887,23 @@ if __name__ == "__main__":
                 ("images", images),
                 ("markdown_reader", markdown_reader),
                 ("csv_reader", csv_reader),
+                ("file_readers", file_readers),
+                ("build_rules", build_rules),
+                ("chunk_unit", chunk_unit),
+                ("prefer_boundaries", prefer_boundaries),
+                ("near_duplicates", near_duplicates),
+                ("ranking", ranking),
+                ("embedding", embedding),
+                ("phrase", phrase),
+                ("ipynb_reader", ipynb_reader),
+                ("office_readers", office_readers),
+                ("epub_reader", epub_reader),
+                ("email_reader", email_reader),
+                ("subtitle_reader", subtitle_reader),
+                ("structured_reader", structured_reader),
+                ("latex_reader", latex_reader),
+                ("virtual_files", virtual_files),
+                ("text_encoding", text_encoding),
                 ("real_repos", real_repos),
                 ("real_repos_regression", real_repos_regression),
                 ("subdir", subdir),
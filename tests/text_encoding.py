@@ -0,0 +1,123 @@
+import json
+from utils import (
+    cargo_run,
+    count_files,
+    goto_root,
+    mk_and_cd_tmp_dir,
+)
+
+korean = "# 한국어 문서\n\n안녕하세요. 이것은 오래된 한국어 문서입니다.\n윈도우에서 만든 파일은 인코딩이 다양합니다.\n"
+japanese = "# 日本語の文書\n\nこんにちは。これは古い日本語の文書です。\nエンコーディングを当ててください。\n"
+
+def write_bytes(path: str, content: bytes):
+    with open(path, "wb") as f:
+        f.write(content)
+
+def get_encoding(file: str):
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", file], stdout=True))
+    encodings = [chunk["build_info"].get("encoding") for chunk in chunks]
+    assert all(encoding == encodings[0] for encoding in encodings)
+    return encodings[0]
+
+def text_encoding():
+    goto_root()
+    mk_and_cd_tmp_dir()
+    write_bytes("euc-kr.txt", korean.encode("cp949"))
+    write_bytes("shift-jis.md", japanese.encode("shift_jis"))
+    write_bytes("utf-16.txt", korean.encode("utf-16"))  # with a BOM
+    write_bytes("utf-8-bom.md", korean.encode("utf-8-sig"))
+    write_bytes("utf-8.txt", korean.encode("utf-8"))
+    write_bytes("latin-1.txt", "Un café au lait, s'il vous plaît.\n".encode("cp1252"))
+    write_bytes("override.txt", "Un café au lait, s'il vous plaît.\n".encode("cp1252"))
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([
+        {"pattern": "override.txt", "reader": "plain_text", "options": {"encoding": "windows-1252"}},
+    ])])
+    cargo_run(["add", "--all"])
+    cargo_run(["build"])
+    cargo_run(["check"])
+    assert count_files() == (7, 0, 7)
+
+    # files are converted to utf-8
+    for file, content in [
+        ("euc-kr.txt", korean),
+        ("shift-jis.md", japanese),
+        ("utf-16.txt", korean),
+        ("utf-8-bom.md", korean),
+        ("utf-8.txt", korean),
+    ]:
+        assert cargo_run(["cat-file", file], stdout=True).strip() == content.strip()
+
+    assert get_encoding("euc-kr.txt") == {"name": "EUC-KR", "detected_by": "guess", "is_lossy": False}
+    assert get_encoding("shift-jis.md") == {"name": "Shift_JIS", "detected_by": "guess", "is_lossy": False}
+    assert get_encoding("utf-16.txt") == {"name": "UTF-16LE", "detected_by": "bom", "is_lossy": False}
+    assert get_encoding("utf-8-bom.md") is None
+    assert get_encoding("utf-8.txt") is None
+    assert get_encoding("override.txt") == {"name": "windows-1252", "detected_by": "config", "is_lossy": False}
+
+    # byte ranges of a converted file are offsets in the original file
+    for file, encoding, bom_len in [
+        ("euc-kr.txt", "cp949", 0),
+        ("shift-jis.md", "shift_jis", 0),
+        ("utf-16.txt", "utf-16-le", 2),
+    ]:
+        content = open(file, "rb").read()
+        chunks = json.loads(cargo_run(["ls-chunks", "--json", file], stdout=True))
+        assert chunks[0]["source"]["range"]["start_byte"] == bom_len
+        assert chunks[-1]["source"]["range"]["end_byte"] == len(content)
+
+        for chunk in chunks:
+            range = chunk["source"]["range"]
+            assert content[range["start_byte"]:range["end_byte"]].decode(encoding) == chunk["data"]
+
+    # the markdown reader knows the headings of a converted file
+    chunks = json.loads(cargo_run(["ls-chunks", "--json", "shift-jis.md"], stdout=True))
+    assert chunks[0]["source"]["heading"] == "日本語の文書"
+
+    # ragit cannot guess the encoding of `latin-1.txt`
+    assert "café" in cargo_run(["cat-file", "latin-1.txt"], stdout=True)
+    assert get_encoding("latin-1.txt")["detected_by"] == "fallback"
+    check_result = cargo_run(["check"], stdout=True)
+    assert "latin-1.txt" in check_result
+    assert "euc-kr.txt" not in check_result
+    assert "override.txt" not in check_result
+
+    # a UTF-8 file with a broken byte is read as UTF-8, not as windows-1252
+    broken = bytearray(korean.encode("utf-8"))
+    broken[20] = 0xff
+    write_bytes("broken-utf-8.txt", bytes(broken))
+    cargo_run(["add", "broken-utf-8.txt"])
+    cargo_run(["build"])
+    assert count_files() == (8, 0, 8)
+    assert "오래된 한국어 문서입니다" in cargo_run(["cat-file", "broken-utf-8.txt"], stdout=True)
+    assert get_encoding("broken-utf-8.txt") == {"name": "UTF-8", "detected_by": "guess", "is_lossy": True}
+    assert "broken-utf-8.txt" in cargo_run(["check"], stdout=True)
+
+    # an invalid encoding
+    cargo_run(["rm", "override.txt"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([
+        {"pattern": "override.txt", "reader": "plain_text", "options": {"encoding": "no-such-encoding"}},
+    ])])
+    cargo_run(["add", "override.txt"])
+    cargo_run(["build"])
+    assert count_files() == (8, 1, 7)
+    cargo_run(["check"])
+
+    # strict mode rejects bytes that cannot be decoded
+    cargo_run(["config", "--set", "strict_file_reader", "true"])
+    cargo_run(["config", "--set", "file_readers", json.dumps([
+        {"pattern": "override.txt", "reader": "plain_text", "options": {"encoding": "shift_jis"}},
+    ])])
+    write_bytes("override.txt", b"\x82\xa0\xff\xff")
+    cargo_run(["build"])
+    assert count_files() == (8, 1, 7)
+    cargo_run(["check"])
+
+    # strict mode rejects a file whose encoding cannot be guessed
+    write_bytes("latin-1-strict.txt", "Une crème brûlée, s'il vous plaît.\n".encode("cp1252"))
+    cargo_run(["add", "latin-1-strict.txt"])
+    cargo_run(["build"])
+    assert count_files() == (9, 2, 7)
+    cargo_run(["check"])
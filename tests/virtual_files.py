@@ -0,0 +1,124 @@
+import io
+import json
+import os
+import tarfile
+import zipfile
+from utils import (
+    cargo_run,
+    count_files,
+    count_images,
+    goto_root,
+    mk_and_cd_tmp_dir,
+    write_string,
+)
+
+def write_zip(path: str, files: dict[str, str | bytes]):
+    with zipfile.ZipFile(path, "w") as f:
+        for name, content in files.items():
+            f.writestr(name, content)
+
+def write_tar_gz(path: str, files: dict[str, str | bytes]):
+    with tarfile.open(path, "w:gz") as f:
+        for name, content in files.items():
+            content = content.encode("utf-8") if isinstance(content, str) else content
+            info = tarfile.TarInfo(name)
+            info.size = len(content)
+            f.addfile(info, io.BytesIO(content))
+
+def ls_files(args: list[str]) -> list[str]:
+    return sorted(json.loads(cargo_run(["ls-files", "--json", "--name-only", *args], stdout=True)))
+
+def virtual_files():
+    goto_root()
+    image = open("tests/images/green.png", "rb").read()
+    mk_and_cd_tmp_dir()
+    write_zip("bundle.zip", {
+        "docs/a.md": "# Installation\n\nRun `vendor-cli install`.\n\n![a green square](../images/green.png)\n",
+        "docs/b.txt": "The vendor's license is MIT.",
+        "images/green.png": image,
+
+        # junks and nested archives are not added
+        "__MACOSX/docs/._a.md": "junk",
+        "docs/.DS_Store": "junk",
+        "nested.zip": b"PK",
+    })
+    write_tar_gz("manual.tar.gz", {
+        "manual/intro.md": "# Introduction\n\nThis is a manual.\n",
+        "../escape.md": "A member with an unsafe path is not added.",
+    })
+    write_string("README.md", "# Hello\n\nThis is a real file.\n")
+
+    cargo_run(["init"])
+    cargo_run(["config", "--set", "model", "dummy"])
+
+    # without `archive` feature, an archive is just a file
+    cargo_run(["add", "bundle.zip"])
+    assert ls_files(["--staged"]) == ["bundle.zip"]
+    cargo_run(["rm", "bundle.zip"])
+
+    cargo_run(["add", "--all"], features=["archive"])
+    assert ls_files(["--staged"]) == [
+        "README.md",
+        "bundle.zip!/docs/a.md",
+        "bundle.zip!/docs/b.txt",
+        "bundle.zip!/images/green.png",
+        "manual.tar.gz!/manual/intro.md",
+    ]
+    cargo_run(["build"], features=["archive"])
+    cargo_run(["check"], features=["archive"])
+    assert count_files() == (5, 0, 5)
+    assert ls_files(["--processed"]) == ls_files([])
+
+    # a markdown file can refer to the images in the same archive
+    assert count_images(["bundle.zip!/docs/a.md"]) == 1
+
+    assert "vendor-cli install" in cargo_run(["cat-file", "bundle.zip!/docs/a.md"], stdout=True)
+    assert "This is a manual." in cargo_run(["cat-file", "manual.tar.gz!/manual/intro.md"], stdout=True)
+
+    # extracted files are not left in `.ragit/`
+    assert not os.path.exists(os.path.join(".ragit", "virtual_files"))
+
+    # nothing's changed
+    cargo_run(["add", "bundle.zip"], features=["archive"])
+    assert count_files() == (5, 0, 5)
+
+    # only the modified file is staged
+    write_zip("bundle.zip", {
+        "docs/a.md": "# Installation\n\nRun `vendor-cli install`.\n\n![a green square](../images/green.png)\n",
+        "docs/b.txt": "The vendor's license is Apache-2.0.",
+        "images/green.png": image,
+    })
+    cargo_run(["add", "bundle.zip"], features=["archive"])
+    assert ls_files(["--staged"]) == ["bundle.zip!/docs/b.txt"]
+    cargo_run(["build"], features=["archive"])
+    cargo_run(["check"], features=["archive"])
+    assert "Apache-2.0" in cargo_run(["cat-file", "bundle.zip!/docs/b.txt"], stdout=True)
+
+    # `rag rm --auto` removes the files that are removed from the archive
+    write_zip("bundle.zip", {
+        "docs/a.md": "# Installation\n\nRun `vendor-cli install`.\n\n![a green square](../images/green.png)\n",
+        "images/green.png": image,
+    })
+    cargo_run(["rm", "--auto", "--all"], features=["archive"])
+    cargo_run(["check"], features=["archive"])
+    assert ls_files([]) == [
+        "README.md",
+        "bundle.zip!/docs/a.md",
+        "bundle.zip!/images/green.png",
+        "manual.tar.gz!/manual/intro.md",
+    ]
+
+    # `rag rm <archive>` removes all the files in the archive
+    cargo_run(["rm", "bundle.zip"], features=["archive"])
+    cargo_run(["check"], features=["archive"])
+    assert ls_files([]) == ["README.md", "manual.tar.gz!/manual/intro.md"]
+
+    os.remove("manual.tar.gz")
+    cargo_run(["rm", "--auto", "--all"], features=["archive"])
+    cargo_run(["check"], features=["archive"])
+    assert ls_files([]) == ["README.md"]
+
+    # a broken archive
+    write_string("broken.zip", "This is not a zip file.")
+    assert cargo_run(["add", "broken.zip"], features=["archive"], check=False) != 0
+    assert count_files() == (1, 0, 1)